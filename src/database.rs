@@ -167,7 +167,6 @@ impl Database {
         let lurd = solution.to_string();
 
         if let Some(best_move_solution) = self.best_move_solution(level_id) {
-            dbg!();
             if solution.moves() < best_move_solution.moves() {
                 self.connection
                     .execute(
@@ -186,7 +185,6 @@ impl Database {
         }
 
         if let Some(best_push_solution) = self.best_push_solution(level_id) {
-            dbg!();
             if solution.pushes() < best_push_solution.pushes() {
                 self.connection
                     .execute(
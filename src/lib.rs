@@ -0,0 +1,10 @@
+//! The board, the solver, the solution checker and the path utilities of the
+//! game, which can be used without it.
+
+#![allow(clippy::op_ref)]
+
+pub mod board;
+pub mod solve;
+mod test;
+pub mod utils;
+pub mod verify;
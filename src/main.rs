@@ -1,7 +1,6 @@
 // #![feature(test)]
 #![allow(clippy::op_ref)]
 
-mod board;
 mod components;
 mod database;
 mod events;
@@ -9,17 +8,15 @@ mod input_map;
 mod plugins;
 mod resources;
 mod settings;
-mod solve;
 mod state;
 mod systems;
-mod test;
-mod utils;
 
 use events::*;
 use input_map::*;
 use leafwing_input_manager::{action_diff::ActionDiffEvent, prelude::*};
 use plugins::*;
 use resources::*;
+use sokoban_rs::{board, solve, utils, verify};
 use state::*;
 use systems::{input::*, level::*, render::*};
 use utils::*;
@@ -27,8 +24,34 @@ use utils::*;
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
 
+/// Parses the command line.
+///
+/// `--analysis-cache <dir>` caches the analyses of levels in the directory,
+/// overriding `analysis_cache_dir` of the config.
+fn parse_command_line(mut args: impl Iterator<Item = String>) -> Result<CommandLine, String> {
+    let mut command_line = CommandLine::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--analysis-cache" => {
+                let dir = args
+                    .next()
+                    .ok_or("`--analysis-cache` requires a directory")?;
+                command_line.analysis_cache_dir = Some(dir.into());
+            }
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+    Ok(command_line)
+}
+
 #[bevy_main]
 fn main() {
+    let command_line = parse_command_line(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("error: {}", error);
+        eprintln!("usage: sokoban-rs [--analysis-cache <dir>]");
+        std::process::exit(2);
+    });
+
     let mut app = App::new();
 
     app.add_plugins((
@@ -78,6 +101,8 @@ fn main() {
         auto_solve::plugin,
     ));
 
+    app.insert_resource(command_line);
+
     app.init_resource::<ActionState<Action>>()
         .insert_resource(default_input_map())
         .add_event::<ActionDiffEvent<Action>>();
@@ -1,8 +1,13 @@
-use std::time::{Duration, Instant};
+use std::{
+    fs::File,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use bevy::{color::palettes::css::*, prelude::*};
+use soukoban::Map;
 
-use crate::{components::*, resources::*, solve::solver::*, systems::input::*, AppState};
+use crate::{components::*, resources::*, solve::solver::*, systems::input::*, verify, AppState};
 
 pub fn plugin(app: &mut App) {
     app.add_systems(
@@ -39,6 +44,7 @@ pub fn load_solver(
     mut solver_state: ResMut<SolverState>,
     board: Query<&Board>,
     config: Res<Config>,
+    command_line: Res<CommandLine>,
 ) {
     let board = &board.single().board;
     let SolverState {
@@ -48,14 +54,74 @@ pub fn load_solver(
     } = &mut *solver_state;
     *origin_board = board.clone();
     let solver = solver.get_mut().unwrap();
-    *solver = Solver::new(
-        origin_board.map.clone(),
-        config.solver.strategy,
-        config.solver.lower_bound_method,
-    );
+    *solver = load_analysis(&origin_board.map, &config, &command_line)
+        .unwrap_or_else(|| Solver::with_options(origin_board.map.clone(), config.solver.clone()));
+    solver.on_progress(|progress| {
+        debug!(
+            "Solver: {} visited, {} open, lower bound {}, depth {} ({} sec)",
+            progress.visited_states,
+            progress.open_states,
+            progress.best_lower_bound,
+            progress.depth,
+            progress.elapsed.as_millis() as f32 / 1000.0
+        );
+    });
+    if let Some(pushes) = solver.estimated_pushes() {
+        debug!("Solver: At least {} pushes", pushes);
+    }
+    if solver.options().goal_macros {
+        if let Some(packing_order) = solver.packing_order() {
+            let entrance = packing_order.entrance();
+            debug!(
+                "Solver: Packing order of {} goals through ({}, {})",
+                packing_order.goals().len(),
+                entrance.x,
+                entrance.y
+            );
+        }
+    }
     stopwatch.reset();
 }
 
+/// Returns the path of the cached analysis of the level, if caching is enabled
+/// by `--analysis-cache` or by the config.
+fn analysis_cache_path(map: &Map, config: &Config, command_line: &CommandLine) -> Option<PathBuf> {
+    let fingerprint = Solver::analysis_fingerprint(map, &config.solver);
+    Some(
+        command_line
+            .analysis_cache_dir
+            .as_ref()
+            .or(config.analysis_cache_dir.as_ref())?
+            .join(format!("{}.toml", fingerprint)),
+    )
+}
+
+/// Creates a solver from the cached analysis of the level, if any.
+fn load_analysis(map: &Map, config: &Config, command_line: &CommandLine) -> Option<Solver> {
+    let file = File::open(analysis_cache_path(map, config, command_line)?).ok()?;
+    match Solver::import_analysis(map.clone(), config.solver.clone(), file) {
+        Ok(solver) => Some(solver),
+        Err(error) => {
+            warn!("Solver: Failed to load analysis: {}", error);
+            None
+        }
+    }
+}
+
+/// Caches the analysis of the level, including the learned deadlocks.
+fn save_analysis(solver: &Solver, config: &Config, command_line: &CommandLine) {
+    let Some(path) = analysis_cache_path(&solver.map, config, command_line) else {
+        return;
+    };
+    let result = std::fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| File::create(&path))
+        .map_err(Into::into)
+        .and_then(|file| solver.export_analysis(file));
+    if let Err(error) = result {
+        warn!("Solver: Failed to save analysis: {}", error);
+    }
+}
+
 /// Unloads the solver state by resetting it to default values.
 pub fn unload_solver(mut solver_state: ResMut<SolverState>) {
     *solver_state = SolverState::default();
@@ -103,6 +169,8 @@ pub fn update_solver(
     mut player_movement: ResMut<PlayerMovement>,
 
     mut next_state: ResMut<NextState<AppState>>,
+    config: Res<Config>,
+    command_line: Res<CommandLine>,
 ) {
     let board = &mut board.single_mut().board;
     let SolverState {
@@ -116,13 +184,13 @@ pub fn update_solver(
     let solver = solver.get_mut().unwrap();
     let timeout = Duration::from_millis(50);
     let timer = Instant::now();
-    match solver.search(timeout) {
-        Ok(solution) => {
-            let mut verify_board = board.clone();
-            for action in &*solution {
-                verify_board.do_action(action.direction());
+    match solver.solve_for(timeout) {
+        SolveProgress::Solved(solution) => {
+            if let Err(error) = verify::replay(&origin_board.map, &solution) {
+                error!("Solver: Invalid solution: {}", error);
+                next_state.set(AppState::Main);
+                return;
             }
-            assert!(verify_board.is_solved());
 
             stopwatch.tick(timer.elapsed());
             info!(
@@ -134,7 +202,92 @@ pub fn update_solver(
                 solution.moves(),
                 solution.pushes()
             );
+            if let Some(optimality_bound) = solver.optimality_bound() {
+                debug!("    Pushes within {}x of optimal", optimality_bound);
+            }
+            if let Some(first_solution) = solver.first_solution() {
+                debug!(
+                    "    First solution: moves: {}, pushes: {}",
+                    first_solution.moves(),
+                    first_solution.pushes()
+                );
+                debug!(
+                    "    Incumbent pushes: {:?}",
+                    solver
+                        .incumbents()
+                        .iter()
+                        .map(|solution| solution.pushes())
+                        .collect::<Vec<_>>()
+                );
+                debug!("    Optimality proven: {}", solver.optimality_proven());
+            }
+            if solver.discarded_states() > 0 {
+                debug!(
+                    "    Discarded states: {} (optimality sacrificed)",
+                    solver.discarded_states()
+                );
+            }
+            if let Some(expanded_states) = solver.stall_fallback() {
+                debug!(
+                    "    Stalled after {} expanded states, fell back to {:?}",
+                    expanded_states,
+                    solver.strategy()
+                );
+            }
+            debug!("    Heuristics: {:?}", solver.heuristics());
+            debug!("    Expanded states: {}", solver.expanded_states());
+            debug!("    Stored states: {}", solver.stored_states());
+            debug!("    Duplicate states: {}", solver.duplicate_states());
+            let statistics = solver.statistics();
+            debug!(
+                "    Memory: {} KiB ({} KiB at peak, {} bytes per state)",
+                statistics.approx_memory_bytes / 1024,
+                statistics.peak_memory_bytes / 1024,
+                statistics.state_bytes
+            );
+            if let Some(micros_per_expansion) = statistics.micros_per_expansion() {
+                debug!("    Time per expansion: {:.1} µs", micros_per_expansion);
+            }
+            debug!(
+                "    Generated states: {} (peak {} open, {} visited)",
+                statistics.generated_states,
+                statistics.peak_open_states,
+                statistics.peak_visited_states
+            );
+            if let Some(initial_lower_bound) = statistics.initial_lower_bound {
+                debug!("    Initial lower bound: {}", initial_lower_bound);
+            }
+            debug!(
+                "    Optimality guaranteed: {}",
+                statistics.optimality_guaranteed
+            );
+            debug!("    Optimality: {:?}", solver.optimality());
+            debug!("    Pruning: {:?}", statistics.pruning);
+            if let Some(pressure_relief) = statistics.pressure_relief {
+                debug!(
+                    "    Pressure relief: {:?} applied {} times to {} states",
+                    pressure_relief, statistics.pressure_reliefs, statistics.relieved_states
+                );
+            }
+            debug!("    Pattern deadlocks: {}", solver.pattern_deadlocks());
+            debug!("    Corral deadlocks: {}", solver.corral_deadlocks());
+            debug!(
+                "    Learned deadlocks: {} ({} hits)",
+                solver.learned_deadlocks().len(),
+                solver.learned_deadlocks().hits()
+            );
+            debug!("    PI-corrals: {}", solver.pi_corrals());
+            debug!("    Bipartite deadlocks: {}", solver.bipartite_deadlocks());
+            debug!("    Cut pushes: {}", solver.cut_pushes());
+            let lower_bound_cache = solver.lower_bound_cache();
+            debug!(
+                "    Lower bound cache: {} hits, {} misses, {} entries",
+                lower_bound_cache.hits(),
+                lower_bound_cache.misses(),
+                lower_bound_cache.len()
+            );
             info!("    Solution: {}", solution.to_string());
+            save_analysis(solver, &config, &command_line);
 
             for action in &*solution {
                 player_move_unchecked(action.direction(), &mut player_movement);
@@ -142,27 +295,93 @@ pub fn update_solver(
             next_state.set(AppState::Main);
             return;
         }
-        Err(SolveError::NoSolution) => {
+        SolveProgress::Failed(SolveError::NoSolution { reason, statistics }) => {
             stopwatch.tick(timer.elapsed());
             info!(
                 "Solver: No solution ({} sec)",
                 stopwatch.elapsed().as_millis() as f32 / 1000.0
             );
+            debug!("    Reason: {}", reason);
+            debug!("    Proven unsolvable: {}", reason.proves_unsolvable());
+            debug!("    Expanded states: {}", statistics.expanded_states);
+            save_analysis(solver, &config, &command_line);
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::ExpansionLimit {
+            expanded,
+            statistics,
+        }) => {
+            stopwatch.tick(timer.elapsed());
+            info!(
+                "Solver: Expansion limit reached ({} sec)",
+                stopwatch.elapsed().as_millis() as f32 / 1000.0
+            );
+            debug!("    Expanded states: {}", expanded);
+            debug!(
+                "    Best lower bound: {:?}, most boxes on goals: {}",
+                statistics.best_lower_bound, statistics.most_boxes_on_goals
+            );
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::OutOfMemory {
+            estimated_bytes,
+            visited,
+            open,
+            statistics,
+        }) => {
+            stopwatch.tick(timer.elapsed());
+            info!(
+                "Solver: Memory limit reached ({} sec)",
+                stopwatch.elapsed().as_millis() as f32 / 1000.0
+            );
+            debug!("    Estimated memory: {} MiB", estimated_bytes / (1 << 20));
+            debug!("    Visited states: {}, open states: {}", visited, open);
+            debug!("    Expanded states: {}", statistics.expanded_states);
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::InvalidOptions(error)) => {
+            error!("Solver: Invalid options: {}", error);
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::InvalidSolution(error)) => {
+            error!("Solver: Invalid solution: {}", error);
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::InvalidLevel(error)) => {
+            error!("Solver: Invalid level: {}", error);
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::Cancelled { statistics }) => {
+            info!(
+                "Solver: Cancelled after {} expanded states",
+                statistics.expanded_states
+            );
+            next_state.set(AppState::Main);
+            return;
+        }
+        SolveProgress::Failed(SolveError::Spill(error)) => {
+            error!("Solver: Failed to spill open states: {}", error);
             next_state.set(AppState::Main);
             return;
         }
-        Err(SolveError::Timeout) => {
+        SolveProgress::Running { .. } | SolveProgress::Failed(SolveError::Timeout { .. }) => {
             stopwatch.tick(timer.elapsed());
         }
     }
     if let Some(best_state) = solver.best_state() {
         // println!(
         //     "lower bound: {:3}, moves: {:3}, pushes: {:3}",
-        //     best_state.lower_bound(&solver),
+        //     best_state.lower_bound(solver.analysis(), solver.context()),
         //     best_state.actions.moves(),
         //     best_state.actions.pushes()
         // );
-        for action in &*best_state.actions {
+        for action in &*best_state.actions_with_moves(solver.analysis(), solver.context()) {
             board.do_action(action.direction());
         }
     }
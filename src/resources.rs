@@ -7,6 +7,7 @@ use crate::{board::Board, database, solve::solver::*, utils::PushState};
 
 use std::{
     collections::{HashMap, VecDeque},
+    path::PathBuf,
     sync::Mutex,
 };
 
@@ -22,7 +23,10 @@ pub struct Config {
     pub instant_move: bool,
     /// Enable auto switch to next unsolved level when the current level is solved.
     pub auto_switch_to_next_unsolved_level: bool,
-    pub solver: SolverConfig,
+    pub solver: SolverOptions,
+    /// Directory where level analyses are cached between runs.
+    #[serde(default)]
+    pub analysis_cache_dir: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -33,15 +37,18 @@ impl Default for Config {
             volume: 0.5,
             instant_move: false,
             auto_switch_to_next_unsolved_level: true,
-            solver: SolverConfig::default(),
+            solver: SolverOptions::default(),
+            analysis_cache_dir: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
-pub struct SolverConfig {
-    pub strategy: Strategy,
-    pub lower_bound_method: LowerBoundMethod,
+/// Options given on the command line, which take precedence over the config.
+#[derive(Resource, Default)]
+pub struct CommandLine {
+    /// Directory where level analyses are cached, given by
+    /// `--analysis-cache <dir>` instead of `analysis_cache_dir` of the config.
+    pub analysis_cache_dir: Option<PathBuf>,
 }
 
 #[derive(Resource, Deref)]
@@ -85,10 +92,9 @@ pub struct SolverState {
 impl Default for SolverState {
     fn default() -> Self {
         Self {
-            solver: Mutex::new(Solver::new(
+            solver: Mutex::new(Solver::with_options(
                 Map::with_dimensions(Vector2::new(0, 0)),
-                Strategy::default(),
-                LowerBoundMethod::default(),
+                SolverOptions::default(),
             )),
             stopwatch: Stopwatch::new(),
             origin_board: Board::with_map(Map::with_dimensions(Vector2::new(0, 0))),
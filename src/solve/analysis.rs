@@ -0,0 +1,65 @@
+use std::{fmt, io};
+
+use serde::{Deserialize, Serialize};
+
+/// Analysis results of a level, which can be persisted between runs.
+#[derive(Serialize, Deserialize)]
+pub struct Analysis {
+    /// Identifies the level and the options the analysis depends on.
+    pub fingerprint: String,
+    pub dead_squares: Vec<[i32; 2]>,
+    /// Player positions and push directions of tunnels.
+    pub tunnels: Vec<([i32; 2], [i32; 2])>,
+    pub goal_distances: Vec<GoalDistances>,
+    pub learned_deadlocks: Vec<LearnedDeadlock>,
+}
+
+/// Distances from positions of the level to a single goal.
+#[derive(Serialize, Deserialize)]
+pub struct GoalDistances {
+    pub goal_position: [i32; 2],
+    pub distances: Vec<([i32; 2], usize)>,
+}
+
+/// Boxes that can never all be pushed onto goals while the player is in the
+/// area they separate.
+#[derive(Serialize, Deserialize)]
+pub struct LearnedDeadlock {
+    pub box_positions: Vec<[i32; 2]>,
+    pub player_position: [i32; 2],
+}
+
+#[derive(Debug)]
+pub enum AnalysisError {
+    Io(io::Error),
+    /// The analysis could not be encoded or decoded.
+    Format(String),
+    /// The analysis belongs to a different level, or depends on different
+    /// options.
+    FingerprintMismatch {
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Format(error) => write!(f, "malformed analysis: {}", error),
+            Self::FingerprintMismatch { expected, found } => write!(
+                f,
+                "analysis fingerprint mismatch: expected {}, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {}
+
+impl From<io::Error> for AnalysisError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
@@ -0,0 +1,346 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::solve::{
+    solver::Strategy,
+    state::{Priority, State},
+};
+
+/// States stored in slots of a single vector and referred to by index. The
+/// slots of removed states are reused by the next inserted ones.
+#[derive(Default)]
+pub struct StateArena {
+    slots: Vec<Option<State>>,
+    free_slots: Vec<u32>,
+}
+
+impl StateArena {
+    /// Stores the state and returns its index.
+    pub fn insert(&mut self, state: State) -> u32 {
+        match self.free_slots.pop() {
+            Some(index) => {
+                self.slots[index as usize] = Some(state);
+                index
+            }
+            None => {
+                self.slots.push(Some(state));
+                u32::try_from(self.slots.len() - 1).expect("too many states")
+            }
+        }
+    }
+
+    /// Returns the state at the index.
+    pub fn get(&self, index: u32) -> &State {
+        self.slots[index as usize].as_ref().unwrap()
+    }
+
+    /// Takes the state at the index out of the arena.
+    pub fn remove(&mut self, index: u32) -> State {
+        self.free_slots.push(index);
+        self.slots[index as usize].take().unwrap()
+    }
+
+    /// Returns the bytes allocated for the slots, excluding the bytes
+    /// allocated by the states.
+    pub fn allocated_bytes(&self) -> usize {
+        self.slots.capacity() * size_of::<Option<State>>()
+            + self.free_slots.capacity() * size_of::<u32>()
+    }
+}
+
+/// An entry of the open list, ordered like the state it refers to.
+#[derive(PartialEq, Eq)]
+struct Entry {
+    priority: Priority,
+    sequence: u64,
+    index: u32,
+    /// The pushes of the state if deeper states are preferred, otherwise
+    /// zero.
+    depth: u32,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .reverse()
+            .then(self.depth.cmp(&other.depth))
+            .then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// How the entries of the open list are ordered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum QueueKind {
+    /// A single binary heap of every entry.
+    #[default]
+    BinaryHeap,
+
+    /// A binary heap of entries for each value of the first component of the
+    /// priority.
+    Buckets,
+}
+
+impl QueueKind {
+    /// Returns the queue suited to the priorities of the strategy.
+    ///
+    /// Buckets are used when the first component of the priority is made of
+    /// lower bounds and pushes, which span a small range of values, and a
+    /// single heap when it counts moves.
+    pub fn for_strategy(strategy: Strategy) -> Self {
+        match strategy {
+            Strategy::Fast
+            | Strategy::OptimalPushMove
+            | Strategy::OptimalPushes
+            | Strategy::Weighted(_)
+            | Strategy::Greedy => Self::Buckets,
+            Strategy::Mixed | Strategy::OptimalMovePush => Self::BinaryHeap,
+        }
+    }
+}
+
+/// A priority queue of the entries of the open list.
+trait EntryQueue: Send {
+    fn push(&mut self, entry: Entry);
+
+    /// Removes and returns the greatest entry.
+    fn pop(&mut self) -> Option<Entry>;
+
+    /// Returns the greatest entry.
+    fn peek(&self) -> Option<&Entry>;
+
+    fn len(&self) -> usize;
+
+    /// Returns the bytes allocated for the entries.
+    fn allocated_bytes(&self) -> usize;
+}
+
+impl EntryQueue for BinaryHeap<Entry> {
+    fn push(&mut self, entry: Entry) {
+        BinaryHeap::push(self, entry);
+    }
+
+    fn pop(&mut self) -> Option<Entry> {
+        BinaryHeap::pop(self)
+    }
+
+    fn peek(&self) -> Option<&Entry> {
+        BinaryHeap::peek(self)
+    }
+
+    fn len(&self) -> usize {
+        BinaryHeap::len(self)
+    }
+
+    fn allocated_bytes(&self) -> usize {
+        self.capacity() * size_of::<Entry>()
+    }
+}
+
+/// Entries in buckets indexed by the first component of their priority, each
+/// ordered by a binary heap.
+///
+/// Entries are popped in the same order as from a single binary heap, but
+/// each push and pop only sifts the entries of one bucket.
+#[derive(Default)]
+struct BucketQueue {
+    buckets: Vec<BinaryHeap<Entry>>,
+    /// The index of the first non-empty bucket, if there are entries.
+    first: usize,
+    len: usize,
+}
+
+impl EntryQueue for BucketQueue {
+    fn push(&mut self, entry: Entry) {
+        let index = entry.priority.0;
+        if index >= self.buckets.len() {
+            self.buckets.resize_with(index + 1, BinaryHeap::new);
+        }
+        self.buckets[index].push(entry);
+        if self.len == 0 || index < self.first {
+            self.first = index;
+        }
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<Entry> {
+        let bucket = self.buckets.get_mut(self.first)?;
+        let entry = bucket.pop()?;
+        if bucket.is_empty() {
+            // The first bucket is rarely refilled, so its entries are freed.
+            *bucket = BinaryHeap::new();
+        }
+        self.len -= 1;
+        if self.len > 0 {
+            while self.buckets[self.first].is_empty() {
+                self.first += 1;
+            }
+        }
+        Some(entry)
+    }
+
+    fn peek(&self) -> Option<&Entry> {
+        self.buckets.get(self.first)?.peek()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn allocated_bytes(&self) -> usize {
+        self.buckets.capacity() * size_of::<BinaryHeap<Entry>>()
+            + self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.capacity() * size_of::<Entry>())
+                .sum::<usize>()
+    }
+}
+
+/// The open states of a search, kept in an arena and ordered by a priority
+/// queue of entries holding their priority and index.
+///
+/// Sifting the queue moves the small entries instead of the states, and the
+/// states stay in place until they are popped.
+pub struct OpenList {
+    arena: StateArena,
+    kind: QueueKind,
+    queue: Box<dyn EntryQueue>,
+    /// Whether states of equal priority with more pushes are popped first.
+    prefers_deeper: bool,
+}
+
+impl Default for OpenList {
+    fn default() -> Self {
+        Self::new(QueueKind::default())
+    }
+}
+
+impl OpenList {
+    /// Creates an empty open list ordered by the queue.
+    pub fn new(kind: QueueKind) -> Self {
+        let queue: Box<dyn EntryQueue> = match kind {
+            QueueKind::BinaryHeap => Box::new(BinaryHeap::new()),
+            QueueKind::Buckets => Box::new(BucketQueue::default()),
+        };
+        Self {
+            arena: StateArena::default(),
+            kind,
+            queue,
+            prefers_deeper: false,
+        }
+    }
+
+    /// Adds the state.
+    pub fn push(&mut self, state: State) {
+        let priority = state.priority();
+        let sequence = state.sequence();
+        let depth = if self.prefers_deeper {
+            u32::try_from(state.path.pushes()).unwrap_or(u32::MAX)
+        } else {
+            0
+        };
+        let index = self.arena.insert(state);
+        self.queue.push(Entry {
+            priority,
+            sequence,
+            index,
+            depth,
+        });
+    }
+
+    /// Removes and returns the best state.
+    pub fn pop(&mut self) -> Option<State> {
+        let entry = self.queue.pop()?;
+        Some(self.arena.remove(entry.index))
+    }
+
+    /// Returns the best state.
+    pub fn peek(&self) -> Option<&State> {
+        self.queue.peek().map(|entry| self.arena.get(entry.index))
+    }
+
+    /// Returns the number of states.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Checks if there are no states.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes the states out of the open list, leaving an empty open list
+    /// with the same queue.
+    pub fn take(&mut self) -> Self {
+        std::mem::replace(self, Self::new(self.kind))
+    }
+
+    /// Checks if states of equal priority with more pushes are popped first.
+    pub fn prefers_deeper(&self) -> bool {
+        self.prefers_deeper
+    }
+
+    /// Pops states of equal priority with more pushes first from now on,
+    /// instead of the states created first.
+    pub fn prefer_deeper(&mut self) {
+        let states = self.take();
+        self.prefers_deeper = true;
+        for state in states {
+            self.push(state);
+        }
+    }
+
+    /// Removes and returns the given number of worst states, in arbitrary
+    /// order.
+    pub fn split_off_worst(&mut self, count: usize) -> Vec<State> {
+        let mut best = Self::new(self.kind);
+        best.prefers_deeper = self.prefers_deeper;
+        for _ in count..self.len() {
+            best.push(self.pop().unwrap());
+        }
+        std::mem::replace(self, best).into_iter().collect()
+    }
+
+    /// Returns the bytes allocated for the arena and the queue, excluding the
+    /// bytes allocated by the states.
+    pub fn allocated_bytes(&self) -> usize {
+        self.arena.allocated_bytes() + self.queue.allocated_bytes()
+    }
+
+    /// Returns the states from the worst to the best, like
+    /// [`BinaryHeap::into_sorted_vec`].
+    pub fn into_sorted_vec(mut self) -> Vec<State> {
+        let mut states = Vec::with_capacity(self.len());
+        while let Some(state) = self.pop() {
+            states.push(state);
+        }
+        states.reverse();
+        states
+    }
+}
+
+impl From<Vec<State>> for OpenList {
+    fn from(states: Vec<State>) -> Self {
+        let mut open_list = Self::default();
+        for state in states {
+            open_list.push(state);
+        }
+        open_list
+    }
+}
+
+impl IntoIterator for OpenList {
+    type Item = State;
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<Option<State>>>;
+
+    /// Returns the states in arbitrary order.
+    fn into_iter(self) -> Self::IntoIter {
+        self.arena.slots.into_iter().flatten()
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::solve::path_finding::PathFinder;
+
+use itertools::Itertools;
+use nalgebra::Vector2;
+use soukoban::{
+    direction::Direction,
+    path_finding::{normalized_area, reachable_area},
+    Action, Actions, Map, Tiles,
+};
+
+/// A push that undoes a pull of the backward search.
+#[derive(Clone, Copy)]
+pub struct Push {
+    player_position: Vector2<i32>,
+    direction: Direction,
+}
+
+/// A state of the backward search, reached by pulling boxes off goals.
+struct BackwardState {
+    player_position: Vector2<i32>,
+    box_positions: HashSet<Vector2<i32>>,
+    /// Pushes undoing the pulls from a solved state, in the order of the pulls.
+    pushes: Vec<Push>,
+    priority: (usize, usize),
+}
+
+impl PartialEq for BackwardState {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for BackwardState {}
+
+impl Ord for BackwardState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).reverse()
+    }
+}
+
+impl PartialOrd for BackwardState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The outcome of expanding a state of the backward search.
+pub enum Step {
+    /// Every reachable state has been expanded.
+    Exhausted,
+    /// The successors of the state have been added to the search.
+    Expanded,
+    /// A successor is the goal.
+    Reached {
+        box_positions: HashSet<Vector2<i32>>,
+        pushes: Vec<Push>,
+    },
+}
+
+/// A search pulling boxes off goals towards the initial box positions.
+pub struct BackwardSearch {
+    initial_box_positions: Vec<Vector2<i32>>,
+    /// Squares from which a box can never be pulled to an initial box
+    /// position.
+    dead_squares: HashSet<Vector2<i32>>,
+    /// Expand the states with the fewest pulls first, instead of the states
+    /// closest to the initial box positions.
+    optimal: bool,
+    heap: BinaryHeap<BackwardState>,
+    /// Pushes of the reached states, keyed by [`key`].
+    visited: HashMap<u64, Vec<Push>>,
+}
+
+impl BackwardSearch {
+    /// Creates a new backward search starting from every solved state.
+    pub fn new(map: &Map, optimal: bool) -> Self {
+        let mut instance = Self {
+            initial_box_positions: map.box_positions().iter().copied().collect(),
+            dead_squares: Self::calculate_dead_squares(map),
+            optimal,
+            heap: BinaryHeap::new(),
+            visited: HashMap::new(),
+        };
+        // The player may be in any area separated by the boxes on goals.
+        let box_positions = map.goal_positions().clone();
+        for y in 0..map.dimensions().y {
+            for x in 0..map.dimensions().x {
+                let player_position = Vector2::new(x, y);
+                if !map[player_position].intersects(Tiles::Floor)
+                    || box_positions.contains(&player_position)
+                {
+                    continue;
+                }
+                let key = key(map, &box_positions, player_position);
+                if instance.visited.contains_key(&key) {
+                    continue;
+                }
+                instance.visited.insert(key, Vec::new());
+                let state =
+                    instance.backward_state(player_position, box_positions.clone(), Vec::new());
+                instance.heap.push(state);
+            }
+        }
+        instance
+    }
+
+    /// Returns the pushes from the reached state with the given key to a
+    /// solved state, in reverse order.
+    pub fn pushes(&self, key: u64) -> Option<&[Push]> {
+        self.visited.get(&key).map(Vec::as_slice)
+    }
+
+    /// Expands the best state, stopping at the first successor whose key is
+    /// the goal.
+    pub fn step(&mut self, map: &Map, mut is_goal: impl FnMut(u64) -> bool) -> Step {
+        let Some(state) = self.heap.pop() else {
+            return Step::Exhausted;
+        };
+        let player_reachable_area = reachable_area(state.player_position, |position| {
+            !can_block_player(map, &state.box_positions, position)
+        });
+        for box_position in state
+            .box_positions
+            .iter()
+            .sorted_by_key(|position| (position.y, position.x))
+        {
+            for pull_direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                // The player stands next to the box and steps back, dragging
+                // the box onto its previous position.
+                let new_box_position = box_position + &pull_direction.into();
+                let new_player_position = new_box_position + &pull_direction.into();
+                if !player_reachable_area.contains(&new_box_position)
+                    || can_block_player(map, &state.box_positions, new_player_position)
+                    || self.dead_squares.contains(&new_box_position)
+                {
+                    continue;
+                }
+
+                let mut new_box_positions = state.box_positions.clone();
+                new_box_positions.remove(box_position);
+                new_box_positions.insert(new_box_position);
+                let key = key(map, &new_box_positions, new_player_position);
+                if self.visited.contains_key(&key) {
+                    continue;
+                }
+                let offset: Vector2<i32> = pull_direction.into();
+                let mut new_pushes = state.pushes.clone();
+                new_pushes.push(Push {
+                    player_position: new_player_position,
+                    direction: Direction::try_from(-offset).unwrap(),
+                });
+                if is_goal(key) {
+                    return Step::Reached {
+                        box_positions: new_box_positions,
+                        pushes: new_pushes,
+                    };
+                }
+                self.visited.insert(key, new_pushes.clone());
+                let new_state =
+                    self.backward_state(new_player_position, new_box_positions, new_pushes);
+                self.heap.push(new_state);
+            }
+        }
+        Step::Expanded
+    }
+
+    /// Returns the number of reached states.
+    pub fn len(&self) -> usize {
+        self.visited.len()
+    }
+
+    /// Checks if there are no reached states.
+    pub fn is_empty(&self) -> bool {
+        self.visited.is_empty()
+    }
+
+    fn backward_state(
+        &self,
+        player_position: Vector2<i32>,
+        box_positions: HashSet<Vector2<i32>>,
+        pushes: Vec<Push>,
+    ) -> BackwardState {
+        // Each pull moves a single box by one square, so this never
+        // overestimates the remaining pulls.
+        let distance = box_positions
+            .iter()
+            .map(|box_position| {
+                self.initial_box_positions
+                    .iter()
+                    .map(|initial_position| (box_position - initial_position).abs().sum() as usize)
+                    .min()
+                    .unwrap_or(0)
+            })
+            .sum();
+        let priority = if self.optimal {
+            (pushes.len(), distance)
+        } else {
+            (distance, pushes.len())
+        };
+        BackwardState {
+            player_position,
+            box_positions,
+            pushes,
+            priority,
+        }
+    }
+
+    /// Calculates the squares no initial box position can be pushed to,
+    /// ignoring other boxes. Pulling a box onto them leads away from the
+    /// initial state for good.
+    fn calculate_dead_squares(map: &Map) -> HashSet<Vector2<i32>> {
+        let mut live_squares: HashSet<_> = map.box_positions().clone();
+        let mut queue: VecDeque<_> = live_squares.iter().copied().collect();
+        while let Some(box_position) = queue.pop_front() {
+            for push_direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let player_position = box_position - &push_direction.into();
+                let new_box_position = box_position + &push_direction.into();
+                if map[player_position].intersects(Tiles::Wall)
+                    || map[new_box_position].intersects(Tiles::Wall)
+                {
+                    continue;
+                }
+                if live_squares.insert(new_box_position) {
+                    queue.push_back(new_box_position);
+                }
+            }
+        }
+        let mut dead_squares = HashSet::new();
+        for y in 0..map.dimensions().y {
+            for x in 0..map.dimensions().x {
+                let position = Vector2::new(x, y);
+                if !map[position].intersects(Tiles::Wall) && !live_squares.contains(&position) {
+                    dead_squares.insert(position);
+                }
+            }
+        }
+        dead_squares
+    }
+}
+
+/// Appends the pushes undoing the pulls of the backward search, given in
+/// reverse order, to the actions, walking the player between them.
+pub fn stitch(
+    map: &Map,
+    mut actions: Actions,
+    mut player_position: Vector2<i32>,
+    mut box_positions: HashSet<Vector2<i32>>,
+    pushes: &[Push],
+) -> Actions {
+    let mut path_finder = PathFinder::new(map.dimensions());
+    for push in pushes.iter().rev() {
+        let path = path_finder
+            .find_path(player_position, push.player_position, |position| {
+                !can_block_player(map, &box_positions, position)
+            })
+            .unwrap();
+        actions.extend(
+            path.windows(2)
+                .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
+                .map(Action::Move),
+        );
+        actions.push(Action::Push(push.direction));
+
+        player_position = push.player_position + &push.direction.into();
+        box_positions.remove(&player_position);
+        box_positions.insert(player_position + &push.direction.into());
+    }
+    actions
+}
+
+/// Returns a key of the box positions and the normalized player position,
+/// independent of the order of the box positions.
+pub fn key(map: &Map, box_positions: &HashSet<Vector2<i32>>, player_position: Vector2<i32>) -> u64 {
+    let normalized_player_position =
+        normalized_area(&reachable_area(player_position, |position| {
+            !can_block_player(map, box_positions, position)
+        }))
+        .unwrap();
+    let mut hasher = DefaultHasher::new();
+    for position in box_positions
+        .iter()
+        .sorted_by_key(|position| (position.y, position.x))
+    {
+        position.hash(&mut hasher);
+    }
+    normalized_player_position.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn can_block_player(
+    map: &Map,
+    box_positions: &HashSet<Vector2<i32>>,
+    position: Vector2<i32>,
+) -> bool {
+    map[position].intersects(Tiles::Wall) || box_positions.contains(&position)
+}
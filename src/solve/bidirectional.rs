@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::solve::{
+    backward::{self, BackwardSearch, Step},
+    path::Path,
+    state::State,
+};
+
+use nalgebra::Vector2;
+use soukoban::{Actions, Map};
+
+/// A backward search pulling boxes off goals, run alongside the forward
+/// search until both reach the same normalized state.
+pub struct BidirectionalSearch {
+    backward: BackwardSearch,
+    /// Player positions and paths of the states reached by the forward
+    /// search.
+    forward: HashMap<u64, (Vector2<i32>, Path)>,
+}
+
+impl BidirectionalSearch {
+    pub fn new(map: &Map) -> Self {
+        Self {
+            backward: BackwardSearch::new(map, false),
+            forward: HashMap::new(),
+        }
+    }
+
+    /// Records a state reached by the forward search, and returns the
+    /// solution through it if the backward search has reached it.
+    pub fn visit_forward(&mut self, map: &Map, state: &State) -> Option<Actions> {
+        // The backward search keeps its boxes in hash sets.
+        let box_positions = state.box_positions.iter().collect();
+        let key = backward::key(map, &box_positions, state.player_position);
+        if let Some(pushes) = self.backward.pushes(key) {
+            return Some(backward::stitch(
+                map,
+                state.path.actions(map),
+                state.player_position,
+                box_positions,
+                pushes,
+            ));
+        }
+        self.forward
+            .entry(key)
+            .or_insert_with(|| (state.player_position, state.path.clone()));
+        None
+    }
+
+    /// Expands the best state of the backward search, and returns the
+    /// solution through a successor if the forward search has reached it.
+    pub fn step(&mut self, map: &Map) -> Option<Actions> {
+        let mut meeting_key = None;
+        let Step::Reached {
+            box_positions,
+            pushes,
+        } = self.backward.step(map, |key| {
+            meeting_key = Some(key);
+            self.forward.contains_key(&key)
+        })
+        else {
+            return None;
+        };
+        let (player_position, path) = &self.forward[&meeting_key.unwrap()];
+        Some(backward::stitch(
+            map,
+            path.actions(map),
+            *player_position,
+            box_positions,
+            &pushes,
+        ))
+    }
+
+    /// Returns the number of states reached by the backward search.
+    pub fn backward_states(&self) -> usize {
+        self.backward.len()
+    }
+}
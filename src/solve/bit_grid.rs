@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles};
+
+/// The number of words of a bit grid.
+const WORDS: usize = 4;
+
+/// A set of squares of a small level, with one bit per square in row-major
+/// order.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct BitGrid {
+    width: i32,
+    words: [u64; WORDS],
+}
+
+impl BitGrid {
+    /// The maximum number of squares of a level held by a bit grid.
+    pub const MAX_SQUARES: i32 = (WORDS * u64::BITS as usize) as i32;
+
+    /// Creates an empty grid for a level of the given dimensions, or `None`
+    /// if the level has too many squares.
+    pub fn new(dimensions: Vector2<i32>) -> Option<Self> {
+        (dimensions.x * dimensions.y <= Self::MAX_SQUARES).then_some(Self {
+            width: dimensions.x,
+            words: [0; WORDS],
+        })
+    }
+
+    /// Creates a grid of the squares of the map with any of the tiles, or
+    /// `None` if the map has too many squares.
+    pub fn with_tiles(map: &Map, tiles: Tiles) -> Option<Self> {
+        let mut grid = Self::new(map.dimensions())?;
+        for y in 0..map.dimensions().y {
+            for x in 0..map.dimensions().x {
+                let position = Vector2::new(x, y);
+                if map[position].intersects(tiles) {
+                    grid.insert(position);
+                }
+            }
+        }
+        Some(grid)
+    }
+
+    pub fn contains(&self, position: Vector2<i32>) -> bool {
+        self.index(position)
+            .is_some_and(|index| self.words[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// Adds the square. Returns whether it was not in the grid yet.
+    pub fn insert(&mut self, position: Vector2<i32>) -> bool {
+        let index = self.index(position).expect("position outside the grid");
+        let word = &mut self.words[index / 64];
+        let is_new = *word & (1 << (index % 64)) == 0;
+        *word |= 1 << (index % 64);
+        is_new
+    }
+
+    /// Removes the square. Returns whether it was in the grid.
+    pub fn remove(&mut self, position: Vector2<i32>) -> bool {
+        let Some(index) = self.index(position) else {
+            return false;
+        };
+        let word = &mut self.words[index / 64];
+        let was_present = *word & (1 << (index % 64)) != 0;
+        *word &= !(1 << (index % 64));
+        was_present
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// Returns the number of squares in both grids.
+    pub fn intersection_len(&self, other: &Self) -> usize {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(word, other_word)| (word & other_word).count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns an iterator over the squares in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = Vector2<i32>> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(word_index * 64 + bit)
+                })
+            })
+            .map(|index| Vector2::new(index as i32 % self.width, index as i32 / self.width))
+    }
+
+    /// Returns the index of the bit of the square, or `None` if it is
+    /// outside the grid.
+    fn index(&self, position: Vector2<i32>) -> Option<usize> {
+        if position.x < 0 || position.y < 0 || position.x >= self.width {
+            return None;
+        }
+        let index = position.y * self.width + position.x;
+        (index < Self::MAX_SQUARES).then_some(index as usize)
+    }
+}
+
+/// The squares of a small level which do not change during the search.
+pub struct SquareMasks {
+    pub walls: BitGrid,
+    pub goals: BitGrid,
+    /// Squares from which a box can never be pushed to a goal.
+    pub dead_squares: BitGrid,
+}
+
+impl SquareMasks {
+    /// Creates the masks of the map, or `None` if the map has too many
+    /// squares.
+    pub fn new(map: &Map, dead_squares: &HashSet<Vector2<i32>>) -> Option<Self> {
+        let mut dead_square_grid = BitGrid::new(map.dimensions())?;
+        for position in dead_squares {
+            dead_square_grid.insert(*position);
+        }
+        Some(Self {
+            walls: BitGrid::with_tiles(map, Tiles::Wall)?,
+            goals: BitGrid::with_tiles(map, Tiles::Goal)?,
+            dead_squares: dead_square_grid,
+        })
+    }
+}
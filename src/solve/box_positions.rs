@@ -0,0 +1,333 @@
+use std::{
+    hash::{Hash, Hasher},
+    iter,
+    sync::Arc,
+};
+
+use crate::solve::{bit_grid::BitGrid, square_grid::CellIndex};
+
+use itertools::{Either, EitherOrBoth, Itertools};
+use nalgebra::Vector2;
+use smallvec::SmallVec;
+use soukoban::Map;
+
+/// The number of cells whose box was moved that are stored next to the
+/// shared cells, beyond which the cells are copied.
+const MAX_MOVED_CELLS: usize = 8;
+
+/// The positions of the boxes of a state, iterated by row and column.
+///
+/// The boxes of a small level are stored as a bit grid. Otherwise, the sorted
+/// cell indices of the boxes of a state are shared with its successors, each
+/// of which only stores the few cells whose box was moved since, and boxes
+/// are found by binary search. Levels with more squares than cell indices
+/// store the positions themselves instead. Either way, comparing and hashing
+/// are independent of the order in which the boxes were moved and of the
+/// cells shared. The representation only depends on the dimensions of the
+/// level, so the box positions of a level are always stored alike.
+#[derive(Clone, Debug)]
+pub struct BoxPositions(Repr);
+
+#[derive(Clone, Debug)]
+enum Repr {
+    Bits(BitGrid),
+    Cells {
+        dimensions: Vector2<i32>,
+        /// The sorted cells of the boxes of an ancestor, shared with the other
+        /// states derived from it.
+        shared: Arc<[CellIndex]>,
+        /// The sorted cells a box has been moved onto or off since, on which
+        /// there is a box if and only if there is none in `shared`.
+        moved: SmallVec<[CellIndex; MAX_MOVED_CELLS]>,
+    },
+    Sorted(Vec<Vector2<i32>>),
+}
+
+impl PartialEq for BoxPositions {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Repr::Bits(grid), Repr::Bits(other_grid)) => grid == other_grid,
+            (
+                Repr::Cells { shared, moved, .. },
+                Repr::Cells {
+                    shared: other_shared,
+                    moved: other_moved,
+                    ..
+                },
+            ) => {
+                if Arc::ptr_eq(shared, other_shared) {
+                    moved == other_moved
+                } else {
+                    cells(shared, moved).eq(cells(other_shared, other_moved))
+                }
+            }
+            (Repr::Sorted(positions), Repr::Sorted(other_positions)) => {
+                positions == other_positions
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for BoxPositions {}
+
+impl Hash for BoxPositions {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            Repr::Bits(grid) => grid.hash(state),
+            Repr::Cells { shared, moved, .. } => {
+                for cell in cells(shared, moved) {
+                    cell.hash(state);
+                }
+            }
+            Repr::Sorted(positions) => positions.hash(state),
+        }
+    }
+}
+
+impl BoxPositions {
+    /// Creates the box positions of a level with the given dimensions.
+    pub fn new(
+        dimensions: Vector2<i32>,
+        positions: impl IntoIterator<Item = Vector2<i32>>,
+    ) -> Self {
+        let positions = positions.into_iter();
+        if let Some(mut grid) = BitGrid::new(dimensions) {
+            for position in positions {
+                grid.insert(position);
+            }
+            return Self(Repr::Bits(grid));
+        }
+        if CellIndex::fits(dimensions) {
+            let mut cells = positions
+                .map(|position| CellIndex::new(dimensions, position).expect("box out of bounds"))
+                .collect_vec();
+            cells.sort_unstable();
+            cells.dedup();
+            return Self(Repr::Cells {
+                dimensions,
+                shared: cells.into(),
+                moved: SmallVec::new(),
+            });
+        }
+        let mut positions = positions.collect_vec();
+        positions.sort_unstable_by_key(|position| (position.y, position.x));
+        positions.dedup();
+        Self(Repr::Sorted(positions))
+    }
+
+    /// Creates the initial box positions of the map.
+    pub fn of(map: &Map) -> Self {
+        Self::new(map.dimensions(), map.box_positions().iter().copied())
+    }
+
+    /// Checks if a box is at the position.
+    pub fn contains(&self, position: &Vector2<i32>) -> bool {
+        match &self.0 {
+            Repr::Bits(grid) => grid.contains(*position),
+            Repr::Cells {
+                dimensions,
+                shared,
+                moved,
+            } => CellIndex::new(*dimensions, *position)
+                .is_some_and(|cell| contains_cell(shared, moved, cell)),
+            Repr::Sorted(positions) => find(positions, position).is_ok(),
+        }
+    }
+
+    /// Adds a box at the position. Returns whether there was no box yet.
+    pub fn insert(&mut self, position: Vector2<i32>) -> bool {
+        match &mut self.0 {
+            Repr::Bits(grid) => grid.insert(position),
+            Repr::Cells {
+                dimensions,
+                shared,
+                moved,
+            } => {
+                let cell = CellIndex::new(*dimensions, position).expect("box out of bounds");
+                if contains_cell(shared, moved, cell) {
+                    return false;
+                }
+                move_box(shared, moved, cell);
+                true
+            }
+            Repr::Sorted(positions) => match find(positions, &position) {
+                Ok(_) => false,
+                Err(index) => {
+                    positions.insert(index, position);
+                    true
+                }
+            },
+        }
+    }
+
+    /// Removes the box at the position. Returns whether there was a box.
+    pub fn remove(&mut self, position: &Vector2<i32>) -> bool {
+        match &mut self.0 {
+            Repr::Bits(grid) => grid.remove(*position),
+            Repr::Cells {
+                dimensions,
+                shared,
+                moved,
+            } => {
+                let Some(cell) = CellIndex::new(*dimensions, *position)
+                    .filter(|cell| contains_cell(shared, moved, *cell))
+                else {
+                    return false;
+                };
+                move_box(shared, moved, cell);
+                true
+            }
+            Repr::Sorted(positions) => match find(positions, position) {
+                Ok(index) => {
+                    positions.remove(index);
+                    true
+                }
+                Err(_) => false,
+            },
+        }
+    }
+
+    /// Returns a copy of the box positions to derive successors from, which
+    /// shares their cells unless the cells moved by a push would not fit next
+    /// to them. In that case, the copy holds new cells, shared by the
+    /// successors.
+    pub fn shared(&self) -> Self {
+        match &self.0 {
+            // A push moves a box off a cell and onto another.
+            Repr::Cells {
+                dimensions,
+                shared,
+                moved,
+            } if moved.len() + 2 > MAX_MOVED_CELLS => Self(Repr::Cells {
+                dimensions: *dimensions,
+                shared: cells(shared, moved).collect(),
+                moved: SmallVec::new(),
+            }),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns an iterator over the box positions by row and column.
+    pub fn iter(&self) -> impl Iterator<Item = Vector2<i32>> + '_ {
+        match &self.0 {
+            Repr::Bits(grid) => Either::Left(grid.iter()),
+            Repr::Cells {
+                dimensions,
+                shared,
+                moved,
+            } => Either::Right(Either::Left(
+                cells(shared, moved).map(|cell| cell.position(dimensions.x)),
+            )),
+            Repr::Sorted(positions) => Either::Right(Either::Right(positions.iter().copied())),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Repr::Bits(grid) => grid.len(),
+            Repr::Cells { shared, moved, .. } => {
+                let moved_off = moved
+                    .iter()
+                    .filter(|cell| shared.binary_search(cell).is_ok())
+                    .count();
+                shared.len() + moved.len() - 2 * moved_off
+            }
+            Repr::Sorted(positions) => positions.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of boxes on the squares of the grid.
+    pub fn count_in(&self, squares: &BitGrid) -> usize {
+        match &self.0 {
+            Repr::Bits(grid) => grid.intersection_len(squares),
+            Repr::Cells { .. } => self
+                .iter()
+                .filter(|position| squares.contains(*position))
+                .count(),
+            Repr::Sorted(positions) => positions
+                .iter()
+                .filter(|position| squares.contains(**position))
+                .count(),
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        match &mut self.0 {
+            Repr::Bits(_) => {}
+            Repr::Cells { moved, .. } => moved.shrink_to_fit(),
+            Repr::Sorted(positions) => positions.shrink_to_fit(),
+        }
+    }
+
+    /// Returns an estimate of the bytes allocated for the boxes that are not
+    /// stored inline, counting an equal part of the cells shared by the box
+    /// positions of several states.
+    pub fn allocated_bytes(&self) -> usize {
+        match &self.0 {
+            Repr::Bits(_) => 0,
+            Repr::Cells { shared, .. } => {
+                (2 * size_of::<usize>() + shared.len() * size_of::<CellIndex>())
+                    / Arc::strong_count(shared)
+            }
+            Repr::Sorted(positions) => positions.capacity() * size_of::<Vector2<i32>>(),
+        }
+    }
+}
+
+/// Returns the sorted cells of the boxes, in `shared` or `moved` but not
+/// both.
+fn cells<'a>(
+    shared: &'a [CellIndex],
+    moved: &'a [CellIndex],
+) -> impl Iterator<Item = CellIndex> + 'a {
+    symmetric_difference(shared.iter().copied(), moved.iter().copied())
+}
+
+/// Returns the elements of either sorted iterator but not both, in order.
+fn symmetric_difference(
+    left: impl Iterator<Item = CellIndex>,
+    right: impl Iterator<Item = CellIndex>,
+) -> impl Iterator<Item = CellIndex> {
+    left.merge_join_by(right, Ord::cmp)
+        .filter_map(|cell| match cell {
+            EitherOrBoth::Left(cell) | EitherOrBoth::Right(cell) => Some(cell),
+            EitherOrBoth::Both(..) => None,
+        })
+}
+
+/// Checks if there is a box on the cell.
+fn contains_cell(shared: &[CellIndex], moved: &[CellIndex], cell: CellIndex) -> bool {
+    shared.binary_search(&cell).is_ok() != moved.binary_search(&cell).is_ok()
+}
+
+/// Moves a box onto or off the cell. Once too many cells have been moved, the
+/// cells are copied instead of being shared.
+fn move_box(
+    shared: &mut Arc<[CellIndex]>,
+    moved: &mut SmallVec<[CellIndex; MAX_MOVED_CELLS]>,
+    cell: CellIndex,
+) {
+    match moved.binary_search(&cell) {
+        Ok(index) => {
+            moved.remove(index);
+        }
+        Err(_) if moved.len() == MAX_MOVED_CELLS => {
+            *shared = symmetric_difference(cells(shared, moved), iter::once(cell)).collect();
+            moved.clear();
+        }
+        Err(index) => moved.insert(index, cell),
+    }
+}
+
+/// Returns the index of the box at the position in the sorted positions, or
+/// the index at which it would be inserted.
+fn find(positions: &[Vector2<i32>], position: &Vector2<i32>) -> Result<usize, usize> {
+    positions.binary_search_by_key(&(position.y, position.x), |position| {
+        (position.y, position.x)
+    })
+}
@@ -0,0 +1,305 @@
+pub mod patterns;
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::solve::box_positions::BoxPositions;
+
+use itertools::Itertools;
+use nalgebra::Vector2;
+use soukoban::{
+    direction::Direction,
+    path_finding::{normalized_area, reachable_area},
+    Map, Tiles,
+};
+
+/// Checks if the box is part of a frozen group with at least one box off goal.
+///
+/// A frozen group whose boxes all sit on goals is not a deadlock.
+pub fn is_freeze_deadlock(
+    map: &Map,
+    box_position: Vector2<i32>,
+    box_positions: &BoxPositions,
+) -> bool {
+    frozen_boxes(map, box_position, box_positions)
+        .iter()
+        .any(|position| !map[*position].intersects(Tiles::Goal))
+}
+
+/// Returns the group of boxes that can never be moved again, including the
+/// box and the adjacent boxes it freezes, or an empty vector if the box can
+/// still be moved.
+pub fn frozen_boxes(
+    map: &Map,
+    box_position: Vector2<i32>,
+    box_positions: &BoxPositions,
+) -> Vec<Vector2<i32>> {
+    let mut walls = HashSet::new();
+    let mut frozen_boxes = Vec::new();
+    if !is_frozen(
+        map,
+        box_position,
+        box_positions,
+        &mut walls,
+        &mut frozen_boxes,
+    ) {
+        return frozen_boxes;
+    }
+
+    // Adjacent boxes may be frozen by the box without being needed to freeze it.
+    let mut index = 0;
+    while index < frozen_boxes.len() {
+        let frozen_box_position = frozen_boxes[index];
+        for direction in [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ] {
+            let neighbor = frozen_box_position + &direction.into();
+            if box_positions.contains(&neighbor) && !walls.contains(&neighbor) {
+                is_frozen(map, neighbor, box_positions, &mut walls, &mut frozen_boxes);
+            }
+        }
+        index += 1;
+    }
+    frozen_boxes
+}
+
+/// Checks if the box is blocked along both axes.
+///
+/// While its neighbors are checked, the box is treated as a wall. Boxes found
+/// to be frozen stay walls and are appended to `frozen_boxes`, unless the box
+/// whose assumption they rely on turns out to be movable.
+fn is_frozen(
+    map: &Map,
+    box_position: Vector2<i32>,
+    box_positions: &BoxPositions,
+    walls: &mut HashSet<Vector2<i32>>,
+    frozen_boxes: &mut Vec<Vector2<i32>>,
+) -> bool {
+    walls.insert(box_position);
+    let frozen_boxes_len = frozen_boxes.len();
+    let frozen = [Direction::Up, Direction::Left]
+        .into_iter()
+        .all(|direction| {
+            let neighbors = [
+                box_position + &direction.into(),
+                box_position - &direction.into(),
+            ];
+            neighbors
+                .iter()
+                .any(|neighbor| map[*neighbor].intersects(Tiles::Wall) || walls.contains(neighbor))
+                || neighbors.iter().any(|neighbor| {
+                    box_positions.contains(neighbor)
+                        && is_frozen(map, *neighbor, box_positions, walls, frozen_boxes)
+                })
+        });
+    if frozen {
+        frozen_boxes.push(box_position);
+    } else {
+        walls.remove(&box_position);
+        for position in frozen_boxes.drain(frozen_boxes_len..) {
+            walls.remove(&position);
+        }
+    }
+    frozen
+}
+
+/// A straight corridor closed at one end. Boxes pushed into it can only be
+/// pushed deeper, unless the player is already behind them.
+#[derive(Clone)]
+pub struct DeadEndCorridor {
+    /// Squares of the corridor, from the closed end to the entrance.
+    pub squares: Vec<Vector2<i32>>,
+    /// Indices of the goals in `squares`, in ascending order.
+    pub goal_depths: Vec<usize>,
+}
+
+impl DeadEndCorridor {
+    /// Checks if the boxes in the corridor can no longer all be pushed onto
+    /// distinct goals in the corridor.
+    ///
+    /// The deepest box can only take the deepest goal not above it, the next
+    /// box the next one, and so on.
+    pub fn is_deadlock(&self, box_positions: &BoxPositions, player_position: Vector2<i32>) -> bool {
+        let box_depths: Vec<_> = self
+            .squares
+            .iter()
+            .positions(|position| box_positions.contains(position))
+            .collect();
+        let Some(shallowest_box_depth) = box_depths.last() else {
+            return false;
+        };
+        if self
+            .squares
+            .iter()
+            .position(|position| *position == player_position)
+            .is_some_and(|player_depth| player_depth < *shallowest_box_depth)
+        {
+            return false;
+        }
+        box_depths.len() > self.goal_depths.len()
+            || box_depths
+                .iter()
+                .zip(&self.goal_depths)
+                .any(|(box_depth, goal_depth)| goal_depth > box_depth)
+    }
+}
+
+/// Returns the dead-end corridors of the level.
+pub fn dead_end_corridors(map: &Map) -> Vec<DeadEndCorridor> {
+    let is_wall = |position: Vector2<i32>| map[position].intersects(Tiles::Wall);
+
+    let mut corridors = Vec::new();
+    for x in 1..map.dimensions().x - 1 {
+        for y in 1..map.dimensions().y - 1 {
+            let end = Vector2::new(x, y);
+            if !map[end].intersects(Tiles::Floor) {
+                continue;
+            }
+            for direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                let offset: Vector2<i32> = direction.into();
+                let side = Vector2::new(offset.y, offset.x);
+                if !is_wall(end - offset) {
+                    continue;
+                }
+                let mut squares = Vec::new();
+                let mut position = end;
+                while map[position].intersects(Tiles::Floor)
+                    && is_wall(position + side)
+                    && is_wall(position - side)
+                {
+                    squares.push(position);
+                    position += offset;
+                }
+                // The corridor must be entered from an open square.
+                if squares.is_empty() || !map[position].intersects(Tiles::Floor) {
+                    continue;
+                }
+                let goal_depths = squares
+                    .iter()
+                    .positions(|position| map[*position].intersects(Tiles::Goal))
+                    .collect();
+                corridors.push(DeadEndCorridor {
+                    squares,
+                    goal_depths,
+                });
+            }
+        }
+    }
+    corridors
+}
+
+/// An area the player cannot reach, together with the boxes inside it and on
+/// its boundary.
+pub struct Corral {
+    pub floor_positions: HashSet<Vector2<i32>>,
+    pub box_positions: BoxPositions,
+}
+
+/// Returns the corrals of the level.
+pub fn corrals(
+    map: &Map,
+    box_positions: &BoxPositions,
+    player_reachable_area: &HashSet<Vector2<i32>>,
+) -> Vec<Corral> {
+    let mut corrals = Vec::new();
+    let mut visited = HashSet::new();
+    for x in 0..map.dimensions().x {
+        for y in 0..map.dimensions().y {
+            let position = Vector2::new(x, y);
+            if !map[position].intersects(Tiles::Floor)
+                || box_positions.contains(&position)
+                || player_reachable_area.contains(&position)
+                || visited.contains(&position)
+            {
+                continue;
+            }
+            // Boxes connect the area to the boxes enclosing it.
+            let area = reachable_area(position, |position| {
+                map[position].intersects(Tiles::Floor) && !player_reachable_area.contains(&position)
+            });
+            visited.extend(area.iter().copied());
+            let (corral_box_positions, floor_positions): (Vec<_>, Vec<_>) = area
+                .into_iter()
+                .partition(|position| box_positions.contains(position));
+            corrals.push(Corral {
+                floor_positions: floor_positions.into_iter().collect(),
+                box_positions: BoxPositions::new(map.dimensions(), corral_box_positions),
+            });
+        }
+    }
+    corrals
+}
+
+/// Checks if the boxes of the corral can never all be pushed to goals.
+///
+/// Only the boxes of the corral are considered, since other boxes can only get
+/// in the way. The check gives up and returns `false` once the player can enter
+/// the corral, or more than `max_states` states have been visited.
+pub fn is_corral_deadlock(
+    map: &Map,
+    dead_squares: &HashSet<Vector2<i32>>,
+    player_position: Vector2<i32>,
+    corral: &Corral,
+    max_states: usize,
+) -> bool {
+    let player_reachable_area = |player_position: Vector2<i32>, box_positions: &BoxPositions| {
+        reachable_area(player_position, |position| {
+            map[position].intersects(Tiles::Floor) && !box_positions.contains(&position)
+        })
+    };
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back((corral.box_positions.clone(), player_position));
+    while let Some((box_positions, player_position)) = queue.pop_front() {
+        if box_positions
+            .iter()
+            .all(|position| map[position].intersects(Tiles::Goal))
+        {
+            return false;
+        }
+        let area = player_reachable_area(player_position, &box_positions);
+        if !area.is_disjoint(&corral.floor_positions) {
+            return false;
+        }
+        if !visited.insert((box_positions.clone(), normalized_area(&area).unwrap())) {
+            continue;
+        }
+        if visited.len() > max_states {
+            return false;
+        }
+
+        for box_position in box_positions.iter() {
+            for push_direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                let new_box_position = box_position + &push_direction.into();
+                if !area.contains(&(box_position - &push_direction.into()))
+                    || !map[new_box_position].intersects(Tiles::Floor)
+                    || box_positions.contains(&new_box_position)
+                    || dead_squares.contains(&new_box_position)
+                {
+                    continue;
+                }
+                let mut new_box_positions = box_positions.clone();
+                new_box_positions.remove(&box_position);
+                new_box_positions.insert(new_box_position);
+                if is_freeze_deadlock(map, new_box_position, &new_box_positions) {
+                    continue;
+                }
+                queue.push_back((new_box_positions, box_position));
+            }
+        }
+    }
+    true
+}
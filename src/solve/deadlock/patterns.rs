@@ -0,0 +1,146 @@
+use std::sync::LazyLock;
+
+use crate::solve::box_positions::BoxPositions;
+
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles};
+
+/// Pattern families, one row per string. Their rotations and reflections are
+/// precomputed in [`PATTERNS`].
+///
+/// - `#`: A wall.
+/// - `$`: A box.
+/// - `?`: A wall or a box.
+/// - ` `: Anything.
+const FAMILIES: [&[&str]; 2] = [
+    // A square of walls and boxes.
+    &["??", "??"],
+    // Two adjacent boxes, each blocked by a wall on the opposite side.
+    &[" $#", "#$ "],
+];
+
+/// All rotations and reflections of the pattern families.
+static PATTERNS: LazyLock<Vec<Pattern>> = LazyLock::new(|| {
+    let mut patterns: Vec<Pattern> = Vec::new();
+    for family in FAMILIES {
+        let mut pattern = Pattern::parse(family);
+        for _ in 0..4 {
+            for variant in [pattern.reflected(), pattern.clone()] {
+                if !patterns.contains(&variant) {
+                    patterns.push(variant);
+                }
+            }
+            pattern = pattern.rotated();
+        }
+    }
+    patterns
+});
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+    Any,
+    Wall,
+    Box,
+    Occupied,
+}
+
+/// A rectangular pattern of walls and boxes.
+#[derive(Clone, PartialEq)]
+struct Pattern {
+    dimensions: Vector2<i32>,
+    cells: Vec<Cell>,
+}
+
+impl Pattern {
+    fn parse(rows: &[&str]) -> Self {
+        let cells: Vec<_> = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|tile| match tile {
+                '#' => Cell::Wall,
+                '$' => Cell::Box,
+                '?' => Cell::Occupied,
+                _ => Cell::Any,
+            })
+            .collect();
+        let dimensions = Vector2::new(rows[0].len() as i32, rows.len() as i32);
+        debug_assert_eq!(cells.len() as i32, dimensions.x * dimensions.y);
+        Self { dimensions, cells }
+    }
+
+    /// Returns the pattern rotated clockwise by 90 degrees.
+    fn rotated(&self) -> Self {
+        let dimensions = Vector2::new(self.dimensions.y, self.dimensions.x);
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                cells.push(self[Vector2::new(y, self.dimensions.y - 1 - x)]);
+            }
+        }
+        Self { dimensions, cells }
+    }
+
+    /// Returns the pattern reflected horizontally.
+    fn reflected(&self) -> Self {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..self.dimensions.y {
+            for x in 0..self.dimensions.x {
+                cells.push(self[Vector2::new(self.dimensions.x - 1 - x, y)]);
+            }
+        }
+        Self {
+            dimensions: self.dimensions,
+            cells,
+        }
+    }
+
+    fn positions(&self) -> impl Iterator<Item = Vector2<i32>> + '_ {
+        (0..self.dimensions.y).flat_map(|y| (0..self.dimensions.x).map(move |x| Vector2::new(x, y)))
+    }
+
+    /// Checks if the pattern matches with its top-left corner at `origin`.
+    fn matches(&self, map: &Map, origin: Vector2<i32>, box_positions: &BoxPositions) -> bool {
+        let mut has_box_off_goal = false;
+        for offset in self.positions() {
+            let position = origin + offset;
+            if !map.in_bounds(position) {
+                return false;
+            }
+            let is_wall = map[position].intersects(Tiles::Wall);
+            let is_box = box_positions.contains(&position);
+            let is_match = match self[offset] {
+                Cell::Any => true,
+                Cell::Wall => is_wall,
+                Cell::Box => is_box,
+                Cell::Occupied => is_wall || is_box,
+            };
+            if !is_match {
+                return false;
+            }
+            has_box_off_goal |= is_box && !map[position].intersects(Tiles::Goal);
+        }
+        has_box_off_goal
+    }
+}
+
+impl std::ops::Index<Vector2<i32>> for Pattern {
+    type Output = Cell;
+
+    fn index(&self, position: Vector2<i32>) -> &Cell {
+        &self.cells[(position.y * self.dimensions.x + position.x) as usize]
+    }
+}
+
+/// Checks if the box is part of a deadlock pattern with at least one box off
+/// goal.
+///
+/// This is cheaper than [`super::is_freeze_deadlock`], but only detects a
+/// subset of freeze deadlocks.
+pub fn is_deadlock(map: &Map, box_position: Vector2<i32>, box_positions: &BoxPositions) -> bool {
+    PATTERNS.iter().any(|pattern| {
+        pattern
+            .positions()
+            .filter(|offset| matches!(pattern[*offset], Cell::Box | Cell::Occupied))
+            .any(|offset| pattern.matches(map, box_position - offset, box_positions))
+    })
+}
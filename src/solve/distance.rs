@@ -0,0 +1,75 @@
+use std::ops::Index;
+
+use crate::solve::square_grid::square_index;
+
+use nalgebra::Vector2;
+
+/// Distances from every position of the level to a single goal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GoalDistanceGrid {
+    goal_position: Vector2<i32>,
+    dimensions: Vector2<i32>,
+    distances: Vec<Option<usize>>,
+}
+
+impl GoalDistanceGrid {
+    /// Creates a new grid where no position can reach the goal.
+    pub fn new(goal_position: Vector2<i32>, dimensions: Vector2<i32>) -> Self {
+        Self {
+            goal_position,
+            dimensions,
+            distances: vec![None; (dimensions.x * dimensions.y) as usize],
+        }
+    }
+
+    /// Returns the position of the goal.
+    pub fn goal_position(&self) -> Vector2<i32> {
+        self.goal_position
+    }
+
+    /// Returns the distance from the position to the goal, or `None` if the
+    /// goal cannot be reached from the position.
+    pub fn get(&self, position: Vector2<i32>) -> Option<usize> {
+        self.index_of(position)
+            .and_then(|index| self.distances[index])
+    }
+
+    /// Sets the distance from the position to the goal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is out of bounds.
+    pub fn set(&mut self, position: Vector2<i32>, distance: usize) {
+        let index = self.index_of(position).expect("position out of bounds");
+        self.distances[index] = Some(distance);
+    }
+
+    /// Returns an iterator over the positions that can reach the goal and
+    /// their distances.
+    pub fn iter(&self) -> impl Iterator<Item = (Vector2<i32>, usize)> + '_ {
+        let width = self.dimensions.x.max(1) as usize;
+        self.distances
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, distance)| {
+                distance.map(|distance| {
+                    (
+                        Vector2::new((index % width) as i32, (index / width) as i32),
+                        distance,
+                    )
+                })
+            })
+    }
+
+    fn index_of(&self, position: Vector2<i32>) -> Option<usize> {
+        square_index(self.dimensions, position)
+    }
+}
+
+impl Index<Vector2<i32>> for GoalDistanceGrid {
+    type Output = Option<usize>;
+
+    fn index(&self, position: Vector2<i32>) -> &Self::Output {
+        &self.distances[self.index_of(position).expect("position out of bounds")]
+    }
+}
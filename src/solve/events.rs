@@ -0,0 +1,131 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::solve::solver::SolveError;
+
+/// An event of a search, sent to another thread through [`event_channel`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SolveEvent {
+    /// The search has started.
+    Started,
+    /// The search is running, sent as often as the progress interval of the
+    /// options.
+    Progress {
+        visited: usize,
+        open: usize,
+        /// The lowest lower bound of the expanded states and the best open
+        /// state.
+        best_lower_bound: usize,
+        /// The most boxes on goals of the expanded states.
+        boxes_on_goals: usize,
+    },
+    /// A solution has been found.
+    SolutionFound { moves: usize, pushes: usize },
+    /// The search has finished, with the error that stopped it if it found
+    /// no solution.
+    Finished { error: Option<SolveError> },
+}
+
+/// The events sent and not yet received.
+struct Queue {
+    events: VecDeque<SolveEvent>,
+    capacity: usize,
+    /// The number of events dropped because the queue was full.
+    dropped: usize,
+    disconnected: bool,
+}
+
+type Shared = Arc<(Mutex<Queue>, Condvar)>;
+
+/// Creates a channel holding at most `capacity` events. When it is full, the
+/// oldest event is dropped, so that the search never waits for the receiver.
+pub fn event_channel(capacity: usize) -> (EventSender, EventReceiver) {
+    assert!(capacity > 0, "the capacity must be positive");
+    let shared = Arc::new((
+        Mutex::new(Queue {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+            disconnected: false,
+        }),
+        Condvar::new(),
+    ));
+    (
+        EventSender {
+            shared: shared.clone(),
+        },
+        EventReceiver { shared },
+    )
+}
+
+/// The sending half of an event channel, attached to a solver.
+pub struct EventSender {
+    shared: Shared,
+}
+
+impl EventSender {
+    /// Sends the event without blocking, dropping the oldest event if the
+    /// channel is full.
+    pub fn send(&self, event: SolveEvent) {
+        let (queue, condvar) = &*self.shared;
+        let mut queue = queue.lock().unwrap();
+        if queue.events.len() == queue.capacity {
+            queue.events.pop_front();
+            queue.dropped += 1;
+        }
+        queue.events.push_back(event);
+        condvar.notify_one();
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let (queue, condvar) = &*self.shared;
+        queue.lock().unwrap().disconnected = true;
+        condvar.notify_all();
+    }
+}
+
+/// The receiving half of an event channel.
+pub struct EventReceiver {
+    shared: Shared,
+}
+
+impl EventReceiver {
+    /// Waits for the next event. Returns `None` once the sender has been
+    /// dropped and every event has been received.
+    pub fn recv(&self) -> Option<SolveEvent> {
+        let (queue, condvar) = &*self.shared;
+        let mut queue = condvar
+            .wait_while(queue.lock().unwrap(), |queue| {
+                queue.events.is_empty() && !queue.disconnected
+            })
+            .unwrap();
+        queue.events.pop_front()
+    }
+
+    /// Waits for the next event for at most the timeout. Returns `None` if
+    /// no event has been sent in time or the sender has been dropped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<SolveEvent> {
+        let (queue, condvar) = &*self.shared;
+        let (mut queue, _) = condvar
+            .wait_timeout_while(queue.lock().unwrap(), timeout, |queue| {
+                queue.events.is_empty() && !queue.disconnected
+            })
+            .unwrap();
+        queue.events.pop_front()
+    }
+
+    /// Returns the next event if one has been sent, without waiting.
+    pub fn try_recv(&self) -> Option<SolveEvent> {
+        self.shared.0.lock().unwrap().events.pop_front()
+    }
+
+    /// Returns the number of events dropped because the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.shared.0.lock().unwrap().dropped
+    }
+}
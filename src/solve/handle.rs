@@ -0,0 +1,115 @@
+use std::{
+    panic,
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::solve::{cancellation::CancellationToken, solver::*};
+
+/// A search running on a worker thread, created by [`Solver::spawn`].
+///
+/// Dropping the handle cancels the search and waits for the worker to exit.
+pub struct SolveHandle {
+    cancellation: CancellationToken,
+    progress: Arc<Mutex<ProgressInfo>>,
+    receiver: Receiver<Result<Solution, SolveError>>,
+    result: Option<Result<Solution, SolveError>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Creates the solver on a worker thread and searches until it finds a
+/// solution, reaches a limit or is cancelled through the token.
+pub(crate) fn spawn(
+    builder: SolverBuilder,
+    cancellation: CancellationToken,
+    mut progress_callback: Option<ProgressCallback>,
+) -> SolveHandle {
+    let progress = Arc::new(Mutex::new(ProgressInfo::default()));
+    let (sender, receiver) = mpsc::channel();
+    let worker = {
+        let progress = progress.clone();
+        thread::Builder::new()
+            .name("solver".to_string())
+            .spawn(move || {
+                let builder = builder.on_progress(move |info| {
+                    *progress.lock().unwrap() = *info;
+                    if let Some(progress_callback) = &mut progress_callback {
+                        progress_callback(info);
+                    }
+                });
+                let result = builder.build_unlimited().and_then(|mut solver| {
+                    let timeout = solver.options().timeout.unwrap_or(Duration::MAX);
+                    solver.search_solution(timeout)
+                });
+                // The handle may have been dropped.
+                let _ = sender.send(result);
+            })
+            .expect("failed to spawn the solver thread")
+    };
+    SolveHandle {
+        cancellation,
+        progress,
+        receiver,
+        result: None,
+        worker: Some(worker),
+    }
+}
+
+impl SolveHandle {
+    /// Returns the result of the search if it has finished, without waiting.
+    pub fn poll(&mut self) -> Option<Result<Solution, SolveError>> {
+        if self.result.is_none() {
+            match self.receiver.try_recv() {
+                Ok(result) => self.result = Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => self.resume_panic(),
+            }
+        }
+        self.result.clone()
+    }
+
+    /// Waits for the search to finish and returns its result.
+    pub fn join(mut self) -> Result<Solution, SolveError> {
+        if let Some(result) = self.result.take() {
+            return result;
+        }
+        match self.receiver.recv() {
+            Ok(result) => result,
+            Err(_) => self.resume_panic(),
+        }
+    }
+
+    /// Returns the progress last reported by the search, as often as the
+    /// progress interval of the options.
+    pub fn progress(&self) -> ProgressInfo {
+        *self.progress.lock().unwrap()
+    }
+
+    /// Cancels the search, which then finishes with
+    /// [`SolveError::Cancelled`] unless it has already finished.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Propagates the panic of the worker, which has exited without sending
+    /// a result.
+    fn resume_panic(&mut self) -> ! {
+        match self.worker.take().map(JoinHandle::join) {
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            _ => panic!("the solver thread exited without a result"),
+        }
+    }
+}
+
+impl Drop for SolveHandle {
+    fn drop(&mut self) {
+        self.cancel();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
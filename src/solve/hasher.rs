@@ -0,0 +1,20 @@
+use std::collections;
+
+/// The hasher of the maps and sets filled during the search.
+///
+/// Their keys are integers and positions computed by the solver, which FxHash
+/// hashes several times faster than SipHash. The `siphash` feature switches
+/// back to the standard hasher, which resists HashDoS attacks with keys
+/// chosen by an attacker.
+#[cfg(not(feature = "siphash"))]
+pub type SolverHasher = rustc_hash::FxBuildHasher;
+
+/// The hasher of the maps and sets filled during the search.
+#[cfg(feature = "siphash")]
+pub type SolverHasher = std::hash::RandomState;
+
+/// A hash map using [`SolverHasher`].
+pub type HashMap<K, V> = collections::HashMap<K, V, SolverHasher>;
+
+/// A hash set using [`SolverHasher`].
+pub type HashSet<T> = collections::HashSet<T, SolverHasher>;
@@ -0,0 +1,79 @@
+use std::{collections::HashSet, time::Duration};
+
+use nalgebra::Vector2;
+use soukoban::{Action, Actions};
+
+use crate::solve::solver::*;
+
+/// The next push recommended from a position, returned by [`Search::hint`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Hint {
+    /// The moves of the player to the box, followed by the push.
+    pub actions: Actions,
+    /// The pushes left after the push: those of the solution if `solved`,
+    /// otherwise the pushes to the best state reached plus its lower bound.
+    pub remaining_pushes: usize,
+    /// Whether the push starts a solution found within the budget, rather
+    /// than the path to the most promising state.
+    pub solved: bool,
+}
+
+/// Runs a `Fast` search from the position for at most the budget, and
+/// returns the first push of the solution, or of the path to the best open
+/// state if no solution is found in time.
+///
+/// Returns `None` if the position is invalid, already solved, or cannot be
+/// solved.
+pub(crate) fn hint(
+    solver: &Search,
+    player_position: Vector2<i32>,
+    box_positions: &HashSet<Vector2<i32>>,
+    budget: Duration,
+) -> Option<Hint> {
+    let options = SolverOptions {
+        strategy: Strategy::Fast,
+        lower_bound_method: solver.options().lower_bound_method,
+        heuristics: solver.options().heuristics.clone(),
+        ..Default::default()
+    };
+    let mut hint_solver = solver
+        .solver_at(player_position, box_positions, options)
+        .ok()?;
+    let mut result = hint_solver.search_for(budget);
+    // The budget may run out before the first state is expanded, which
+    // leaves no push to recommend.
+    if matches!(result, Err(SolveError::Timeout { .. })) && hint_solver.expanded_states() == 0 {
+        match hint_solver.step().ok()? {
+            SearchStep::Solved(solution) => result = Ok(solution),
+            SearchStep::Exhausted => return None,
+            SearchStep::Expanded(_) => {}
+        }
+    }
+    let (actions, remaining_pushes, solved) = match result {
+        Ok(solution) => {
+            let pushes = solution.pushes();
+            (solution, pushes, true)
+        }
+        Err(SolveError::NoSolution { .. } | SolveError::InvalidOptions(_)) => return None,
+        Err(_) => {
+            let best_state = hint_solver.best_state()?;
+            let remaining_pushes = best_state.path.pushes()
+                + best_state.lower_bound(hint_solver.analysis(), hint_solver.context())?;
+            (
+                best_state.actions_with_moves(hint_solver.analysis(), hint_solver.context()),
+                remaining_pushes,
+                false,
+            )
+        }
+    };
+    let push_index = actions
+        .iter()
+        .position(|action| matches!(action, Action::Push(_)))?;
+    let mut hint_actions = Actions::new();
+    hint_actions.extend(actions.iter().take(push_index + 1).copied());
+    Some(Hint {
+        actions: hint_actions,
+        remaining_pushes: remaining_pushes - 1,
+        solved,
+    })
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use crate::solve::{solver::*, state::State};
+
+use soukoban::Actions;
+
+/// A state on the current path of the depth-first search, and its successors
+/// left to search.
+struct Frame {
+    state: State,
+    normalized_hash: u64,
+    successors: std::vec::IntoIter<State>,
+}
+
+/// An iterative deepening A* search, which only keeps the current path and a
+/// bounded transposition table in memory.
+///
+/// The search can be paused on timeout and resumed by the next call.
+pub struct IdaStar {
+    root: State,
+    /// States whose cost exceeds the bound are not expanded.
+    bound: usize,
+    /// The minimum cost of the states exceeding the bound.
+    next_bound: Option<usize>,
+    stack: Vec<Frame>,
+    path: HashSet<u64>,
+    /// The minimum costs from the root of the states expanded in the current
+    /// iteration.
+    transpositions: HashMap<u64, usize>,
+    capacity: usize,
+    iteration_expanded_states: usize,
+}
+
+impl IdaStar {
+    /// Creates a new search starting from the root state, whose transposition
+    /// table holds at most `capacity` states.
+    pub fn new(root: State, capacity: usize, solver: &Search) -> Self {
+        let (_, bound) = Self::costs(&root, solver);
+        let mut instance = Self {
+            root,
+            bound,
+            next_bound: None,
+            stack: Vec::new(),
+            path: HashSet::new(),
+            transpositions: HashMap::new(),
+            capacity,
+            iteration_expanded_states: 0,
+        };
+        instance.push(instance.root.clone(), solver);
+        instance
+    }
+
+    /// Continues the search until a solution is found, the search space is
+    /// exhausted or the timeout is reached.
+    ///
+    /// The number of expanded states is added to `expanded_states`.
+    pub fn search(
+        &mut self,
+        solver: &Search,
+        timer: Instant,
+        timeout: Duration,
+        expanded_states: &mut usize,
+    ) -> Result<Actions, SolveError> {
+        if self.root.is_solved(solver.analysis(), solver.context()) {
+            return Ok(self
+                .root
+                .actions_with_moves(solver.analysis(), solver.context()));
+        }
+        loop {
+            while let Some(frame) = self.stack.last_mut() {
+                solver.check_limits(timer, timeout, solver.expanded_states() + *expanded_states)?;
+                let Some(successor) = frame.successors.next() else {
+                    let frame = self.stack.pop().unwrap();
+                    self.path.remove(&frame.normalized_hash);
+                    continue;
+                };
+                let (cost, estimated_cost) = Self::costs(&successor, solver);
+                if estimated_cost > self.bound {
+                    self.next_bound = Some(
+                        self.next_bound
+                            .map_or(estimated_cost, |bound| bound.min(estimated_cost)),
+                    );
+                    continue;
+                }
+                if successor.is_solved(solver.analysis(), solver.context()) {
+                    return Ok(successor.actions_with_moves(solver.analysis(), solver.context()));
+                }
+                let normalized_hash =
+                    successor.normalized_hash(solver.analysis(), solver.context());
+                if self.path.contains(&normalized_hash) {
+                    continue;
+                }
+                // A state reached at a lower cost has already been searched
+                // with a larger budget.
+                let is_full = self.transpositions.len() >= self.capacity;
+                match self.transpositions.get_mut(&normalized_hash) {
+                    Some(minimum_cost) if *minimum_cost <= cost => continue,
+                    Some(minimum_cost) => *minimum_cost = cost,
+                    None if !is_full => {
+                        self.transpositions.insert(normalized_hash, cost);
+                    }
+                    None => {}
+                }
+                *expanded_states += 1;
+                self.iteration_expanded_states += 1;
+                self.push(successor, solver);
+            }
+
+            let Some(next_bound) = self.next_bound.take() else {
+                return Err(solver.no_solution(NoSolutionReason::SearchExhausted {
+                    visited_states: self.iteration_expanded_states,
+                }));
+            };
+            self.bound = next_bound;
+            self.transpositions.clear();
+            self.iteration_expanded_states = 0;
+            self.push(self.root.clone(), solver);
+        }
+    }
+
+    /// Returns the deepest state on the current path.
+    pub fn current_state(&self) -> Option<&State> {
+        self.stack.last().map(|frame| &frame.state)
+    }
+
+    /// Returns the number of states kept in memory.
+    pub fn stored_states(&self) -> usize {
+        self.transpositions.len() + self.stack.len()
+    }
+
+    fn push(&mut self, state: State, solver: &Search) {
+        let normalized_hash = state.normalized_hash(solver.analysis(), solver.context());
+        self.path.insert(normalized_hash);
+        self.stack.push(Frame {
+            successors: state
+                .successors(solver.analysis(), solver.context())
+                .into_iter(),
+            state,
+            normalized_hash,
+        });
+    }
+
+    /// Returns the cost of the state from the root, and its estimated cost to
+    /// the solution.
+    fn costs(state: &State, solver: &Search) -> (usize, usize) {
+        let lower_bound = state
+            .lower_bound(solver.analysis(), solver.context())
+            .unwrap();
+        let cost = match solver.strategy() {
+            Strategy::OptimalMovePush => state.path.moves(),
+            _ => state.path.pushes(),
+        };
+        let estimated_lower_bound = match solver.strategy() {
+            // Rounding down keeps the weighted lower bound within the weight
+            // of the optimal pushes.
+            Strategy::Weighted(weight) => (weight.max(1.0) as f64 * lower_bound as f64) as usize,
+            _ => lower_bound,
+        };
+        (cost, cost + estimated_lower_bound)
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use nalgebra::Vector2;
+use soukoban::{direction::Direction, Map, Tiles};
+
+/// How strongly the squares of the level influence each other, measured as
+/// a distance. Boxes on squares at a short distance are likely to get in
+/// each other's way.
+///
+/// Entering a corridor square, which has at most two neighboring floor
+/// squares, costs 1, and entering any other square costs 2, since a box in a
+/// room can be pushed around by others instead of blocking them.
+#[derive(Clone, Debug)]
+pub struct Influence {
+    indices: HashMap<Vector2<i32>, usize>,
+    distances: Vec<usize>,
+}
+
+impl Influence {
+    /// Calculates the influence distances between every pair of floor squares.
+    pub fn new(map: &Map) -> Self {
+        let is_floor = |position: Vector2<i32>| map[position].intersects(Tiles::Floor);
+        let mut indices = HashMap::new();
+        for y in 0..map.dimensions().y {
+            for x in 0..map.dimensions().x {
+                let position = Vector2::new(x, y);
+                if is_floor(position) {
+                    indices.insert(position, indices.len());
+                }
+            }
+        }
+        let neighbors = |position: Vector2<i32>| {
+            [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ]
+            .map(|direction| position + &direction.into())
+            .into_iter()
+            .filter(move |neighbor| is_floor(*neighbor))
+        };
+        let costs: HashMap<_, _> = indices
+            .keys()
+            .map(|position| {
+                let cost = if neighbors(*position).count() <= 2 {
+                    1
+                } else {
+                    2
+                };
+                (*position, cost)
+            })
+            .collect();
+
+        let mut distances = vec![usize::MAX; indices.len() * indices.len()];
+        for (from, from_index) in &indices {
+            let row = &mut distances[from_index * indices.len()..][..indices.len()];
+            row[*from_index] = 0;
+            let mut heap = BinaryHeap::from([(Reverse(0), [from.x, from.y])]);
+            while let Some((Reverse(distance), [x, y])) = heap.pop() {
+                let position = Vector2::new(x, y);
+                if distance > row[indices[&position]] {
+                    continue;
+                }
+                for neighbor in neighbors(position) {
+                    let new_distance = distance + costs[&neighbor];
+                    let index = indices[&neighbor];
+                    if new_distance < row[index] {
+                        row[index] = new_distance;
+                        heap.push((Reverse(new_distance), [neighbor.x, neighbor.y]));
+                    }
+                }
+            }
+        }
+        Self { indices, distances }
+    }
+
+    /// Returns the influence distance between the squares, or `None` if
+    /// either is not a floor square or they are not connected.
+    pub fn distance(&self, from: Vector2<i32>, to: Vector2<i32>) -> Option<usize> {
+        let from = self.indices.get(&from)?;
+        let to = self.indices.get(&to)?;
+        let distance = self.distances[from * self.indices.len() + to];
+        (distance != usize::MAX).then_some(distance)
+    }
+}
@@ -0,0 +1,139 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::solve::{box_positions::BoxPositions, hasher::HashMap};
+
+use nalgebra::Vector2;
+use soukoban::{
+    path_finding::{normalized_area, reachable_area},
+    Map, Tiles,
+};
+
+/// A set of boxes that can never all be pushed onto goals while the player
+/// is in the area they separate.
+struct Deadlock {
+    /// Box positions, sorted by row, then by column.
+    box_positions: Vec<Vector2<i32>>,
+    /// Normalized player position, considering only the boxes of the deadlock.
+    player_position: Vector2<i32>,
+}
+
+/// A bounded store of box configurations proven dead during the search.
+///
+/// Deadlocks are indexed by each of their box positions, so only the
+/// deadlocks involving a moved box need to be checked.
+#[derive(Default)]
+pub struct LearnedDeadlocks {
+    capacity: usize,
+    deadlocks: HashMap<u64, Deadlock>,
+    cells: HashMap<Vector2<i32>, Vec<u64>>,
+    hits: usize,
+}
+
+impl LearnedDeadlocks {
+    /// Creates a new store holding at most `capacity` deadlocks.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Records that the boxes are dead while the player is at the given
+    /// position. Once the store is full, new deadlocks are ignored.
+    pub fn insert(
+        &mut self,
+        map: &Map,
+        box_positions: &BoxPositions,
+        player_position: Vector2<i32>,
+    ) {
+        if self.deadlocks.len() >= self.capacity {
+            return;
+        }
+        let box_positions: Vec<_> = box_positions.iter().collect();
+        let player_position =
+            Self::normalized_player_position(map, player_position, &box_positions);
+        let key = Self::key(&box_positions, player_position);
+        if self.deadlocks.contains_key(&key) {
+            return;
+        }
+        for box_position in &box_positions {
+            self.cells.entry(*box_position).or_default().push(key);
+        }
+        self.deadlocks.insert(
+            key,
+            Deadlock {
+                box_positions,
+                player_position,
+            },
+        );
+    }
+
+    /// Checks if a learned deadlock involving the moved box is contained in
+    /// the state.
+    pub fn contains(
+        &mut self,
+        map: &Map,
+        moved_to: Vector2<i32>,
+        box_positions: &BoxPositions,
+        player_position: Vector2<i32>,
+    ) -> bool {
+        let Some(keys) = self.cells.get(&moved_to) else {
+            return false;
+        };
+        let is_dead = keys.iter().any(|key| {
+            let deadlock = &self.deadlocks[key];
+            deadlock
+                .box_positions
+                .iter()
+                .all(|position| box_positions.contains(position))
+                && Self::normalized_player_position(map, player_position, &deadlock.box_positions)
+                    == deadlock.player_position
+        });
+        if is_dead {
+            self.hits += 1;
+        }
+        is_dead
+    }
+
+    /// Returns an iterator over the box positions and the normalized player
+    /// position of each learned deadlock.
+    #[cfg(feature = "serde")]
+    pub fn iter(&self) -> impl Iterator<Item = (&[Vector2<i32>], Vector2<i32>)> {
+        self.deadlocks
+            .values()
+            .map(|deadlock| (deadlock.box_positions.as_slice(), deadlock.player_position))
+    }
+
+    /// Returns the number of learned deadlocks.
+    pub fn len(&self) -> usize {
+        self.deadlocks.len()
+    }
+
+    /// Checks if there are no learned deadlocks.
+    pub fn is_empty(&self) -> bool {
+        self.deadlocks.is_empty()
+    }
+
+    /// Returns the number of states found to contain a learned deadlock.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    fn key(box_positions: &[Vector2<i32>], player_position: Vector2<i32>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        box_positions.hash(&mut hasher);
+        player_position.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn normalized_player_position(
+        map: &Map,
+        player_position: Vector2<i32>,
+        box_positions: &[Vector2<i32>],
+    ) -> Vector2<i32> {
+        normalized_area(&reachable_area(player_position, |position| {
+            map[position].intersects(Tiles::Floor) && !box_positions.contains(&position)
+        }))
+        .unwrap()
+    }
+}
@@ -0,0 +1,938 @@
+#[cfg(feature = "log")]
+use std::time::Instant;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::OnceLock,
+};
+
+use crate::{
+    solve::{
+        bit_grid::SquareMasks,
+        cancellation::CancellationToken,
+        deadlock::{self, DeadEndCorridor},
+        distance::GoalDistanceGrid,
+        influence::Influence,
+        packing_order::PackingOrder,
+        solver::{LowerBoundMethod, SolverOptions},
+        square_grid::{SquareGrid, SquareInfo},
+        symmetry::Symmetry,
+        zobrist::ZobristKeys,
+    },
+    utils::box_pushable_paths_with_positions,
+};
+
+use itertools::Itertools;
+use nalgebra::Vector2;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use soukoban::{
+    direction::Direction,
+    path_finding::{normalized_area, reachable_area},
+    Level, Map, Tiles,
+};
+
+/// An area of the level and the squares through which boxes enter it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Region {
+    pub cells: HashSet<Vector2<i32>>,
+    pub entrances: Vec<Vector2<i32>>,
+}
+
+/// A reason the solver cannot search a level.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LevelError {
+    /// The player is not at the position recorded by the level.
+    NoPlayer,
+    NoBoxes,
+    BoxGoalMismatch {
+        boxes: usize,
+        goals: usize,
+    },
+    /// A box is outside the area the player can reach.
+    UnreachableBox {
+        position: Vector2<i32>,
+    },
+    GoalInWall {
+        position: Vector2<i32>,
+    },
+    /// The level is narrower or shorter than 3 squares, too small to
+    /// enclose a floor with walls.
+    TooSmall {
+        dimensions: Vector2<i32>,
+    },
+    /// The player of a position is outside the area of the level the player
+    /// can reach, such as in a wall.
+    PlayerInWall {
+        position: Vector2<i32>,
+    },
+    /// The player of a position is on a box.
+    PlayerOnBox {
+        position: Vector2<i32>,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoPlayer => write!(f, "the level has no player"),
+            Self::NoBoxes => write!(f, "the level has no boxes"),
+            Self::BoxGoalMismatch { boxes, goals } => {
+                write!(f, "the level has {} boxes but {} goals", boxes, goals)
+            }
+            Self::UnreachableBox { position } => write!(
+                f,
+                "the box at ({}, {}) cannot be reached",
+                position.x, position.y
+            ),
+            Self::GoalInWall { position } => {
+                write!(
+                    f,
+                    "the goal at ({}, {}) is in a wall",
+                    position.x, position.y
+                )
+            }
+            Self::TooSmall { dimensions } => write!(
+                f,
+                "the level is too small: {}x{}",
+                dimensions.x, dimensions.y
+            ),
+            Self::PlayerInWall { position } => write!(
+                f,
+                "the player at ({}, {}) is in a wall",
+                position.x, position.y
+            ),
+            Self::PlayerOnBox { position } => write!(
+                f,
+                "the player at ({}, {}) is on a box",
+                position.x, position.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+/// The analyses of a level, which only depend on the level and the options
+/// listed below.
+///
+/// Each analysis is calculated on first use. The analysis is `Sync`, so one
+/// instance can be shared through an `Arc` by the solvers of the same level,
+/// including solvers on other threads.
+pub struct LevelAnalysis {
+    map: Map,
+    lower_bound_method: LowerBoundMethod,
+    symmetry_reduction: bool,
+    lower_bounds: OnceLock<HashMap<Vector2<i32>, usize>>,
+    goal_distances: OnceLock<Vec<GoalDistanceGrid>>,
+    tunnels: OnceLock<HashSet<(Vector2<i32>, Direction)>>,
+    dead_squares: OnceLock<HashSet<Vector2<i32>>>,
+    squares: OnceLock<SquareGrid<SquareInfo>>,
+    square_masks: OnceLock<Option<SquareMasks>>,
+    closet_entrances: OnceLock<HashSet<(Vector2<i32>, Direction)>>,
+    dead_end_corridors: OnceLock<Vec<DeadEndCorridor>>,
+    packing_order: OnceLock<Option<PackingOrder>>,
+    goal_rooms: OnceLock<Vec<Region>>,
+    influence: OnceLock<Influence>,
+    symmetries: OnceLock<Vec<Symmetry>>,
+    zobrist_keys: OnceLock<ZobristKeys>,
+}
+
+impl LevelAnalysis {
+    /// Creates the analysis of the level for solvers with the given options.
+    ///
+    /// Only `lower_bound_method` and `symmetry_reduction` are used.
+    pub fn new(map: Map, options: &SolverOptions) -> Self {
+        Self {
+            map,
+            lower_bound_method: options.lower_bound_method,
+            symmetry_reduction: options.symmetry_reduction,
+            lower_bounds: OnceLock::new(),
+            goal_distances: OnceLock::new(),
+            tunnels: OnceLock::new(),
+            dead_squares: OnceLock::new(),
+            squares: OnceLock::new(),
+            square_masks: OnceLock::new(),
+            closet_entrances: OnceLock::new(),
+            dead_end_corridors: OnceLock::new(),
+            packing_order: OnceLock::new(),
+            goal_rooms: OnceLock::new(),
+            influence: OnceLock::new(),
+            symmetries: OnceLock::new(),
+            zobrist_keys: OnceLock::new(),
+        }
+    }
+
+    /// Checks that the level can be searched, returning the first problem
+    /// found. Positions are checked in reading order.
+    pub fn validate(map: &Map) -> Result<(), LevelError> {
+        let dimensions = map.dimensions();
+        if dimensions.x < 3 || dimensions.y < 3 {
+            return Err(LevelError::TooSmall { dimensions });
+        }
+        let player_position = map.player_position();
+        if !map.in_bounds(player_position) || !map[player_position].intersects(Tiles::Player) {
+            return Err(LevelError::NoPlayer);
+        }
+        let boxes = map.box_positions().len();
+        let goals = map.goal_positions().len();
+        if boxes == 0 {
+            return Err(LevelError::NoBoxes);
+        }
+        if boxes != goals {
+            return Err(LevelError::BoxGoalMismatch { boxes, goals });
+        }
+        if let Some(position) = map
+            .goal_positions()
+            .iter()
+            .copied()
+            .sorted_by_key(|position| (position.y, position.x))
+            .find(|&position| map[position].intersects(Tiles::Wall))
+        {
+            return Err(LevelError::GoalInWall { position });
+        }
+        if let Some(position) = map
+            .box_positions()
+            .iter()
+            .copied()
+            .sorted_by_key(|position| (position.y, position.x))
+            .find(|&position| !map[position].intersects(Tiles::Floor))
+        {
+            return Err(LevelError::UnreachableBox { position });
+        }
+        Ok(())
+    }
+
+    /// Checks that the player and the boxes make a position of the level,
+    /// such as one reached by playing, returning the first problem found.
+    /// Boxes are checked in reading order.
+    pub fn validate_position(
+        map: &Map,
+        player_position: Vector2<i32>,
+        box_positions: &HashSet<Vector2<i32>>,
+    ) -> Result<(), LevelError> {
+        let is_floor = |position: Vector2<i32>| {
+            map.in_bounds(position) && map[position].intersects(Tiles::Floor)
+        };
+        if !is_floor(player_position) {
+            return Err(LevelError::PlayerInWall {
+                position: player_position,
+            });
+        }
+        let boxes = box_positions.len();
+        let goals = map.goal_positions().len();
+        if boxes != goals {
+            return Err(LevelError::BoxGoalMismatch { boxes, goals });
+        }
+        if let Some(position) = box_positions
+            .iter()
+            .copied()
+            .sorted_by_key(|position| (position.y, position.x))
+            .find(|&position| !is_floor(position))
+        {
+            return Err(LevelError::UnreachableBox { position });
+        }
+        if box_positions.contains(&player_position) {
+            return Err(LevelError::PlayerOnBox {
+                position: player_position,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the level.
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// Checks if solvers with the given options can use the analysis.
+    pub fn is_compatible(&self, options: &SolverOptions) -> bool {
+        options.lower_bound_method == self.lower_bound_method
+            && options.symmetry_reduction == self.symmetry_reduction
+    }
+
+    /// Sets analyses imported from a previous run, which are not calculated
+    /// again.
+    pub(crate) fn import(
+        &mut self,
+        dead_squares: HashSet<Vector2<i32>>,
+        tunnels: HashSet<(Vector2<i32>, Direction)>,
+        goal_distances: Vec<GoalDistanceGrid>,
+    ) {
+        let _ = self.dead_squares.set(dead_squares);
+        let _ = self.tunnels.set(tunnels);
+        let _ = self.goal_distances.set(goal_distances);
+    }
+
+    /// Returns a reference to the set of tunnels.
+    pub fn tunnels(&self) -> &HashSet<(Vector2<i32>, Direction)> {
+        self.tunnels.get_or_init(|| self.calculate_tunnels())
+    }
+
+    /// Calculates and returns the set of tunnels in the level.
+    fn calculate_tunnels(&self) -> HashSet<(Vector2<i32>, Direction)> {
+        #[cfg(feature = "log")]
+        let timer = Instant::now();
+        let mut tunnels = HashSet::new();
+        for x in 1..self.map.dimensions().x - 1 {
+            for y in 1..self.map.dimensions().y - 1 {
+                let box_position = Vector2::new(x, y);
+                if !self.map[box_position].intersects(Tiles::Floor) {
+                    continue;
+                }
+
+                for (up, right, down, left) in [
+                    Direction::Up,
+                    Direction::Right,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Up,
+                    Direction::Right,
+                    Direction::Down,
+                ]
+                .into_iter()
+                .tuple_windows()
+                {
+                    let player_position = box_position + &down.into();
+
+                    //  .      .      .
+                    // #$# or #$_ or _$#
+                    // #@#    #@#    #@#
+                    if self.map[player_position + &left.into()].intersects(Tiles::Wall)
+                        && self.map[player_position + &right.into()].intersects(Tiles::Wall)
+                        && (self.map[box_position + &left.into()].intersects(Tiles::Wall)
+                            && self.map[box_position + &right.into()].intersects(Tiles::Wall)
+                            || self.map[box_position + &right.into()].intersects(Tiles::Wall)
+                                && self.map[box_position + &left.into()].intersects(Tiles::Floor)
+                            || self.map[box_position + &right.into()].intersects(Tiles::Floor)
+                                && self.map[box_position + &left.into()].intersects(Tiles::Wall))
+                        && self.map[box_position].intersects(Tiles::Floor)
+                        && self
+                            .lower_bounds()
+                            .contains_key(&(box_position + &up.into()))
+                        && !self.map[box_position].intersects(Tiles::Goal)
+                    {
+                        tunnels.insert((player_position, up));
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "log")]
+        log_analysis("tunnels", tunnels.len(), timer);
+        tunnels
+    }
+
+    /// Returns a reference to the set of dead squares.
+    ///
+    /// A box on a dead square can never be pushed to any goal, even if there
+    /// are no other boxes.
+    pub fn dead_squares(&self) -> &HashSet<Vector2<i32>> {
+        self.dead_squares
+            .get_or_init(|| self.calculate_dead_squares())
+    }
+
+    /// Returns the lower bound, dead flag and tunnels of each square, which
+    /// are looked up for every generated state.
+    pub fn squares(&self) -> &SquareGrid<SquareInfo> {
+        self.squares.get_or_init(|| self.calculate_squares())
+    }
+
+    /// Collects the lower bounds, dead squares and tunnels into a grid.
+    fn calculate_squares(&self) -> SquareGrid<SquareInfo> {
+        let mut squares = SquareGrid::new(self.map.dimensions(), SquareInfo::default());
+        for (position, lower_bound) in self.lower_bounds() {
+            squares.get_mut(*position).lower_bound = Some(*lower_bound);
+        }
+        for position in self.dead_squares() {
+            squares.get_mut(*position).is_dead = true;
+        }
+        for (player_position, direction) in self.tunnels() {
+            squares.get_mut(*player_position).insert_tunnel(*direction);
+        }
+        squares
+    }
+
+    /// Returns the walls, goals and dead squares as bit grids, or `None` if
+    /// the level is too large.
+    pub fn square_masks(&self) -> Option<&SquareMasks> {
+        self.square_masks
+            .get_or_init(|| SquareMasks::new(&self.map, self.dead_squares()))
+            .as_ref()
+    }
+
+    /// Returns the pushes of a box out of a doorway into a room without goals,
+    /// after which the box can never be pushed back out of the room.
+    ///
+    /// A doorway is a floor with walls on both sides, which is the only
+    /// connection between the room and the rest of the level.
+    pub fn closet_entrances(&self) -> &HashSet<(Vector2<i32>, Direction)> {
+        self.closet_entrances
+            .get_or_init(|| self.calculate_closet_entrances())
+    }
+
+    /// Returns the dead-end corridors of the level.
+    pub fn dead_end_corridors(&self) -> &[DeadEndCorridor] {
+        self.dead_end_corridors
+            .get_or_init(|| deadlock::dead_end_corridors(&self.map))
+    }
+
+    /// Returns the influence distances between the squares of the level.
+    pub fn influence(&self) -> &Influence {
+        self.influence.get_or_init(|| Influence::new(&self.map))
+    }
+
+    /// Returns the symmetries of the level under which states are treated as
+    /// the same, starting with the identity.
+    pub fn symmetries(&self) -> &[Symmetry] {
+        self.symmetries.get_or_init(|| {
+            if self.symmetry_reduction {
+                Symmetry::of(&self.map)
+            } else {
+                vec![Symmetry::Identity]
+            }
+        })
+    }
+
+    /// Returns the keys from which the hashes of states are calculated.
+    pub fn zobrist_keys(&self) -> &ZobristKeys {
+        self.zobrist_keys
+            .get_or_init(|| ZobristKeys::new(self.map.dimensions()))
+    }
+
+    /// Returns the packing order of the goal room, or `None` if the goals are
+    /// not in a room behind a single entrance.
+    pub fn packing_order(&self) -> Option<&PackingOrder> {
+        self.packing_order
+            .get_or_init(|| PackingOrder::new(&self.map))
+            .as_ref()
+    }
+
+    /// Returns the goal rooms of the level, ordered by their first goal in
+    /// reading order.
+    ///
+    /// A goal room is the smallest area holding some goals which is separated
+    /// from the player by a single square, its entrance, like the goal room
+    /// of the packing order. It holds no box off a goal, and no smaller room
+    /// holds more of its goals.
+    pub fn goal_rooms(&self) -> &[Region] {
+        self.goal_rooms.get_or_init(|| self.calculate_goal_rooms())
+    }
+
+    /// Calculates and returns the goal rooms of the level.
+    fn calculate_goal_rooms(&self) -> Vec<Region> {
+        let is_floor = |position: Vector2<i32>| {
+            self.map.in_bounds(position) && self.map[position].intersects(Tiles::Floor)
+        };
+        let goal_positions = self.map.goal_positions();
+        let player_position = self.map.player_position();
+        // The smallest room behind a single square for each set of goals.
+        let mut rooms: HashMap<Vec<Vector2<i32>>, Region> = HashMap::new();
+        for y in 0..self.map.dimensions().y {
+            for x in 0..self.map.dimensions().x {
+                let entrance = Vector2::new(x, y);
+                if !is_floor(entrance)
+                    || goal_positions.contains(&entrance)
+                    || entrance == player_position
+                {
+                    continue;
+                }
+                let mut visited = HashSet::new();
+                for direction in [
+                    Direction::Up,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Right,
+                ] {
+                    let neighbor = entrance + &direction.into();
+                    if !is_floor(neighbor) || visited.contains(&neighbor) {
+                        continue;
+                    }
+                    let room = reachable_area(neighbor, |position| {
+                        position != entrance && is_floor(position)
+                    });
+                    visited.extend(room.iter().copied());
+                    let goals = room
+                        .iter()
+                        .copied()
+                        .filter(|position| goal_positions.contains(position))
+                        .sorted_by_key(|position| (position.y, position.x))
+                        .collect_vec();
+                    if goals.is_empty()
+                        || room.contains(&player_position)
+                        || self.map.box_positions().iter().any(|position| {
+                            room.contains(position) && !goal_positions.contains(position)
+                        })
+                    {
+                        continue;
+                    }
+                    if rooms
+                        .get(&goals)
+                        .is_none_or(|region| room.len() < region.cells.len())
+                    {
+                        let region = Region {
+                            cells: room,
+                            entrances: vec![entrance],
+                        };
+                        rooms.insert(goals, region);
+                    }
+                }
+            }
+        }
+        // Rooms inside a room holding more goals are part of it.
+        let goal_sets = rooms.keys().cloned().collect_vec();
+        rooms
+            .into_iter()
+            .filter(|(goals, _)| {
+                !goal_sets.iter().any(|other_goals| {
+                    other_goals.len() > goals.len()
+                        && goals.iter().all(|goal| other_goals.contains(goal))
+                })
+            })
+            .sorted_by_key(|(goals, _)| (goals[0].y, goals[0].x))
+            .map(|(_, region)| region)
+            .collect()
+    }
+
+    /// Returns the lower bound of the pushes of a box on the square to a
+    /// goal, or `None` if the square is dead or not a floor.
+    pub fn lower_bound_at(&self, position: Vector2<i32>) -> Option<usize> {
+        self.lower_bounds().get(&position).copied()
+    }
+
+    /// Calculates and returns the set of closet entrances.
+    fn calculate_closet_entrances(&self) -> HashSet<(Vector2<i32>, Direction)> {
+        let is_floor = |position: Vector2<i32>| {
+            self.map.in_bounds(position) && self.map[position].intersects(Tiles::Floor)
+        };
+
+        let mut closet_entrances = HashSet::new();
+        for x in 1..self.map.dimensions().x - 1 {
+            for y in 1..self.map.dimensions().y - 1 {
+                let doorway = Vector2::new(x, y);
+                if !is_floor(doorway) {
+                    continue;
+                }
+                for push_direction in [
+                    Direction::Up,
+                    Direction::Right,
+                    Direction::Down,
+                    Direction::Left,
+                ] {
+                    let offset: Vector2<i32> = push_direction.into();
+                    let side = Vector2::new(offset.y, offset.x);
+                    if !self.map[doorway + side].intersects(Tiles::Wall)
+                        || !self.map[doorway - side].intersects(Tiles::Wall)
+                        || !is_floor(doorway + offset)
+                        || !is_floor(doorway - offset)
+                    {
+                        continue;
+                    }
+
+                    let Some(room) = self.room(doorway, push_direction) else {
+                        continue;
+                    };
+                    if room
+                        .iter()
+                        .any(|position| self.map[*position].intersects(Tiles::Goal))
+                    {
+                        continue;
+                    }
+                    if !self.can_leave_room(doorway, push_direction, &room) {
+                        closet_entrances.insert((doorway, push_direction));
+                    }
+                }
+            }
+        }
+        closet_entrances
+    }
+
+    /// Returns the floors behind the doorway in the direction, or `None` if
+    /// they are connected to the front of the doorway, or there are too many
+    /// of them to be a closet.
+    fn room(&self, doorway: Vector2<i32>, direction: Direction) -> Option<HashSet<Vector2<i32>>> {
+        /// The maximum number of floors in a closet.
+        const MAX_CLOSET_SIZE: usize = 64;
+
+        let entrance = doorway + &direction.into();
+        let mut room = HashSet::from([entrance]);
+        let mut queue = VecDeque::from([entrance]);
+        while let Some(position) = queue.pop_front() {
+            for direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                let neighbor = position + &direction.into();
+                if neighbor == doorway
+                    || !self.map[neighbor].intersects(Tiles::Floor)
+                    || !room.insert(neighbor)
+                {
+                    continue;
+                }
+                if room.len() > MAX_CLOSET_SIZE {
+                    return None;
+                }
+                queue.push_back(neighbor);
+            }
+        }
+        // Otherwise the doorway is not the only connection.
+        (!room.contains(&(doorway - &direction.into()))).then_some(room)
+    }
+
+    /// Checks if a box pushed from the doorway into the room can be pushed
+    /// back to the doorway, if there are no other boxes.
+    fn can_leave_room(
+        &self,
+        doorway: Vector2<i32>,
+        push_direction: Direction,
+        room: &HashSet<Vector2<i32>>,
+    ) -> bool {
+        let player_reachable_area = |player_position: Vector2<i32>, box_position| {
+            reachable_area(player_position, |position| {
+                self.map[position].intersects(Tiles::Floor) && position != box_position
+            })
+        };
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([(doorway + &push_direction.into(), doorway)]);
+        while let Some((box_position, player_position)) = queue.pop_front() {
+            if box_position == doorway {
+                return true;
+            }
+            let area = player_reachable_area(player_position, box_position);
+            if !visited.insert((box_position, normalized_area(&area).unwrap())) {
+                continue;
+            }
+            for push_direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                let new_box_position = box_position + &push_direction.into();
+                if area.contains(&(box_position - &push_direction.into()))
+                    && (room.contains(&new_box_position) || new_box_position == doorway)
+                {
+                    queue.push_back((new_box_position, box_position));
+                }
+            }
+        }
+        false
+    }
+
+    /// Calculates and returns the set of dead squares.
+    ///
+    /// Boxes are pulled away from each goal, ignoring the reachability of the
+    /// player. Squares which no box can be pulled to are dead squares.
+    fn calculate_dead_squares(&self) -> HashSet<Vector2<i32>> {
+        #[cfg(feature = "log")]
+        let timer = Instant::now();
+        let is_floor = |position: Vector2<i32>| {
+            self.map.in_bounds(position) && self.map[position].intersects(Tiles::Floor)
+        };
+
+        let mut live_squares = self.map.goal_positions().clone();
+        let mut queue: VecDeque<_> = live_squares.iter().copied().collect();
+        while let Some(box_position) = queue.pop_front() {
+            for pull_direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                let next_box_position = box_position + &pull_direction.into();
+                let next_player_position = next_box_position + &pull_direction.into();
+                if is_floor(next_box_position)
+                    && is_floor(next_player_position)
+                    && live_squares.insert(next_box_position)
+                {
+                    queue.push_back(next_box_position);
+                }
+            }
+        }
+
+        let mut dead_squares = HashSet::new();
+        for x in 0..self.map.dimensions().x {
+            for y in 0..self.map.dimensions().y {
+                let position = Vector2::new(x, y);
+                if is_floor(position) && !live_squares.contains(&position) {
+                    dead_squares.insert(position);
+                }
+            }
+        }
+        #[cfg(feature = "log")]
+        log_analysis("dead squares", dead_squares.len(), timer);
+        dead_squares
+    }
+
+    /// Returns a reference to the set of lower bounds.
+    ///
+    /// The lower bound of a position is the minimum distance to any goal.
+    pub fn lower_bounds(&self) -> &HashMap<Vector2<i32>, usize> {
+        self.lower_bounds
+            .get_or_init(|| self.calculate_lower_bounds())
+    }
+
+    /// Returns the distances from each position to each goal.
+    pub fn goal_distances(&self) -> &[GoalDistanceGrid] {
+        self.goal_distances
+            .get_or_init(|| self.calculate_goal_distances(None).unwrap())
+    }
+
+    /// Returns the distances from each position to each goal like
+    /// [`LevelAnalysis::goal_distances`], or `None` if the token is cancelled
+    /// before they are calculated.
+    pub fn try_goal_distances(
+        &self,
+        cancellation: &CancellationToken,
+    ) -> Option<&[GoalDistanceGrid]> {
+        if let Some(goal_distances) = self.goal_distances.get() {
+            return Some(goal_distances);
+        }
+        let goal_distances = self.calculate_goal_distances(Some(cancellation))?;
+        Some(self.goal_distances.get_or_init(|| goal_distances))
+    }
+
+    /// Calculates and returns the set of lower bounds.
+    fn calculate_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
+        #[cfg(feature = "log")]
+        let timer = Instant::now();
+        let mut lower_bounds = HashMap::new();
+        for goal_distances in self.goal_distances() {
+            for (position, distance) in goal_distances.iter() {
+                let lower_bound = lower_bounds.entry(position).or_insert(usize::MAX);
+                *lower_bound = (*lower_bound).min(distance);
+            }
+        }
+        #[cfg(feature = "log")]
+        log_analysis("lower bounds", lower_bounds.len(), timer);
+        lower_bounds
+    }
+
+    /// Calculates and returns the distances from each position to each goal,
+    /// or `None` if the token is cancelled first.
+    fn calculate_goal_distances(
+        &self,
+        cancellation: Option<&CancellationToken>,
+    ) -> Option<Vec<GoalDistanceGrid>> {
+        #[cfg(feature = "log")]
+        let timer = Instant::now();
+        let is_cancelled = || cancellation.is_some_and(CancellationToken::is_cancelled);
+        let walls = HashSet::new();
+        let mut goal_distances = self.initial_goal_distances(&walls);
+        match self.lower_bound_method {
+            LowerBoundMethod::MinimumPush => {
+                for grid in &mut goal_distances {
+                    if is_cancelled() {
+                        return None;
+                    }
+                    self.minimum_push_distances(grid, &walls);
+                }
+            }
+            LowerBoundMethod::MinimumMove => {
+                self.minimum_move_distances(&mut goal_distances, is_cancelled)?
+            }
+            LowerBoundMethod::ManhattanDistance => {
+                for grid in &mut goal_distances {
+                    self.manhattan_distances(grid, &walls);
+                }
+            }
+        }
+        #[cfg(feature = "log")]
+        log_analysis("goal distances", goal_distances.len(), timer);
+        Some(goal_distances)
+    }
+
+    /// Calculates and returns the distances from each position to each goal
+    /// not covered by the given walls.
+    ///
+    /// The minimum move method depends on the initial player position, which
+    /// may be walled off by the frozen boxes, so the minimum push method is
+    /// used instead.
+    pub(crate) fn calculate_frozen_goal_distances(
+        &self,
+        walls: &HashSet<Vector2<i32>>,
+    ) -> Vec<GoalDistanceGrid> {
+        let mut goal_distances = self.initial_goal_distances(walls);
+        for grid in &mut goal_distances {
+            match self.lower_bound_method {
+                LowerBoundMethod::MinimumPush | LowerBoundMethod::MinimumMove => {
+                    self.minimum_push_distances(grid, walls)
+                }
+                LowerBoundMethod::ManhattanDistance => self.manhattan_distances(grid, walls),
+            }
+        }
+        goal_distances
+    }
+
+    /// Returns a grid for each goal not covered by the given walls, in which
+    /// only the goal itself is set.
+    fn initial_goal_distances(&self, walls: &HashSet<Vector2<i32>>) -> Vec<GoalDistanceGrid> {
+        self.map
+            .goal_positions()
+            .iter()
+            .filter(|position| !walls.contains(position))
+            .sorted_by_key(|position| (position.y, position.x))
+            .map(|goal_position| {
+                let mut grid = GoalDistanceGrid::new(*goal_position, self.map.dimensions());
+                // There may be situations in the level where the box is
+                // already on the goal and cannot be reached by the player.
+                grid.set(*goal_position, 0);
+                grid
+            })
+            .collect()
+    }
+
+    /// Calculates the distances to the goal using the minimum push method.
+    ///
+    /// The distances are found by pulling a box away from the goal using a
+    /// breadth-first search. Positions from which a box can never be pushed to
+    /// the goal are left unset.
+    fn minimum_push_distances(&self, grid: &mut GoalDistanceGrid, walls: &HashSet<Vector2<i32>>) {
+        let is_floor = |position: Vector2<i32>| {
+            self.map.in_bounds(position)
+                && !self.map[position].intersects(Tiles::Wall)
+                && !walls.contains(&position)
+        };
+
+        let goal_position = grid.goal_position();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        for direction in [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ] {
+            let player_position = goal_position + &direction.into();
+            if is_floor(player_position) {
+                visited.insert((goal_position, player_position));
+                queue.push_back((goal_position, player_position, 0));
+            }
+        }
+
+        while let Some((box_position, player_position, pushes)) = queue.pop_front() {
+            let player_reachable_area = reachable_area(player_position, |position| {
+                is_floor(position) && position != box_position
+            });
+            for pull_direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                let next_box_position = box_position + &pull_direction.into();
+                let next_player_position = next_box_position + &pull_direction.into();
+                if !player_reachable_area.contains(&next_box_position)
+                    || !is_floor(next_player_position)
+                {
+                    continue;
+                }
+                if !visited.insert((next_box_position, next_player_position)) {
+                    continue;
+                }
+                if grid.get(next_box_position).is_none() {
+                    grid.set(next_box_position, pushes + 1);
+                }
+                queue.push_back((next_box_position, next_player_position, pushes + 1));
+            }
+        }
+    }
+
+    /// Calculates the distances to each goal using the minimum move method.
+    /// Returns `None` if cancelled, which is checked for each square.
+    fn minimum_move_distances(
+        &self,
+        goal_distances: &mut [GoalDistanceGrid],
+        is_cancelled: impl Fn() -> bool,
+    ) -> Option<()> {
+        for x in 1..self.map.dimensions().x - 1 {
+            for y in 1..self.map.dimensions().y - 1 {
+                if is_cancelled() {
+                    return None;
+                }
+                let position = Vector2::new(x, y);
+                if !self.map[position].intersects(Tiles::Floor)
+                    || self.dead_squares().contains(&position)
+                {
+                    continue;
+                }
+
+                let paths =
+                    box_pushable_paths_with_positions(&self.map, &position, &HashSet::new());
+                for grid in goal_distances.iter_mut() {
+                    if let Some(distance) = paths
+                        .iter()
+                        .filter(|path| path.0.box_position == grid.goal_position())
+                        .map(|path| path.1.len() - 1)
+                        .min()
+                    {
+                        if grid.get(position).is_none() {
+                            grid.set(position, distance);
+                        }
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// Calculates the distances to the goal using the Manhattan distance method.
+    fn manhattan_distances(&self, grid: &mut GoalDistanceGrid, walls: &HashSet<Vector2<i32>>) {
+        for x in 1..self.map.dimensions().x - 1 {
+            for y in 1..self.map.dimensions().y - 1 {
+                let position = Vector2::new(x, y);
+                if !self.map[position].intersects(Tiles::Floor)
+                    || self.dead_squares().contains(&position)
+                    || walls.contains(&position)
+                {
+                    continue;
+                }
+                grid.set(
+                    position,
+                    manhattan_distance(&grid.goal_position(), &position) as usize,
+                );
+            }
+        }
+    }
+
+    /// Formats the lower bounds for each position in the level as a grid,
+    /// for debugging.
+    pub fn lower_bounds_grid(&self) -> String {
+        let mut grid = String::new();
+        for y in 0..self.map.dimensions().y {
+            for x in 0..self.map.dimensions().x {
+                let position = Vector2::new(x, y);
+                if let Some(lower_bound) = self.lower_bounds().get(&position) {
+                    grid.push_str(&format!("{:3} ", lower_bound));
+                } else {
+                    grid.push_str(&format!("{:3} ", "###"));
+                }
+            }
+            grid.push('\n');
+        }
+        grid
+    }
+}
+
+/// Analyzes the level with the default options of the solver, for tools
+/// such as level editors. Each analysis is still calculated on first use.
+///
+/// The level is not validated, see [`LevelAnalysis::validate`].
+pub fn analyze(level: &Level) -> LevelAnalysis {
+    LevelAnalysis::new(level.map().clone(), &SolverOptions::default())
+}
+
+/// Logs the time taken by an analysis of the level and the number of items
+/// it found.
+#[cfg(feature = "log")]
+fn log_analysis(analysis: &str, count: usize, timer: Instant) {
+    log::debug!(analysis, count, elapsed:? = timer.elapsed(); "{analysis} calculated");
+}
+
+/// Calculates the Manhattan distance between two 2D vectors.
+fn manhattan_distance(a: &Vector2<i32>, b: &Vector2<i32>) -> i32 {
+    (a.x - b.x).abs() + (a.y - b.y).abs()
+}
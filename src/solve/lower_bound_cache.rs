@@ -0,0 +1,84 @@
+use crate::solve::{hasher::HashMap, solver::hash_table_bytes};
+
+/// A bounded cache of lower bounds keyed by box configuration.
+///
+/// Entries are kept in two generations. When the current generation is full,
+/// it replaces the previous one, so the least recently used entries are
+/// evicted first.
+#[derive(Default)]
+pub struct LowerBoundCache {
+    capacity: usize,
+    current: HashMap<u64, Option<usize>>,
+    previous: HashMap<u64, Option<usize>>,
+    hits: usize,
+    misses: usize,
+}
+
+impl LowerBoundCache {
+    /// Creates a new cache holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the cached lower bound of the box configuration.
+    pub fn get(&mut self, key: u64) -> Option<Option<usize>> {
+        if let Some(lower_bound) = self.current.get(&key) {
+            self.hits += 1;
+            return Some(*lower_bound);
+        }
+        if let Some(lower_bound) = self.previous.remove(&key) {
+            self.hits += 1;
+            self.insert(key, lower_bound);
+            return Some(lower_bound);
+        }
+        self.misses += 1;
+        None
+    }
+
+    /// Caches the lower bound of the box configuration.
+    pub fn insert(&mut self, key: u64, lower_bound: Option<usize>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.current.len() >= self.capacity.div_ceil(2) {
+            self.previous = std::mem::take(&mut self.current);
+        }
+        self.current.insert(key, lower_bound);
+    }
+
+    /// Removes all entries, keeping the statistics.
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.previous.clear();
+    }
+
+    /// Returns an estimate of the bytes allocated by the entries.
+    pub fn allocated_bytes(&self) -> usize {
+        let entry_size = size_of::<(u64, Option<usize>)>();
+        hash_table_bytes(self.current.capacity(), entry_size)
+            + hash_table_bytes(self.previous.capacity(), entry_size)
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.current.len() + self.previous.len()
+    }
+
+    /// Checks if there are no cached entries.
+    pub fn is_empty(&self) -> bool {
+        self.current.is_empty() && self.previous.is_empty()
+    }
+
+    /// Returns the number of lookups that found a cached lower bound.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the number of lookups that found no cached lower bound.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
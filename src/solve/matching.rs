@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+/// Calculates the minimum total cost of assigning each row to a distinct column
+/// using the Hungarian algorithm.
+///
+/// `costs[row][column]` is `None` if the row cannot be assigned to the column.
+/// Returns `None` if no assignment covers every row.
+pub fn minimum_cost_matching(costs: &[Vec<Option<usize>>]) -> Option<usize> {
+    let rows = costs.len();
+    if rows == 0 {
+        return Some(0);
+    }
+    let columns = costs[0].len();
+    if rows > columns {
+        return None;
+    }
+
+    // Forbidden edges cost more than any assignment made of allowed edges.
+    let forbidden = (costs.iter().flatten().flatten().max().copied().unwrap_or(0) as i64 + 1)
+        * (rows as i64 + 1);
+    let cost =
+        |row: usize, column: usize| costs[row - 1][column - 1].map_or(forbidden, |c| c as i64);
+
+    // 1-indexed potentials, index 0 is a virtual column.
+    let mut u = vec![0i64; rows + 1];
+    let mut v = vec![0i64; columns + 1];
+    let mut assignment = vec![0usize; columns + 1];
+    let mut way = vec![0usize; columns + 1];
+    for row in 1..=rows {
+        assignment[0] = row;
+        let mut current_column = 0;
+        let mut min_values = vec![i64::MAX; columns + 1];
+        let mut used = vec![false; columns + 1];
+        loop {
+            used[current_column] = true;
+            let current_row = assignment[current_column];
+            let mut delta = i64::MAX;
+            let mut next_column = 0;
+            for column in 1..=columns {
+                if used[column] {
+                    continue;
+                }
+                let reduced_cost = cost(current_row, column) - u[current_row] - v[column];
+                if reduced_cost < min_values[column] {
+                    min_values[column] = reduced_cost;
+                    way[column] = current_column;
+                }
+                if min_values[column] < delta {
+                    delta = min_values[column];
+                    next_column = column;
+                }
+            }
+            for column in 0..=columns {
+                if used[column] {
+                    u[assignment[column]] += delta;
+                    v[column] -= delta;
+                } else {
+                    min_values[column] -= delta;
+                }
+            }
+            current_column = next_column;
+            if assignment[current_column] == 0 {
+                break;
+            }
+        }
+        loop {
+            let previous_column = way[current_column];
+            assignment[current_column] = assignment[previous_column];
+            current_column = previous_column;
+            if current_column == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut sum = 0;
+    for column in 1..=columns {
+        let row = assignment[column];
+        if row == 0 {
+            continue;
+        }
+        sum += costs[row - 1][column - 1]?;
+    }
+    Some(sum)
+}
+
+/// Extends the matching of rows to columns to a maximum matching using the
+/// Hopcroft-Karp algorithm.
+///
+/// `adjacency[row]` lists the columns the row can be matched to, and
+/// `matching[row]` is the column matched to the row, if any. The initial
+/// matching must be valid. Returns `true` if every row is matched.
+pub fn maximum_matching(
+    adjacency: &[Vec<usize>],
+    columns: usize,
+    matching: &mut [Option<usize>],
+) -> bool {
+    let rows = adjacency.len();
+    let mut column_matching = vec![None; columns];
+    for (row, column) in matching.iter().enumerate() {
+        if let Some(column) = column {
+            column_matching[*column] = Some(row);
+        }
+    }
+
+    loop {
+        // Layer the rows by breadth-first search from the free rows.
+        let mut layers = vec![usize::MAX; rows];
+        let mut queue = VecDeque::new();
+        for row in 0..rows {
+            if matching[row].is_none() {
+                layers[row] = 0;
+                queue.push_back(row);
+            }
+        }
+        let mut found_augmenting_path = false;
+        while let Some(row) = queue.pop_front() {
+            for &column in &adjacency[row] {
+                match column_matching[column] {
+                    None => found_augmenting_path = true,
+                    Some(next_row) if layers[next_row] == usize::MAX => {
+                        layers[next_row] = layers[row] + 1;
+                        queue.push_back(next_row);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if !found_augmenting_path {
+            break;
+        }
+
+        // Augment along vertex-disjoint shortest paths.
+        for row in 0..rows {
+            if matching[row].is_none() {
+                augment(row, adjacency, &mut layers, matching, &mut column_matching);
+            }
+        }
+    }
+    matching.iter().all(Option::is_some)
+}
+
+/// Searches for an augmenting path from the row along the layers.
+fn augment(
+    row: usize,
+    adjacency: &[Vec<usize>],
+    layers: &mut [usize],
+    matching: &mut [Option<usize>],
+    column_matching: &mut [Option<usize>],
+) -> bool {
+    for &column in &adjacency[row] {
+        let is_augmenting = match column_matching[column] {
+            None => true,
+            Some(next_row) => {
+                layers[next_row] == layers[row] + 1
+                    && augment(next_row, adjacency, layers, matching, column_matching)
+            }
+        };
+        if is_augmenting {
+            matching[row] = Some(column);
+            column_matching[column] = Some(row);
+            return true;
+        }
+    }
+    // The row is a dead end for the current phase.
+    layers[row] = usize::MAX;
+    false
+}
@@ -1,2 +1,38 @@
+#[cfg(feature = "serde")]
+pub mod analysis;
+pub mod arena;
+pub mod backward;
+pub mod bidirectional;
+pub mod bit_grid;
+pub mod box_positions;
+pub mod cancellation;
+pub mod deadlock;
+pub mod distance;
+pub mod events;
+pub mod handle;
+pub mod hasher;
+pub mod hint;
+pub mod ida_star;
+pub mod influence;
+pub mod learned_deadlocks;
+pub mod level_analysis;
+pub mod lower_bound_cache;
+mod matching;
+pub mod packing_order;
+pub mod parallel;
+pub mod path;
+pub mod path_finding;
+pub mod pattern_database;
+pub mod portfolio;
+pub mod search_algorithm;
+pub mod search_graph;
+#[cfg(feature = "serde")]
+pub mod serialization;
 pub mod solver;
+pub mod spill;
+pub mod square_grid;
 pub mod state;
+pub mod state_context;
+pub mod symmetry;
+pub mod visited;
+pub mod zobrist;
@@ -0,0 +1,223 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+
+use crate::solve::box_positions::BoxPositions;
+
+use itertools::Itertools;
+use nalgebra::Vector2;
+use soukoban::{direction::Direction, path_finding::reachable_area, Action, Map, Tiles};
+
+/// The goals of a goal room with a single entrance, in the order in which
+/// they can be filled through the entrance.
+#[derive(Clone, Debug)]
+pub struct PackingOrder {
+    entrance: Vector2<i32>,
+    room: HashSet<Vector2<i32>>,
+    goals: Vec<Vector2<i32>>,
+}
+
+impl PackingOrder {
+    /// Calculates the packing order by pulling the boxes out of the solved
+    /// goal room one at a time, or returns `None` if the goals are not in a
+    /// room behind a single entrance or cannot all be filled through it.
+    pub fn new(map: &Map) -> Option<Self> {
+        let (room, entrance) = Self::goal_room(map)?;
+        let mut box_positions = map.goal_positions().clone();
+        let mut goals = Vec::new();
+        while !box_positions.is_empty() {
+            // Removing a box never prevents the others from being pulled out,
+            // so any box that can be pulled out can go first.
+            let goal = box_positions
+                .iter()
+                .copied()
+                .sorted_by_key(|position| (position.y, position.x))
+                .find(|goal| {
+                    let mut box_positions = box_positions.clone();
+                    box_positions.remove(goal);
+                    Self::can_pull_out(map, &room, entrance, *goal, &box_positions)
+                })?;
+            box_positions.remove(&goal);
+            goals.push(goal);
+        }
+        // The box pulled out last is the first to be pushed in.
+        goals.reverse();
+        Some(Self {
+            entrance,
+            room,
+            goals,
+        })
+    }
+
+    /// Returns the square through which boxes enter the goal room.
+    pub fn entrance(&self) -> Vector2<i32> {
+        self.entrance
+    }
+
+    /// Returns the squares of the goal room, excluding the entrance.
+    pub fn room(&self) -> &HashSet<Vector2<i32>> {
+        &self.room
+    }
+
+    /// Returns the goals in the order they should be filled.
+    pub fn goals(&self) -> &[Vector2<i32>] {
+        &self.goals
+    }
+
+    /// Returns the next goal to fill, if the boxes in the goal room fill the
+    /// first goals of the packing order.
+    pub fn next_goal(&self, box_positions: &BoxPositions) -> Option<Vector2<i32>> {
+        let filled_goals = self
+            .goals
+            .iter()
+            .take_while(|goal| box_positions.contains(goal))
+            .count();
+        let boxes_in_room = box_positions
+            .iter()
+            .filter(|position| self.room.contains(position))
+            .count();
+        if boxes_in_room != filled_goals {
+            return None;
+        }
+        self.goals.get(filled_goals).copied()
+    }
+
+    /// Returns the smallest area containing every goal that is separated from
+    /// the player by a single square, and that square.
+    fn goal_room(map: &Map) -> Option<(HashSet<Vector2<i32>>, Vector2<i32>)> {
+        let goal_positions = map.goal_positions();
+        let first_goal = *goal_positions
+            .iter()
+            .min_by_key(|position| (position.y, position.x))?;
+        let mut goal_room: Option<(HashSet<Vector2<i32>>, Vector2<i32>)> = None;
+        for y in 0..map.dimensions().y {
+            for x in 0..map.dimensions().x {
+                let entrance = Vector2::new(x, y);
+                if !map[entrance].intersects(Tiles::Floor)
+                    || goal_positions.contains(&entrance)
+                    || entrance == map.player_position()
+                {
+                    continue;
+                }
+                let room = reachable_area(first_goal, |position| {
+                    position != entrance && map[position].intersects(Tiles::Floor)
+                });
+                if room.contains(&map.player_position())
+                    || !goal_positions.iter().all(|goal| room.contains(goal))
+                    || map.box_positions().iter().any(|position| {
+                        room.contains(position) && !goal_positions.contains(position)
+                    })
+                {
+                    continue;
+                }
+                if goal_room
+                    .as_ref()
+                    .is_none_or(|(goal_room, _)| room.len() < goal_room.len())
+                {
+                    goal_room = Some((room, entrance));
+                }
+            }
+        }
+        goal_room
+    }
+
+    /// Checks if the box on the goal can be pulled out of the goal room by a
+    /// player coming through the entrance, while the other boxes stay in
+    /// place.
+    fn can_pull_out(
+        map: &Map,
+        room: &HashSet<Vector2<i32>>,
+        entrance: Vector2<i32>,
+        goal: Vector2<i32>,
+        box_positions: &HashSet<Vector2<i32>>,
+    ) -> bool {
+        let is_free = |position: Vector2<i32>| {
+            map[position].intersects(Tiles::Floor) && !box_positions.contains(&position)
+        };
+        let mut visited = HashSet::from([(goal, entrance)]);
+        let mut queue = VecDeque::from([(goal, entrance)]);
+        while let Some((box_position, player_position)) = queue.pop_front() {
+            if !room.contains(&box_position) && box_position != entrance {
+                return true;
+            }
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let new_player_position = player_position + &direction.into();
+                if !is_free(new_player_position) || new_player_position == box_position {
+                    continue;
+                }
+                if visited.insert((box_position, new_player_position)) {
+                    queue.push_back((box_position, new_player_position));
+                }
+                // The box follows the player stepping away from it.
+                if player_position - &direction.into() == box_position
+                    && visited.insert((player_position, new_player_position))
+                {
+                    queue.push_back((player_position, new_player_position));
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Returns the actions pushing the box to the target, with the player
+/// position before each action, while the other boxes stay in place.
+pub fn push_box_to(
+    map: &Map,
+    other_box_positions: &BoxPositions,
+    player_position: Vector2<i32>,
+    box_position: Vector2<i32>,
+    target: Vector2<i32>,
+) -> Option<Vec<(Action, Vector2<i32>)>> {
+    let is_free = |position: Vector2<i32>| {
+        map[position].intersects(Tiles::Floor) && !other_box_positions.contains(&position)
+    };
+    let initial_state = (box_position, player_position);
+    let mut came_from = HashMap::from([(initial_state, None)]);
+    let mut queue = VecDeque::from([initial_state]);
+    while let Some(state) = queue.pop_front() {
+        let (box_position, player_position) = state;
+        if box_position == target {
+            let mut actions = Vec::new();
+            let mut state = state;
+            while let Some((previous_state, action)) = came_from[&state] {
+                let (_, previous_player_position) = previous_state;
+                actions.push((action, previous_player_position));
+                state = previous_state;
+            }
+            actions.reverse();
+            return Some(actions);
+        }
+        for direction in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            let new_player_position = player_position + &direction.into();
+            let (new_state, action) = if new_player_position == box_position {
+                let new_box_position = box_position + &direction.into();
+                if !is_free(new_box_position) {
+                    continue;
+                }
+                (
+                    (new_box_position, new_player_position),
+                    Action::Push(direction),
+                )
+            } else {
+                if !is_free(new_player_position) {
+                    continue;
+                }
+                ((box_position, new_player_position), Action::Move(direction))
+            };
+            if let Entry::Vacant(entry) = came_from.entry(new_state) {
+                entry.insert(Some((state, action)));
+                queue.push_back(new_state);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::{hash_map::Entry, BinaryHeap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::solve::{hasher::HashMap, solver::*, state::State};
+
+use soukoban::Actions;
+
+/// The open and reached states owned by a thread.
+#[derive(Default)]
+struct Shard {
+    heap: BinaryHeap<State>,
+    /// The lowest costs at which the states have been reached, keyed by
+    /// [`Search::state_key`].
+    costs: HashMap<u64, (usize, usize)>,
+}
+
+impl Shard {
+    /// Adds the state to the heap, unless it has already been reached at the
+    /// same or a lower cost.
+    fn insert(&mut self, key: u64, state: State, solver: &Search) {
+        let cost = solver.state_cost(&state);
+        match self.costs.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(cost);
+            }
+            // States of optimal strategies are searched again when they are
+            // reached at a lower cost, since the threads expand them out of
+            // order.
+            Entry::Occupied(mut entry) if solver.expands_in_cost_order() && cost < *entry.get() => {
+                entry.insert(cost);
+            }
+            Entry::Occupied(_) => return,
+        }
+        self.heap.push(state);
+    }
+
+    /// Checks if the state has been reached at a lower cost since it was
+    /// added to the heap.
+    fn is_stale(&self, key: u64, state: &State, solver: &Search) -> bool {
+        self.costs[&key] < solver.state_cost(state)
+    }
+}
+
+/// The numbers of idle threads and of batches of states not yet received,
+/// updated together so that every thread running out of states is detected.
+#[derive(Default)]
+struct Termination {
+    idle_threads: usize,
+    pending_batches: usize,
+}
+
+/// The state shared by the threads of a search.
+struct Shared {
+    solution: Mutex<Option<State>>,
+    termination: Mutex<Termination>,
+    done: AtomicBool,
+    exhausted: AtomicBool,
+}
+
+/// The results of a thread.
+struct Report {
+    shard: Shard,
+    /// The receiver of the states sent to the thread after it stopped.
+    receiver: Receiver<Vec<(u64, State)>>,
+    expanded_states: usize,
+    generated_states: usize,
+    pruned_states: PruningStatistics,
+}
+
+/// A hash-distributed A* search. Each thread owns the states whose key maps
+/// to it, and sends the successors it generates to their owners.
+///
+/// The search can be paused on timeout and resumed by the next call.
+pub struct ParallelSearch {
+    shards: Vec<Shard>,
+    /// The best solution found, which is only returned once no open state
+    /// can lead to a cheaper one for optimal strategies.
+    solution: Option<State>,
+}
+
+impl ParallelSearch {
+    /// Creates a new search split between the given number of threads.
+    pub fn new(threads: usize) -> Self {
+        Self {
+            shards: (0..threads).map(|_| Shard::default()).collect(),
+            solution: None,
+        }
+    }
+
+    /// Adds the state to the heap of its owner.
+    pub fn insert(&mut self, state: State, solver: &Search) {
+        let key = solver.state_key(&state);
+        let owner = owner(key, self.shards.len());
+        self.shards[owner].insert(key, state, solver);
+    }
+
+    /// Continues the search until a solution is found, the search space is
+    /// exhausted or the timeout is reached.
+    ///
+    /// The number of expanded states is added to `expanded_states`.
+    pub fn search(
+        &mut self,
+        solver: &Search,
+        timer: Instant,
+        timeout: Duration,
+        expanded_states: &mut usize,
+    ) -> Result<Actions, SolveError> {
+        let threads = self.shards.len();
+        let shared = Shared {
+            solution: Mutex::new(self.solution.take()),
+            termination: Mutex::new(Termination::default()),
+            done: AtomicBool::new(false),
+            exhausted: AtomicBool::new(false),
+        };
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..threads).map(|_| mpsc::channel()).unzip();
+        let reports: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = std::mem::take(&mut self.shards)
+                .into_iter()
+                .zip(receivers)
+                .enumerate()
+                .map(|(index, (shard, receiver))| {
+                    let seed = solver.seed();
+                    let senders = senders.clone();
+                    let shared = &shared;
+                    scope.spawn(move || {
+                        let solver = Search::from_seed(seed);
+                        run(
+                            index, shard, receiver, &senders, shared, &solver, timer, timeout,
+                        )
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut stored_states = 0;
+        drop(senders);
+        for mut report in reports {
+            // Keep the states sent after the search stopped for the next call.
+            for (key, state) in report.receiver.try_iter().flatten() {
+                report.shard.insert(key, state, solver);
+            }
+            *expanded_states += report.expanded_states;
+            solver
+                .context()
+                .add_generated_states(report.generated_states);
+            solver.context().add_pruned_states(report.pruned_states);
+            stored_states += report.shard.costs.len();
+            self.shards.push(report.shard);
+        }
+        self.solution = shared.solution.into_inner().unwrap();
+        let exhausted = shared.exhausted.load(Ordering::SeqCst);
+        match &self.solution {
+            Some(solution) if exhausted || !solver.expands_in_cost_order() => {
+                Ok(solution.actions_with_moves(solver.analysis(), solver.context()))
+            }
+            None if exhausted => Err(solver.no_solution(NoSolutionReason::SearchExhausted {
+                visited_states: stored_states,
+            })),
+            _ => Err(solver.timeout_error()),
+        }
+    }
+
+    /// Returns the best open state of all threads.
+    pub fn best_state(&self) -> Option<&State> {
+        self.shards
+            .iter()
+            .filter_map(|shard| shard.heap.peek())
+            .max()
+    }
+
+    /// Returns the number of states kept in memory.
+    pub fn stored_states(&self) -> usize {
+        self.shards.iter().map(|shard| shard.costs.len()).sum()
+    }
+}
+
+/// Expands the states of the shard until a solution is found, every thread
+/// runs out of states or the timeout is reached.
+#[allow(clippy::too_many_arguments)]
+fn run(
+    index: usize,
+    mut shard: Shard,
+    receiver: Receiver<Vec<(u64, State)>>,
+    senders: &[Sender<Vec<(u64, State)>>],
+    shared: &Shared,
+    solver: &Search,
+    timer: Instant,
+    timeout: Duration,
+) -> Report {
+    let threads = senders.len();
+    let mut expanded_states = 0;
+    let mut idle = false;
+    while !shared.done.load(Ordering::SeqCst) {
+        if timer.elapsed() >= timeout || solver.is_cancelled() {
+            shared.done.store(true, Ordering::SeqCst);
+            break;
+        }
+        let batch = if idle {
+            receiver.recv_timeout(Duration::from_millis(1)).ok()
+        } else {
+            receiver.try_recv().ok()
+        };
+        if let Some(batch) = batch {
+            for (key, state) in batch {
+                shard.insert(key, state, solver);
+            }
+            let mut termination = shared.termination.lock().unwrap();
+            termination.pending_batches -= 1;
+            if idle {
+                termination.idle_threads -= 1;
+                idle = false;
+            }
+            continue;
+        }
+
+        // Open states no cheaper than the solution cannot lead to a cheaper
+        // one.
+        let solution_priority = shared
+            .solution
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(State::priority);
+        let state = match shard.heap.peek() {
+            Some(state) if solution_priority.is_none_or(|priority| state.priority() < priority) => {
+                shard.heap.pop()
+            }
+            _ => None,
+        };
+        let Some(state) = state else {
+            if !idle {
+                idle = true;
+                let mut termination = shared.termination.lock().unwrap();
+                termination.idle_threads += 1;
+                if termination.idle_threads == threads && termination.pending_batches == 0 {
+                    shared.exhausted.store(true, Ordering::SeqCst);
+                    shared.done.store(true, Ordering::SeqCst);
+                }
+            }
+            continue;
+        };
+
+        if state.is_solved(solver.analysis(), solver.context()) {
+            let mut solution = shared.solution.lock().unwrap();
+            if solution
+                .as_ref()
+                .is_none_or(|solution| state.priority() < solution.priority())
+            {
+                *solution = Some(state);
+            }
+            if !solver.expands_in_cost_order() {
+                shared.done.store(true, Ordering::SeqCst);
+            }
+            continue;
+        }
+        if solver.expands_in_cost_order()
+            && shard.is_stale(solver.state_key(&state), &state, solver)
+        {
+            continue;
+        }
+        expanded_states += 1;
+
+        let mut batches = vec![Vec::new(); threads];
+        for successor in state.successors(solver.analysis(), solver.context()) {
+            let key = solver.state_key(&successor);
+            let owner = owner(key, threads);
+            if owner == index {
+                shard.insert(key, successor, solver);
+            } else {
+                batches[owner].push((key, successor));
+            }
+        }
+        for (owner, batch) in batches.into_iter().enumerate() {
+            if batch.is_empty() {
+                continue;
+            }
+            shared.termination.lock().unwrap().pending_batches += 1;
+            let _ = senders[owner].send(batch);
+        }
+    }
+
+    Report {
+        shard,
+        receiver,
+        expanded_states,
+        generated_states: solver.generated_states(),
+        pruned_states: solver.context().pruned_states(),
+    }
+}
+
+/// Returns the index of the thread owning the states with the given key.
+fn owner(key: u64, threads: usize) -> usize {
+    (key % threads as u64) as usize
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use crate::solve::path_finding::PathFinder;
+use nalgebra::Vector2;
+
+use soukoban::{direction::Direction, Action, Actions, Map, Tiles};
+
+/// A push on the path to a state, linked to the push before it.
+struct Push {
+    previous: Option<Arc<Push>>,
+    /// The player position before the push.
+    player_position: Vector2<i32>,
+    direction: Direction,
+}
+
+impl Drop for Push {
+    fn drop(&mut self) {
+        // Dropping a long path recursively could overflow the stack.
+        let mut previous = self.previous.take();
+        while let Some(push) = previous {
+            match Arc::try_unwrap(push) {
+                Ok(mut push) => previous = push.previous.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// The pushes leading from the initial state to a state, sharing the pushes
+/// of its ancestors. The player moves between the pushes are only found when
+/// the actions are reconstructed.
+#[derive(Clone, Default)]
+pub struct Path {
+    last: Option<Arc<Push>>,
+    moves: usize,
+    pushes: usize,
+}
+
+impl Path {
+    /// Returns the path followed by walking the given number of moves to the
+    /// player position, and pushing in the direction.
+    pub fn with_push(
+        &self,
+        walk: usize,
+        player_position: Vector2<i32>,
+        direction: Direction,
+    ) -> Self {
+        Self {
+            last: Some(Arc::new(Push {
+                previous: self.last.clone(),
+                player_position,
+                direction,
+            })),
+            moves: self.moves + walk + 1,
+            pushes: self.pushes + 1,
+        }
+    }
+
+    /// Returns the number of moves, including the pushes. Only the pushes are
+    /// counted if the moves between them are not tracked.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    /// Returns the number of pushes.
+    pub fn pushes(&self) -> usize {
+        self.pushes
+    }
+
+    /// Returns the player position before the last push and its direction.
+    pub fn last_push(&self) -> Option<(Vector2<i32>, Direction)> {
+        self.last
+            .as_ref()
+            .map(|push| (push.player_position, push.direction))
+    }
+
+    /// Reconstructs the actions from the initial state of the map, walking
+    /// the player along a shortest path to each push.
+    pub fn actions(&self, map: &Map) -> Actions {
+        self.actions_with(map, &mut PathFinder::new(map.dimensions()))
+    }
+
+    /// Returns the path of the pushes, each given by the player position
+    /// before it and its direction, with the given number of moves.
+    pub fn from_pushes(
+        pushes: impl IntoIterator<Item = (Vector2<i32>, Direction)>,
+        moves: usize,
+    ) -> Self {
+        let mut path = pushes
+            .into_iter()
+            .fold(Self::default(), |path, (player_position, direction)| {
+                path.with_push(0, player_position, direction)
+            });
+        path.moves = moves;
+        path
+    }
+
+    /// Returns the player position before each push and its direction, from
+    /// the first push to the last.
+    pub fn pushes_in_order(&self) -> Vec<(Vector2<i32>, Direction)> {
+        let mut pushes = Vec::with_capacity(self.pushes);
+        let mut push = self.last.as_deref();
+        while let Some(current) = push {
+            pushes.push((current.player_position, current.direction));
+            push = current.previous.as_deref();
+        }
+        pushes.reverse();
+        pushes
+    }
+
+    /// Reconstructs the actions like [`Path::actions`], finding the player
+    /// paths with the buffers of the path finder.
+    pub fn actions_with(&self, map: &Map, path_finder: &mut PathFinder) -> Actions {
+        let mut actions = Actions::new();
+        let mut player_position = map.player_position();
+        let mut box_positions = map.box_positions().clone();
+        for (push_position, direction) in self.pushes_in_order() {
+            let path = path_finder
+                .find_path(player_position, push_position, |position| {
+                    !map[position].intersects(Tiles::Wall) && !box_positions.contains(&position)
+                })
+                .unwrap();
+            actions.extend(
+                path.windows(2)
+                    .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
+                    .map(Action::Move),
+            );
+            actions.push(Action::Push(direction));
+
+            player_position = push_position + &direction.into();
+            box_positions.remove(&player_position);
+            box_positions.insert(player_position + &direction.into());
+        }
+        actions
+    }
+
+    /// Returns an estimate of the bytes allocated for the last push, which
+    /// is shared with the paths of the descendants of the state.
+    pub fn allocated_bytes(&self) -> usize {
+        self.last
+            .as_ref()
+            .map_or(0, |_| 2 * size_of::<usize>() + size_of::<Push>())
+    }
+}
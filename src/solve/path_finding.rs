@@ -0,0 +1,136 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::solve::square_grid::square_index;
+
+use nalgebra::Vector2;
+use soukoban::direction::Direction;
+
+/// Finds shortest player paths with A*, reusing its buffers between calls.
+///
+/// The cost and parent of each square are stored row by row, and stamped
+/// with the generation of the call that set them, so the buffers never need
+/// to be cleared.
+pub struct PathFinder {
+    dimensions: Vector2<i32>,
+    generation: u32,
+    generations: Vec<u32>,
+    costs: Vec<u32>,
+    parents: Vec<u32>,
+    open_set: BinaryHeap<(Reverse<u32>, u32, u32)>,
+}
+
+impl PathFinder {
+    /// Creates a path finder for levels of the given dimensions.
+    pub fn new(dimensions: Vector2<i32>) -> Self {
+        let len = (dimensions.x.max(0) * dimensions.y.max(0)) as usize;
+        Self {
+            dimensions,
+            generation: 0,
+            generations: vec![0; len],
+            costs: vec![0; len],
+            parents: vec![0; len],
+            open_set: BinaryHeap::new(),
+        }
+    }
+
+    /// Returns a shortest path of accessible positions from `from` to `to`,
+    /// including both, or `None` if there is no such path.
+    ///
+    /// Paths leaving the level are found by
+    /// [`soukoban::path_finding::find_path`] instead.
+    pub fn find_path(
+        &mut self,
+        from: Vector2<i32>,
+        to: Vector2<i32>,
+        is_accessible: impl Fn(Vector2<i32>) -> bool,
+    ) -> Option<Vec<Vector2<i32>>> {
+        let (Some(from_index), Some(to_index)) = (
+            square_index(self.dimensions, from),
+            square_index(self.dimensions, to),
+        ) else {
+            return soukoban::path_finding::find_path(from, to, is_accessible);
+        };
+        self.next_generation();
+
+        self.open_set.clear();
+        self.visit(from_index, 0, from_index);
+        self.open_set
+            .push((Reverse(manhattan_distance(from, to)), 0, from_index as u32));
+        while let Some((_, cost, index)) = self.open_set.pop() {
+            let index = index as usize;
+            if index == to_index {
+                return Some(self.construct_path(from_index, to_index));
+            }
+            if cost > self.costs[index] {
+                // A cheaper path to the square has been found since.
+                continue;
+            }
+            let position = self.position(index);
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let neighbor = position + &direction.into();
+                if !is_accessible(neighbor) {
+                    continue;
+                }
+                let Some(neighbor_index) = square_index(self.dimensions, neighbor) else {
+                    // The path leaves the level.
+                    return soukoban::path_finding::find_path(from, to, is_accessible);
+                };
+                let new_cost = cost + 1;
+                if self.generations[neighbor_index] == self.generation
+                    && self.costs[neighbor_index] <= new_cost
+                {
+                    continue;
+                }
+                self.visit(neighbor_index, new_cost, index);
+                self.open_set.push((
+                    Reverse(new_cost + manhattan_distance(neighbor, to)),
+                    new_cost,
+                    neighbor_index as u32,
+                ));
+            }
+        }
+        None
+    }
+
+    /// Starts a new call, invalidating the costs and parents of the previous
+    /// ones.
+    fn next_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            // Stamps of 4 billion calls ago would look current again.
+            self.generations.fill(0);
+            self.generation = 1;
+        }
+    }
+
+    fn visit(&mut self, index: usize, cost: u32, parent: usize) {
+        self.generations[index] = self.generation;
+        self.costs[index] = cost;
+        self.parents[index] = parent as u32;
+    }
+
+    fn construct_path(&self, from_index: usize, to_index: usize) -> Vec<Vector2<i32>> {
+        let mut path = vec![self.position(to_index)];
+        let mut index = to_index;
+        while index != from_index {
+            index = self.parents[index] as usize;
+            path.push(self.position(index));
+        }
+        path.reverse();
+        path
+    }
+
+    fn position(&self, index: usize) -> Vector2<i32> {
+        let width = self.dimensions.x as usize;
+        Vector2::new((index % width) as i32, (index / width) as i32)
+    }
+}
+
+fn manhattan_distance(a: Vector2<i32>, b: Vector2<i32>) -> u32 {
+    (a - b).abs().sum() as u32
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Instant,
+};
+
+use itertools::Itertools;
+use nalgebra::Vector2;
+use soukoban::{
+    direction::Direction,
+    path_finding::{normalized_area, reachable_area},
+    Map, Tiles,
+};
+
+/// Exact push counts required to push each group of boxes onto distinct goals,
+/// ignoring all other boxes.
+#[derive(Clone)]
+pub struct PatternDatabase {
+    group_size: usize,
+    pushes: HashMap<Vec<Vector2<i32>>, usize>,
+}
+
+impl PatternDatabase {
+    /// Returns the number of boxes in each group.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// Returns the minimum number of pushes required to push the boxes onto
+    /// distinct goals, or `None` if they can never be.
+    ///
+    /// The box positions must be sorted by row, then by column.
+    pub fn get(&self, box_positions: &[Vector2<i32>]) -> Option<usize> {
+        self.pushes.get(box_positions).copied()
+    }
+}
+
+/// Builds a pattern database by pulling groups of boxes away from the goals
+/// using a breadth-first search, which can be interrupted and resumed.
+pub struct PatternDatabaseBuilder {
+    group_size: usize,
+    pushes: HashMap<Vec<Vector2<i32>>, usize>,
+    visited: HashSet<(Vec<Vector2<i32>>, Vector2<i32>)>,
+    queue: VecDeque<(Vec<Vector2<i32>>, Vector2<i32>, usize)>,
+}
+
+impl PatternDatabaseBuilder {
+    /// Creates a new builder, starting from every placement of a group of
+    /// boxes on goals.
+    pub fn new(map: &Map, group_size: usize) -> Self {
+        let group_size = group_size.clamp(1, map.goal_positions().len().max(1));
+        let mut instance = Self {
+            group_size,
+            pushes: HashMap::new(),
+            visited: HashSet::new(),
+            queue: VecDeque::new(),
+        };
+
+        let floors: Vec<_> = (0..map.dimensions().y)
+            .flat_map(|y| (0..map.dimensions().x).map(move |x| Vector2::new(x, y)))
+            .filter(|position| map[*position].intersects(Tiles::Floor))
+            .collect();
+        for box_positions in map
+            .goal_positions()
+            .iter()
+            .copied()
+            .sorted_by_key(|position| (position.y, position.x))
+            .combinations(group_size)
+        {
+            // The player may end up in any area separated by the boxes.
+            let mut covered = HashSet::new();
+            for floor in &floors {
+                if box_positions.contains(floor) || covered.contains(floor) {
+                    continue;
+                }
+                let area = Self::player_reachable_area(map, *floor, &box_positions);
+                let player_position = normalized_area(&area).unwrap();
+                covered.extend(area);
+                instance.insert(box_positions.clone(), player_position, 0);
+            }
+        }
+        instance
+    }
+
+    /// Continues building until the database is complete or the deadline, if
+    /// any, is reached. Returns `true` if the database is complete.
+    pub fn build(&mut self, map: &Map, deadline: Option<Instant>) -> bool {
+        while let Some((box_positions, player_position, pushes)) = self.queue.pop_front() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.queue
+                    .push_front((box_positions, player_position, pushes));
+                return false;
+            }
+
+            let area = Self::player_reachable_area(map, player_position, &box_positions);
+            for (index, box_position) in box_positions.iter().enumerate() {
+                for pull_direction in [
+                    Direction::Up,
+                    Direction::Right,
+                    Direction::Down,
+                    Direction::Left,
+                ] {
+                    let new_box_position = box_position + &pull_direction.into();
+                    let new_player_position = new_box_position + &pull_direction.into();
+                    if !area.contains(&new_box_position)
+                        || !map[new_player_position].intersects(Tiles::Floor)
+                        || box_positions.contains(&new_player_position)
+                    {
+                        continue;
+                    }
+
+                    let mut new_box_positions = box_positions.clone();
+                    new_box_positions[index] = new_box_position;
+                    new_box_positions.sort_by_key(|position| (position.y, position.x));
+                    let new_player_position = normalized_area(&Self::player_reachable_area(
+                        map,
+                        new_player_position,
+                        &new_box_positions,
+                    ))
+                    .unwrap();
+                    self.insert(new_box_positions, new_player_position, pushes + 1);
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns the complete pattern database.
+    pub fn finish(self) -> PatternDatabase {
+        debug_assert!(self.queue.is_empty());
+        PatternDatabase {
+            group_size: self.group_size,
+            pushes: self.pushes,
+        }
+    }
+
+    fn insert(
+        &mut self,
+        box_positions: Vec<Vector2<i32>>,
+        player_position: Vector2<i32>,
+        pushes: usize,
+    ) {
+        if !self
+            .visited
+            .insert((box_positions.clone(), player_position))
+        {
+            return;
+        }
+        // States are visited in order of pushes, so the first is the minimum.
+        self.pushes.entry(box_positions.clone()).or_insert(pushes);
+        self.queue
+            .push_back((box_positions, player_position, pushes));
+    }
+
+    fn player_reachable_area(
+        map: &Map,
+        player_position: Vector2<i32>,
+        box_positions: &[Vector2<i32>],
+    ) -> HashSet<Vector2<i32>> {
+        reachable_area(player_position, |position| {
+            map[position].intersects(Tiles::Floor) && !box_positions.contains(&position)
+        })
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::solve::solver::*;
+
+use soukoban::Actions;
+
+/// How long a run searches before checking whether it has been cancelled.
+const SLICE: Duration = Duration::from_millis(10);
+
+/// A configuration raced by portfolio search.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PortfolioSpec {
+    pub strategy: Strategy,
+    pub algorithm: Algorithm,
+}
+
+/// When portfolio search stops and which solution it returns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PortfolioPolicy {
+    /// Return the first solution found, cancelling the other runs
+    #[default]
+    FirstSolution,
+
+    /// Return the solution with the fewest pushes, then moves, found before
+    /// the deadline. The other runs are cancelled early once a solution is
+    /// known to be push optimal.
+    BestWithinDeadline,
+}
+
+/// The outcome of a single run of portfolio search.
+#[derive(Debug)]
+pub struct PortfolioRun {
+    pub spec: PortfolioSpec,
+    /// Cancelled runs report a timeout.
+    pub result: Result<Actions, SolveError>,
+    pub expanded_states: usize,
+    pub stored_states: usize,
+    pub elapsed: Duration,
+}
+
+/// The outcome of portfolio search.
+#[derive(Debug)]
+pub struct PortfolioResult {
+    /// The index of the run whose solution was chosen, if any.
+    pub winner: Option<usize>,
+    /// The runs in the order of their specs.
+    pub runs: Vec<PortfolioRun>,
+}
+
+impl PortfolioResult {
+    /// Returns the chosen solution, if any.
+    pub fn solution(&self) -> Option<&Actions> {
+        let run = &self.runs[self.winner?];
+        run.result.as_ref().ok()
+    }
+}
+
+/// Races the specs against each other, each on its own thread sharing the
+/// analyses of the solver.
+pub fn solve(
+    solver: &Search,
+    specs: &[PortfolioSpec],
+    policy: PortfolioPolicy,
+    timeout: Duration,
+) -> PortfolioResult {
+    let timer = Instant::now();
+    let cancelled = AtomicBool::new(false);
+    let first_solution = Mutex::new(None);
+    let runs: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = specs
+            .iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                let mut seed = solver.seed();
+                seed.options.strategy = spec.strategy;
+                seed.options.algorithm = spec.algorithm;
+                let cancelled = &cancelled;
+                let first_solution = &first_solution;
+                scope.spawn(move || {
+                    let mut solver = Search::from_seed(seed);
+                    solver.initialize();
+                    let result = loop {
+                        let remaining = timeout.saturating_sub(timer.elapsed());
+                        if cancelled.load(Ordering::SeqCst) || remaining.is_zero() {
+                            break Err(solver.timeout_error());
+                        }
+                        match solver.search_for(remaining.min(SLICE)) {
+                            Err(SolveError::Timeout { .. }) => continue,
+                            result => break result,
+                        }
+                    };
+                    if result.is_ok() {
+                        first_solution.lock().unwrap().get_or_insert(index);
+                        if policy == PortfolioPolicy::FirstSolution
+                            || solver.optimality_bound() == Some(1.0)
+                        {
+                            cancelled.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    PortfolioRun {
+                        spec: *spec,
+                        result,
+                        expanded_states: solver.expanded_states(),
+                        stored_states: solver.stored_states(),
+                        elapsed: timer.elapsed(),
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let winner = match policy {
+        PortfolioPolicy::FirstSolution => first_solution.into_inner().unwrap(),
+        PortfolioPolicy::BestWithinDeadline => runs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, run)| Some((index, run.result.as_ref().ok()?)))
+            .min_by_key(|(_, solution)| (solution.pushes(), solution.moves()))
+            .map(|(index, _)| index),
+    };
+    PortfolioResult { winner, runs }
+}
@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+use soukoban::Actions;
+
+use crate::solve::{
+    box_positions::BoxPositions, level_analysis::LevelAnalysis, path::Path, solver::*, state::State,
+};
+
+/// A search run by [`Search::solve`] in place of the one selected by
+/// [`Algorithm`], set by [`Search::set_search_algorithm`].
+///
+/// States are generated and evaluated through the [`SearchContext`], which
+/// applies the same deadlock pruning and lower bounds as the built-in
+/// algorithms.
+pub trait SearchAlgorithm: Send {
+    /// Searches for a solution until one is found or a limit of the solver
+    /// is reached. Calling it again after a timeout should resume the
+    /// search.
+    fn search(&mut self, context: &mut SearchContext) -> Result<Actions, SolveError>;
+}
+
+/// The A* search of [`Algorithm::AStar`], which keeps every open and
+/// visited state in the solver.
+pub struct AStarSearch;
+
+impl SearchAlgorithm for AStarSearch {
+    fn search(&mut self, context: &mut SearchContext) -> Result<Actions, SolveError> {
+        context.solver.a_star_search(context.timer, context.timeout)
+    }
+}
+
+/// The solver as seen by a [`SearchAlgorithm`] during a call to
+/// [`Search::solve`].
+pub struct SearchContext<'a> {
+    solver: &'a mut Search,
+    timer: Instant,
+    timeout: Duration,
+}
+
+impl<'a> SearchContext<'a> {
+    pub(crate) fn new(solver: &'a mut Search, timer: Instant, timeout: Duration) -> Self {
+        Self {
+            solver,
+            timer,
+            timeout,
+        }
+    }
+
+    pub fn analysis(&self) -> &LevelAnalysis {
+        self.solver.analysis()
+    }
+
+    pub fn options(&self) -> &SolverOptions {
+        self.solver.options()
+    }
+
+    /// Returns the state of the level before any push.
+    pub fn initial_state(&self) -> State {
+        State::new(
+            self.solver.map.player_position(),
+            BoxPositions::of(&self.solver.map),
+            Path::default(),
+            self.solver.analysis(),
+            self.solver.context(),
+        )
+    }
+
+    /// Expands the state, counting it in the statistics, and returns its
+    /// successors which are not deadlocked. Returns the error of the limit
+    /// once a limit is reached or the search is cancelled.
+    pub fn expand(&mut self, state: &State) -> Result<Vec<State>, SolveError> {
+        self.solver
+            .check_limits(self.timer, self.timeout, self.solver.expanded_states())?;
+        self.solver.record_expanded_state();
+        Ok(state.successors(self.solver.analysis(), self.solver.context()))
+    }
+
+    /// Returns the lower bound of the pushes needed to solve the state, or
+    /// `None` if it is a deadlock.
+    pub fn lower_bound(&self, state: &State) -> Option<usize> {
+        state.lower_bound(self.solver.analysis(), self.solver.context())
+    }
+
+    pub fn is_solved(&self, state: &State) -> bool {
+        state.is_solved(self.solver.analysis(), self.solver.context())
+    }
+
+    /// Returns the actions leading to the state, including the moves
+    /// between pushes.
+    pub fn actions(&self, state: &State) -> Actions {
+        state.actions_with_moves(self.solver.analysis(), self.solver.context())
+    }
+
+    /// Returns the error of a search which cannot find a solution, with the
+    /// statistics of the solver.
+    pub fn no_solution(&self, reason: NoSolutionReason) -> SolveError {
+        self.solver.no_solution(reason)
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashSet, io::Write};
+
+use crate::solve::{solver::Search, state::State};
+
+/// A state expanded by the search, or the solved state it reached.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchNode {
+    /// The normalized hash of the state.
+    pub key: u64,
+    pub moves: usize,
+    pub pushes: usize,
+    pub lower_bound: usize,
+    pub boxes_on_goals: usize,
+    pub solved: bool,
+}
+
+impl SearchNode {
+    /// Creates a node describing the state.
+    pub fn new(state: &State, solver: &Search) -> Self {
+        Self {
+            key: state.normalized_hash(solver.analysis(), solver.context()),
+            moves: state.path.moves(),
+            pushes: state.path.pushes(),
+            lower_bound: state
+                .lower_bound(solver.analysis(), solver.context())
+                .unwrap(),
+            boxes_on_goals: state.boxes_on_goals(),
+            solved: state.is_solved(solver.analysis(), solver.context()),
+        }
+    }
+}
+
+/// The states expanded by the search and the edges to their successors,
+/// recorded until the number of nodes reaches the capacity.
+///
+/// Nodes are identified by the normalized hash of their state, so states
+/// reached along several paths are merged.
+#[derive(Default)]
+pub struct SearchGraph {
+    capacity: usize,
+    nodes: Vec<SearchNode>,
+    node_keys: HashSet<u64>,
+    edges: Vec<(u64, u64)>,
+    edge_keys: HashSet<(u64, u64)>,
+    truncated: bool,
+}
+
+impl SearchGraph {
+    /// Creates a new graph holding at most `capacity` nodes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Records the node and the edges to its successors, unless the graph is
+    /// full.
+    pub fn insert(&mut self, node: SearchNode, successors: impl IntoIterator<Item = u64>) {
+        let key = node.key;
+        if !self.node_keys.contains(&key) {
+            if self.nodes.len() >= self.capacity {
+                self.truncated = true;
+                return;
+            }
+            self.node_keys.insert(key);
+            self.nodes.push(node);
+        }
+        for successor in successors {
+            if self.edge_keys.insert((key, successor)) {
+                self.edges.push((key, successor));
+            }
+        }
+    }
+
+    /// Returns the nodes in the order they were recorded, starting with the
+    /// initial state.
+    pub fn nodes(&self) -> &[SearchNode] {
+        &self.nodes
+    }
+
+    /// Returns the edges from the expanded states to their successors.
+    pub fn edges(&self) -> &[(u64, u64)] {
+        &self.edges
+    }
+
+    /// Checks if nodes have been left out because the graph was full.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Writes the graph in the GraphViz DOT format.
+    pub fn write_dot(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "digraph search {{")?;
+        writeln!(writer, "    node [shape=box];")?;
+        for node in &self.nodes {
+            write!(
+                writer,
+                "    \"{}\" [label=\"g={}/{} h={}\\nboxes on goals: {}\"",
+                node.key, node.moves, node.pushes, node.lower_bound, node.boxes_on_goals
+            )?;
+            if node.solved {
+                write!(writer, ", peripheries=2")?;
+            }
+            writeln!(writer, "];")?;
+        }
+        for (from, to) in &self.edges {
+            writeln!(writer, "    \"{}\" -> \"{}\";", from, to)?;
+        }
+        writeln!(writer, "}}")
+    }
+
+    /// Writes the graph as JSON. The keys are written as strings, since they
+    /// do not fit in the numbers of many JSON parsers.
+    pub fn write_json(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write!(writer, "{{\"truncated\":{},\"nodes\":[", self.truncated)?;
+        for (index, node) in self.nodes.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(
+                writer,
+                "{{\"key\":\"{}\",\"moves\":{},\"pushes\":{},\"lower_bound\":{},\"boxes_on_goals\":{},\"solved\":{}}}",
+                node.key, node.moves, node.pushes, node.lower_bound, node.boxes_on_goals, node.solved
+            )?;
+        }
+        write!(writer, "],\"edges\":[")?;
+        for (index, (from, to)) in self.edges.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "[\"{}\",\"{}\"]", from, to)?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
@@ -0,0 +1,67 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use soukoban::{Actions, Level};
+
+/// A level as its XSB map and its metadata, such as the title and the
+/// author, rather than the tiles of the map.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct LevelRecord {
+    pub xsb: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl From<&Level> for LevelRecord {
+    fn from(level: &Level) -> Self {
+        Self {
+            xsb: level.map().to_string(),
+            metadata: level
+                .metadata()
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl LevelRecord {
+    /// Parses the level, with the metadata written after the map. Values of
+    /// several lines are written as blocks ending with `<key>-end:`, like
+    /// comments.
+    pub fn to_level(&self) -> Result<Level, <Level as FromStr>::Err> {
+        let mut xsb = self.xsb.clone();
+        for (key, value) in &self.metadata {
+            if value.contains('\n') {
+                xsb.push_str(&format!("{key}:\n{value}\n{key}-end:\n"));
+            } else {
+                xsb.push_str(&format!("{key}: {value}\n"));
+            }
+        }
+        Level::from_str(&xsb)
+    }
+}
+
+/// A solution as its actions in LURD notation, with its moves and pushes.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SolutionRecord {
+    pub lurd: String,
+    pub moves: usize,
+    pub pushes: usize,
+}
+
+impl From<&Actions> for SolutionRecord {
+    fn from(actions: &Actions) -> Self {
+        Self {
+            lurd: actions.to_string(),
+            moves: actions.moves(),
+            pushes: actions.pushes(),
+        }
+    }
+}
+
+impl SolutionRecord {
+    /// Parses the actions of the solution.
+    pub fn actions(&self) -> Result<Actions, <Actions as FromStr>::Err> {
+        Actions::from_str(&self.lurd)
+    }
+}
@@ -1,17 +1,61 @@
 use std::{
-    cell::OnceCell,
-    collections::{BinaryHeap, HashMap, HashSet},
+    cell::{Cell, OnceCell, Ref, RefCell},
+    collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet},
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::{Deref, DerefMut},
+    sync::Arc,
     time::{Duration, Instant},
 };
 
-use crate::{box_pushable_paths_with_positions, solve::state::*};
+#[cfg(feature = "serde")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "serde")]
+use crate::solve::analysis::{Analysis, AnalysisError, GoalDistances, LearnedDeadlock};
+use crate::solve::{
+    arena::{OpenList, QueueKind},
+    backward::{self, BackwardSearch, Step},
+    bidirectional::BidirectionalSearch,
+    bit_grid::SquareMasks,
+    box_positions::BoxPositions,
+    cancellation::CancellationToken,
+    deadlock::DeadEndCorridor,
+    distance::GoalDistanceGrid,
+    events::{EventSender, SolveEvent},
+    handle::{self, SolveHandle},
+    hasher,
+    hint::{self, Hint},
+    ida_star::IdaStar,
+    influence::Influence,
+    learned_deadlocks::LearnedDeadlocks,
+    level_analysis::{LevelAnalysis, LevelError},
+    lower_bound_cache::LowerBoundCache,
+    packing_order::PackingOrder,
+    parallel::ParallelSearch,
+    path::Path,
+    pattern_database::{PatternDatabase, PatternDatabaseBuilder},
+    portfolio::{self, PortfolioPolicy, PortfolioResult, PortfolioSpec},
+    search_algorithm::{AStarSearch, SearchAlgorithm, SearchContext},
+    search_graph::{SearchGraph, SearchNode},
+    spill::SpillFile,
+    square_grid::{CellIndex, SquareGrid, SquareInfo},
+    state::*,
+    state_context::StateContext,
+    symmetry::Symmetry,
+    visited::VisitedStates,
+    zobrist::ZobristKeys,
+};
+use crate::verify;
 
 use itertools::Itertools;
 use nalgebra::Vector2;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use soukoban::{direction::Direction, path_finding::reachable_area, Actions, Map, Tiles};
+use soukoban::{direction::Direction, Actions, Level, Map, Tiles};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Strategy {
     /// Speed priority
     #[default]
@@ -25,9 +69,52 @@ pub enum Strategy {
 
     /// Find push optimal solutions with best moves
     OptimalPushMove,
+
+    /// Find push optimal solutions, ignoring moves
+    OptimalPushes,
+
+    /// Find solutions with at most the given factor of the optimal pushes
+    Weighted(f32),
+
+    /// Find any solution, ignoring its length
+    Greedy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Algorithm {
+    /// Keep every open and visited state in memory
+    #[default]
+    AStar,
+
+    /// Iterative deepening on the estimated cost, keeping only the current
+    /// path and a bounded transposition table in memory
+    IdaStar,
+
+    /// Keep only the best `width` open states, which gives up optimality
+    Beam { width: usize },
+
+    /// Search backward by pulling boxes off goals alongside the forward
+    /// search, until both meet
+    Bidirectional,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HeuristicKind {
+    /// Sum of the lower bounds of each box to its nearest goal
+    #[default]
+    ClosestGoal,
+
+    /// Minimum cost of assigning each box to a distinct goal
+    Matching,
+
+    /// Sum of the exact push counts of groups of boxes, ignoring other boxes
+    PatternDatabase { group_size: usize },
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum LowerBoundMethod {
     /// Minimum push count to nearest goal
     MinimumPush,
@@ -40,328 +127,3329 @@ pub enum LowerBoundMethod {
     ManhattanDistance,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SuccessorOrdering {
+    /// Push the boxes in order of position
+    #[default]
+    Position,
+
+    /// Keep pushing the box pushed last, then push boxes that decrease the
+    /// lower bound, then the others
+    Inertia,
+}
+
+/// How the visited states are remembered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum VisitedFilter {
+    /// Remember the hash of every visited state
+    #[default]
+    Exact,
+
+    /// Remember the visited states in a Bloom filter of the given size, which
+    /// may claim that a new state has been visited
+    Approximate { bytes: usize },
+}
+
+/// Pruning of pushes far from the previous pushes, which gives up finding a
+/// solution from some states.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RelevanceCuts {
+    /// Maximum influence distance between a box and a recently pushed box
+    /// for the box to be pushed.
+    pub threshold: usize,
+    /// Number of previous pushes whose boxes are considered recent.
+    pub window: usize,
+}
+
+/// Switching to another strategy when the search stops making progress.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StallPolicy {
+    /// Number of expansions without a lower lower bound or more boxes on
+    /// goals after which the search is stalled.
+    pub expansions: usize,
+    /// The strategy with which the open states are searched after a stall.
+    pub fallback: Strategy,
+}
+
+/// Relieving the memory pressure of the open states once there are too many.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PressureRelief {
+    /// Number of open states above which the policy is applied.
+    pub max_open_states: usize,
+    pub policy: ReliefPolicy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ReliefPolicy {
+    /// Expand the states with the most pushes first among states of equal
+    /// priority, which reach a solution sooner and leave fewer open states
+    PreferDeeper,
+
+    /// Discard the given percentage of the open states with the worst
+    /// priority, which gives up optimality
+    Prune { percent: u8 },
+
+    /// Write the given percentage of the open states with the worst priority
+    /// to a temporary file, from which they are read back once they are the
+    /// best open states
+    Spill { percent: u8 },
+}
+
+/// How often the progress callback of a solver is called.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ProgressInterval {
+    /// Every given number of expanded states
+    Expansions(usize),
+
+    /// Every given time, which is read as often as for the timeout
+    Time(Duration),
+}
+
+impl Default for ProgressInterval {
+    fn default() -> Self {
+        Self::Time(Duration::from_secs(1))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SolverOptions {
+    pub strategy: Strategy,
+    pub algorithm: Algorithm,
+    /// Maximum number of states remembered by IDA* to avoid searching them
+    /// again.
+    pub transposition_table_capacity: usize,
+    pub lower_bound_method: LowerBoundMethod,
+    /// The lower bound of a state is the maximum of these heuristics.
+    pub heuristics: Vec<HeuristicKind>,
+    /// Add the pushes of boxes on goals that must make way for an adjacent
+    /// box, and then return, to the lower bounds.
+    ///
+    /// Only an adjacent box that cannot be pushed until the box on the goal
+    /// moves is detected. Boxes that must pass each other to reach their
+    /// goals are not, since the goals can be assigned so that they do not.
+    #[cfg_attr(feature = "serde", serde(alias = "linear_conflicts"))]
+    pub make_way_penalties: bool,
+    /// Treat boxes frozen on goals as walls when calculating lower bounds.
+    pub frozen_boxes_as_walls: bool,
+    /// Maximum number of box configurations whose lower bounds are cached.
+    pub lower_bound_cache_capacity: usize,
+    /// Prune states with areas the player cannot enter that can no longer be
+    /// solved.
+    pub corral_deadlocks: bool,
+    /// Maximum number of corral deadlocks remembered, so that states
+    /// containing them are pruned without another analysis.
+    pub learned_deadlocks_capacity: usize,
+    /// Only push the boxes of a corral the player must enter, when one exists.
+    pub pi_corral_pruning: bool,
+    /// Prune states whose boxes cannot be pushed onto distinct goals.
+    pub bipartite_deadlocks: bool,
+    /// Keep searching for cheaper solutions after the first one, until the
+    /// timeout is reached or the search space is exhausted.
+    pub anytime: bool,
+    /// Number of threads searching in parallel, each owning the states whose
+    /// hash maps to it.
+    pub threads: usize,
+    /// Evaluate the pushes of each expanded state on the threads of rayon.
+    /// Only pays off on levels with many boxes.
+    pub parallel_successors: bool,
+    /// Maximum number of states expanded before giving up, which unlike the
+    /// timeout does not depend on the speed of the machine.
+    pub max_expansions: Option<u64>,
+    /// Maximum estimated memory used by the open and visited states before
+    /// giving up, in bytes.
+    pub max_memory_bytes: Option<usize>,
+    /// Maximum time spent by [`Search::solve`], which searches until another
+    /// limit is reached if `None`.
+    pub timeout: Option<Duration>,
+    /// How often the callback set by [`Search::on_progress`] is called.
+    pub progress_interval: ProgressInterval,
+    /// Maximum number of expanded states recorded in the search graph, which
+    /// is not recorded if `None`.
+    pub record_graph: Option<usize>,
+    /// Push boxes onto the goals of a goal room in its packing order as a
+    /// single successor, which is the only successor in Fast mode.
+    pub goal_macros: bool,
+    /// Order in which the successors of a state are generated, which decides
+    /// the expansion order of states of equal priority.
+    pub successor_ordering: SuccessorOrdering,
+    /// Skip pushes of boxes far from the boxes pushed recently. If the search
+    /// is exhausted, it is repeated without the cuts.
+    pub relevance_cuts: Option<RelevanceCuts>,
+    /// Treat states that are reflections or rotations of each other under the
+    /// symmetries of the level as the same state.
+    pub symmetry_reduction: bool,
+    /// Compare visited states with equal hashes in full, counting hash
+    /// collisions instead of pruning the colliding states. Keeps a copy of
+    /// every visited state. Levels with more than 65536 squares are not
+    /// supported.
+    pub verify_hashes: bool,
+    /// How the visited states are remembered. An approximate filter bounds
+    /// their memory, but prunes some new states, so solutions may be missed.
+    pub visited_filter: VisitedFilter,
+    /// Find a solution with the `Fast` strategy first, then prune the states
+    /// of the optimal search that cannot lead to a cheaper solution.
+    pub branch_and_bound: bool,
+    /// Switch to a fallback strategy when the expanded states stop getting
+    /// closer to the solution.
+    pub stall_policy: Option<StallPolicy>,
+    /// Apply a policy to the open states whenever there are too many of them.
+    pub pressure_relief: Option<PressureRelief>,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self {
+            strategy: Strategy::default(),
+            algorithm: Algorithm::default(),
+            transposition_table_capacity: 100_000,
+            lower_bound_method: LowerBoundMethod::default(),
+            heuristics: vec![HeuristicKind::default()],
+            make_way_penalties: false,
+            frozen_boxes_as_walls: false,
+            lower_bound_cache_capacity: 1_000_000,
+            corral_deadlocks: false,
+            learned_deadlocks_capacity: 10_000,
+            pi_corral_pruning: false,
+            bipartite_deadlocks: false,
+            anytime: false,
+            threads: 1,
+            parallel_successors: false,
+            max_expansions: None,
+            max_memory_bytes: None,
+            timeout: None,
+            progress_interval: ProgressInterval::default(),
+            record_graph: None,
+            goal_macros: false,
+            successor_ordering: SuccessorOrdering::default(),
+            relevance_cuts: None,
+            symmetry_reduction: false,
+            verify_hashes: false,
+            visited_filter: VisitedFilter::default(),
+            branch_and_bound: false,
+            stall_policy: None,
+            pressure_relief: None,
+        }
+    }
+}
+
+/// A builder of a solver ready to run with [`Search::solve`], created by
+/// [`Solver::builder`].
+pub struct SolverBuilder {
+    map: Map,
+    options: SolverOptions,
+    progress_callback: Option<ProgressCallback>,
+    events: Option<EventSender>,
+    cancellation: Option<CancellationToken>,
+    search_algorithm: Option<Box<dyn SearchAlgorithm>>,
+}
+
+impl SolverBuilder {
+    /// Replaces every option, including those set before.
+    pub fn options(mut self, options: SolverOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.options.strategy = strategy;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.options.algorithm = algorithm;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_expansions(mut self, max_expansions: u64) -> Self {
+        self.options.max_expansions = Some(max_expansions);
+        self
+    }
+
+    pub fn max_memory_bytes(mut self, max_memory_bytes: usize) -> Self {
+        self.options.max_memory_bytes = Some(max_memory_bytes);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Sets the callback called with the progress of the search, as by
+    /// [`Search::on_progress`].
+    pub fn on_progress(mut self, callback: impl FnMut(&ProgressInfo) + Send + 'static) -> Self {
+        self.progress_callback = Some(Box::new(callback));
+        self
+    }
+
+    pub fn progress_interval(mut self, progress_interval: ProgressInterval) -> Self {
+        self.options.progress_interval = progress_interval;
+        self
+    }
+
+    /// Sets the token with which the search is cancelled, as by
+    /// [`Search::set_cancellation`]. The analysis of the level done by
+    /// [`SolverBuilder::build`] is cancelled by it as well.
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Sets the channel to which the events of the search are sent, as by
+    /// [`Search::send_events`].
+    pub fn events(mut self, sender: EventSender) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Sets the search run in place of the configured algorithm, as by
+    /// [`Search::set_search_algorithm`].
+    pub fn search_algorithm(mut self, search_algorithm: impl SearchAlgorithm + 'static) -> Self {
+        self.search_algorithm = Some(Box::new(search_algorithm));
+        self
+    }
+
+    /// Analyzes the level and creates the solver, or returns
+    /// [`SolveError::InvalidLevel`] if the level cannot be searched and
+    /// [`SolveError::InvalidOptions`] if the options cannot be used together
+    /// or do not limit the search.
+    pub fn build(self) -> Result<Solver> {
+        let limits = (
+            self.options.timeout,
+            self.options.max_expansions,
+            self.options.max_memory_bytes,
+        );
+        if limits == (None, None, None) {
+            return Err(SolveError::InvalidOptions(
+                "a timeout, expansion or memory limit is required".to_string(),
+            ));
+        }
+        if self.options.timeout == Some(Duration::ZERO) {
+            return Err(SolveError::InvalidOptions(
+                "timeout must be positive".to_string(),
+            ));
+        }
+        self.build_unlimited()
+    }
+
+    /// Searches on a worker thread, as by [`Solver::spawn`]. The search is
+    /// cancelled through the token set by [`SolverBuilder::cancellation`],
+    /// if any, and the progress callback is still called on the worker.
+    pub fn spawn(mut self) -> SolveHandle {
+        let cancellation = self
+            .cancellation
+            .get_or_insert_with(CancellationToken::new)
+            .clone();
+        let progress_callback = self.progress_callback.take();
+        handle::spawn(self, cancellation, progress_callback)
+    }
+
+    /// Creates the solver as [`SolverBuilder::build`], without requiring a
+    /// limit.
+    pub(crate) fn build_unlimited(self) -> Result<Solver> {
+        LevelAnalysis::validate(&self.map).map_err(SolveError::InvalidLevel)?;
+        let analysis = LevelAnalysis::new(self.map, &self.options);
+        // The distances to the goals are the longest analysis of most levels.
+        if let Some(cancellation) = &self.cancellation {
+            if analysis.try_goal_distances(cancellation).is_none() {
+                return Err(SolveError::Cancelled {
+                    statistics: Box::default(),
+                });
+            }
+        }
+        let mut solver = Solver::with_analysis(Arc::new(analysis), self.options);
+        solver
+            .validate_options()
+            .map_err(SolveError::InvalidOptions)?;
+        solver.cancellation = self.cancellation;
+        *solver.progress_callback.get_mut() = self.progress_callback;
+        solver.events = self.events;
+        solver.search_algorithm = self.search_algorithm;
+        Ok(solver)
+    }
+}
+
+/// A solver of a level.
+///
+/// The solver is a thin wrapper of a [`Search`], to which it dereferences, and
+/// keeps the constructors of earlier versions. The search holds the open and
+/// visited states and the statistics of a run, and shares the analysis of the
+/// level with other searches.
 pub struct Solver {
+    search: Search,
+}
+
+/// A search of a level: its open and visited states, the solutions found so
+/// far and the statistics of the run.
+///
+/// A search is created from the analysis of the level by [`Search::new`], so
+/// that searches with other options, or on other threads, share it instead
+/// of analyzing the level again.
+pub struct Search {
     pub map: Map,
-    strategy: Strategy,
-    lower_bound_method: LowerBoundMethod,
-    lower_bounds: OnceCell<HashMap<Vector2<i32>, usize>>,
-    tunnels: OnceCell<HashSet<(Vector2<i32>, Direction)>>,
-    visited: HashSet<u64>,
-    heap: BinaryHeap<State>,
+    analysis: Arc<LevelAnalysis>,
+    /// The options, and the caches and counters filled while evaluating
+    /// states.
+    context: StateContext,
+    pattern_database_builder: Option<PatternDatabaseBuilder>,
+    visited: VisitedStates,
+    /// The visited states by their hashes, if hashes are verified.
+    verified_states: hasher::HashMap<u64, StateKey>,
+    hash_collisions: usize,
+    heap: OpenList,
+    /// Successors of the current wave of beam search.
+    next_wave: Vec<State>,
+    ida_star: Option<IdaStar>,
+    parallel: Option<ParallelSearch>,
+    bidirectional: Option<BidirectionalSearch>,
+    backward: Option<BackwardSearch>,
+    search_graph: Option<SearchGraph>,
+    /// The `Fast` search finding the first solution for branch and bound.
+    probe: Option<Box<Search>>,
+    /// The first solution found by anytime search or branch and bound.
+    first_solution: Option<Actions>,
+    /// The cheapest solution found by anytime search or branch and bound,
+    /// and its cost.
+    best_solution: Option<(Actions, (usize, usize))>,
+    /// The solutions found by anytime search or branch and bound, each
+    /// cheaper than the previous one.
+    incumbents: Vec<Actions>,
+    /// The lowest costs at which each state has been reached by anytime
+    /// search or the strategies which expand states in order of cost.
+    best_costs: hasher::HashMap<u64, (usize, usize)>,
+    optimality_proven: bool,
+    expanded_states: usize,
+    discarded_states: usize,
+    /// The number of generated states skipped because an equal state had
+    /// already been generated at no greater cost.
+    duplicate_states: usize,
+    /// The lowest lower bound and the most boxes on goals of the expanded
+    /// states.
+    progress: (usize, usize),
+    /// The number of expansions since the progress last improved.
+    stalled_expansions: usize,
+    /// The strategy replaced by the fallback of the stall policy, and the
+    /// number of expanded states at the switch.
+    fallback: Option<(Strategy, usize)>,
+    /// The number of times the pressure relief policy has been applied, and
+    /// the number of open states it has reordered, discarded or spilled.
+    pressure_reliefs: (usize, usize),
+    /// The open states spilled by the pressure relief policy.
+    spill: Option<SpillFile>,
+    /// The number of times the limits have been checked.
+    limit_checks: Cell<usize>,
+    progress_callback: RefCell<Option<ProgressCallback>>,
+    events: Option<EventSender>,
+    cancellation: Option<CancellationToken>,
+    search_algorithm: Option<Box<dyn SearchAlgorithm>>,
+    /// The number of expanded states and the search time when the progress
+    /// was last reported.
+    last_progress: Cell<(usize, Duration)>,
+    statistics: Cell<SolveStatistics>,
+    /// The time spent in the calls to [`Search::solve`] that have returned.
+    search_time: Duration,
+    no_solution_reason: Option<NoSolutionReason>,
+    /// The lower bound of the initial state, or `None` if it is dead.
+    initial_lower_bound: Option<usize>,
+}
+
+/// The analyses of a solver, from which solvers can be created on other
+/// threads without analyzing the level again.
+pub(crate) struct SolverSeed {
+    analysis: Arc<LevelAnalysis>,
+    pub(crate) options: SolverOptions,
+    pattern_database: OnceCell<PatternDatabase>,
+    cancellation: Option<CancellationToken>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SolveError {
-    Timeout,
-    NoSolution,
+    /// The time limit has been reached. The search is resumed by the next
+    /// call to [`Search::solve`].
+    Timeout {
+        /// The statistics of the search when it stopped.
+        statistics: Box<SolveStatistics>,
+    },
+    /// No solution has been found, and the search cannot be resumed.
+    NoSolution {
+        reason: NoSolutionReason,
+        /// The statistics of the search when it stopped.
+        statistics: Box<SolveStatistics>,
+    },
+    /// The maximum number of expanded states has been reached.
+    ExpansionLimit {
+        expanded: u64,
+        /// The statistics of the search when it stopped.
+        statistics: Box<SolveStatistics>,
+    },
+    /// The estimated memory used by the search exceeds the budget.
+    OutOfMemory {
+        estimated_bytes: usize,
+        visited: usize,
+        open: usize,
+        /// The statistics of the search when it stopped.
+        statistics: Box<SolveStatistics>,
+    },
+    /// The options cannot be used together.
+    InvalidOptions(String),
+    /// The actions given to the solver are not a solution of the level.
+    InvalidSolution(String),
+    /// The search has been cancelled through its cancellation token.
+    Cancelled {
+        /// The statistics of the search when it was cancelled.
+        statistics: Box<SolveStatistics>,
+    },
+    /// The open states could not be spilled to or read back from a file.
+    Spill(String),
+    /// The level cannot be searched.
+    InvalidLevel(LevelError),
 }
 
-type Result<T> = std::result::Result<T, SolveError>;
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Timeout { statistics } => write!(
+                f,
+                "timed out after {:.3} s and {} expanded states, best lower bound {}, \
+                 at most {} boxes on goals",
+                statistics.search_time.as_secs_f64(),
+                statistics.expanded_states,
+                statistics
+                    .best_lower_bound
+                    .map_or("unknown".to_string(), |lower_bound| lower_bound.to_string()),
+                statistics.most_boxes_on_goals
+            ),
+            Self::NoSolution { reason, statistics } => write!(
+                f,
+                "no solution after {} expanded states: {}",
+                statistics.expanded_states, reason
+            ),
+            Self::ExpansionLimit { expanded, .. } => {
+                write!(
+                    f,
+                    "expansion limit reached after {} expanded states",
+                    expanded
+                )
+            }
+            Self::OutOfMemory {
+                estimated_bytes,
+                visited,
+                open,
+                ..
+            } => write!(
+                f,
+                "out of memory: about {} bytes for {} visited and {} open states",
+                estimated_bytes, visited, open
+            ),
+            Self::InvalidOptions(error) => write!(f, "invalid options: {}", error),
+            Self::InvalidSolution(error) => write!(f, "invalid solution: {}", error),
+            Self::Cancelled { statistics } => write!(
+                f,
+                "cancelled after {} expanded states",
+                statistics.expanded_states
+            ),
+            Self::Spill(error) => write!(f, "failed to spill open states: {}", error),
+            Self::InvalidLevel(error) => write!(f, "invalid level: {}", error),
+        }
+    }
+}
 
-impl Solver {
-    /// Creates a new solver.
-    pub fn new(map: Map, strategy: Strategy, lower_bound_method: LowerBoundMethod) -> Self {
-        let mut instance = Self {
-            map,
-            strategy,
-            lower_bound_method,
-            lower_bounds: OnceCell::new(),
-            tunnels: OnceCell::new(),
-            visited: HashSet::new(),
-            heap: BinaryHeap::new(),
-        };
-        instance.heap.push(State::new(
-            instance.map.player_position(),
-            instance.map.box_positions().clone(),
-            Actions::new(),
-            &instance,
-        ));
-        instance
+impl std::error::Error for SolveError {}
+
+/// Statistics of the search, updated periodically while it runs.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SolveStatistics {
+    /// The number of open states, including spilled states.
+    pub open_states: usize,
+    /// The number of visited states.
+    pub visited_states: usize,
+    /// The bytes of a state of the level, including the bytes it allocates,
+    /// estimated from the best open state.
+    pub state_bytes: usize,
+    /// The estimated memory used by the search, in bytes.
+    pub approx_memory_bytes: usize,
+    /// The highest estimate of the memory used by the search, in bytes.
+    pub peak_memory_bytes: usize,
+    /// The number of expanded states.
+    pub expanded_states: usize,
+    /// The time spent in calls to [`Search::solve`].
+    pub search_time: Duration,
+    /// The pressure relief policy, if it has been applied.
+    pub pressure_relief: Option<ReliefPolicy>,
+    /// The number of times the pressure relief policy has been applied.
+    pub pressure_reliefs: usize,
+    /// The number of open states reordered, discarded or spilled by the
+    /// pressure relief policy.
+    pub relieved_states: usize,
+    /// The numbers of pushes and states pruned by each rule.
+    pub pruning: PruningStatistics,
+    /// The number of successors generated, before duplicates are skipped.
+    pub generated_states: usize,
+    /// The highest number of open states, as of the updates.
+    pub peak_open_states: usize,
+    /// The highest number of visited states, as of the updates.
+    pub peak_visited_states: usize,
+    /// The lower bound of the initial state, or `None` if it is dead.
+    pub initial_lower_bound: Option<usize>,
+    /// The moves and pushes of the solution found by the last call to
+    /// [`Search::solve`], if any.
+    pub solution: Option<(usize, usize)>,
+    /// Whether the solutions found so far are optimal in pushes or in
+    /// moves, as returned by [`Search::optimality`].
+    pub optimality_guaranteed: bool,
+    /// The lowest lower bound of the expanded states and the best open
+    /// state, or `None` before the search starts.
+    pub best_lower_bound: Option<usize>,
+    /// The most boxes on goals of the expanded states.
+    pub most_boxes_on_goals: usize,
+}
+
+/// The numbers of pushes and states pruned by each rule since the search
+/// started.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PruningStatistics {
+    /// Pushes of a box onto a wall, another box or a dead square.
+    pub blocked_pushes: usize,
+    /// Pushes from a square the player cannot reach.
+    pub unreachable_pushes: usize,
+    /// Pushes through a tunnel merged into the push entering it.
+    pub tunnel_pushes: usize,
+    /// Pushes of a box into a closet.
+    pub closet_pushes: usize,
+    /// Pushes of a box into a dead-end corridor it cannot leave.
+    pub corridor_deadlocks: usize,
+    /// Pushes freezing a box off a goal.
+    pub freeze_deadlocks: usize,
+    /// Pushes matching a deadlock pattern.
+    pub pattern_deadlocks: usize,
+    /// States pruned by corral deadlock detection.
+    pub corral_deadlocks: usize,
+    /// States whose successors were restricted to the pushes of a PI-corral.
+    pub pi_corrals: usize,
+    /// States whose boxes cannot be pushed onto distinct goals.
+    pub bipartite_deadlocks: usize,
+    /// Pushes skipped by relevance cuts.
+    pub cut_pushes: usize,
+    /// Generated states skipped because an equal state had already been
+    /// generated at no greater cost.
+    pub duplicate_states: usize,
+}
+
+impl SolveStatistics {
+    /// Returns the average time of an expansion in microseconds, or `None`
+    /// if no state has been expanded.
+    pub fn micros_per_expansion(&self) -> Option<f64> {
+        (self.expanded_states > 0)
+            .then(|| self.search_time.as_secs_f64() * 1e6 / self.expanded_states as f64)
     }
+}
 
-    /// Searches for solution using the A* algorithm.
-    pub fn search(&mut self, timeout: Duration) -> Result<Actions> {
-        let timer = Instant::now();
-        self.visited
-            .insert(self.heap.peek().unwrap().normalized_hash(self));
-        while let Some(state) = self.heap.pop() {
-            if timer.elapsed() >= timeout {
-                return Err(SolveError::Timeout);
+/// The cost by which solutions are compared.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Metric {
+    Pushes,
+    Moves,
+}
+
+/// The outcome of checking whether a solution is optimal.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Verdict {
+    /// No solution is cheaper than the given one.
+    Optimal,
+    /// A cheaper solution, which is optimal.
+    Improved(Actions),
+    /// The search was stopped by the error before it could decide.
+    Unknown(SolveError),
+}
+
+/// The metric in which a solution is provably optimal.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Optimality {
+    /// No solution has fewer pushes.
+    PushOptimal,
+    /// No solution has fewer moves.
+    MoveOptimal,
+    /// The solution may not be optimal in either metric.
+    None,
+}
+
+/// A solution, with the guarantees of the search which found it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Solution {
+    pub actions: Actions,
+    pub moves: usize,
+    pub pushes: usize,
+    /// The strategy of the search when it found the solution, which is the
+    /// fallback strategy once the stall policy has switched to it.
+    pub strategy: Strategy,
+    pub optimality: Optimality,
+}
+
+/// The outcome of a single step of the search.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SearchStep {
+    /// A state has been expanded.
+    Expanded(ExpandedState),
+    /// A solved state has been reached.
+    Solved(Actions),
+    /// Every reachable state has been searched without finding a solution.
+    Exhausted,
+}
+
+/// The progress of a search running in time slices.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SolveProgress {
+    /// The budget has run out before the search finished.
+    Running {
+        /// The number of visited states.
+        visited: usize,
+        /// The number of open states.
+        open: usize,
+        /// The lowest lower bound of the expanded states and the best open
+        /// state.
+        best_lower_bound: usize,
+    },
+    Solved(Actions),
+    Failed(SolveError),
+}
+
+/// The progress of a search, passed to the callback set by
+/// [`Search::on_progress`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ProgressInfo {
+    pub visited_states: usize,
+    pub open_states: usize,
+    pub expanded_states: usize,
+    /// The lowest lower bound of the expanded states and the best open state.
+    pub best_lower_bound: usize,
+    /// The pushes of the best open state, or of the deepest state on the
+    /// current path of IDA*.
+    pub depth: usize,
+    /// The time spent in calls to [`Search::solve`].
+    pub elapsed: Duration,
+}
+
+/// A callback called with the progress of a search.
+pub type ProgressCallback = Box<dyn FnMut(&ProgressInfo) + Send>;
+
+/// A state expanded by a step of the search.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExpandedState {
+    pub player_position: Vector2<i32>,
+    pub box_positions: BoxPositions,
+    pub lower_bound: usize,
+    /// The moves made to reach the state, which do not include the moves
+    /// between pushes for `OptimalPushes`.
+    pub moves: usize,
+    pub pushes: usize,
+    pub priority: Priority,
+    /// The number of successors added to the open states.
+    pub successors: usize,
+}
+
+/// The reason why a level has no solution.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NoSolutionReason {
+    /// The number of boxes differs from the number of goals.
+    BoxGoalCountMismatch { boxes: usize, goals: usize },
+    /// The player cannot reach any of the boxes off goals.
+    PlayerSealedOff,
+    /// The box can never be pushed onto a goal.
+    BoxOnDeadSquare(Vector2<i32>),
+    /// Every reachable state has been searched.
+    SearchExhausted { visited_states: usize },
+    /// Every state kept by beam search has been searched. The level may still
+    /// be solvable from the discarded states.
+    BeamExhausted { discarded_states: usize },
+    /// Every state not pruned by the pressure relief policy has been
+    /// searched. The level may still be solvable from the pruned states.
+    PrunedExhausted { discarded_states: usize },
+    /// Every state not claimed to have been visited by the approximate
+    /// visited filter has been searched. The level may still be solvable
+    /// from the states it pruned.
+    FilterExhausted { visited_states: usize },
+}
+
+impl NoSolutionReason {
+    /// Checks if the reason proves that the level has no solution, either
+    /// statically or by searching every reachable state. Incomplete searches
+    /// do not prove it.
+    pub fn proves_unsolvable(&self) -> bool {
+        matches!(
+            self,
+            Self::BoxGoalCountMismatch { .. }
+                | Self::PlayerSealedOff
+                | Self::BoxOnDeadSquare(_)
+                | Self::SearchExhausted { .. }
+        )
+    }
+}
+
+impl fmt::Display for NoSolutionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BoxGoalCountMismatch { boxes, goals } => {
+                write!(f, "{} boxes but {} goals", boxes, goals)
             }
-            if state.is_solved(self) {
-                return Ok(state.actions);
+            Self::PlayerSealedOff => write!(f, "the player cannot reach any box off goals"),
+            Self::BoxOnDeadSquare(position) => write!(
+                f,
+                "the box at ({}, {}) can never reach a goal",
+                position.x, position.y
+            ),
+            Self::SearchExhausted { visited_states } => {
+                write!(f, "all {} reachable states searched", visited_states)
             }
-
-            for successor in state.successors(self) {
-                if !self.visited.insert(successor.normalized_hash(self)) {
-                    continue;
-                }
-                self.heap.push(successor);
+            Self::BeamExhausted { discarded_states } => {
+                write!(f, "beam exhausted, {} states discarded", discarded_states)
             }
-
-            // Solver::shrink_heap(&mut self.heap);
+            Self::PrunedExhausted { discarded_states } => write!(
+                f,
+                "open states exhausted, {} states pruned",
+                discarded_states
+            ),
+            Self::FilterExhausted { visited_states } => write!(
+                f,
+                "states exhausted, {} states in the approximate visited filter",
+                visited_states
+            ),
         }
+    }
+}
+
+type Result<T> = std::result::Result<T, SolveError>;
+
+/// The number of limit checks between readings of the time.
+const TIME_CHECK_INTERVAL: usize = 16;
 
-        Err(SolveError::NoSolution)
+/// Creates a solver with the default options, validating the level as
+/// [`Solver::try_new`].
+impl TryFrom<Level> for Solver {
+    type Error = LevelError;
+
+    fn try_from(level: Level) -> std::result::Result<Self, LevelError> {
+        Self::try_new(level.map().clone(), SolverOptions::default())
     }
+}
 
-    pub fn strategy(&self) -> Strategy {
-        self.strategy
+impl Solver {
+    /// Returns a builder of a solver of the level with the default options.
+    pub fn builder(map: Map) -> SolverBuilder {
+        SolverBuilder {
+            map,
+            options: SolverOptions::default(),
+            progress_callback: None,
+            events: None,
+            cancellation: None,
+            search_algorithm: None,
+        }
     }
 
-    /// Returns the best state in the binary heap, or `None` if it is empty.
-    pub fn best_state(&self) -> Option<&State> {
-        self.heap.peek()
+    /// Searches for a solution of the level on a worker thread, until the
+    /// configured timeout or another limit is reached, or the search is
+    /// cancelled through the returned handle.
+    pub fn spawn(map: Map, options: SolverOptions) -> SolveHandle {
+        Solver::builder(map).options(options).spawn()
     }
 
-    /// Returns a reference to the set of tunnels.
-    pub fn tunnels(&self) -> &HashSet<(Vector2<i32>, Direction)> {
-        self.tunnels.get_or_init(|| self.calculate_tunnels())
+    /// Creates a new solver with the strategy and lower bound method, and the
+    /// default value of every other option.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is invalid, see [`LevelAnalysis::validate`].
+    #[deprecated(note = "use `Solver::builder` or `Solver::with_options`")]
+    pub fn new(map: Map, strategy: Strategy, lower_bound_method: LowerBoundMethod) -> Self {
+        Solver::builder(map)
+            .options(SolverOptions {
+                strategy,
+                lower_bound_method,
+                ..SolverOptions::default()
+            })
+            .build_unlimited()
+            .unwrap_or_else(|error| panic!("{error}"))
     }
 
-    /// Calculates and returns the set of tunnels in the level.
-    fn calculate_tunnels(&self) -> HashSet<(Vector2<i32>, Direction)> {
-        let mut tunnels = HashSet::new();
-        for x in 1..self.map.dimensions().x - 1 {
-            for y in 1..self.map.dimensions().y - 1 {
-                let box_position = Vector2::new(x, y);
-                if !self.map[box_position].intersects(Tiles::Floor) {
-                    continue;
-                }
+    /// Creates a new solver with the options.
+    ///
+    /// The level is expected to be valid, the analysis of an invalid level
+    /// may panic. Use [`Solver::try_new`] for levels which have not been
+    /// validated.
+    pub fn with_options(map: Map, options: SolverOptions) -> Self {
+        let analysis = LevelAnalysis::new(map, &options);
+        Self::with_analysis(Arc::new(analysis), options)
+    }
 
-                for (up, right, down, left) in [
-                    Direction::Up,
-                    Direction::Right,
-                    Direction::Down,
-                    Direction::Left,
-                    Direction::Up,
-                    Direction::Right,
-                    Direction::Down,
-                ]
-                .into_iter()
-                .tuple_windows()
-                {
-                    let player_position = box_position + &down.into();
-
-                    //  .      .      .
-                    // #$# or #$_ or _$#
-                    // #@#    #@#    #@#
-                    if self.map[player_position + &left.into()].intersects(Tiles::Wall)
-                        && self.map[player_position + &right.into()].intersects(Tiles::Wall)
-                        && (self.map[box_position + &left.into()].intersects(Tiles::Wall)
-                            && self.map[box_position + &right.into()].intersects(Tiles::Wall)
-                            || self.map[box_position + &right.into()].intersects(Tiles::Wall)
-                                && self.map[box_position + &left.into()].intersects(Tiles::Floor)
-                            || self.map[box_position + &right.into()].intersects(Tiles::Floor)
-                                && self.map[box_position + &left.into()].intersects(Tiles::Wall))
-                        && self.map[box_position].intersects(Tiles::Floor)
-                        && self
-                            .lower_bounds()
-                            .contains_key(&(box_position + &up.into()))
-                        && !self.map[box_position].intersects(Tiles::Goal)
-                    {
-                        tunnels.insert((player_position, up));
-                    }
-                }
-            }
+    /// Creates a new solver, or returns the first problem found by
+    /// [`LevelAnalysis::validate`] if the level cannot be searched.
+    pub fn try_new(map: Map, options: SolverOptions) -> std::result::Result<Self, LevelError> {
+        LevelAnalysis::validate(&map)?;
+        Ok(Self::with_options(map, options))
+    }
+
+    /// Creates a new solver sharing the analysis of the level with other
+    /// solvers, which may run on other threads.
+    ///
+    /// The analysis must have been created with the same lower bound method
+    /// and symmetry reduction as `options`, or [`Search::solve`] fails with
+    /// [`SolveError::InvalidOptions`].
+    pub fn with_analysis(analysis: Arc<LevelAnalysis>, options: SolverOptions) -> Self {
+        Self {
+            search: Search::new(&analysis, options),
         }
-        tunnels
     }
 
-    /// Returns a reference to the set of lower bounds.
-    pub fn lower_bounds(&self) -> &HashMap<Vector2<i32>, usize> {
-        self.lower_bounds
-            .get_or_init(|| self.calculate_lower_bounds())
-    }
-
-    /// Calculates and returns the set of lower bounds.
-    fn calculate_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
-        match self.lower_bound_method {
-            LowerBoundMethod::MinimumPush => self.minimum_push_lower_bounds(),
-            LowerBoundMethod::MinimumMove => self.minimum_move_lower_bounds(),
-            LowerBoundMethod::ManhattanDistance => self.manhattan_distance_lower_bounds(),
-        }
-    }
-
-    /// Calculates and returns the lower bounds using the minimum push method.
-    fn minimum_push_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
-        let mut lower_bounds = HashMap::new();
-        for goal_position in self.map.goal_positions() {
-            lower_bounds.insert(*goal_position, 0);
-            let mut player_position = None;
-            for pull_direction in [
-                Direction::Up,
-                Direction::Right,
-                Direction::Down,
-                Direction::Left,
-            ] {
-                let next_box_position = goal_position + &pull_direction.into();
-                let next_player_position = next_box_position + &pull_direction.into();
-                if self.map.in_bounds(next_player_position)
-                    && !self.map[next_player_position].intersects(Tiles::Wall)
-                    && !self.map[next_box_position].intersects(Tiles::Wall)
-                {
-                    player_position = Some(next_player_position);
-                    break;
-                }
-            }
-            if let Some(player_position) = player_position {
-                self.minimum_push_to(
-                    *goal_position,
-                    player_position,
-                    &mut lower_bounds,
-                    &mut HashSet::new(),
+    /// Creates a new solver, reusing the analysis exported by
+    /// [`Search::export_analysis`] instead of analyzing the level again.
+    #[cfg(feature = "serde")]
+    pub fn import_analysis(
+        map: Map,
+        options: SolverOptions,
+        mut reader: impl Read,
+    ) -> std::result::Result<Self, AnalysisError> {
+        let mut analysis = String::new();
+        reader.read_to_string(&mut analysis)?;
+        let analysis: Analysis =
+            toml::from_str(&analysis).map_err(|error| AnalysisError::Format(error.to_string()))?;
+
+        let fingerprint = Self::analysis_fingerprint(&map, &options);
+        if analysis.fingerprint != fingerprint {
+            return Err(AnalysisError::FingerprintMismatch {
+                expected: fingerprint,
+                found: analysis.fingerprint,
+            });
+        }
+
+        let mut level_analysis = LevelAnalysis::new(map, &options);
+        let to_vector = |[x, y]: [i32; 2]| Vector2::new(x, y);
+        let dead_squares = analysis.dead_squares.into_iter().map(to_vector).collect();
+        let tunnels = analysis
+            .tunnels
+            .into_iter()
+            .map(|(player_position, direction)| {
+                Direction::try_from(to_vector(direction))
+                    .map(|direction| (to_vector(player_position), direction))
+                    .map_err(|_| AnalysisError::Format("invalid tunnel direction".to_string()))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        let goal_distances = analysis
+            .goal_distances
+            .into_iter()
+            .map(|goal_distances| {
+                let mut grid = GoalDistanceGrid::new(
+                    to_vector(goal_distances.goal_position),
+                    level_analysis.map().dimensions(),
                 );
-            } else {
-                continue;
-            }
+                for (position, distance) in goal_distances.distances {
+                    grid.set(to_vector(position), distance);
+                }
+                grid
+            })
+            .collect();
+        level_analysis.import(dead_squares, tunnels, goal_distances);
+        let mut instance = Search::uninitialized(Arc::new(level_analysis), options);
+        for deadlock in analysis.learned_deadlocks {
+            let box_positions = BoxPositions::new(
+                instance.map.dimensions(),
+                deadlock.box_positions.into_iter().map(to_vector),
+            );
+            instance.context.learned_deadlocks_mut().insert(
+                &instance.map,
+                &box_positions,
+                to_vector(deadlock.player_position),
+            );
         }
-        lower_bounds
+
+        instance.initialize();
+        Ok(Self { search: instance })
+    }
+
+    /// Returns a fingerprint of the level and the options the analysis
+    /// depends on.
+    pub fn analysis_fingerprint(map: &Map, options: &SolverOptions) -> String {
+        let mut hasher = DefaultHasher::new();
+        map.to_string().hash(&mut hasher);
+        format!("{:?}", options.lower_bound_method).hash(&mut hasher);
+        hasher.finish().to_string()
+    }
+
+    /// Creates a solver of the level with the default options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is invalid, see [`LevelAnalysis::validate`].
+    #[deprecated(note = "use `Solver::try_from`")]
+    pub fn from(level: Level) -> Self {
+        Self::try_from(level).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Searches from the initial state with the strategy from the next call
+    /// to [`Search::solve`], as [`Search::reset`].
+    #[deprecated(note = "use `Solver::reset`")]
+    pub fn initial(&mut self, strategy: Strategy) {
+        self.search.reset(strategy);
+    }
+}
+
+impl Deref for Solver {
+    type Target = Search;
+
+    fn deref(&self) -> &Search {
+        &self.search
+    }
+}
+
+impl DerefMut for Solver {
+    fn deref_mut(&mut self) -> &mut Search {
+        &mut self.search
+    }
+}
+
+impl Search {
+    /// Creates a search of the level sharing its analysis with other
+    /// searches, which may run on other threads.
+    ///
+    /// The analysis must have been created with the same lower bound method
+    /// and symmetry reduction as `options`, or [`Search::solve`] fails with
+    /// [`SolveError::InvalidOptions`].
+    pub fn new(analysis: &Arc<LevelAnalysis>, options: SolverOptions) -> Self {
+        let mut instance = Self::uninitialized(analysis.clone(), options);
+        instance.initialize();
+        #[cfg(feature = "log")]
+        log::debug!(
+            fingerprint:% = Solver::analysis_fingerprint(&instance.map, &instance.context.options),
+            boxes = instance.map.box_positions().len(),
+            initial_lower_bound:? = instance.initial_lower_bound;
+            "solver created"
+        );
+        instance
     }
 
-    fn minimum_push_to(
+    /// Writes the analysis of the level, including the deadlocks learned so
+    /// far, so that it can be reused by [`Solver::import_analysis`].
+    #[cfg(feature = "serde")]
+    pub fn export_analysis(
         &self,
-        box_position: Vector2<i32>,
-        player_position: Vector2<i32>,
-        lower_bounds: &mut HashMap<Vector2<i32>, usize>,
-        visited: &mut HashSet<(Vector2<i32>, Direction)>,
-    ) {
-        let player_reachable_area = reachable_area(player_position, |position| {
-            !self.map[position].intersects(Tiles::Wall) && position != box_position
-        });
-        for pull_direction in [
-            Direction::Up,
-            Direction::Right,
-            Direction::Down,
-            Direction::Left,
-        ] {
-            let next_box_position = box_position + &pull_direction.into();
-            if self.map[next_box_position].intersects(Tiles::Wall) {
-                continue;
-            }
+        mut writer: impl Write,
+    ) -> std::result::Result<(), AnalysisError> {
+        let from_vector = |position: Vector2<i32>| [position.x, position.y];
+        let analysis = Analysis {
+            fingerprint: Solver::analysis_fingerprint(&self.map, &self.context.options),
+            dead_squares: self
+                .dead_squares()
+                .iter()
+                .sorted_by_key(|position| (position.y, position.x))
+                .map(|position| from_vector(*position))
+                .collect(),
+            tunnels: self
+                .tunnels()
+                .iter()
+                .map(|(player_position, direction)| {
+                    (
+                        from_vector(*player_position),
+                        from_vector((*direction).into()),
+                    )
+                })
+                .sorted()
+                .collect(),
+            goal_distances: self
+                .goal_distances()
+                .iter()
+                .map(|grid| GoalDistances {
+                    goal_position: from_vector(grid.goal_position()),
+                    distances: grid
+                        .iter()
+                        .map(|(position, distance)| (from_vector(position), distance))
+                        .collect(),
+                })
+                .collect(),
+            learned_deadlocks: self
+                .learned_deadlocks()
+                .iter()
+                .map(|(box_positions, player_position)| LearnedDeadlock {
+                    box_positions: box_positions.iter().copied().map(from_vector).collect(),
+                    player_position: from_vector(player_position),
+                })
+                .collect(),
+        };
+        let analysis =
+            toml::to_string(&analysis).map_err(|error| AnalysisError::Format(error.to_string()))?;
+        writer.write_all(analysis.as_bytes())?;
+        Ok(())
+    }
 
-            let next_player_position = next_box_position + &pull_direction.into();
-            if !self.map.in_bounds(next_player_position)
-                || self.map[next_player_position].intersects(Tiles::Wall)
-            {
-                continue;
-            }
-            if !player_reachable_area.contains(&next_player_position) {
-                continue;
-            }
+    /// Returns the analyses of the level done so far, to create solvers on
+    /// other threads.
+    pub(crate) fn seed(&self) -> SolverSeed {
+        SolverSeed {
+            analysis: self.analysis.clone(),
+            options: self.context.options.clone(),
+            pattern_database: self.context.pattern_database.clone(),
+            cancellation: self.cancellation.clone(),
+        }
+    }
 
-            let lower_bound = *lower_bounds.get(&next_box_position).unwrap_or(&usize::MAX);
-            let new_lower_bound = lower_bounds[&box_position] + 1;
-            if !visited.insert((next_box_position, pull_direction)) {
-                continue;
-            }
-            if new_lower_bound < lower_bound {
-                lower_bounds.insert(next_box_position, new_lower_bound);
-            }
-            self.minimum_push_to(
-                next_box_position,
-                next_player_position,
-                lower_bounds,
-                visited,
-            );
+    /// Creates a solver without an initial state from the analyses of another
+    /// solver.
+    pub(crate) fn from_seed(seed: SolverSeed) -> Self {
+        let mut instance = Self::uninitialized(seed.analysis, seed.options);
+        instance.context.pattern_database = seed.pattern_database;
+        instance.cancellation = seed.cancellation;
+        instance
+    }
+
+    /// Creates a new solver without an initial state.
+    fn uninitialized(analysis: Arc<LevelAnalysis>, mut options: SolverOptions) -> Self {
+        if options.heuristics.is_empty() {
+            options.heuristics.push(HeuristicKind::default());
+        }
+        let map = analysis.map().clone();
+        let visited = VisitedStates::new(options.visited_filter);
+        let heap = OpenList::new(QueueKind::for_strategy(options.strategy));
+        Self {
+            map,
+            context: StateContext::new(options, &analysis),
+            analysis,
+            pattern_database_builder: None,
+            visited,
+            verified_states: hasher::HashMap::default(),
+            hash_collisions: 0,
+            heap,
+            next_wave: Vec::new(),
+            ida_star: None,
+            parallel: None,
+            bidirectional: None,
+            backward: None,
+            search_graph: None,
+            probe: None,
+            first_solution: None,
+            best_solution: None,
+            incumbents: Vec::new(),
+            best_costs: hasher::HashMap::default(),
+            optimality_proven: false,
+            expanded_states: 0,
+            discarded_states: 0,
+            duplicate_states: 0,
+            progress: (usize::MAX, 0),
+            stalled_expansions: 0,
+            fallback: None,
+            pressure_reliefs: (0, 0),
+            spill: None,
+            limit_checks: Cell::new(0),
+            progress_callback: RefCell::new(None),
+            events: None,
+            cancellation: None,
+            search_algorithm: None,
+            last_progress: Cell::new((0, Duration::ZERO)),
+            statistics: Cell::new(SolveStatistics::default()),
+            search_time: Duration::ZERO,
+            no_solution_reason: None,
+            initial_lower_bound: None,
         }
     }
 
-    /// Calculates and returns the lower bounds using the minimum move method.
-    fn minimum_move_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
-        let mut lower_bounds = HashMap::new();
-        for x in 1..self.map.dimensions().x - 1 {
-            for y in 1..self.map.dimensions().y - 1 {
-                let position = Vector2::new(x, y);
-                // There may be situations in the level where the box is
-                // already on the goal and cannot be reached by the player.
-                if self.map[position].intersects(Tiles::Goal) {
-                    lower_bounds.insert(position, 0);
-                    continue;
-                }
-                if !self.map[position].intersects(Tiles::Floor)
-                // || self.map[position].intersects(Tiles::Deadlock)
-                {
-                    continue;
-                }
+    /// Diagnoses the level and creates the initial state.
+    pub(crate) fn initialize(&mut self) {
+        if self.context.pattern_database.get().is_some() || self.pattern_database_builder.is_some()
+        {
+            // The pattern database has been copied from another solver, or is
+            // still being built by a previous search.
+        } else if let Some(group_size) =
+            self.context
+                .options
+                .heuristics
+                .iter()
+                .find_map(|heuristic| match heuristic {
+                    HeuristicKind::PatternDatabase { group_size } => Some(*group_size),
+                    _ => None,
+                })
+        {
+            self.pattern_database_builder =
+                Some(PatternDatabaseBuilder::new(&self.map, group_size));
+        }
+        self.search_graph = self.context.options.record_graph.map(SearchGraph::new);
+        self.no_solution_reason = self.diagnose();
+        if self.no_solution_reason.is_some() {
+            return;
+        }
+        self.push_initial_state();
+    }
 
-                let paths =
-                    box_pushable_paths_with_positions(&self.map, &position, &HashSet::new());
-                if let Some(lower_bound) = paths
-                    .iter()
-                    .filter(|path| self.map[path.0.box_position].intersects(Tiles::Goal))
-                    .map(|path| path.1.len() - 1)
-                    .min()
-                {
-                    lower_bounds.insert(position, lower_bound);
-                }
-            }
+    /// Pushes the initial state onto the open states, after the forced
+    /// pushes, unless it is dead.
+    fn push_initial_state(&mut self) {
+        let initial_state = State::new(
+            self.map.player_position(),
+            BoxPositions::of(&self.map),
+            Path::default(),
+            &self.analysis,
+            &self.context,
+        );
+        self.initial_lower_bound = initial_state.lower_bound(&self.analysis, &self.context);
+        if !initial_state.is_dead(&self.analysis, &self.context) {
+            let initial_state = self.apply_forced_pushes(initial_state);
+            self.heap.push(initial_state);
         }
-        lower_bounds
     }
 
-    /// Calculates and returns the lower bounds using the Manhattan distance method.
-    fn manhattan_distance_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
-        let mut lower_bounds = HashMap::new();
-        for x in 1..self.map.dimensions().x - 1 {
-            for y in 1..self.map.dimensions().y - 1 {
-                let position = Vector2::new(x, y);
-                // There may be situations in the level where the box is
-                // already on the goal and cannot be reached by the player.
-                if self.map[position].intersects(Tiles::Goal) {
-                    lower_bounds.insert(position, 0);
-                    continue;
-                }
-                if !self.map[position].intersects(Tiles::Floor)
-                // || self.map.get(&position).intersects(Tiles::Deadlock)
-                {
-                    continue;
-                }
-                let lower_bound = self
-                    .map
-                    .goal_positions()
-                    .iter()
-                    .map(|box_pos| manhattan_distance(box_pos, &position))
-                    .min()
-                    .unwrap() as usize;
-                lower_bounds.insert(position, lower_bound);
+    /// Applies the pushes which are the only way to continue from the state,
+    /// until a solved state or a choice is reached. The pushes are kept in the
+    /// actions of the returned state, so they prefix every solution.
+    ///
+    /// Only deadlocks rule out pushes, and the pushes are not counted in the
+    /// statistics of the search.
+    fn apply_forced_pushes(&self, mut state: State) -> State {
+        let counts = self.context.counts();
+        let mut reached =
+            hasher::HashSet::from_iter([state.normalized_hash(&self.analysis, &self.context)]);
+        while !state.is_solved(&self.analysis, &self.context) {
+            let mut successors = state.unpruned_successors(&self.analysis, &self.context);
+            if successors.len() != 1 {
+                break;
+            }
+            let successor = successors.pop().unwrap();
+            // The forced pushes lead back to an earlier state.
+            if !reached.insert(successor.normalized_hash(&self.analysis, &self.context)) {
+                break;
             }
+            state = successor;
         }
-        lower_bounds
+        self.context.set_counts(counts);
+        state
+    }
+
+    /// Discards the search, keeping the analyses of the level, the learned
+    /// deadlocks and the cached lower bounds, so that the next call to
+    /// [`Search::solve`] starts a fresh run.
+    pub fn restart(&mut self) {
+        self.visited = VisitedStates::new(self.context.options.visited_filter);
+        self.verified_states = hasher::HashMap::default();
+        self.hash_collisions = 0;
+        self.next_wave = Vec::new();
+        self.ida_star = None;
+        self.parallel = None;
+        self.bidirectional = None;
+        self.backward = None;
+        self.probe = None;
+        self.first_solution = None;
+        self.best_solution = None;
+        self.incumbents = Vec::new();
+        self.best_costs = hasher::HashMap::default();
+        self.optimality_proven = false;
+        self.expanded_states = 0;
+        self.discarded_states = 0;
+        self.duplicate_states = 0;
+        self.context.take_counts();
+        self.context.relevance_cuts_active = self.context.options.relevance_cuts.is_some();
+        self.progress = (usize::MAX, 0);
+        self.stalled_expansions = 0;
+        if let Some((strategy, _)) = self.fallback.take() {
+            self.context.options.strategy = strategy;
+        }
+        self.pressure_reliefs = (0, 0);
+        self.spill = None;
+        self.heap = OpenList::new(QueueKind::for_strategy(self.context.options.strategy));
+        self.context.sequence.set(0);
+        self.last_progress.set((0, Duration::ZERO));
+        self.statistics.set(SolveStatistics::default());
+        self.search_time = Duration::ZERO;
+        self.initialize();
+    }
+
+    /// Discards the search like [`Search::restart`], including a search
+    /// stopped by the timeout, and searches with the given strategy from the
+    /// next call to [`Search::solve`]. The analyses of the level are kept,
+    /// since they do not depend on the strategy.
+    pub fn reset(&mut self, strategy: Strategy) {
+        self.fallback = None;
+        self.context.options.strategy = strategy;
+        self.restart();
+    }
+
+    /// Searches for a solution until the timeout, as by [`Search::solve_for`].
+    #[deprecated(note = "use `Solver::solve`, or `Solver::solve_for` to search a slice at a time")]
+    pub fn search(&mut self, timeout: Duration) -> Result<Actions> {
+        self.search_for(timeout)
     }
 
-    /// Shrinks the heap by retaining only a subset of states based on heuristics.
-    #[expect(dead_code)]
-    fn shrink_heap(heap: &mut BinaryHeap<State>) {
-        let max_pressure = 200_000;
-        if heap.len() > max_pressure {
-            let mut heuristics: Vec<_> = heap.iter().map(|state| state.heuristic()).collect();
-            heuristics.sort_unstable();
+    /// Searches for solution using the configured algorithm.
+    ///
+    /// When the timeout is reached, the search can be resumed by calling this
+    /// method again, which continues from the states left open and adds to
+    /// the statistics. The options are fixed when the solver is created, so
+    /// every call searches with the same strategy.
+    pub(crate) fn search_for(&mut self, timeout: Duration) -> Result<Actions> {
+        // Only the first call of a run has not spent any time searching.
+        if self.search_time.is_zero() {
+            #[cfg(feature = "log")]
+            log::debug!(
+                strategy:? = self.context.options.strategy,
+                algorithm:? = self.context.options.algorithm;
+                "search started"
+            );
+            self.send_event(|| SolveEvent::Started);
+        }
+        let timer = Instant::now();
+        let mut result = self.run_search(timer, timeout);
+        self.search_time += timer.elapsed();
+        self.update_statistics(self.expanded_states, self.search_time);
+        // Threads stop like on timeout when cancelled, and the statistics
+        // have been updated since the cancellation was noticed.
+        if matches!(
+            result,
+            Err(SolveError::Timeout { .. } | SolveError::Cancelled { .. })
+        ) && self.is_cancelled()
+        {
+            result = Err(SolveError::Cancelled {
+                statistics: Box::new(self.statistics()),
+            });
+        }
+        let mut statistics = self.statistics.get();
+        statistics.solution = result
+            .as_ref()
+            .ok()
+            .map(|solution| (solution.moves(), solution.pushes()));
+        self.statistics.set(statistics);
+        // The errors carry the statistics as of the end of the call.
+        if let Err(
+            SolveError::Timeout { statistics }
+            | SolveError::NoSolution { statistics, .. }
+            | SolveError::ExpansionLimit { statistics, .. }
+            | SolveError::OutOfMemory { statistics, .. }
+            | SolveError::Cancelled { statistics },
+        ) = &mut result
+        {
+            **statistics = self.statistics();
+        }
+        #[cfg(feature = "log")]
+        match &result {
+            Ok(solution) => log::debug!(
+                moves = solution.moves(),
+                pushes = solution.pushes(),
+                expanded_states = self.expanded_states,
+                elapsed:? = self.search_time;
+                "solution found"
+            ),
+            Err(error) => log::debug!(
+                error:% = error,
+                expanded_states = self.expanded_states,
+                elapsed:? = self.search_time;
+                "search stopped"
+            ),
+        }
+        self.send_result_events(&result);
+        result
+    }
 
-            let alpha = 0.8;
-            let heuristic_median = heuristics[(heuristics.len() as f32 * alpha) as usize];
-            heap.retain(|state| state.heuristic() <= heuristic_median);
+    /// Returns the error of a search stopped by the time limit, with the
+    /// statistics of the last update.
+    pub(crate) fn timeout_error(&self) -> SolveError {
+        SolveError::Timeout {
+            statistics: Box::new(self.statistics()),
         }
     }
 
-    /// Prints the lower bounds for each position in the level.
-    #[expect(dead_code)]
-    pub fn print_lower_bounds(&self) {
-        for y in 0..self.map.dimensions().y {
-            for x in 0..self.map.dimensions().x {
-                let position = Vector2::new(x, y);
-                if let Some(lower_bound) = self.lower_bounds().get(&position) {
-                    print!("{:3} ", lower_bound);
-                } else {
-                    print!("{:3} ", "###");
-                }
-            }
-            println!();
+    /// Returns the error of a search which cannot find a solution, with the
+    /// statistics of the last update.
+    pub(crate) fn no_solution(&self, reason: NoSolutionReason) -> SolveError {
+        SolveError::NoSolution {
+            reason,
+            statistics: Box::new(self.statistics()),
         }
     }
-}
 
-/// Calculates the Manhattan distance between two 2D vectors.
-fn manhattan_distance(a: &Vector2<i32>, b: &Vector2<i32>) -> i32 {
-    (a.x - b.x).abs() + (a.y - b.y).abs()
+    fn run_search(&mut self, timer: Instant, timeout: Duration) -> Result<Actions> {
+        self.prepare_search(timer.checked_add(timeout))?;
+        if self.is_cancelled() {
+            return Err(SolveError::Cancelled {
+                statistics: Box::new(self.statistics()),
+            });
+        }
+        if let Some(mut search_algorithm) = self.search_algorithm.take() {
+            let result = search_algorithm.search(&mut SearchContext::new(self, timer, timeout));
+            self.search_algorithm = Some(search_algorithm);
+            return result;
+        }
+        if self.context.options.algorithm == Algorithm::IdaStar {
+            return self.ida_star_search(timer, timeout);
+        }
+        if self.context.options.anytime {
+            return self.anytime_search(timer, timeout);
+        }
+        if self.context.options.threads > 1 {
+            return self.parallel_search(timer, timeout);
+        }
+        self.visit_initial_state();
+        if let Algorithm::Beam { width } = self.context.options.algorithm {
+            return self.beam_search(width, timer, timeout);
+        }
+        if self.context.options.branch_and_bound && self.best_solution.is_none() {
+            self.probe_search(timer, timeout)?;
+        }
+        if self.context.options.algorithm == Algorithm::Bidirectional {
+            let mut bidirectional = self
+                .bidirectional
+                .take()
+                .unwrap_or_else(|| BidirectionalSearch::new(&self.map));
+            let result = self.bidirectional_search(&mut bidirectional, timer, timeout);
+            self.bidirectional = Some(bidirectional);
+            return result;
+        }
+        AStarSearch.search(&mut SearchContext::new(self, timer, timeout))
+    }
+
+    /// Expands the best open state until a solution is found, the open
+    /// states are exhausted or a limit is reached.
+    pub(crate) fn a_star_search(&mut self, timer: Instant, timeout: Duration) -> Result<Actions> {
+        loop {
+            self.check_limits(timer, timeout, self.expanded_states)?;
+            self.relieve_pressure()?;
+            match self.expand_best_state() {
+                SearchStep::Expanded(_) => {}
+                SearchStep::Solved(solution) if self.context.options.branch_and_bound => {
+                    // States are expanded in order of cost, and the others
+                    // cannot lead to a solution as cheap as the incumbent.
+                    let cost = self.solution_cost(&solution);
+                    self.incumbents.push(solution.clone());
+                    self.best_solution = Some((solution.clone(), cost));
+                    self.optimality_proven = true;
+                    return Ok(solution);
+                }
+                SearchStep::Exhausted if self.context.options.branch_and_bound => {
+                    // No solution is cheaper than the incumbent.
+                    if let Some((solution, _)) = &self.best_solution {
+                        self.optimality_proven = true;
+                        return Ok(solution.clone());
+                    }
+                    return Err(self.no_solution(NoSolutionReason::SearchExhausted {
+                        visited_states: self.best_costs.len(),
+                    }));
+                }
+                SearchStep::Solved(solution) => return Ok(solution),
+                SearchStep::Exhausted
+                    if self.context.relevance_cuts_active && self.cut_pushes() > 0 =>
+                {
+                    // The cuts may have pruned every solution. The states
+                    // are searched again without them, keeping the search
+                    // graph and the statistics of the run.
+                    self.context.relevance_cuts_active = false;
+                    self.visited = VisitedStates::new(self.context.options.visited_filter);
+                    self.verified_states = hasher::HashMap::default();
+                    self.context.sequence.set(0);
+                    self.push_initial_state();
+                    self.visit_initial_state();
+                }
+                SearchStep::Exhausted if self.discarded_states > 0 => {
+                    return Err(self.no_solution(NoSolutionReason::PrunedExhausted {
+                        discarded_states: self.discarded_states,
+                    }));
+                }
+                SearchStep::Exhausted => {
+                    return Err(self.no_solution(self.search_exhausted()));
+                }
+            }
+        }
+    }
+
+    /// Reads the spilled states back once they are the best open states, and
+    /// applies the pressure relief policy if there are too many open states.
+    fn relieve_pressure(&mut self) -> Result<()> {
+        let Some(pressure_relief) = self.context.options.pressure_relief else {
+            return Ok(());
+        };
+        if let Some(spill) = &mut self.spill {
+            // The spilled states are read back before a worse state is
+            // expanded, so states are still expanded in order of priority.
+            let spilled_best = spill.best_priority().is_some_and(|priority| {
+                self.heap
+                    .peek()
+                    .is_none_or(|state| priority < state.priority())
+            });
+            if spilled_best {
+                let mut spill = self.spill.take().unwrap();
+                let states = spill
+                    .read(self)
+                    .map_err(|error| SolveError::Spill(error.to_string()))?;
+                self.spill = Some(spill);
+                for state in states {
+                    self.heap.push(state);
+                }
+            }
+        }
+        if self.heap.len() <= pressure_relief.max_open_states {
+            return Ok(());
+        }
+        let relieved_states = match pressure_relief.policy {
+            ReliefPolicy::PreferDeeper => {
+                if self.heap.prefers_deeper() {
+                    return Ok(());
+                }
+                self.heap.prefer_deeper();
+                self.heap.len()
+            }
+            ReliefPolicy::Prune { percent } => {
+                let count = self.heap.len() * percent as usize / 100;
+                self.discarded_states += self.heap.split_off_worst(count).len();
+                count
+            }
+            ReliefPolicy::Spill { percent } => {
+                let count = self.heap.len() * percent as usize / 100;
+                let states = self.heap.split_off_worst(count);
+                let spill = match &mut self.spill {
+                    Some(spill) => spill,
+                    None => self.spill.insert(
+                        SpillFile::new().map_err(|error| SolveError::Spill(error.to_string()))?,
+                    ),
+                };
+                spill
+                    .write(states)
+                    .map_err(|error| SolveError::Spill(error.to_string()))?;
+                count
+            }
+        };
+        self.pressure_reliefs.0 += 1;
+        self.pressure_reliefs.1 += relieved_states;
+        #[cfg(feature = "log")]
+        log::debug!(
+            policy:? = pressure_relief.policy,
+            relieved_states,
+            open_states = self.heap.len();
+            "pressure relieved"
+        );
+        Ok(())
+    }
+
+    /// Searches for a solution using the configured algorithm, until the
+    /// configured timeout or another limit is reached.
+    pub fn solve(&mut self) -> Result<Actions> {
+        let result = self.search_for(self.context.options.timeout.unwrap_or(Duration::MAX));
+        // The search is not resumed after the configured timeout.
+        if let Err(error @ SolveError::Timeout { .. }) = &result {
+            self.send_event(|| SolveEvent::Finished {
+                error: Some(error.clone()),
+            });
+        }
+        result
+    }
+
+    /// Searches for at most the budget of time, for running the solver a slice
+    /// at a time, e.g. once per frame. Each call resumes the search from the
+    /// states left open by the previous one, adding to the statistics.
+    pub fn solve_for(&mut self, budget: Duration) -> SolveProgress {
+        match self.search_for(budget) {
+            Ok(solution) => SolveProgress::Solved(solution),
+            Err(SolveError::Timeout { .. }) => SolveProgress::Running {
+                visited: self.visited.len() + self.best_costs.len(),
+                open: self.heap.len() + self.next_wave.len(),
+                best_lower_bound: self.best_lower_bound(),
+            },
+            Err(error) => SolveProgress::Failed(error),
+        }
+    }
+
+    /// Returns the lowest lower bound of the expanded states and the best open
+    /// state.
+    fn best_lower_bound(&self) -> usize {
+        let (best_lower_bound, _) = self.progress;
+        self.best_state()
+            .and_then(|state| state.lower_bound(&self.analysis, &self.context))
+            .map_or(best_lower_bound, |lower_bound| {
+                lower_bound.min(best_lower_bound)
+            })
+    }
+
+    /// Sets the callback called with the progress of the search, as often as
+    /// the progress interval of the options. No progress is reported
+    /// otherwise.
+    pub fn on_progress(&mut self, callback: impl FnMut(&ProgressInfo) + Send + 'static) {
+        *self.progress_callback.get_mut() = Some(Box::new(callback));
+    }
+
+    /// Calls the progress callback, if any, once the progress interval has
+    /// passed since the last call.
+    fn report_progress(&self, expanded_states: usize, elapsed: impl FnOnce() -> Duration) {
+        let mut progress_callback = self.progress_callback.borrow_mut();
+        if progress_callback.is_none() && self.events.is_none() {
+            return;
+        }
+        let (last_expanded_states, last_elapsed) = self.last_progress.get();
+        let elapsed = match self.context.options.progress_interval {
+            ProgressInterval::Expansions(interval) => {
+                if expanded_states < last_expanded_states + interval {
+                    return;
+                }
+                elapsed()
+            }
+            ProgressInterval::Time(interval) => {
+                let elapsed = elapsed();
+                if elapsed < last_elapsed + interval {
+                    return;
+                }
+                elapsed
+            }
+        };
+        self.last_progress.set((expanded_states, elapsed));
+        let progress = ProgressInfo {
+            visited_states: self.visited.len() + self.best_costs.len(),
+            open_states: self.heap.len() + self.next_wave.len(),
+            expanded_states,
+            best_lower_bound: self.best_lower_bound(),
+            depth: self.best_state().map_or(0, |state| state.path.pushes()),
+            elapsed,
+        };
+        if let Some(progress_callback) = progress_callback.as_mut() {
+            progress_callback(&progress);
+        }
+        self.send_event(|| SolveEvent::Progress {
+            visited: progress.visited_states,
+            open: progress.open_states,
+            best_lower_bound: progress.best_lower_bound,
+            boxes_on_goals: self.progress.1,
+        });
+    }
+
+    /// Sets the token with which the search is cancelled from another
+    /// thread. A cancelled search returns [`SolveError::Cancelled`] the next
+    /// time it reads the time, and so do later calls.
+    pub fn set_cancellation(&mut self, cancellation: CancellationToken) {
+        self.cancellation = Some(cancellation);
+    }
+
+    /// Runs the search in place of the one selected by the `algorithm`
+    /// option from the next call to [`Search::solve`]. The options which
+    /// configure the built-in algorithms, such as `threads` and `anytime`,
+    /// are ignored, and the solutions are not known to be optimal.
+    pub fn set_search_algorithm(&mut self, search_algorithm: impl SearchAlgorithm + 'static) {
+        self.search_algorithm = Some(Box::new(search_algorithm));
+    }
+
+    /// Checks if the search has been cancelled through its token.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Sends the events of the search to the channel, as often as the
+    /// progress interval of the options for the progress events.
+    pub fn send_events(&mut self, sender: EventSender) {
+        self.events = Some(sender);
+    }
+
+    /// Sends the event to the channel of the solver, if any.
+    fn send_event(&self, event: impl FnOnce() -> SolveEvent) {
+        if let Some(events) = &self.events {
+            events.send(event());
+        }
+    }
+
+    /// Sends the events of the result of a call to [`Search::solve`],
+    /// which finishes the search unless it can be resumed.
+    fn send_result_events(&self, result: &Result<Actions>) {
+        match result {
+            Ok(solution) => {
+                self.send_event(|| SolveEvent::SolutionFound {
+                    moves: solution.moves(),
+                    pushes: solution.pushes(),
+                });
+                self.send_event(|| SolveEvent::Finished { error: None });
+            }
+            Err(SolveError::Timeout { .. }) => {}
+            Err(error) => self.send_event(|| SolveEvent::Finished {
+                error: Some(error.clone()),
+            }),
+        }
+    }
+
+    /// Expands a single state of the A* search, for driving the search one
+    /// step at a time. A solution is returned once its state is popped, or
+    /// generated with the `Fast` strategy, as by [`Search::solve`].
+    pub fn step(&mut self) -> Result<SearchStep> {
+        self.prepare_search(None)?;
+        if self.context.options.algorithm != Algorithm::AStar
+            || self.context.options.anytime
+            || self.context.options.threads > 1
+        {
+            return Err(SolveError::InvalidOptions(
+                "stepping only supports A* without anytime mode or threads".to_string(),
+            ));
+        }
+        self.visit_initial_state();
+        Ok(self.expand_best_state())
+    }
+
+    /// Validates the options and finishes building the pattern database
+    /// before the deadline, if any.
+    fn prepare_search(&mut self, deadline: Option<Instant>) -> Result<()> {
+        self.validate_options()
+            .map_err(SolveError::InvalidOptions)?;
+        if let Some(reason) = &self.no_solution_reason {
+            return Err(self.no_solution(reason.clone()));
+        }
+        if let Some(builder) = &mut self.pattern_database_builder {
+            if !builder.build(&self.map, deadline) {
+                return Err(self.timeout_error());
+            }
+            let pattern_database = self.pattern_database_builder.take().unwrap().finish();
+            let _ = self.context.pattern_database.set(pattern_database);
+            self.context.lower_bound_cache_mut().clear();
+            // Recalculate the lower bounds of the states created without the
+            // pattern database.
+            let states = self.heap.take();
+            for state in states {
+                let state = State::new(
+                    state.player_position,
+                    state.box_positions,
+                    state.path,
+                    &self.analysis,
+                    &self.context,
+                );
+                if !state.is_dead(&self.analysis, &self.context) {
+                    self.heap.push(state);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the best open state as visited, or records its cost if states
+    /// are expanded in order of cost. Only the initial state has not been
+    /// marked when generated.
+    fn visit_initial_state(&mut self) {
+        let Some(initial_state) = self.heap.peek().cloned() else {
+            return;
+        };
+        if self.reopens_states() {
+            let cost = self.state_cost(&initial_state);
+            self.best_costs
+                .entry(self.state_key(&initial_state))
+                .or_insert(cost);
+        } else {
+            self.mark_visited(&initial_state);
+        }
+    }
+
+    /// Pops open states until one is expanded or solved.
+    fn expand_best_state(&mut self) -> SearchStep {
+        // States of optimal and weighted strategies are reopened when reached
+        // again at a lower cost, which can happen even after they have been
+        // expanded, since the lower bound of the pattern database is not
+        // consistent and the weighted one is not either.
+        let closes_on_pop = self.reopens_states();
+        while let Some(state) = self.heap.pop() {
+            // The state has been reached again at a lower cost since it was
+            // pushed onto the heap.
+            if closes_on_pop && self.best_costs[&self.state_key(&state)] < self.state_cost(&state) {
+                continue;
+            }
+            if self.meets_incumbent(&state) {
+                continue;
+            }
+            if state.is_solved(&self.analysis, &self.context) {
+                self.record_expansion(&state, &[]);
+                return SearchStep::Solved(state.actions_with_moves(&self.analysis, &self.context));
+            }
+            self.expanded_states += 1;
+
+            let mut successors = 0;
+            let states = if self.strategy() == Strategy::Fast {
+                // The first solved successor is returned without generating
+                // the others, since solved states have the lowest priority.
+                let states = state
+                    .successors_iter(&self.analysis, &self.context)
+                    .take_while_inclusive(|successor| {
+                        !successor.is_solved(&self.analysis, &self.context)
+                    })
+                    .collect_vec();
+                if let Some(solved_state) = states
+                    .last()
+                    .filter(|state| state.is_solved(&self.analysis, &self.context))
+                {
+                    self.record_expansion(&state, &states);
+                    self.record_expansion(solved_state, &[]);
+                    return SearchStep::Solved(
+                        solved_state.actions_with_moves(&self.analysis, &self.context),
+                    );
+                }
+                states
+            } else {
+                state.successors(&self.analysis, &self.context)
+            };
+            self.record_expansion(&state, &states);
+            // Duplicates are skipped when generated, so that a state reached
+            // from several parents is only opened once, unless it is reached
+            // at a lower cost.
+            for successor in states {
+                if closes_on_pop {
+                    let cost = self.state_cost(&successor);
+                    let key = self.state_key(&successor);
+                    if self
+                        .best_costs
+                        .get(&key)
+                        .is_some_and(|best_cost| *best_cost <= cost)
+                    {
+                        self.duplicate_states += 1;
+                        continue;
+                    }
+                    if self.meets_incumbent(&successor) {
+                        continue;
+                    }
+                    self.best_costs.insert(key, cost);
+                } else if !self.mark_visited(&successor) {
+                    self.duplicate_states += 1;
+                    continue;
+                }
+                self.heap.push(successor);
+                successors += 1;
+            }
+            let lower_bound = state.lower_bound(&self.analysis, &self.context).unwrap();
+            self.record_progress(lower_bound, state.boxes_on_goals());
+            return SearchStep::Expanded(ExpandedState {
+                lower_bound,
+                moves: state.path.moves(),
+                pushes: state.path.pushes(),
+                priority: state.priority(),
+                successors,
+                player_position: state.player_position,
+                box_positions: state.box_positions,
+            });
+        }
+        SearchStep::Exhausted
+    }
+
+    /// Returns why the search found no solution after searching every open
+    /// state, which does not prove that there is none if the visited filter
+    /// is approximate.
+    fn search_exhausted(&self) -> NoSolutionReason {
+        let visited_states = self.visited.len() + self.best_costs.len();
+        if self.visited.false_positive_rate().is_some() {
+            NoSolutionReason::FilterExhausted { visited_states }
+        } else {
+            NoSolutionReason::SearchExhausted { visited_states }
+        }
+    }
+
+    /// Marks the state as visited. Returns whether it had not been visited.
+    ///
+    /// If hashes are verified, a state whose hash equals the hash of a
+    /// different visited state is counted as a collision and not pruned.
+    fn mark_visited(&mut self, state: &State) -> bool {
+        let hash = state.normalized_hash(&self.analysis, &self.context);
+        let is_new = self.visited.insert(hash);
+        if !self.context.options.verify_hashes {
+            return is_new;
+        }
+        let key = state.normalized_key(&self.analysis, &self.context);
+        match self.verified_states.entry(hash) {
+            Entry::Vacant(entry) => {
+                entry.insert(key);
+                true
+            }
+            Entry::Occupied(entry) if *entry.get() != key => {
+                self.hash_collisions += 1;
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+
+    /// Records the progress made by an expanded state, and switches to the
+    /// fallback strategy of the stall policy once neither the lowest lower
+    /// bound nor the most boxes on goals of the expanded states has improved
+    /// for the configured number of expansions.
+    fn record_progress(&mut self, lower_bound: usize, boxes_on_goals: usize) {
+        let (best_lower_bound, most_boxes_on_goals) = self.progress;
+        if lower_bound < best_lower_bound || boxes_on_goals > most_boxes_on_goals {
+            self.progress = (
+                lower_bound.min(best_lower_bound),
+                boxes_on_goals.max(most_boxes_on_goals),
+            );
+            self.stalled_expansions = 0;
+            return;
+        }
+        self.stalled_expansions += 1;
+        let Some(stall_policy) = self.context.options.stall_policy else {
+            return;
+        };
+        if self.fallback.is_some() || self.stalled_expansions < stall_policy.expansions {
+            return;
+        }
+        let reopened_states = self.reopens_states();
+        self.fallback = Some((self.context.options.strategy, self.expanded_states));
+        self.context.options.strategy = stall_policy.fallback;
+        #[cfg(feature = "log")]
+        log::debug!(
+            strategy:? = stall_policy.fallback,
+            expanded_states = self.expanded_states;
+            "stall fallback"
+        );
+
+        // The priorities of the open states depend on the strategy, so the
+        // heap is rebuilt, keeping the order of states of equal priority.
+        let states = std::mem::replace(
+            &mut self.heap,
+            OpenList::new(QueueKind::for_strategy(self.context.options.strategy)),
+        )
+        .into_sorted_vec();
+        self.best_costs = hasher::HashMap::default();
+        let reopens_states = self.reopens_states();
+        for state in states.into_iter().rev() {
+            let state = State::new(
+                state.player_position,
+                state.box_positions,
+                state.path,
+                &self.analysis,
+                &self.context,
+            );
+            if reopens_states {
+                let cost = self.state_cost(&state);
+                match self.best_costs.entry(self.state_key(&state)) {
+                    Entry::Occupied(entry) if *entry.get() <= cost => continue,
+                    Entry::Occupied(mut entry) => {
+                        entry.insert(cost);
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(cost);
+                    }
+                }
+            } else if !self.mark_visited(&state) && reopened_states {
+                // The other strategies mark states as visited when generated.
+                continue;
+            }
+            self.heap.push(state);
+        }
+    }
+
+    /// Records the state and the edges to its successors in the search
+    /// graph, if enabled.
+    fn record_expansion(&mut self, state: &State, successors: &[State]) {
+        if self.search_graph.is_none() {
+            return;
+        }
+        let node = SearchNode::new(state, self);
+        let successors: Vec<_> = successors
+            .iter()
+            .map(|successor| successor.normalized_hash(&self.analysis, &self.context))
+            .collect();
+        self.search_graph.as_mut().unwrap().insert(node, successors);
+    }
+
+    /// Checks if the level can be solved within the given number of pushes.
+    ///
+    /// States whose pushes plus lower bound exceed `max_pushes` are pruned,
+    /// and states are searched again when reached with fewer pushes. The goal
+    /// macros, relevance cuts and PI-corrals of the options are not applied,
+    /// so `Ok(false)` proves that no such solution exists. Each call starts a
+    /// new search, which returns `SolveError::Timeout` with the statistics of
+    /// this search, rather than those of the solver, if it cannot decide in
+    /// time.
+    pub fn solvable_within(&self, max_pushes: usize, timeout: Duration) -> Result<bool> {
+        let timer = Instant::now();
+        if self.no_solution_reason.is_some() {
+            return Ok(false);
+        }
+        let within_bound = |state: &State| {
+            state
+                .lower_bound(&self.analysis, &self.context)
+                .is_some_and(|lower_bound| state.path.pushes() + lower_bound <= max_pushes)
+        };
+        let initial_state = State::new(
+            self.map.player_position(),
+            BoxPositions::of(&self.map),
+            Path::default(),
+            &self.analysis,
+            &self.context,
+        );
+        if !within_bound(&initial_state) {
+            return Ok(false);
+        }
+        // The fewest pushes with which each state has been reached.
+        let mut best_pushes = hasher::HashMap::from_iter([(
+            initial_state.normalized_hash(&self.analysis, &self.context),
+            0,
+        )]);
+        let mut statistics = SolveStatistics {
+            initial_lower_bound: initial_state.lower_bound(&self.analysis, &self.context),
+            ..Default::default()
+        };
+        let mut heap = BinaryHeap::from([initial_state]);
+        while let Some(state) = heap.pop() {
+            if timer.elapsed() >= timeout {
+                statistics.open_states = heap.len() + 1;
+                statistics.visited_states = best_pushes.len();
+                statistics.search_time = timer.elapsed();
+                return Err(SolveError::Timeout {
+                    statistics: Box::new(statistics),
+                });
+            }
+            if state.is_solved(&self.analysis, &self.context) {
+                return Ok(true);
+            }
+            let pushes = state.path.pushes();
+            if best_pushes[&state.normalized_hash(&self.analysis, &self.context)] < pushes {
+                continue;
+            }
+            statistics.expanded_states += 1;
+            if let Some(lower_bound) = state.lower_bound(&self.analysis, &self.context) {
+                statistics.best_lower_bound = Some(
+                    statistics
+                        .best_lower_bound
+                        .map_or(lower_bound, |best| best.min(lower_bound)),
+                );
+            }
+            statistics.most_boxes_on_goals =
+                statistics.most_boxes_on_goals.max(state.boxes_on_goals());
+            for successor in state.unpruned_successors(&self.analysis, &self.context) {
+                statistics.generated_states += 1;
+                if !within_bound(&successor) {
+                    continue;
+                }
+                let pushes = successor.path.pushes();
+                match best_pushes.entry(successor.normalized_hash(&self.analysis, &self.context)) {
+                    Entry::Occupied(entry) if *entry.get() <= pushes => continue,
+                    Entry::Occupied(mut entry) => {
+                        entry.insert(pushes);
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(pushes);
+                    }
+                }
+                heap.push(successor);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Searches for up to `k` solutions with distinct push sequences, ordered
+    /// by the cost of the strategy.
+    ///
+    /// Solutions differing only in the moves between pushes are not told
+    /// apart, and neither are transpositions, which reach a goal state with
+    /// the same normalized hash by the same pushes in another order. Each
+    /// state is expanded at most `k` times, so that the solutions can reach
+    /// it along different paths. Each call starts a new A* search,
+    /// which returns the solutions found so far when the timeout is reached,
+    /// or `SolveError::Timeout` if none has been found.
+    pub fn solve_all(&self, k: usize, timeout: Duration) -> Result<Vec<Actions>> {
+        let timer = Instant::now();
+        if let Some(reason) = &self.no_solution_reason {
+            return Err(self.no_solution(reason.clone()));
+        }
+        let initial_state = State::new(
+            self.map.player_position(),
+            BoxPositions::of(&self.map),
+            Path::default(),
+            &self.analysis,
+            &self.context,
+        );
+        let mut solutions = Vec::new();
+        // The normalized hash of the goal state and the sorted pushes of each
+        // solution, which are equal for transpositions.
+        let mut goal_states = hasher::HashSet::default();
+        // The number of times each state has been expanded.
+        let mut expansions = hasher::HashMap::default();
+        let mut heap = BinaryHeap::new();
+        if !initial_state.is_dead(&self.analysis, &self.context) {
+            heap.push(initial_state);
+        }
+        while solutions.len() < k {
+            if timer.elapsed() >= timeout {
+                if solutions.is_empty() {
+                    return Err(self.timeout_error());
+                }
+                break;
+            }
+            let Some(state) = heap.pop() else {
+                if solutions.is_empty() {
+                    return Err(self.no_solution(NoSolutionReason::SearchExhausted {
+                        visited_states: expansions.len(),
+                    }));
+                }
+                break;
+            };
+            if state.is_solved(&self.analysis, &self.context) {
+                let solution = state.actions_with_moves(&self.analysis, &self.context);
+                let mut pushes = self.push_sequence(&solution);
+                pushes.sort_by_key(|&(position, direction)| {
+                    let direction: Vector2<i32> = direction.into();
+                    (position.y, position.x, direction.y, direction.x)
+                });
+                if goal_states
+                    .insert((state.normalized_hash(&self.analysis, &self.context), pushes))
+                {
+                    solutions.push(solution);
+                }
+                continue;
+            }
+            let count = expansions
+                .entry(state.normalized_hash(&self.analysis, &self.context))
+                .or_insert(0);
+            if *count >= k {
+                continue;
+            }
+            *count += 1;
+            heap.extend(state.successors(&self.analysis, &self.context));
+        }
+        // Strategies which do not expand states in order of cost may find
+        // cheaper solutions later.
+        solutions.sort_by_key(|solution| self.solution_cost(solution));
+        Ok(solutions)
+    }
+
+    /// Checks if the solution is optimal in the metric, or finds a cheaper
+    /// one.
+    ///
+    /// The solution is replayed first by [`verify::replay`], and
+    /// `SolveError::InvalidSolution` is returned with the message of the
+    /// [`ReplayError`](verify::ReplayError) if it does not solve the level.
+    /// Otherwise it becomes the incumbent of an optimal branch and bound
+    /// search, which prunes every state that cannot lead to a strictly
+    /// cheaper solution, so exhausting the search proves the solution
+    /// optimal. Each call starts a new search
+    /// sharing the analyses of this solver.
+    pub fn prove_optimal(
+        &self,
+        actions: &Actions,
+        metric: Metric,
+        timeout: Duration,
+    ) -> Result<Verdict> {
+        verify::replay(&self.map, actions)
+            .map_err(|error| SolveError::InvalidSolution(error.to_string()))?;
+        let mut seed = self.seed();
+        seed.options.strategy = match metric {
+            Metric::Pushes => Strategy::OptimalPushes,
+            Metric::Moves => Strategy::OptimalMovePush,
+        };
+        seed.options.algorithm = Algorithm::AStar;
+        seed.options.anytime = false;
+        seed.options.threads = 1;
+        seed.options.relevance_cuts = None;
+        seed.options.record_graph = None;
+        seed.options.branch_and_bound = true;
+        let mut solver = Search::from_seed(seed);
+        solver.initialize();
+        let cost = solver.solution_cost(actions);
+        solver.incumbents.push(actions.clone());
+        solver.best_solution = Some((actions.clone(), cost));
+        match solver.search_for(timeout) {
+            Ok(_) if solver.incumbents.len() == 1 => Ok(Verdict::Optimal),
+            Ok(solution) => Ok(Verdict::Improved(solution)),
+            Err(
+                error @ (SolveError::Timeout { .. }
+                | SolveError::ExpansionLimit { .. }
+                | SolveError::OutOfMemory { .. }),
+            ) => Ok(Verdict::Unknown(error)),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns the player position and direction of each push of the actions.
+    fn push_sequence(&self, actions: &Actions) -> Vec<(Vector2<i32>, Direction)> {
+        let mut player_position = self.map.player_position();
+        let mut pushes = Vec::new();
+        for action in &**actions {
+            if action.is_push() {
+                pushes.push((player_position, action.direction()));
+            }
+            player_position += &action.direction().into();
+        }
+        pushes
+    }
+
+    /// Races the specs against each other, each on its own thread sharing the
+    /// analyses of this solver, and returns the solution chosen by the policy
+    /// with the statistics of every run.
+    pub fn solve_portfolio(
+        &self,
+        specs: &[PortfolioSpec],
+        policy: PortfolioPolicy,
+        timeout: Duration,
+    ) -> PortfolioResult {
+        portfolio::solve(self, specs, policy, timeout)
+    }
+
+    /// Recommends the next push from the position of the player and the
+    /// boxes, searching for at most the budget, as by a hint button. The
+    /// search of this solver is not affected.
+    ///
+    /// Returns `None` if the position is not a position of the level, is
+    /// solved, or cannot be solved.
+    pub fn hint(
+        &self,
+        player_position: Vector2<i32>,
+        box_positions: &HashSet<Vector2<i32>>,
+        budget: Duration,
+    ) -> Option<Hint> {
+        hint::hint(self, player_position, box_positions, budget)
+    }
+
+    /// Creates a solver which searches from the position of the player and
+    /// the boxes, such as the position reached by the moves played so far,
+    /// with the options of this solver and the given strategy. The analyses
+    /// which do not depend on the position are shared.
+    ///
+    /// The solutions of the new solver start at the position, so they follow
+    /// the moves played to reach it. Returns the first problem found by
+    /// [`LevelAnalysis::validate_position`] if the position does not fit the
+    /// level.
+    pub fn initial_from(
+        &self,
+        player_position: Vector2<i32>,
+        box_positions: HashSet<Vector2<i32>>,
+        strategy: Strategy,
+    ) -> std::result::Result<Solver, LevelError> {
+        let options = SolverOptions {
+            strategy,
+            ..self.context.options.clone()
+        };
+        self.solver_at(player_position, &box_positions, options)
+    }
+
+    /// Creates a solver for the position of the player and the boxes on the
+    /// level of this solver, reusing the analyses which do not depend on the
+    /// position.
+    pub(crate) fn solver_at(
+        &self,
+        player_position: Vector2<i32>,
+        box_positions: &HashSet<Vector2<i32>>,
+        options: SolverOptions,
+    ) -> std::result::Result<Solver, LevelError> {
+        LevelAnalysis::validate_position(&self.map, player_position, box_positions)?;
+        let mut map = self.map.clone();
+        let removed_boxes = map
+            .box_positions()
+            .difference(box_positions)
+            .copied()
+            .collect_vec();
+        let added_boxes = box_positions
+            .difference(map.box_positions())
+            .copied()
+            .collect_vec();
+        for (removed_box, added_box) in removed_boxes.into_iter().zip(added_boxes) {
+            map.set_box_position(removed_box, added_box);
+        }
+        map.set_player_position(player_position);
+        let mut analysis = LevelAnalysis::new(map, &options);
+        // The distances to the goals by minimum moves depend on the initial
+        // player position.
+        if options.lower_bound_method == self.context.options.lower_bound_method
+            && options.lower_bound_method != LowerBoundMethod::MinimumMove
+        {
+            analysis.import(
+                self.analysis.dead_squares().clone(),
+                self.analysis.tunnels().clone(),
+                self.analysis.goal_distances().to_vec(),
+            );
+        }
+        Ok(Solver::with_analysis(Arc::new(analysis), options))
+    }
+
+    /// Searches for solution backward, pulling boxes off goals until they
+    /// reach their initial positions, and returns it as forward actions.
+    ///
+    /// Solutions have the fewest pushes unless the strategy is `Fast`,
+    /// `Mixed` or `Greedy`.
+    pub fn solve_backward(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        if let Some(reason) = &self.no_solution_reason {
+            return Err(self.no_solution(reason.clone()));
+        }
+        let optimal = !matches!(
+            self.context.options.strategy,
+            Strategy::Fast | Strategy::Mixed | Strategy::Greedy
+        );
+        let backward = self
+            .backward
+            .get_or_insert_with(|| BackwardSearch::new(&self.map, optimal));
+        let initial_key = backward::key(
+            &self.map,
+            self.map.box_positions(),
+            self.map.player_position(),
+        );
+        if backward.pushes(initial_key).is_some() {
+            return Ok(Actions::new());
+        }
+        loop {
+            if timer.elapsed() >= timeout {
+                return Err(self.timeout_error());
+            }
+            match backward.step(&self.map, |key| key == initial_key) {
+                Step::Exhausted => {
+                    let visited_states = backward.len();
+                    return Err(
+                        self.no_solution(NoSolutionReason::SearchExhausted { visited_states })
+                    );
+                }
+                Step::Expanded => self.expanded_states += 1,
+                Step::Reached { pushes, .. } => {
+                    return Ok(backward::stitch(
+                        &self.map,
+                        Actions::new(),
+                        self.map.player_position(),
+                        self.map.box_positions().clone(),
+                        &pushes,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Searches for solution using beam search, expanding the states in the
+    /// heap as a wave and keeping only the best `width` of their successors
+    /// as the next wave.
+    fn beam_search(&mut self, width: usize, timer: Instant, timeout: Duration) -> Result<Actions> {
+        loop {
+            while let Some(state) = self.heap.peek() {
+                self.check_limits(timer, timeout, self.expanded_states)?;
+                if state.is_solved(&self.analysis, &self.context) {
+                    return Ok(self
+                        .heap
+                        .pop()
+                        .unwrap()
+                        .actions_with_moves(&self.analysis, &self.context));
+                }
+                let state = self.heap.pop().unwrap();
+                self.expanded_states += 1;
+
+                for successor in state.successors(&self.analysis, &self.context) {
+                    if !self.mark_visited(&successor) {
+                        self.duplicate_states += 1;
+                        continue;
+                    }
+                    self.next_wave.push(successor);
+                }
+            }
+            if self.next_wave.is_empty() {
+                return Err(self.no_solution(if self.discarded_states > 0 {
+                    NoSolutionReason::BeamExhausted {
+                        discarded_states: self.discarded_states,
+                    }
+                } else {
+                    self.search_exhausted()
+                }));
+            }
+            let mut next_wave = std::mem::take(&mut self.next_wave);
+            if next_wave.len() > width {
+                next_wave.select_nth_unstable_by_key(width, |state| state.priority());
+                self.discarded_states += next_wave.len() - width;
+                next_wave.truncate(width);
+            }
+            self.heap = next_wave.into();
+        }
+    }
+
+    /// Searches for solution using the A* algorithm, expanding a state of the
+    /// backward search after each state of the forward search.
+    fn bidirectional_search(
+        &mut self,
+        bidirectional: &mut BidirectionalSearch,
+        timer: Instant,
+        timeout: Duration,
+    ) -> Result<Actions> {
+        if self.expanded_states == 0 {
+            if let Some(initial_state) = self.heap.peek() {
+                if let Some(solution) = bidirectional.visit_forward(&self.map, initial_state) {
+                    return Ok(solution);
+                }
+            }
+        }
+        while let Some(state) = self.heap.pop() {
+            if let Err(error) = self.check_limits(timer, timeout, self.expanded_states) {
+                self.heap.push(state);
+                return Err(error);
+            }
+            if state.is_solved(&self.analysis, &self.context) {
+                return Ok(state.actions_with_moves(&self.analysis, &self.context));
+            }
+            self.expanded_states += 1;
+
+            for successor in state.successors(&self.analysis, &self.context) {
+                if !self.mark_visited(&successor) {
+                    self.duplicate_states += 1;
+                    continue;
+                }
+                if let Some(solution) = bidirectional.visit_forward(&self.map, &successor) {
+                    return Ok(solution);
+                }
+                self.heap.push(successor);
+            }
+            if let Some(solution) = bidirectional.step(&self.map) {
+                return Ok(solution);
+            }
+        }
+
+        Err(self.no_solution(self.search_exhausted()))
+    }
+
+    /// Searches for solution using the A* algorithm, continuing after each
+    /// solution with its cost as an upper bound. Returns the cheapest solution
+    /// found when the timeout is reached or the search space is exhausted.
+    fn anytime_search(&mut self, timer: Instant, timeout: Duration) -> Result<Actions> {
+        if self.best_costs.is_empty() {
+            if let Some(initial_state) = self.heap.peek() {
+                let cost = self.state_cost(initial_state);
+                self.best_costs.insert(self.state_key(initial_state), cost);
+            }
+        }
+        while let Some(state) = self.heap.pop() {
+            if let Err(error) = self.check_limits(timer, timeout, self.expanded_states) {
+                self.heap.push(state);
+                return match &self.best_solution {
+                    Some((solution, _)) => Ok(solution.clone()),
+                    None => Err(error),
+                };
+            }
+            let cost = self.state_cost(&state);
+            if state.is_solved(&self.analysis, &self.context) {
+                if self
+                    .best_solution
+                    .as_ref()
+                    .is_none_or(|(_, best_cost)| cost < *best_cost)
+                {
+                    let solution = state.actions_with_moves(&self.analysis, &self.context);
+                    #[cfg(feature = "log")]
+                    if self.first_solution.is_none() {
+                        log::debug!(
+                            moves = solution.moves(),
+                            pushes = solution.pushes(),
+                            expanded_states = self.expanded_states;
+                            "first solution found"
+                        );
+                    }
+                    self.first_solution.get_or_insert_with(|| solution.clone());
+                    self.incumbents.push(solution.clone());
+                    self.best_solution = Some((solution, cost));
+                }
+                continue;
+            }
+            // The state has been reached again at a lower cost since it was
+            // pushed onto the heap.
+            if self.best_costs[&self.state_key(&state)] < cost || self.exceeds_best_solution(&state)
+            {
+                continue;
+            }
+            self.expanded_states += 1;
+
+            for successor in state.successors(&self.analysis, &self.context) {
+                let cost = self.state_cost(&successor);
+                let key = self.state_key(&successor);
+                if self
+                    .best_costs
+                    .get(&key)
+                    .is_some_and(|best_cost| *best_cost <= cost)
+                {
+                    self.duplicate_states += 1;
+                    continue;
+                }
+                if self.exceeds_best_solution(&successor) {
+                    continue;
+                }
+                self.best_costs.insert(key, cost);
+                self.heap.push(successor);
+            }
+        }
+
+        match &self.best_solution {
+            Some((solution, _)) => {
+                self.optimality_proven = true;
+                Ok(solution.clone())
+            }
+            None => Err(self.no_solution(NoSolutionReason::SearchExhausted {
+                visited_states: self.best_costs.len(),
+            })),
+        }
+    }
+
+    /// Finds the first incumbent of branch and bound with the `Fast` strategy,
+    /// sharing the analyses of the level.
+    fn probe_search(&mut self, timer: Instant, timeout: Duration) -> Result<()> {
+        if self.probe.is_none() {
+            let mut seed = self.seed();
+            seed.options.strategy = Strategy::Fast;
+            seed.options.branch_and_bound = false;
+            let mut probe = Search::from_seed(seed);
+            probe.initialize();
+            self.probe = Some(Box::new(probe));
+        }
+        let probe = self.probe.as_mut().unwrap();
+        let expanded_states = probe.expanded_states();
+        let result = probe.search_for(timeout.saturating_sub(timer.elapsed()));
+        self.expanded_states += probe.expanded_states() - expanded_states;
+        let solution = result?;
+        self.probe = None;
+        let cost = self.solution_cost(&solution);
+        #[cfg(feature = "log")]
+        log::debug!(
+            moves = solution.moves(),
+            pushes = solution.pushes(),
+            expanded_states = self.expanded_states;
+            "first solution found"
+        );
+        self.first_solution = Some(solution.clone());
+        self.incumbents.push(solution.clone());
+        self.best_solution = Some((solution, cost));
+        Ok(())
+    }
+
+    /// Checks if the state cannot lead to a solution cheaper than the
+    /// incumbent of branch and bound.
+    fn meets_incumbent(&self, state: &State) -> bool {
+        if !self.context.options.branch_and_bound {
+            return false;
+        }
+        let Some((_, (best_cost, _))) = &self.best_solution else {
+            return false;
+        };
+        let (cost, _) = self.state_cost(state);
+        cost + state.lower_bound(&self.analysis, &self.context).unwrap() >= *best_cost
+    }
+
+    /// Checks if the strategy expands states in the order of their cost, so
+    /// that no solution is cheaper than the first one popped.
+    pub(crate) fn expands_in_cost_order(&self) -> bool {
+        matches!(
+            self.context.options.strategy,
+            Strategy::OptimalMovePush | Strategy::OptimalPushMove | Strategy::OptimalPushes
+        )
+    }
+
+    /// Checks if states are reopened when reached again at a lower cost,
+    /// which the bound of `Weighted` relies on as well as the strategies
+    /// expanding states in order of cost. The approximate visited filter
+    /// cannot tell the costs apart, so `Weighted` then prunes every state
+    /// reached again.
+    pub(crate) fn reopens_states(&self) -> bool {
+        self.expands_in_cost_order()
+            || matches!(self.context.options.strategy, Strategy::Weighted(_))
+                && self.context.options.visited_filter == VisitedFilter::Exact
+    }
+
+    /// Returns the key by which states are told apart when they are reached
+    /// again at a lower cost.
+    pub(crate) fn state_key(&self, state: &State) -> u64 {
+        if self.context.minimizes_moves() {
+            state.exact_hash(&self.analysis)
+        } else {
+            state.normalized_hash(&self.analysis, &self.context)
+        }
+    }
+
+    /// Returns the cost of the actions minimized by anytime search, the moves
+    /// and pushes in the order of the strategy.
+    pub(crate) fn solution_cost(&self, actions: &Actions) -> (usize, usize) {
+        match self.context.options.strategy {
+            Strategy::OptimalMovePush => (actions.moves(), actions.pushes()),
+            _ => (actions.pushes(), actions.moves()),
+        }
+    }
+
+    /// Returns the cost of the path to the state, in the same order as
+    /// `solution_cost`.
+    pub(crate) fn state_cost(&self, state: &State) -> (usize, usize) {
+        match self.context.options.strategy {
+            Strategy::OptimalMovePush => (state.path.moves(), state.path.pushes()),
+            _ => (state.path.pushes(), state.path.moves()),
+        }
+    }
+
+    /// Checks if the state cannot lead to a solution cheaper than the best
+    /// one found by anytime search.
+    fn exceeds_best_solution(&self, state: &State) -> bool {
+        let Some((_, (best_cost, _))) = &self.best_solution else {
+            return false;
+        };
+        let (cost, _) = self.state_cost(state);
+        cost + state.lower_bound(&self.analysis, &self.context).unwrap() > *best_cost
+    }
+
+    /// Searches for solution using hash-distributed A*, with the states split
+    /// between the threads by their hash.
+    fn parallel_search(&mut self, timer: Instant, timeout: Duration) -> Result<Actions> {
+        let mut parallel = self
+            .parallel
+            .take()
+            .unwrap_or_else(|| ParallelSearch::new(self.context.options.threads));
+        for state in self.heap.take() {
+            parallel.insert(state, self);
+        }
+        let mut expanded_states = 0;
+        let result = parallel.search(self, timer, timeout, &mut expanded_states);
+        self.expanded_states += expanded_states;
+        self.parallel = Some(parallel);
+        result
+    }
+
+    /// Searches for solution using the IDA* algorithm, starting from the
+    /// initial state in the heap.
+    fn ida_star_search(&mut self, timer: Instant, timeout: Duration) -> Result<Actions> {
+        let mut ida_star = match self.ida_star.take() {
+            Some(ida_star) => ida_star,
+            None => {
+                let Some(initial_state) = self.heap.pop() else {
+                    return Err(
+                        self.no_solution(NoSolutionReason::SearchExhausted { visited_states: 0 })
+                    );
+                };
+                IdaStar::new(
+                    initial_state,
+                    self.context.options.transposition_table_capacity,
+                    self,
+                )
+            }
+        };
+        let mut expanded_states = 0;
+        let result = ida_star.search(self, timer, timeout, &mut expanded_states);
+        self.expanded_states += expanded_states;
+        self.ida_star = Some(ida_star);
+        result
+    }
+
+    /// Checks that neither the timeout, the maximum number of expanded states
+    /// nor the memory budget has been reached.
+    ///
+    /// The time is only read on every `TIME_CHECK_INTERVAL`th check, counted
+    /// across calls to the search, so that even tiny timeouts expand some
+    /// states.
+    pub(crate) fn check_limits(
+        &self,
+        timer: Instant,
+        timeout: Duration,
+        expanded_states: usize,
+    ) -> Result<()> {
+        let limit_checks = self.limit_checks.get();
+        self.limit_checks.set(limit_checks + 1);
+        if limit_checks.is_multiple_of(TIME_CHECK_INTERVAL) {
+            let elapsed = timer.elapsed();
+            self.update_statistics(expanded_states, self.search_time + elapsed);
+            if self.is_cancelled() {
+                return Err(SolveError::Cancelled {
+                    statistics: Box::new(self.statistics()),
+                });
+            }
+            if matches!(
+                self.context.options.progress_interval,
+                ProgressInterval::Time(_)
+            ) {
+                self.report_progress(expanded_states, || self.search_time + elapsed);
+            }
+            if elapsed >= timeout {
+                return Err(self.timeout_error());
+            }
+        }
+        if matches!(
+            self.context.options.progress_interval,
+            ProgressInterval::Expansions(_)
+        ) {
+            self.report_progress(expanded_states, || self.search_time + timer.elapsed());
+        }
+        if let Some(max_expansions) = self.context.options.max_expansions {
+            if expanded_states as u64 >= max_expansions {
+                return Err(SolveError::ExpansionLimit {
+                    expanded: expanded_states as u64,
+                    statistics: Box::new(self.statistics()),
+                });
+            }
+        }
+        if let Some(max_memory_bytes) = self.context.options.max_memory_bytes {
+            let estimated_bytes = self.estimated_memory_bytes();
+            if estimated_bytes > max_memory_bytes {
+                return Err(SolveError::OutOfMemory {
+                    estimated_bytes,
+                    visited: self.visited.len() + self.best_costs.len(),
+                    open: self.heap.len() + self.next_wave.len(),
+                    statistics: Box::new(self.statistics()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an estimate of the memory used by the open and visited states
+    /// and the lower bound cache, in bytes.
+    ///
+    /// The open states are assumed to be as large as the best one.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        let state_bytes = |states: usize, capacity: usize, sample: Option<&State>| {
+            capacity * size_of::<State>() + states * sample.map_or(0, State::allocated_bytes)
+        };
+        self.heap.allocated_bytes()
+            + self.heap.len() * self.heap.peek().map_or(0, State::allocated_bytes)
+            + state_bytes(
+                self.next_wave.len(),
+                self.next_wave.capacity(),
+                self.next_wave.first(),
+            )
+            + self.visited.allocated_bytes()
+            + hash_table_bytes(
+                self.verified_states.capacity(),
+                size_of::<(u64, StateKey)>(),
+            )
+            + self.verified_states.len()
+                * self.heap.peek().map_or(0, |state| {
+                    state.box_positions.len() * size_of::<CellIndex>()
+                })
+            + hash_table_bytes(
+                self.best_costs.capacity(),
+                size_of::<(u64, (usize, usize))>(),
+            )
+            + self.context.lower_bound_cache().allocated_bytes()
+    }
+
+    /// Returns the statistics of the search, as of the last update.
+    ///
+    /// They are updated together with the reading of the time, and when a
+    /// call to [`Search::solve`] returns.
+    pub fn statistics(&self) -> SolveStatistics {
+        self.statistics.get()
+    }
+
+    fn update_statistics(&self, expanded_states: usize, search_time: Duration) {
+        let statistics = self.statistics.get();
+        let approx_memory_bytes = self.estimated_memory_bytes();
+        let open_states =
+            self.heap.len() + self.next_wave.len() + self.spill.as_ref().map_or(0, SpillFile::len);
+        let visited_states = self.stored_states();
+        self.statistics.set(SolveStatistics {
+            open_states,
+            visited_states,
+            state_bytes: size_of::<State>()
+                + self
+                    .heap
+                    .peek()
+                    .or(self.next_wave.first())
+                    .map_or(0, State::allocated_bytes),
+            approx_memory_bytes,
+            peak_memory_bytes: statistics.peak_memory_bytes.max(approx_memory_bytes),
+            expanded_states,
+            search_time,
+            pressure_relief: self
+                .context
+                .options
+                .pressure_relief
+                .filter(|_| self.pressure_reliefs.0 > 0)
+                .map(|pressure_relief| pressure_relief.policy),
+            pressure_reliefs: self.pressure_reliefs.0,
+            relieved_states: self.pressure_reliefs.1,
+            pruning: PruningStatistics {
+                duplicate_states: self.duplicate_states,
+                ..self.context.pruned_states()
+            },
+            generated_states: self.context.generated_states(),
+            peak_open_states: statistics.peak_open_states.max(open_states),
+            peak_visited_states: statistics.peak_visited_states.max(visited_states),
+            initial_lower_bound: self.initial_lower_bound,
+            solution: statistics.solution,
+            optimality_guaranteed: self.optimality() != Optimality::None,
+            best_lower_bound: Some(self.best_lower_bound())
+                .filter(|&lower_bound| lower_bound != usize::MAX),
+            most_boxes_on_goals: self.progress.1,
+        });
+    }
+
+    /// Checks that the options can be used together.
+    fn validate_options(&self) -> std::result::Result<(), String> {
+        if self.context.options.threads == 0 {
+            return Err("threads must be positive".to_string());
+        }
+        if !self.analysis.is_compatible(&self.context.options) {
+            return Err(
+                "the analysis of the level uses another lower bound method or symmetry reduction"
+                    .to_string(),
+            );
+        }
+        if self.context.options.threads > 1
+            && (self.context.options.algorithm != Algorithm::AStar
+                || self.context.options.anytime
+                || self.context.options.max_expansions.is_some()
+                || self.context.options.max_memory_bytes.is_some())
+        {
+            return Err(
+                "parallel search only supports A* without anytime mode, expansion or memory limit"
+                    .to_string(),
+            );
+        }
+        if self.context.options.max_memory_bytes.is_some()
+            && matches!(
+                self.context.options.algorithm,
+                Algorithm::IdaStar | Algorithm::Bidirectional
+            )
+        {
+            return Err(format!(
+                "memory limit does not support {:?}",
+                self.context.options.algorithm
+            ));
+        }
+        if self.context.options.record_graph.is_some()
+            && (self.context.options.algorithm != Algorithm::AStar
+                || self.context.options.anytime
+                || self.context.options.threads > 1)
+        {
+            return Err(
+                "search graph is only recorded by A* without anytime mode or threads".to_string(),
+            );
+        }
+        if self.context.options.relevance_cuts.is_some() {
+            if self.context.options.algorithm != Algorithm::AStar
+                || self.context.options.anytime
+                || self.context.options.threads > 1
+            {
+                return Err(
+                    "relevance cuts are only supported by A* without anytime mode or threads"
+                        .to_string(),
+                );
+            }
+            if !matches!(
+                self.context.options.strategy,
+                Strategy::Fast | Strategy::Mixed | Strategy::Greedy
+            ) {
+                return Err(format!(
+                    "relevance cuts cannot guarantee the optimality of {:?}",
+                    self.context.options.strategy
+                ));
+            }
+        }
+        if self.context.options.branch_and_bound {
+            if self.context.options.algorithm != Algorithm::AStar
+                || self.context.options.anytime
+                || self.context.options.threads > 1
+            {
+                return Err(
+                    "branch and bound only supports A* without anytime mode or threads".to_string(),
+                );
+            }
+            if !self.expands_in_cost_order() {
+                return Err(format!(
+                    "branch and bound does not support {:?}",
+                    self.context.options.strategy
+                ));
+            }
+        }
+        if self.context.options.verify_hashes && !CellIndex::fits(self.map.dimensions()) {
+            return Err(format!(
+                "hash verification supports levels of at most {} squares",
+                u16::MAX as usize + 1
+            ));
+        }
+        if self.context.options.visited_filter != VisitedFilter::Exact {
+            if self.context.options.anytime
+                || self.context.options.threads > 1
+                || self.context.options.stall_policy.is_some()
+                || self.context.options.verify_hashes
+                || self.context.options.algorithm == Algorithm::IdaStar
+            {
+                return Err(
+                    "approximate visited filter is not supported by IDA*, anytime mode, threads, stall policy or hash verification"
+                        .to_string(),
+                );
+            }
+            if self.expands_in_cost_order() {
+                return Err(format!(
+                    "approximate visited filter does not support {:?}",
+                    self.context.options.strategy
+                ));
+            }
+        }
+        if let Some(stall_policy) = &self.context.options.stall_policy {
+            if self.context.options.algorithm != Algorithm::AStar
+                || self.context.options.anytime
+                || self.context.options.threads > 1
+                || self.context.options.branch_and_bound
+                || self.context.options.relevance_cuts.is_some()
+            {
+                return Err(
+                    "stall policy only supports A* without anytime mode, threads or pruning"
+                        .to_string(),
+                );
+            }
+            // The actions of `OptimalPushes` do not include the moves between
+            // pushes.
+            if self.context.options.strategy == Strategy::OptimalPushes
+                || stall_policy.fallback == Strategy::OptimalPushes
+            {
+                return Err("stall policy does not support OptimalPushes".to_string());
+            }
+        }
+        if let Some(pressure_relief) = &self.context.options.pressure_relief {
+            if self.context.options.algorithm != Algorithm::AStar
+                || self.context.options.anytime
+                || self.context.options.threads > 1
+                || self.context.options.branch_and_bound
+                || self.context.options.stall_policy.is_some()
+                || self.context.options.relevance_cuts.is_some()
+            {
+                return Err(
+                    "pressure relief only supports A* without anytime mode, threads, stall policy or pruning"
+                        .to_string(),
+                );
+            }
+            if pressure_relief.max_open_states == 0 {
+                return Err("maximum open states must be positive".to_string());
+            }
+            if let ReliefPolicy::Prune { percent } | ReliefPolicy::Spill { percent } =
+                pressure_relief.policy
+            {
+                if !(1..100).contains(&percent) {
+                    return Err("relieved percentage must be between 1 and 99".to_string());
+                }
+            }
+        }
+        if self.context.options.anytime && self.context.options.algorithm != Algorithm::AStar {
+            return Err(format!(
+                "anytime search does not support {:?}",
+                self.context.options.algorithm
+            ));
+        }
+        if let Algorithm::Beam { width } = self.context.options.algorithm {
+            if width == 0 {
+                return Err("beam width must be positive".to_string());
+            }
+            if !matches!(
+                self.context.options.strategy,
+                Strategy::Fast | Strategy::Mixed | Strategy::Greedy
+            ) {
+                return Err(format!(
+                    "beam search cannot guarantee the optimality of {:?}",
+                    self.context.options.strategy
+                ));
+            }
+        }
+        if self.context.options.algorithm == Algorithm::Bidirectional
+            && self.context.options.strategy != Strategy::Fast
+        {
+            return Err(format!(
+                "bidirectional search does not support {:?}",
+                self.context.options.strategy
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the level for structural problems that make it unsolvable,
+    /// without searching.
+    fn diagnose(&self) -> Option<NoSolutionReason> {
+        let boxes = self.map.box_positions().len();
+        let goals = self.map.goal_positions().len();
+        if boxes != goals {
+            return Some(NoSolutionReason::BoxGoalCountMismatch { boxes, goals });
+        }
+
+        let box_positions_off_goal: Vec<_> = self
+            .map
+            .box_positions()
+            .iter()
+            .filter(|position| !self.map[**position].intersects(Tiles::Goal))
+            .sorted_by_key(|position| (position.y, position.x))
+            .collect();
+        // Floors are the squares the player can reach, ignoring boxes.
+        if !box_positions_off_goal.is_empty()
+            && box_positions_off_goal
+                .iter()
+                .all(|position| !self.map[**position].intersects(Tiles::Floor))
+        {
+            return Some(NoSolutionReason::PlayerSealedOff);
+        }
+
+        box_positions_off_goal
+            .into_iter()
+            .find(|position| {
+                self.dead_squares().contains(position)
+                    || !self.lower_bounds().contains_key(position)
+            })
+            .map(|position| NoSolutionReason::BoxOnDeadSquare(*position))
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.context.options.strategy
+    }
+
+    /// Returns the factor by which the pushes of a found solution may exceed
+    /// the optimal pushes, or `None` if there is no such guarantee.
+    pub fn optimality_bound(&self) -> Option<f32> {
+        if !self.searches_in_cost_order() {
+            return None;
+        }
+        if self.context.options.algorithm == Algorithm::IdaStar {
+            // IDA* deepens on the pushes, or the moves for move optimal
+            // solutions, plus the lower bound.
+            return match self.context.options.strategy {
+                Strategy::OptimalMovePush => None,
+                Strategy::Weighted(weight) => Some(weight.max(1.0)),
+                _ => Some(1.0),
+            };
+        }
+        match self.context.options.strategy {
+            Strategy::Fast | Strategy::Mixed | Strategy::OptimalMovePush | Strategy::Greedy => None,
+            Strategy::OptimalPushMove | Strategy::OptimalPushes => Some(1.0),
+            // States first reached along a costlier path are only reopened
+            // with the exact visited filter.
+            Strategy::Weighted(weight) => self.reopens_states().then_some(weight.max(1.0)),
+        }
+    }
+
+    /// Checks if the algorithm and the options keep the order in which the
+    /// strategy expands states, which every optimality guarantee relies on.
+    fn searches_in_cost_order(&self) -> bool {
+        if self.search_algorithm.is_some() {
+            return false;
+        }
+        if matches!(
+            self.context.options.algorithm,
+            Algorithm::Beam { .. } | Algorithm::Bidirectional
+        ) {
+            return false;
+        }
+        if self
+            .context
+            .options
+            .pressure_relief
+            .is_some_and(|pressure_relief| {
+                matches!(pressure_relief.policy, ReliefPolicy::Prune { .. })
+            })
+        {
+            return false;
+        }
+        // Parallel search returns the first solution found by any thread,
+        // unless states are expanded in the order of their cost.
+        self.context.options.threads <= 1 || self.expands_in_cost_order()
+    }
+
+    /// Returns the metric in which the solutions found by the search so far
+    /// are provably optimal.
+    ///
+    /// Unlike [`Search::optimality_bound`], it depends on the run: relevance
+    /// cuts which have cut pushes void the guarantee, and so does an
+    /// approximate visited filter, which may have claimed that a new state
+    /// had been visited.
+    pub fn optimality(&self) -> Optimality {
+        let metric = match self.context.options.strategy {
+            Strategy::OptimalMovePush => Optimality::MoveOptimal,
+            _ => Optimality::PushOptimal,
+        };
+        if self.optimality_proven {
+            return metric;
+        }
+        if self.cut_pushes() > 0
+            || self.discarded_states > 0
+            || matches!(
+                self.context.options.visited_filter,
+                VisitedFilter::Approximate { .. }
+            )
+        {
+            return Optimality::None;
+        }
+        let optimal = match self.context.options.strategy {
+            Strategy::OptimalMovePush => self.searches_in_cost_order(),
+            _ => self.optimality_bound() == Some(1.0),
+        };
+        if optimal {
+            metric
+        } else {
+            Optimality::None
+        }
+    }
+
+    /// Searches for a solution as [`Search::solve_for`], and returns it with the
+    /// strategy which found it and its optimality.
+    pub fn search_solution(&mut self, timeout: Duration) -> Result<Solution> {
+        let actions = self.search_for(timeout)?;
+        Ok(Solution {
+            moves: actions.moves(),
+            pushes: actions.pushes(),
+            actions,
+            strategy: self.context.options.strategy,
+            optimality: self.optimality(),
+        })
+    }
+
+    /// Returns the first solution found by anytime search or branch and
+    /// bound, which the returned solution may improve on.
+    pub fn first_solution(&self) -> Option<&Actions> {
+        self.first_solution.as_ref()
+    }
+
+    /// Returns the solutions found by anytime search or branch and bound, in
+    /// the order they were found, each cheaper than the previous one.
+    pub fn incumbents(&self) -> &[Actions] {
+        &self.incumbents
+    }
+
+    /// Checks if anytime search or branch and bound has exhausted the search
+    /// space after finding its solution, which proves that no solution has
+    /// fewer pushes, or fewer moves for `OptimalMovePush`.
+    pub fn optimality_proven(&self) -> bool {
+        self.optimality_proven
+    }
+
+    /// Returns the graph of the states expanded by the search, if recorded.
+    pub fn search_graph(&self) -> Option<&SearchGraph> {
+        self.search_graph.as_ref()
+    }
+
+    /// Returns the heuristics whose maximum is the lower bound of a state.
+    pub fn heuristics(&self) -> &[HeuristicKind] {
+        self.context.heuristics()
+    }
+
+    pub fn options(&self) -> &SolverOptions {
+        self.context.options()
+    }
+
+    /// Returns the options, and the caches and counters filled while
+    /// evaluating states, which the methods of [`State`] take along with the
+    /// analysis of the level.
+    pub fn context(&self) -> &StateContext {
+        &self.context
+    }
+
+    /// Returns the estimated minimum number of pushes required to solve the
+    /// level, or `None` if the level is trivially unsolvable.
+    pub fn estimated_pushes(&self) -> Option<usize> {
+        State::new(
+            self.map.player_position(),
+            BoxPositions::of(&self.map),
+            Path::default(),
+            &self.analysis,
+            &self.context,
+        )
+        .lower_bound(&self.analysis, &self.context)
+    }
+
+    /// Checks if a box of the level already sits on a dead square.
+    pub fn is_trivially_unsolvable(&self) -> bool {
+        self.estimated_pushes().is_none()
+    }
+
+    /// Returns the pattern database, or `None` if it is not built yet.
+    pub fn pattern_database(&self) -> Option<&PatternDatabase> {
+        self.context.pattern_database()
+    }
+
+    /// Returns the cache of lower bounds keyed by box configuration.
+    pub fn lower_bound_cache(&self) -> Ref<'_, LowerBoundCache> {
+        self.context.lower_bound_cache()
+    }
+
+    /// Returns the deadlocks learned during the search.
+    pub fn learned_deadlocks(&self) -> Ref<'_, LearnedDeadlocks> {
+        self.context.learned_deadlocks()
+    }
+
+    /// Returns the number of states expanded by the search.
+    pub fn expanded_states(&self) -> usize {
+        self.expanded_states
+    }
+
+    /// Returns the number of visited states whose hash equals the hash of a
+    /// different visited state, if hashes are verified.
+    pub fn hash_collisions(&self) -> usize {
+        self.hash_collisions
+    }
+
+    /// Returns the probability that the approximate visited filter claims
+    /// that a new state has been visited, at its current fill, or `None` if
+    /// the visited states are remembered exactly.
+    pub fn visited_false_positive_rate(&self) -> Option<f64> {
+        self.visited.false_positive_rate()
+    }
+
+    /// Returns the number of open states discarded by beam search.
+    pub fn discarded_states(&self) -> usize {
+        self.discarded_states
+    }
+
+    /// Returns the number of generated states which were not opened, because
+    /// an equal state had already been generated at no greater cost.
+    pub fn duplicate_states(&self) -> usize {
+        self.duplicate_states
+    }
+
+    /// Returns the number of states pruned by corral deadlock detection.
+    pub fn corral_deadlocks(&self) -> usize {
+        self.context.pruned_states().corral_deadlocks
+    }
+
+    /// Returns the number of states whose successors were restricted to the
+    /// pushes of a PI-corral.
+    pub fn pi_corrals(&self) -> usize {
+        self.context.pruned_states().pi_corrals
+    }
+
+    /// Returns the number of states pruned by deadlock patterns.
+    pub fn pattern_deadlocks(&self) -> usize {
+        self.context.pruned_states().pattern_deadlocks
+    }
+
+    /// Returns the number of pushes skipped by relevance cuts.
+    pub fn cut_pushes(&self) -> usize {
+        self.context.pruned_states().cut_pushes
+    }
+
+    /// Returns the number of successors generated, before duplicates are
+    /// skipped.
+    pub fn generated_states(&self) -> usize {
+        self.context.generated_states()
+    }
+
+    pub(crate) fn record_expanded_state(&mut self) {
+        self.expanded_states += 1;
+    }
+
+    /// Returns the number of times the area reachable by the player has been
+    /// calculated for a state.
+    pub fn flood_fills(&self) -> usize {
+        self.context.flood_fills()
+    }
+
+    /// Returns the number of states pruned because their boxes cannot be
+    /// pushed onto distinct goals.
+    pub fn bipartite_deadlocks(&self) -> usize {
+        self.context.pruned_states().bipartite_deadlocks
+    }
+
+    /// Returns the best state in the binary heap, or the deepest state on
+    /// the current path of IDA*, or `None` if there is none.
+    pub fn best_state(&self) -> Option<&State> {
+        if let Some(parallel) = &self.parallel {
+            return parallel.best_state();
+        }
+        match &self.ida_star {
+            Some(ida_star) => ida_star.current_state(),
+            None => self.heap.peek(),
+        }
+    }
+
+    /// Returns the number of states kept in memory by the search.
+    pub fn stored_states(&self) -> usize {
+        if let Some(ida_star) = &self.ida_star {
+            return ida_star.stored_states();
+        }
+        let backward_states = self
+            .bidirectional
+            .as_ref()
+            .map_or(0, |bidirectional| bidirectional.backward_states())
+            + self.backward.as_ref().map_or(0, |backward| backward.len());
+        let parallel_states = self
+            .parallel
+            .as_ref()
+            .map_or(0, |parallel| parallel.stored_states());
+        self.visited.len() + self.best_costs.len() + parallel_states + backward_states
+    }
+
+    /// Returns the number of expanded states after which the stall policy
+    /// switched to its fallback strategy, if it has.
+    pub fn stall_fallback(&self) -> Option<usize> {
+        self.fallback.map(|(_, expanded_states)| expanded_states)
+    }
+
+    /// Returns the analysis of the level, which can be shared with other
+    /// solvers through [`Solver::with_analysis`].
+    pub fn analysis(&self) -> &Arc<LevelAnalysis> {
+        &self.analysis
+    }
+
+    /// Returns the tunnels of the level, see [`LevelAnalysis::tunnels`].
+    pub fn tunnels(&self) -> &HashSet<(Vector2<i32>, Direction)> {
+        self.analysis.tunnels()
+    }
+
+    /// Returns the dead squares of the level, see
+    /// [`LevelAnalysis::dead_squares`].
+    pub fn dead_squares(&self) -> &HashSet<Vector2<i32>> {
+        self.analysis.dead_squares()
+    }
+
+    /// Returns the lower bound, dead flag and tunnels of each square, see
+    /// [`LevelAnalysis::squares`].
+    pub fn squares(&self) -> &SquareGrid<SquareInfo> {
+        self.analysis.squares()
+    }
+
+    /// Returns the keys from which the hashes of states are calculated.
+    pub fn zobrist_keys(&self) -> &ZobristKeys {
+        self.analysis.zobrist_keys()
+    }
+
+    /// Returns the walls, goals and dead squares as bit grids, see
+    /// [`LevelAnalysis::square_masks`].
+    pub fn square_masks(&self) -> Option<&SquareMasks> {
+        self.analysis.square_masks()
+    }
+
+    /// Returns the closet entrances of the level, see
+    /// [`LevelAnalysis::closet_entrances`].
+    pub fn closet_entrances(&self) -> &HashSet<(Vector2<i32>, Direction)> {
+        self.analysis.closet_entrances()
+    }
+
+    /// Returns the dead-end corridors of the level.
+    pub fn dead_end_corridors(&self) -> &[DeadEndCorridor] {
+        self.analysis.dead_end_corridors()
+    }
+
+    /// Returns the influence distances between the squares of the level.
+    pub fn influence(&self) -> &Influence {
+        self.analysis.influence()
+    }
+
+    /// Returns the symmetries of the level, see
+    /// [`LevelAnalysis::symmetries`].
+    pub fn symmetries(&self) -> &[Symmetry] {
+        self.analysis.symmetries()
+    }
+
+    /// Returns the packing order of the goal room, see
+    /// [`LevelAnalysis::packing_order`].
+    pub fn packing_order(&self) -> Option<&PackingOrder> {
+        self.analysis.packing_order()
+    }
+
+    /// Returns the lower bounds of the squares, see
+    /// [`LevelAnalysis::lower_bounds`].
+    pub fn lower_bounds(&self) -> &HashMap<Vector2<i32>, usize> {
+        self.analysis.lower_bounds()
+    }
+
+    /// Returns the distances from each position to each goal.
+    pub fn goal_distances(&self) -> &[GoalDistanceGrid] {
+        self.analysis.goal_distances()
+    }
+
+    /// Returns the distances from each position to each remaining goal, where
+    /// the boxes frozen on goals are treated as walls.
+    ///
+    /// `frozen_boxes` must be sorted, since it is used as the cache key.
+    pub fn frozen_goal_distances(&self, frozen_boxes: &[Vector2<i32>]) -> Arc<[GoalDistanceGrid]> {
+        self.context
+            .frozen_goal_distances(&self.analysis, frozen_boxes)
+    }
+}
+
+/// Estimates the bytes allocated by a hash table with the given capacity.
+pub(crate) fn hash_table_bytes(capacity: usize, entry_size: usize) -> usize {
+    if capacity == 0 {
+        return 0;
+    }
+    // The table keeps at least an eighth of its buckets empty, and stores a
+    // control byte per bucket plus a trailing group of them.
+    let buckets = (capacity * 8 / 7).next_power_of_two();
+    buckets * (entry_size + 1) + 16
 }
@@ -0,0 +1,161 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::solve::{
+    box_positions::BoxPositions,
+    path::Path,
+    solver::Search,
+    state::{Priority, State},
+};
+
+use nalgebra::Vector2;
+use soukoban::direction::Direction;
+
+/// The number of spill files created by this process, which tells their
+/// names apart.
+static SPILL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Open states written to a temporary file, which is removed when dropped.
+///
+/// Each state is a line of integers: the player position, the moves, the
+/// number of boxes and their positions, then the player position and the
+/// direction of each push.
+pub struct SpillFile {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    len: usize,
+    /// The best priority of the written states.
+    best_priority: Option<Priority>,
+}
+
+impl SpillFile {
+    /// Creates an empty spill file in the temporary directory.
+    pub fn new() -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "sokoban-spill-{}-{}",
+            std::process::id(),
+            SPILL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let writer = BufWriter::new(File::create(&path)?);
+        Ok(Self {
+            path,
+            writer: Some(writer),
+            len: 0,
+            best_priority: None,
+        })
+    }
+
+    /// Appends the states.
+    pub fn write(&mut self, states: impl IntoIterator<Item = State>) -> io::Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self
+                .writer
+                .insert(BufWriter::new(File::create(&self.path)?)),
+        };
+        for state in states {
+            let pushes = state.path.pushes_in_order();
+            let mut numbers = vec![
+                state.player_position.x,
+                state.player_position.y,
+                state.path.moves() as i32,
+                state.box_positions.len() as i32,
+            ];
+            numbers.extend(
+                state
+                    .box_positions
+                    .iter()
+                    .flat_map(|position| [position.x, position.y]),
+            );
+            numbers.extend(pushes.iter().flat_map(|(position, direction)| {
+                let offset: Vector2<i32> = (*direction).into();
+                [position.x, position.y, offset.x, offset.y]
+            }));
+            let line = numbers.iter().map(i32::to_string).collect::<Vec<_>>();
+            writeln!(writer, "{}", line.join(" "))?;
+            self.len += 1;
+            self.best_priority = Some(
+                self.best_priority
+                    .map_or(state.priority(), |priority| priority.min(state.priority())),
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the number of states in the file.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if there are no states in the file.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the best priority of the states in the file, or `None` if it
+    /// is empty.
+    pub fn best_priority(&self) -> Option<Priority> {
+        self.best_priority
+    }
+
+    /// Reads the states back, recalculating them for the solver, and empties
+    /// the file.
+    pub fn read(&mut self, solver: &Search) -> io::Result<Vec<State>> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid spilled state");
+        let mut states = Vec::with_capacity(self.len);
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let numbers = line?
+                .split(' ')
+                .map(|number| number.parse::<i32>().map_err(|_| invalid()))
+                .collect::<io::Result<Vec<_>>>()?;
+            let [x, y, moves, boxes, rest @ ..] = numbers.as_slice() else {
+                return Err(invalid());
+            };
+            let (boxes, pushes) = rest
+                .split_at_checked(*boxes as usize * 2)
+                .ok_or_else(invalid)?;
+            if pushes.len() % 4 != 0 {
+                return Err(invalid());
+            }
+            let box_positions = BoxPositions::new(
+                solver.map.dimensions(),
+                boxes
+                    .chunks(2)
+                    .map(|position| Vector2::new(position[0], position[1])),
+            );
+            let pushes = pushes
+                .chunks(4)
+                .map(|push| {
+                    let direction = Direction::try_from(Vector2::new(push[2], push[3]))
+                        .map_err(|_| invalid())?;
+                    Ok((Vector2::new(push[0], push[1]), direction))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            let path = Path::from_pushes(pushes, *moves as usize);
+            states.push(State::new(
+                Vector2::new(*x, *y),
+                box_positions,
+                path,
+                solver.analysis(),
+                solver.context(),
+            ));
+        }
+        self.len = 0;
+        self.best_priority = None;
+        Ok(states)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.writer = None;
+        let _ = fs::remove_file(&self.path);
+    }
+}
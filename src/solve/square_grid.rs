@@ -0,0 +1,109 @@
+use nalgebra::Vector2;
+use soukoban::direction::Direction;
+
+/// Returns the index of the position in a grid of the given dimensions stored
+/// row by row, or `None` if the position is out of bounds.
+pub fn square_index(dimensions: Vector2<i32>, position: Vector2<i32>) -> Option<usize> {
+    if position.x < 0 || position.y < 0 || position.x >= dimensions.x || position.y >= dimensions.y
+    {
+        return None;
+    }
+    Some((position.y * dimensions.x + position.x) as usize)
+}
+
+/// The index of a square of a level stored row by row, packed into 16 bits.
+///
+/// Cell indices are ordered by row and column, like the positions they stand
+/// for.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct CellIndex(u16);
+
+impl CellIndex {
+    /// Returns the cell index of the position, or `None` if the position is
+    /// out of bounds or the level has too many squares.
+    pub fn new(dimensions: Vector2<i32>, position: Vector2<i32>) -> Option<Self> {
+        square_index(dimensions, position)
+            .and_then(|index| u16::try_from(index).ok())
+            .map(Self)
+    }
+
+    /// Checks if every square of a level of the given dimensions has a cell
+    /// index.
+    pub fn fits(dimensions: Vector2<i32>) -> bool {
+        dimensions.x.max(0) as usize * dimensions.y.max(0) as usize <= u16::MAX as usize + 1
+    }
+
+    /// Returns the position of the square in a level of the given width.
+    pub fn position(self, width: i32) -> Vector2<i32> {
+        let index = i32::from(self.0);
+        Vector2::new(index % width, index / width)
+    }
+}
+
+/// A value for each square of a level, stored row by row.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SquareGrid<T> {
+    dimensions: Vector2<i32>,
+    squares: Vec<T>,
+}
+
+impl<T: Clone> SquareGrid<T> {
+    /// Creates a grid with the same value on every square.
+    pub fn new(dimensions: Vector2<i32>, value: T) -> Self {
+        Self {
+            dimensions,
+            squares: vec![value; (dimensions.x * dimensions.y) as usize],
+        }
+    }
+
+    /// Returns the value of the square, or `None` if the position is out of
+    /// bounds.
+    pub fn get(&self, position: Vector2<i32>) -> Option<&T> {
+        square_index(self.dimensions, position).map(|index| &self.squares[index])
+    }
+
+    /// Returns a mutable reference to the value of the square.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is out of bounds.
+    pub fn get_mut(&mut self, position: Vector2<i32>) -> &mut T {
+        let index = square_index(self.dimensions, position).expect("position out of bounds");
+        &mut self.squares[index]
+    }
+}
+
+/// The analyses of a square looked up for every generated state.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SquareInfo {
+    /// The minimum number of pushes of a box on the square to any goal, or
+    /// `None` if it cannot reach any goal.
+    pub lower_bound: Option<usize>,
+    /// Whether a box on the square can never be pushed to any goal.
+    pub is_dead: bool,
+    /// The directions in which the player on the square pushes a box through
+    /// a tunnel, one bit each.
+    tunnels: u8,
+}
+
+impl SquareInfo {
+    /// Checks if the player on the square pushes a box through a tunnel in
+    /// the direction.
+    pub fn is_tunnel(&self, direction: Direction) -> bool {
+        self.tunnels & direction_bit(direction) != 0
+    }
+
+    /// Marks the push from the square in the direction as a tunnel.
+    pub fn insert_tunnel(&mut self, direction: Direction) {
+        self.tunnels |= direction_bit(direction);
+    }
+}
+
+fn direction_bit(direction: Direction) -> u8 {
+    match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left => 4,
+        Direction::Right => 8,
+    }
+}
@@ -1,27 +1,64 @@
 use std::{
     cell::OnceCell,
     cmp::Ordering,
-    collections::HashSet,
+    collections::{HashSet, VecDeque},
     hash::{DefaultHasher, Hash, Hasher},
 };
 
-use crate::solve::solver::*;
+use crate::solve::{
+    box_positions::BoxPositions,
+    deadlock, hasher,
+    level_analysis::LevelAnalysis,
+    matching::{maximum_matching, minimum_cost_matching},
+    packing_order::push_box_to,
+    path::Path,
+    solver::*,
+    square_grid::CellIndex,
+    state_context::{Counts, StateContext},
+    symmetry::Symmetry,
+};
 
+use itertools::{Either, Itertools};
 use nalgebra::Vector2;
+use rayon::prelude::*;
+use smallvec::SmallVec;
 use soukoban::{
-    deadlock,
     direction::Direction,
-    path_finding::{find_path, normalized_area, reachable_area},
-    Action, Actions, Tiles,
+    path_finding::{normalized_area, reachable_area},
+    Actions, Tiles,
 };
 
-#[derive(Clone, Eq)]
+/// Priority of a state, compared lexicographically. Lower is better.
+pub type Priority = (usize, usize, usize);
+
+/// The sorted cell indices of the boxes and the player of a normalized state,
+/// telling apart states with equal hashes.
+pub type StateKey = (Vec<CellIndex>, CellIndex);
+
+#[derive(Clone)]
 pub struct State {
     pub player_position: Vector2<i32>,
-    pub box_positions: HashSet<Vector2<i32>>,
-    pub actions: Actions,
-    heuristic: usize,
-    lower_bound: OnceCell<usize>,
+    pub box_positions: BoxPositions,
+    pub path: Path,
+    /// The XOR of the Zobrist keys of the boxes.
+    box_hash: u64,
+    /// The normalized hash, calculated from the reachable area the first
+    /// time the area is needed.
+    normalized_hash: OnceCell<u64>,
+    priority: Priority,
+    lower_bound: OnceCell<Option<usize>>,
+    boxes_on_goals: usize,
+    /// Boxes frozen on goals, sorted by position.
+    frozen_boxes: Vec<Vector2<i32>>,
+    /// Pairs of boxes not frozen on goals and the distinct goals they are
+    /// matched to, if bipartite deadlocks are detected.
+    goal_matching: Vec<(Vector2<i32>, Vector2<i32>)>,
+    /// Positions of the boxes moved by the most recent pushes, if relevance
+    /// cuts are applied.
+    recent_pushes: Vec<Vector2<i32>>,
+    /// The order in which the state was created by the solver, breaking ties
+    /// between states of equal priority.
+    sequence: u64,
 }
 
 impl PartialEq for State {
@@ -30,18 +67,23 @@ impl PartialEq for State {
     }
 }
 
+impl Eq for State {}
+
 impl Hash for State {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.player_position.hash(state);
-        for position in &self.box_positions {
-            position.hash(state);
-        }
+        self.box_positions.hash(state);
     }
 }
 
 impl Ord for State {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.heuristic.cmp(&other.heuristic).reverse()
+        // Among states of equal priority, the earliest created one is expanded
+        // first.
+        self.priority
+            .cmp(&other.priority)
+            .reverse()
+            .then(other.sequence.cmp(&self.sequence))
     }
 }
 
@@ -54,176 +96,1560 @@ impl PartialOrd for State {
 impl State {
     pub fn new(
         player_position: Vector2<i32>,
-        box_positions: HashSet<Vector2<i32>>,
-        actions: Actions,
-        solver: &Solver,
+        box_positions: BoxPositions,
+        path: Path,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
     ) -> Self {
+        let box_hash = analysis.zobrist_keys().boxes_hash(box_positions.iter());
+        Self::with_lower_bound(
+            player_position,
+            box_positions,
+            box_hash,
+            path,
+            OnceCell::new(),
+            Vec::new(),
+            analysis,
+            context,
+        )
+    }
+
+    /// Creates a successor of the parent state in which a single box has been
+    /// moved from `moved_from` to `moved_to`.
+    ///
+    /// The lower bound is derived from the lower bound of the parent state
+    /// when possible, instead of being recalculated over all boxes.
+    ///
+    /// If enabled, a box frozen on a goal by the push is recorded, so that it
+    /// is treated as a wall by the lower bound of this state and its
+    /// descendants.
+    #[allow(clippy::too_many_arguments)]
+    fn successor_of(
+        parent: Parent<'_>,
+        moved_from: Vector2<i32>,
+        moved_to: Vector2<i32>,
+        player_position: Vector2<i32>,
+        box_positions: BoxPositions,
+        path: Path,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Self {
+        let mut frozen_boxes = parent.frozen_boxes.to_vec();
+        if context.options().frozen_boxes_as_walls {
+            let frozen_boxes_len = frozen_boxes.len();
+            for position in deadlock::frozen_boxes(analysis.map(), moved_to, &box_positions) {
+                if analysis.map()[position].intersects(Tiles::Goal)
+                    && !frozen_boxes.contains(&position)
+                {
+                    frozen_boxes.push(position);
+                }
+            }
+            if frozen_boxes.len() != frozen_boxes_len {
+                frozen_boxes.sort_by_key(|position| (position.y, position.x));
+            }
+        }
+
+        let lower_bound = OnceCell::new();
+        if frozen_boxes.is_empty() {
+            if let Some(value) =
+                parent.incremental_lower_bound(moved_from, moved_to, analysis, context)
+            {
+                lower_bound.set(Some(value)).unwrap();
+            }
+        }
+        let box_hash =
+            analysis
+                .zobrist_keys()
+                .moved_box_hash(parent.box_hash, moved_from, moved_to);
+        let instance = Self::with_lower_bound(
+            player_position,
+            box_positions,
+            box_hash,
+            path,
+            lower_bound,
+            frozen_boxes,
+            analysis,
+            context,
+        );
+        #[cfg(feature = "verify-lower-bound")]
+        debug_assert_eq!(
+            instance.lower_bound(analysis, context),
+            instance.calculate_lower_bound(analysis, context)
+        );
+        instance
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_lower_bound(
+        player_position: Vector2<i32>,
+        box_positions: BoxPositions,
+        box_hash: u64,
+        path: Path,
+        lower_bound: OnceCell<Option<usize>>,
+        frozen_boxes: Vec<Vector2<i32>>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Self {
+        let boxes_on_goals = match analysis.square_masks() {
+            Some(masks) => box_positions.count_in(&masks.goals),
+            None => box_positions
+                .iter()
+                .filter(|position| analysis.map()[*position].intersects(Tiles::Goal))
+                .count(),
+        };
         let mut instance = Self {
             player_position,
             box_positions,
-            actions,
-            heuristic: 0,
-            lower_bound: OnceCell::new(),
+            path,
+            box_hash,
+            normalized_hash: OnceCell::new(),
+            priority: (0, 0, 0),
+            lower_bound,
+            boxes_on_goals,
+            frozen_boxes,
+            goal_matching: Vec::new(),
+            recent_pushes: Vec::new(),
+            sequence: context.next_sequence(),
         };
-        debug_assert!(instance.actions.moves() < 10_000);
-        debug_assert!(instance.actions.pushes() < 10_000);
-        debug_assert!(instance.lower_bound(solver) < 10_000);
-        instance.heuristic = match solver.strategy() {
-            Strategy::Fast => instance.lower_bound(solver) * 10_000 + instance.actions.moves(),
-            Strategy::Mixed => instance.lower_bound(solver) + instance.actions.moves(),
-            Strategy::OptimalMovePush => {
-                instance.actions.moves() * 100_000_000
-                    + instance.actions.pushes() * 10_000
-                    + instance.lower_bound(solver)
-            }
-            Strategy::OptimalPushMove => {
-                instance.actions.pushes() * 100_000_000
-                    + instance.actions.moves() * 10_000
-                    + instance.lower_bound(solver)
+        // Dead states are never added to the heap, so their priority is irrelevant.
+        let Some(lower_bound) = instance.lower_bound(analysis, context) else {
+            instance.priority = (usize::MAX, usize::MAX, usize::MAX);
+            return instance;
+        };
+        let moves = instance.path.moves();
+        let pushes = instance.path.pushes();
+        instance.priority = match context.strategy() {
+            Strategy::Fast => (lower_bound, moves, 0),
+            Strategy::Mixed => (lower_bound + moves, 0, 0),
+            Strategy::OptimalMovePush => (moves, pushes, lower_bound),
+            Strategy::OptimalPushMove => (pushes, moves, lower_bound),
+            Strategy::OptimalPushes => (pushes + lower_bound, lower_bound, 0),
+            Strategy::Weighted(weight) => {
+                // Rounding down keeps the weighted lower bound within the
+                // weight of the optimal pushes.
+                let weighted_lower_bound = (weight.max(1.0) as f64 * lower_bound as f64) as usize;
+                (pushes + weighted_lower_bound, lower_bound, moves)
+            }
+            Strategy::Greedy => {
+                let boxes_off_goals = instance.box_positions.len() - instance.boxes_on_goals;
+                (lower_bound, boxes_off_goals, moves)
             }
         };
         instance.box_positions.shrink_to_fit();
-        instance.actions.shrink_to_fit();
         instance
     }
 
+    /// Returns the actions of the current state, reconstructing the player
+    /// moves between pushes.
+    pub fn actions_with_moves(&self, analysis: &LevelAnalysis, context: &StateContext) -> Actions {
+        self.path
+            .actions_with(analysis.map(), &mut context.path_finder_mut())
+    }
+
     /// Returns a vector of successor states for the current state.
-    pub fn successors(&self, solver: &Solver) -> Vec<State> {
-        let mut successors = Vec::new();
-        let player_reachable_area = self.player_reachable_area(solver);
-        for box_position in &self.box_positions {
-            for push_direction in [
+    pub fn successors(&self, analysis: &LevelAnalysis, context: &StateContext) -> Vec<State> {
+        self.successors_iter(analysis, context).collect()
+    }
+
+    /// Returns the successor states reached by a single push, without the
+    /// goal macros, relevance cuts and PI-corrals, which may leave out the
+    /// pushes of every solution.
+    pub fn unpruned_successors(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Vec<State> {
+        Pushes::with_pruning(self, analysis, context, false).collect()
+    }
+
+    /// Returns the successor states of the current state, which are only
+    /// generated once the iterator reaches them, box by box and direction by
+    /// direction.
+    ///
+    /// Successors ordered by inertia or generated in parallel are all
+    /// generated by the first call to `next`.
+    pub fn successors_iter<'a>(
+        &'a self,
+        analysis: &'a LevelAnalysis,
+        context: &'a StateContext,
+    ) -> impl Iterator<Item = State> + 'a {
+        let goal_macro = context
+            .options()
+            .goal_macros
+            .then(|| self.goal_macro(analysis, context))
+            .flatten();
+        // Filling the goal room in its packing order is assumed to be right,
+        // so the other successors are skipped in Fast mode.
+        let pushes = if goal_macro.is_some() && context.strategy() == Strategy::Fast {
+            Either::Right(Vec::new().into_iter())
+        } else if context.options().parallel_successors
+            || context.options().successor_ordering == SuccessorOrdering::Inertia
+        {
+            let pushes = Pushes::new(self, analysis, context);
+            let mut successors = if context.options().parallel_successors {
+                pushes.collect_parallel()
+            } else {
+                pushes.collect_vec()
+            };
+            // The goal macro stays ahead of the other successors.
+            if context.options().successor_ordering == SuccessorOrdering::Inertia {
+                self.order_successors(&mut successors, analysis, context);
+            }
+            Either::Right(successors.into_iter())
+        } else {
+            Either::Left(Pushes::new(self, analysis, context))
+        };
+        goal_macro
+            .into_iter()
+            .inspect(|_| context.record_generated_state())
+            .chain(pushes)
+    }
+
+    /// Returns the recent pushes after pushing a box to the position.
+    fn recent_pushes_with(
+        recent_pushes: &[Vector2<i32>],
+        box_position: Vector2<i32>,
+        relevance_cuts: RelevanceCuts,
+    ) -> Vec<Vector2<i32>> {
+        let skipped = (recent_pushes.len() + 1).saturating_sub(relevance_cuts.window);
+        recent_pushes
+            .iter()
+            .copied()
+            .chain([box_position])
+            .skip(skipped)
+            .collect()
+    }
+
+    /// Orders the successors by keeping the box pushed last moving, then
+    /// decreasing the lower bound.
+    ///
+    /// The sequence numbers are handed out again in the new order, so that
+    /// successors of equal priority are also expanded in this order.
+    fn order_successors(
+        &self,
+        successors: &mut [State],
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) {
+        let last_pushed_box = self
+            .path
+            .last_push()
+            .map(|(_, direction)| self.player_position + &direction.into());
+        let lower_bound = self.lower_bound(analysis, context);
+        let sequences = successors
+            .iter()
+            .map(|successor| successor.sequence)
+            .collect_vec();
+        successors.sort_by_cached_key(|successor| {
+            if last_pushed_box.is_some_and(|position| !successor.box_positions.contains(&position))
+            {
+                0
+            } else if successor.lower_bound(analysis, context) < lower_bound {
+                1
+            } else {
+                2
+            }
+        });
+        for (successor, sequence) in successors.iter_mut().zip(sequences) {
+            successor.sequence = sequence;
+        }
+    }
+
+    /// Returns the successor in which a box has been pushed onto the next
+    /// goal of the packing order, if a box outside the goal room can reach it.
+    fn goal_macro(&self, analysis: &LevelAnalysis, context: &StateContext) -> Option<State> {
+        let packing_order = analysis.packing_order()?;
+        let goal = packing_order.next_goal(&self.box_positions)?;
+        let goal_distances = analysis
+            .goal_distances()
+            .iter()
+            .find(|grid| grid.goal_position() == goal)?;
+        // Boxes closer to the goal are tried first.
+        let box_positions = self
+            .box_positions
+            .iter()
+            .filter(|position| !packing_order.room().contains(position))
+            .filter_map(|position| Some((goal_distances.get(position)?, position)))
+            .sorted_by_key(|(distance, position)| (*distance, position.y, position.x))
+            .map(|(_, position)| position);
+        for box_position in box_positions {
+            let mut new_box_positions = self.box_positions.clone();
+            new_box_positions.remove(&box_position);
+            let Some(path) = push_box_to(
+                analysis.map(),
+                &new_box_positions,
+                self.player_position,
+                box_position,
+                goal,
+            ) else {
+                continue;
+            };
+            new_box_positions.insert(goal);
+
+            let tracks_moves = context.strategy() != Strategy::OptimalPushes;
+            let mut new_path = self.path.clone();
+            let mut walk = 0;
+            let mut new_player_position = self.player_position;
+            for (action, player_position) in path {
+                if action.is_push() {
+                    new_path = new_path.with_push(walk, player_position, action.direction());
+                    walk = 0;
+                } else if tracks_moves {
+                    walk += 1;
+                }
+                new_player_position = player_position + &action.direction().into();
+            }
+            let mut new_state = State::new(
+                new_player_position,
+                new_box_positions,
+                new_path,
+                analysis,
+                context,
+            );
+            if new_state.is_dead(analysis, context) {
+                continue;
+            }
+            if let Some(relevance_cuts) = context.active_relevance_cuts() {
+                new_state.recent_pushes =
+                    Self::recent_pushes_with(&self.recent_pushes, goal, relevance_cuts);
+            }
+            return Some(new_state);
+        }
+        None
+    }
+
+    /// Checks if the current state represents a solved level.
+    pub fn is_solved(&self, analysis: &LevelAnalysis, context: &StateContext) -> bool {
+        self.lower_bound(analysis, context) == Some(0)
+    }
+
+    /// Checks if the current state can never be solved.
+    pub fn is_dead(&self, analysis: &LevelAnalysis, context: &StateContext) -> bool {
+        self.lower_bound(analysis, context).is_none()
+    }
+
+    /// Returns the priority of the current state.
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Returns the fields of the state read while its pushes are evaluated,
+    /// which can be shared between threads.
+    fn as_parent(&self, analysis: &LevelAnalysis, context: &StateContext) -> Parent<'_> {
+        Parent {
+            box_positions: &self.box_positions,
+            path: &self.path,
+            box_hash: self.box_hash,
+            lower_bound: self.lower_bound(analysis, context),
+            frozen_boxes: &self.frozen_boxes,
+            goal_matching: &self.goal_matching,
+            recent_pushes: &self.recent_pushes,
+        }
+    }
+
+    /// Returns the order in which the state was created by the solver.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the number of boxes on goals.
+    pub fn boxes_on_goals(&self) -> usize {
+        self.boxes_on_goals
+    }
+
+    /// Returns a normalized hash of the current state, which is the same for
+    /// the images of the state under the symmetries of the level.
+    pub fn normalized_hash(&self, analysis: &LevelAnalysis, context: &StateContext) -> u64 {
+        *self.normalized_hash.get_or_init(|| {
+            self.calculate_normalized_hash(&self.player_reachable_area(analysis, context), analysis)
+        })
+    }
+
+    /// Calculates the normalized hash from the reachable area of the player.
+    fn calculate_normalized_hash(
+        &self,
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        analysis: &LevelAnalysis,
+    ) -> u64 {
+        let symmetries = analysis.symmetries();
+        if symmetries.len() == 1 {
+            let player_position = normalized_area(player_reachable_area).unwrap();
+            return self.box_hash ^ analysis.zobrist_keys().player_key(player_position);
+        }
+        self.symmetric_hash(symmetries, analysis, |symmetry| {
+            Self::normalized_image(player_reachable_area, symmetry, analysis)
+        })
+    }
+
+    /// Calculates the normalized hash from the reachable area, unless it has
+    /// been calculated, so that it does not need another flood fill.
+    fn share_reachable_area(
+        &self,
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        analysis: &LevelAnalysis,
+    ) {
+        self.normalized_hash
+            .get_or_init(|| self.calculate_normalized_hash(player_reachable_area, analysis));
+    }
+
+    /// Returns a hash of the current state, telling apart player positions
+    /// within the same area, which is the same for the images of the state
+    /// under the symmetries of the level.
+    pub fn exact_hash(&self, analysis: &LevelAnalysis) -> u64 {
+        let symmetries = analysis.symmetries();
+        if symmetries.len() == 1 {
+            return self.box_hash ^ analysis.zobrist_keys().player_key(self.player_position);
+        }
+        self.symmetric_hash(symmetries, analysis, |symmetry| {
+            symmetry.apply(self.player_position, analysis.map().dimensions())
+        })
+    }
+
+    /// Returns the key of the normalized state, which is the same for the
+    /// images of the state under the symmetries of the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level has more squares than cell indices.
+    pub fn normalized_key(&self, analysis: &LevelAnalysis, context: &StateContext) -> StateKey {
+        let dimensions = analysis.map().dimensions();
+        let cell = |position| CellIndex::new(dimensions, position).expect("level too large");
+        let player_reachable_area = self.player_reachable_area(analysis, context);
+        analysis
+            .symmetries()
+            .iter()
+            .map(|symmetry| {
+                let box_cells = self
+                    .box_positions
+                    .iter()
+                    .map(|position| cell(symmetry.apply(position, dimensions)))
+                    .sorted()
+                    .collect_vec();
+                let player_position =
+                    Self::normalized_image(&player_reachable_area, symmetry, analysis);
+                (box_cells, cell(player_position))
+            })
+            .min()
+            .unwrap()
+    }
+
+    /// Returns the smallest hash of the images of the state under the
+    /// symmetries, where `player_position` returns the player position of
+    /// each image.
+    fn symmetric_hash(
+        &self,
+        symmetries: &[Symmetry],
+        analysis: &LevelAnalysis,
+        player_position: impl Fn(&Symmetry) -> Vector2<i32>,
+    ) -> u64 {
+        let zobrist_keys = analysis.zobrist_keys();
+        symmetries
+            .iter()
+            .map(|symmetry| {
+                zobrist_keys.boxes_hash(
+                    self.box_positions
+                        .iter()
+                        .map(|position| symmetry.apply(position, analysis.map().dimensions())),
+                ) ^ zobrist_keys.player_key(player_position(symmetry))
+            })
+            .min()
+            .unwrap()
+    }
+
+    /// Returns the normalized player position of the image of the reachable
+    /// area under the symmetry.
+    fn normalized_image(
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        symmetry: &Symmetry,
+        analysis: &LevelAnalysis,
+    ) -> Vector2<i32> {
+        player_reachable_area
+            .iter()
+            .map(|position| symmetry.apply(*position, analysis.map().dimensions()))
+            .min_by_key(|position| (position.y, position.x))
+            .unwrap()
+    }
+
+    /// Returns an estimate of the bytes allocated by the state, excluding its
+    /// own size.
+    pub fn allocated_bytes(&self) -> usize {
+        self.box_positions.allocated_bytes()
+            + self.path.allocated_bytes()
+            + (self.frozen_boxes.capacity() + self.recent_pushes.capacity())
+                * size_of::<Vector2<i32>>()
+            + self.goal_matching.capacity() * size_of::<(Vector2<i32>, Vector2<i32>)>()
+    }
+
+    /// Returns the lower bound value for the current state, or `None` if the
+    /// state can never be solved.
+    pub fn lower_bound(&self, analysis: &LevelAnalysis, context: &StateContext) -> Option<usize> {
+        *self.lower_bound.get_or_init(|| {
+            // The lower bound does not depend on the player position.
+            let key = self.box_positions_hash();
+            if let Some(lower_bound) = context.lower_bound_cache_mut().get(key) {
+                return lower_bound;
+            }
+            let lower_bound = self.calculate_lower_bound(analysis, context);
+            context.lower_bound_cache_mut().insert(key, lower_bound);
+            lower_bound
+        })
+    }
+
+    /// Returns a hash of the box positions and the boxes frozen on goals,
+    /// independent of the order of the box positions.
+    fn box_positions_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.box_hash.hash(&mut hasher);
+        self.frozen_boxes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Calculates and returns the lower bound value for the current state.
+    ///
+    /// The lower bound is the maximum of the enabled heuristics, since each of
+    /// them is admissible on its own.
+    fn calculate_lower_bound(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<usize> {
+        let mut lower_bound = 0;
+        for heuristic in context.heuristics() {
+            lower_bound = lower_bound.max(match heuristic {
+                HeuristicKind::ClosestGoal => self.closest_goal_lower_bound(analysis, context),
+                HeuristicKind::Matching => self.matching_lower_bound(analysis, context),
+                HeuristicKind::PatternDatabase { .. } => {
+                    self.pattern_database_lower_bound(analysis, context)
+                }
+            }?);
+        }
+        if self.frozen_boxes.is_empty() {
+            return Some(lower_bound);
+        }
+        Some(lower_bound.max(self.frozen_lower_bound(analysis, context)?))
+    }
+
+    /// Returns the lower bound of the boxes not frozen on goals, where the
+    /// frozen boxes are treated as walls.
+    fn frozen_lower_bound(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<usize> {
+        let goal_distances = context.frozen_goal_distances(analysis, &self.frozen_boxes);
+        let costs: Vec<Vec<_>> = self
+            .box_positions
+            .iter()
+            .filter(|box_position| !self.frozen_boxes.contains(box_position))
+            .map(|box_position| {
+                goal_distances
+                    .iter()
+                    .map(|grid| grid.get(box_position))
+                    .collect()
+            })
+            .collect();
+        minimum_cost_matching(&costs)
+    }
+
+    /// Returns the sum of the lower bounds of each box to its nearest goal.
+    fn closest_goal_lower_bound(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<usize> {
+        let mut sum: usize = 0;
+        let squares = analysis.squares();
+        for box_position in self.box_positions.iter() {
+            sum += squares.get(box_position)?.lower_bound?;
+        }
+        if context.options().make_way_penalties {
+            for blocking_box_position in self.blocking_boxes(analysis) {
+                // The box either returns to its goal after making way, or is
+                // pushed to another goal.
+                sum += analysis
+                    .goal_distances()
+                    .iter()
+                    .filter(|grid| grid.goal_position() != blocking_box_position)
+                    .filter_map(|grid| grid.get(blocking_box_position))
+                    .min()
+                    .unwrap_or(2)
+                    .min(2);
+            }
+        }
+        Some(sum)
+    }
+
+    /// Returns the minimum cost of assigning each box to a distinct goal.
+    fn matching_lower_bound(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<usize> {
+        let blocking_boxes = if context.options().make_way_penalties {
+            self.blocking_boxes(analysis)
+        } else {
+            hasher::HashSet::default()
+        };
+        let goal_distances = analysis.goal_distances();
+        let costs: Vec<Vec<_>> = self
+            .box_positions
+            .iter()
+            .map(|box_position| {
+                goal_distances
+                    .iter()
+                    .map(|grid| {
+                        // A blocking box has to make way and then return to its goal.
+                        if grid.goal_position() == box_position
+                            && blocking_boxes.contains(&box_position)
+                        {
+                            return Some(2);
+                        }
+                        grid.get(box_position)
+                    })
+                    .collect()
+            })
+            .collect();
+        minimum_cost_matching(&costs)
+    }
+
+    /// Returns the sum of the pattern database values of groups of adjacent
+    /// boxes, or the matching lower bound if the database is not built yet.
+    fn pattern_database_lower_bound(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<usize> {
+        let Some(pattern_database) = context.pattern_database() else {
+            return self.matching_lower_bound(analysis, context);
+        };
+        let box_positions: Vec<_> = self.box_positions.iter().collect();
+        let mut sum = 0;
+        for group in box_positions.chunks(pattern_database.group_size()) {
+            if group.len() == pattern_database.group_size() {
+                sum += pattern_database.get(group)?;
+            } else {
+                for box_position in group {
+                    sum += analysis.squares().get(*box_position)?.lower_bound?;
+                }
+            }
+        }
+        Some(sum)
+    }
+
+    /// Returns the boxes on goals that must be pushed at least once before an
+    /// adjacent box can be moved.
+    ///
+    /// A box off goal that could be pushed if only walls were present, but
+    /// cannot be pushed in any direction while the adjacent box stays in place,
+    /// forces the adjacent box to move first.
+    fn blocking_boxes(&self, analysis: &LevelAnalysis) -> hasher::HashSet<Vector2<i32>> {
+        let mut blocking_boxes = hasher::HashSet::default();
+        for box_position in self.box_positions.iter() {
+            if analysis.map()[box_position].intersects(Tiles::Goal)
+                || !Self::is_box_movable(box_position, None, analysis)
+            {
+                continue;
+            }
+            for direction in [
                 Direction::Up,
                 Direction::Down,
                 Direction::Left,
                 Direction::Right,
             ] {
-                let mut new_box_position = box_position + &push_direction.into();
-                if self.can_block_box(new_box_position, solver) {
+                let neighbor_position = box_position + &direction.into();
+                if !self.box_positions.contains(&neighbor_position)
+                    || !analysis.map()[neighbor_position].intersects(Tiles::Goal)
+                {
                     continue;
                 }
+                if !Self::is_box_movable(box_position, Some(neighbor_position), analysis) {
+                    blocking_boxes.insert(neighbor_position);
+                }
+            }
+        }
+        blocking_boxes
+    }
 
-                let next_player_position = box_position - &push_direction.into();
-                if self.can_block_player(next_player_position, solver)
-                    || !player_reachable_area.contains(&next_player_position)
-                {
-                    continue;
+    /// Checks if a box can be pushed in any direction, considering only walls
+    /// and the given obstacle.
+    fn is_box_movable(
+        box_position: Vector2<i32>,
+        obstacle: Option<Vector2<i32>>,
+        analysis: &LevelAnalysis,
+    ) -> bool {
+        [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ]
+        .into_iter()
+        .any(|direction| {
+            [
+                box_position + &direction.into(),
+                box_position - &direction.into(),
+            ]
+            .into_iter()
+            .all(|position| {
+                !analysis.map()[position].intersects(Tiles::Wall) && Some(position) != obstacle
+            })
+        })
+    }
+
+    /// Returns the boundary boxes of the PI-corral with the fewest boxes, or
+    /// `None` if there is no PI-corral.
+    ///
+    /// A PI-corral is an area the player cannot enter, where every boundary
+    /// box can only be pushed into the area, and all such pushes are possible.
+    /// Since the area has to be entered eventually unless it is solved, only
+    /// the pushes of its boundary boxes need to be considered.
+    fn pi_corral_box_positions(
+        &self,
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        analysis: &LevelAnalysis,
+    ) -> Option<BoxPositions> {
+        deadlock::corrals(analysis.map(), &self.box_positions, player_reachable_area)
+            .into_iter()
+            .filter_map(|corral| {
+                let is_solved = corral
+                    .box_positions
+                    .iter()
+                    .all(|position| analysis.map()[position].intersects(Tiles::Goal))
+                    && !corral
+                        .floor_positions
+                        .iter()
+                        .any(|position| analysis.map()[*position].intersects(Tiles::Goal));
+                if is_solved {
+                    return None;
                 }
 
-                let mut new_actions = self.actions.clone();
-                let path = find_path(self.player_position, next_player_position, |position| {
-                    !self.can_block_player(position, solver)
-                })
-                .unwrap();
-                new_actions.extend(
-                    path.windows(2)
-                        .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
-                        .map(Action::Move),
+                let boundary_box_positions = BoxPositions::new(
+                    analysis.map().dimensions(),
+                    corral.box_positions.iter().filter(|box_position| {
+                        [
+                            Direction::Up,
+                            Direction::Down,
+                            Direction::Left,
+                            Direction::Right,
+                        ]
+                        .into_iter()
+                        .any(|direction| {
+                            player_reachable_area.contains(&(box_position + &direction.into()))
+                        })
+                    }),
                 );
-                new_actions.push(Action::Push(push_direction));
-
-                // skip tunnels
-                while solver
-                    .tunnels()
-                    .contains(&((new_box_position - &push_direction.into()), push_direction))
-                {
-                    if self.can_block_box(new_box_position + &push_direction.into(), solver) {
-                        break;
+                let mut has_push = false;
+                for box_position in boundary_box_positions.iter() {
+                    for push_direction in [
+                        Direction::Up,
+                        Direction::Down,
+                        Direction::Left,
+                        Direction::Right,
+                    ] {
+                        let new_box_position = box_position + &push_direction.into();
+                        if can_block_box(&self.box_positions, new_box_position, analysis) {
+                            continue;
+                        }
+                        let is_player_reachable = player_reachable_area
+                            .contains(&(box_position - &push_direction.into()));
+                        let is_inward = corral.floor_positions.contains(&new_box_position);
+                        if is_player_reachable != is_inward {
+                            // Either a push out of the corral is possible, or
+                            // a push into the corral is not.
+                            return None;
+                        }
+                        has_push |= is_inward;
                     }
-                    new_box_position += &push_direction.into();
-                    new_actions.push(Action::Push(push_direction));
                 }
+                has_push.then_some(boundary_box_positions)
+            })
+            .min_by_key(|box_positions| box_positions.len())
+    }
+
+    /// Returns pairs of the boxes not frozen on goals and distinct goals they
+    /// can still be pushed to, or `None` if there is no such matching.
+    ///
+    /// The matching of the parent state is extended, since only a single box
+    /// has been moved.
+    fn goal_matching(
+        &self,
+        parent: Parent<'_>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<Vec<(Vector2<i32>, Vector2<i32>)>> {
+        let frozen_goal_distances;
+        let goal_distances = if self.frozen_boxes.is_empty() {
+            analysis.goal_distances()
+        } else {
+            frozen_goal_distances = context.frozen_goal_distances(analysis, &self.frozen_boxes);
+            &frozen_goal_distances
+        };
+        let box_positions: Vec<_> = self
+            .box_positions
+            .iter()
+            .filter(|box_position| !self.frozen_boxes.contains(box_position))
+            .collect();
+        let adjacency: Vec<Vec<_>> = box_positions
+            .iter()
+            .map(|box_position| {
+                (0..goal_distances.len())
+                    .filter(|goal| goal_distances[*goal].get(*box_position).is_some())
+                    .collect()
+            })
+            .collect();
 
-                let mut new_box_positions = self.box_positions.clone();
-                new_box_positions.remove(box_position);
-                new_box_positions.insert(new_box_position);
-
-                // skip deadlocks
-                if !solver.map[new_box_position].intersects(Tiles::Goal)
-                    && deadlock::is_freeze_deadlock(
-                        &solver.map,
-                        new_box_position,
-                        &new_box_positions,
-                        &mut HashSet::new(),
-                    )
+        let mut matching = vec![None; box_positions.len()];
+        let mut is_matched = vec![false; goal_distances.len()];
+        for (box_position, goal_position) in parent.goal_matching {
+            let Some(row) = box_positions
+                .iter()
+                .position(|position| position == box_position)
+            else {
+                continue;
+            };
+            if let Some(&goal) = adjacency[row].iter().find(|goal| {
+                !is_matched[**goal] && goal_distances[**goal].goal_position() == *goal_position
+            }) {
+                matching[row] = Some(goal);
+                is_matched[goal] = true;
+            }
+        }
+        if !maximum_matching(&adjacency, goal_distances.len(), &mut matching) {
+            return None;
+        }
+        Some(
+            box_positions
+                .into_iter()
+                .zip(matching)
+                .map(|(box_position, goal)| {
+                    (box_position, goal_distances[goal.unwrap()].goal_position())
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the boxes of an area the player cannot enter that can no
+    /// longer be solved, if any.
+    ///
+    /// The boxes of a dead corral are learned by the caller, so that other
+    /// states containing them are pruned without another analysis.
+    fn dead_corral(
+        &self,
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        analysis: &LevelAnalysis,
+    ) -> Option<BoxPositions> {
+        /// The maximum number of states visited for each corral.
+        const MAX_CORRAL_STATES: usize = 1_000;
+
+        deadlock::corrals(analysis.map(), &self.box_positions, player_reachable_area)
+            .into_iter()
+            .find(|corral| {
+                deadlock::is_corral_deadlock(
+                    analysis.map(),
+                    analysis.dead_squares(),
+                    self.player_position,
+                    corral,
+                    MAX_CORRAL_STATES,
+                )
+            })
+            .map(|corral| corral.box_positions)
+    }
+
+    /// Checks if the box passes a closet entrance while being pushed from
+    /// `from` to `to`.
+    fn enters_closet(
+        from: Vector2<i32>,
+        to: Vector2<i32>,
+        push_direction: Direction,
+        analysis: &LevelAnalysis,
+    ) -> bool {
+        let mut position = from;
+        while position != to {
+            if analysis
+                .closet_entrances()
+                .contains(&(position, push_direction))
+            {
+                return true;
+            }
+            position += &push_direction.into();
+        }
+        false
+    }
+
+    /// Returns the number of moves the player needs to reach each square of
+    /// the reachable area in the current state.
+    fn player_distances(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> hasher::HashMap<Vector2<i32>, usize> {
+        context.record_flood_fill();
+        let mut distances = hasher::HashMap::from_iter([(self.player_position, 0)]);
+        let mut queue = VecDeque::from([self.player_position]);
+        while let Some(position) = queue.pop_front() {
+            let distance = distances[&position];
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let neighbor = position + &direction.into();
+                if can_block_player(&self.box_positions, neighbor, analysis)
+                    || distances.contains_key(&neighbor)
                 {
                     continue;
                 }
+                distances.insert(neighbor, distance + 1);
+                queue.push_back(neighbor);
+            }
+        }
+        distances
+    }
 
-                let new_player_position = new_box_position - &push_direction.into();
+    /// Returns the reachable area for the player in the current state.
+    pub fn player_reachable_area(
+        &self,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> HashSet<Vector2<i32>> {
+        context.record_flood_fill();
+        reachable_area(self.player_position, |position| {
+            !can_block_player(&self.box_positions, position, analysis)
+        })
+    }
 
-                let new_state =
-                    State::new(new_player_position, new_box_positions, new_actions, solver);
-                successors.push(new_state);
+    /// Returns the reachable area for the player after a push, updated from
+    /// the reachable area of the parent state, in which the box was on `from`.
+    ///
+    /// The square the box left joins the area together with the squares
+    /// behind it. The square the box entered leaves the area, and the area is
+    /// filled again if this may split it.
+    pub fn reachable_area_after_push(
+        &self,
+        parent_area: &HashSet<Vector2<i32>>,
+        from: Vector2<i32>,
+        to: Vector2<i32>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> HashSet<Vector2<i32>> {
+        if parent_area.contains(&to) && !Self::is_bypassable(to, parent_area, from) {
+            return self.player_reachable_area(analysis, context);
+        }
+        let mut area = parent_area.clone();
+        area.remove(&to);
+        let mut queue = VecDeque::new();
+        if area.insert(from) {
+            queue.push_back(from);
+        }
+        while let Some(position) = queue.pop_front() {
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let neighbor = position + &direction.into();
+                if !can_block_player(&self.box_positions, neighbor, analysis)
+                    && area.insert(neighbor)
+                {
+                    queue.push_back(neighbor);
+                }
             }
         }
-        successors
+        area
     }
 
-    /// Checks if the current state represents a solved level.
-    pub fn is_solved(&self, solver: &Solver) -> bool {
-        self.lower_bound(solver) == 0
-    }
+    /// Checks if the neighbors of the square within the area stay connected
+    /// once it is blocked, where `freed` is a square joining the area.
+    ///
+    /// The squares around it connect the neighbors in most cases. Otherwise a
+    /// path between them is searched within a bounded number of squares,
+    /// beyond which the square is assumed to split the area.
+    fn is_bypassable(
+        position: Vector2<i32>,
+        area: &HashSet<Vector2<i32>>,
+        freed: Vector2<i32>,
+    ) -> bool {
+        /// The maximum number of squares searched for a path between the
+        /// neighbors.
+        const MAX_SQUARES: usize = 64;
+        // The squares around the position in clockwise order, starting with
+        // the one above it. Neighbors are at even indices.
+        const RING: [(i32, i32); 8] = [
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+        ];
 
-    /// Returns the heuristic value of the current state.
-    pub fn heuristic(&self) -> usize {
-        self.heuristic
+        let is_open = |square: Vector2<i32>| {
+            square != position && (square == freed || area.contains(&square))
+        };
+        let ring = RING.map(|(x, y)| position + Vector2::new(x, y));
+        // Two consecutive neighbors are connected through the corner between
+        // them. The neighbors form a single group unless fewer links than
+        // neighbors minus one join them.
+        let neighbors = (0..8).step_by(2).filter(|&i| is_open(ring[i])).count();
+        let links = (0..8)
+            .step_by(2)
+            .filter(|&i| is_open(ring[i]) && is_open(ring[i + 1]) && is_open(ring[(i + 2) % 8]))
+            .count();
+        if links + 1 >= neighbors {
+            return true;
+        }
+
+        let mut unreached = ring
+            .into_iter()
+            .step_by(2)
+            .filter(|square| is_open(*square))
+            .collect_vec();
+        let start = unreached.pop().unwrap();
+        let mut visited = hasher::HashSet::from_iter([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(square) = queue.pop_front() {
+            if visited.len() > MAX_SQUARES {
+                return false;
+            }
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let neighbor = square + &direction.into();
+                if is_open(neighbor) && visited.insert(neighbor) {
+                    unreached.retain(|square| *square != neighbor);
+                    if unreached.is_empty() {
+                        return true;
+                    }
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        false
     }
+}
 
-    /// Returns a normalized clone of the current state.
-    pub fn normalized(&self, solver: &Solver) -> Self {
-        let mut instance = self.clone();
-        instance.player_position = self.normalized_player_position(solver);
-        instance
+/// The directions in which each box is pushed, in the order its successors
+/// are generated.
+const PUSH_DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// Checks if a position can block the player's movement.
+fn can_block_player(
+    box_positions: &BoxPositions,
+    position: Vector2<i32>,
+    analysis: &LevelAnalysis,
+) -> bool {
+    let is_wall = match analysis.square_masks() {
+        Some(masks) => masks.walls.contains(position),
+        None => analysis.map()[position].intersects(Tiles::Wall),
+    };
+    is_wall || box_positions.contains(&position)
+}
+
+/// Checks if a position can block a box's movement.
+///
+/// A box pushed onto a dead square can never reach a goal.
+fn can_block_box(
+    box_positions: &BoxPositions,
+    position: Vector2<i32>,
+    analysis: &LevelAnalysis,
+) -> bool {
+    let is_blocked = match analysis.square_masks() {
+        Some(masks) => masks.walls.contains(position) || masks.dead_squares.contains(position),
+        // Positions out of bounds are never reached by a box.
+        None => {
+            analysis
+                .squares()
+                .get(position)
+                .is_none_or(|square| square.is_dead)
+                || analysis.map()[position].intersects(Tiles::Wall)
+        }
+    };
+    is_blocked || box_positions.contains(&position)
+}
+
+/// The fields of a state read while its pushes are evaluated, which unlike
+/// the state can be shared between the threads evaluating them.
+#[derive(Clone, Copy)]
+struct Parent<'a> {
+    box_positions: &'a BoxPositions,
+    path: &'a Path,
+    box_hash: u64,
+    lower_bound: Option<usize>,
+    frozen_boxes: &'a [Vector2<i32>],
+    goal_matching: &'a [(Vector2<i32>, Vector2<i32>)],
+    recent_pushes: &'a [Vector2<i32>],
+}
+
+impl Parent<'_> {
+    /// Checks if the box is close enough to a recently pushed box to be
+    /// pushed. Every box is relevant before the first push.
+    fn is_relevant_push(
+        &self,
+        box_position: Vector2<i32>,
+        relevance_cuts: RelevanceCuts,
+        analysis: &LevelAnalysis,
+    ) -> bool {
+        self.recent_pushes.is_empty()
+            || self.recent_pushes.iter().any(|position| {
+                analysis
+                    .influence()
+                    .distance(box_position, *position)
+                    .is_some_and(|distance| distance <= relevance_cuts.threshold)
+            })
     }
 
-    /// Returns a normalized hash of the current state.
-    pub fn normalized_hash(&self, solver: &Solver) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.normalized(solver).hash(&mut hasher);
-        hasher.finish()
+    /// Returns the lower bound of a successor in which a single box has been
+    /// moved, or `None` if it must be recalculated over all boxes.
+    fn incremental_lower_bound(
+        &self,
+        moved_from: Vector2<i32>,
+        moved_to: Vector2<i32>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+    ) -> Option<usize> {
+        // Only the sum of independent per-box lower bounds can be updated in place.
+        if context.heuristics() != [HeuristicKind::ClosestGoal]
+            || context.options().make_way_penalties
+        {
+            return None;
+        }
+        let lower_bound = self.lower_bound?;
+        let squares = analysis.squares();
+        Some(
+            lower_bound - squares.get(moved_from)?.lower_bound?
+                + squares.get(moved_to)?.lower_bound?,
+        )
     }
+}
+
+/// The successors of a state reached by pushing a single box, generated on
+/// demand.
+struct Pushes<'a> {
+    parent: Parent<'a>,
+    analysis: &'a LevelAnalysis,
+    context: &'a StateContext,
+    push_context: PushContext,
+    box_positions: SmallVec<[Vector2<i32>; 16]>,
+    /// The index of the next push, four per box.
+    next_push: usize,
+}
+
+/// What the pushes of a state share, computed once for all of them.
+struct PushContext {
+    /// The boxes of the state, whose cells are shared by its successors.
+    parent_box_positions: BoxPositions,
+    relevance_cuts: Option<RelevanceCuts>,
+    /// The lengths of the walks of the player, if moves are counted.
+    player_distances: Option<hasher::HashMap<Vector2<i32>, usize>>,
+    player_reachable_area: HashSet<Vector2<i32>>,
+}
+
+/// A push which passed the checks made before the learned deadlocks.
+struct CheckedPush {
+    box_position: Vector2<i32>,
+    new_box_position: Vector2<i32>,
+    new_player_position: Vector2<i32>,
+    new_box_positions: BoxPositions,
+    new_path: Path,
+}
 
-    /// Returns the lower bound value for the current state.
-    fn lower_bound(&self, solver: &Solver) -> usize {
-        *self
-            .lower_bound
-            .get_or_init(|| self.calculate_lower_bound(solver))
+/// A push evaluated with a context on another thread, whose effects on the
+/// context of the state are applied afterwards in the order of the pushes.
+struct ParallelPush {
+    /// The counts recorded by the checks made before the learned deadlocks.
+    counts: Counts,
+    /// The outcome of the remaining checks, if the push passed those.
+    successor: Option<ParallelSuccessor>,
+}
+
+struct ParallelSuccessor {
+    new_box_position: Vector2<i32>,
+    new_box_positions: BoxPositions,
+    new_player_position: Vector2<i32>,
+    /// The successor, or the boxes of the dead corral found in it if it is
+    /// pruned.
+    result: Result<State, Option<BoxPositions>>,
+    /// The counts recorded by the remaining checks.
+    counts: Counts,
+}
+
+impl<'a> Pushes<'a> {
+    fn new(state: &'a State, analysis: &'a LevelAnalysis, context: &'a StateContext) -> Self {
+        Self::with_pruning(state, analysis, context, true)
     }
 
-    /// Calculates and returns the lower bound value for the current state.
-    fn calculate_lower_bound(&self, solver: &Solver) -> usize {
-        let mut sum: usize = 0;
-        for box_position in &self.box_positions {
-            match solver.lower_bounds().get(box_position) {
-                Some(lower_bound) => sum += lower_bound,
-                None => return 10_000 - 1,
+    /// Creates the pushes of the state, restricted by the relevance cuts and
+    /// the PI-corrals if `pruned`.
+    fn with_pruning(
+        state: &'a State,
+        analysis: &'a LevelAnalysis,
+        context: &'a StateContext,
+        pruned: bool,
+    ) -> Self {
+        let relevance_cuts = context.active_relevance_cuts().filter(|_| pruned);
+        // Only the lengths of the walks to the pushes are needed, the moves are
+        // found again for the solution.
+        let player_distances = (context.strategy() != Strategy::OptimalPushes)
+            .then(|| state.player_distances(analysis, context));
+        let player_reachable_area = match &player_distances {
+            Some(player_distances) => player_distances.keys().copied().collect(),
+            None => state.player_reachable_area(analysis, context),
+        };
+        state.share_reachable_area(&player_reachable_area, analysis);
+        let mut box_positions = None;
+        if pruned && context.options().pi_corral_pruning {
+            if let Some(positions) = state.pi_corral_box_positions(&player_reachable_area, analysis)
+            {
+                context.record_pi_corral();
+                box_positions = Some(positions.iter().collect());
             }
         }
-        sum
+        Self {
+            parent: state.as_parent(analysis, context),
+            analysis,
+            context,
+            push_context: PushContext {
+                parent_box_positions: state.box_positions.shared(),
+                relevance_cuts,
+                player_distances,
+                player_reachable_area,
+            },
+            box_positions: box_positions.unwrap_or_else(|| state.box_positions.iter().collect()),
+            next_push: 0,
+        }
     }
 
-    /// Checks if a position can block the player's movement.
-    fn can_block_player(&self, position: Vector2<i32>, solver: &Solver) -> bool {
-        solver.map[position].intersects(Tiles::Wall) || self.box_positions.contains(&position)
+    /// Evaluates the remaining pushes on the threads of rayon, each with a
+    /// context of its own sharing the analysis of the level, and returns the
+    /// successors in the order the iterator would generate them.
+    ///
+    /// The learned deadlocks are checked and learned afterwards in that
+    /// order, so the successors, their sequence numbers and the counts of the
+    /// context of the state are the same as with the iterator.
+    fn collect_parallel(self) -> Vec<State> {
+        let (parent, analysis, context, push_context) =
+            (self.parent, self.analysis, self.context, &self.push_context);
+        let pushes = self
+            .box_positions
+            .iter()
+            .flat_map(|box_position| PUSH_DIRECTIONS.map(|direction| (*box_position, direction)))
+            .skip(self.next_push)
+            .collect_vec();
+        let mut workers = context.successor_workers_mut(analysis);
+        let chunk_len = pushes.len().div_ceil(workers.len()).max(1);
+        let evaluated: Vec<Vec<ParallelPush>> = workers
+            .par_iter_mut()
+            .zip(pushes.par_chunks(chunk_len))
+            .map(|(worker, pushes)| {
+                pushes
+                    .iter()
+                    .map(|&(box_position, push_direction)| {
+                        push_context.evaluate(
+                            parent,
+                            analysis,
+                            worker,
+                            box_position,
+                            push_direction,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        drop(workers);
+
+        let mut successors = Vec::new();
+        for push in evaluated.into_iter().flatten() {
+            context.add_counts(push.counts);
+            let Some(successor) = push.successor else {
+                continue;
+            };
+            if context.learned_deadlocks_mut().contains(
+                analysis.map(),
+                successor.new_box_position,
+                &successor.new_box_positions,
+                successor.new_player_position,
+            ) {
+                continue;
+            }
+            context.add_counts(successor.counts);
+            let sequence = context.next_sequence();
+            match successor.result {
+                Ok(mut new_state) => {
+                    new_state.sequence = sequence;
+                    context.record_generated_state();
+                    successors.push(new_state);
+                }
+                Err(Some(dead_corral)) => context.learned_deadlocks_mut().insert(
+                    analysis.map(),
+                    &dead_corral,
+                    successor.new_player_position,
+                ),
+                Err(None) => {}
+            }
+        }
+        successors
     }
+}
 
-    /// Checks if a position can block a box's movement.
-    fn can_block_box(&self, position: Vector2<i32>, solver: &Solver) -> bool {
-        solver.map[position].intersects(Tiles::Wall /* | Tiles::Deadlock */)
-            || !solver.lower_bounds().contains_key(&position)
-            || self.box_positions.contains(&position)
+impl PushContext {
+    /// Returns the successor in which the box is pushed in the direction, or
+    /// `None` if the push is pruned.
+    fn push(
+        &self,
+        parent: Parent<'_>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+        box_position: Vector2<i32>,
+        push_direction: Direction,
+    ) -> Option<State> {
+        let push = self.checked_push(parent, analysis, context, box_position, push_direction)?;
+        if context.learned_deadlocks_mut().contains(
+            analysis.map(),
+            push.new_box_position,
+            &push.new_box_positions,
+            push.new_player_position,
+        ) {
+            return None;
+        }
+        let new_player_position = push.new_player_position;
+        match self.successor(parent, analysis, context, push) {
+            Ok(new_state) => Some(new_state),
+            Err(dead_corral) => {
+                if let Some(dead_corral) = dead_corral {
+                    context.learned_deadlocks_mut().insert(
+                        analysis.map(),
+                        &dead_corral,
+                        new_player_position,
+                    );
+                }
+                None
+            }
+        }
     }
 
-    /// Returns the normalized player position based on reachable area.
-    fn normalized_player_position(&self, solver: &Solver) -> Vector2<i32> {
-        normalized_area(&self.player_reachable_area(solver)).unwrap()
+    /// Evaluates the push with a context on another thread, taking the counts
+    /// it records.
+    fn evaluate(
+        &self,
+        parent: Parent<'_>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+        box_position: Vector2<i32>,
+        push_direction: Direction,
+    ) -> ParallelPush {
+        let push = self.checked_push(parent, analysis, context, box_position, push_direction);
+        let counts = context.take_counts();
+        let successor = push.map(|push| {
+            let new_box_position = push.new_box_position;
+            let new_box_positions = push.new_box_positions.clone();
+            let new_player_position = push.new_player_position;
+            let result = self.successor(parent, analysis, context, push);
+            ParallelSuccessor {
+                new_box_position,
+                new_box_positions,
+                new_player_position,
+                result,
+                counts: context.take_counts(),
+            }
+        });
+        ParallelPush { counts, successor }
     }
 
-    /// Returns the reachable area for the player in the current state.
-    fn player_reachable_area(&self, solver: &Solver) -> HashSet<Vector2<i32>> {
-        reachable_area(self.player_position, |position| {
-            !self.can_block_player(position, solver)
+    /// Returns the push of the box in the direction, or `None` if it is
+    /// pruned by the checks made before the learned deadlocks.
+    fn checked_push(
+        &self,
+        parent: Parent<'_>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+        box_position: Vector2<i32>,
+        push_direction: Direction,
+    ) -> Option<CheckedPush> {
+        let relevance_cuts = self.relevance_cuts;
+        let player_distances = &self.player_distances;
+        let player_reachable_area = &self.player_reachable_area;
+        let mut new_box_position = box_position + &push_direction.into();
+        if can_block_box(parent.box_positions, new_box_position, analysis) {
+            context.record_blocked_push();
+            return None;
+        }
+
+        let next_player_position = box_position - &push_direction.into();
+        if can_block_player(parent.box_positions, next_player_position, analysis)
+            || !player_reachable_area.contains(&next_player_position)
+        {
+            context.record_unreachable_push();
+            return None;
+        }
+
+        if relevance_cuts.is_some_and(|relevance_cuts| {
+            !parent.is_relevant_push(box_position, relevance_cuts, analysis)
+        }) {
+            context.record_cut_push();
+            return None;
+        }
+
+        let walk = player_distances.as_ref().map_or(0, |player_distances| {
+            player_distances[&next_player_position]
+        });
+        let mut new_path = parent
+            .path
+            .with_push(walk, next_player_position, push_direction);
+
+        // skip tunnels
+        while analysis
+            .squares()
+            .get(new_box_position - &push_direction.into())
+            .is_some_and(|square| square.is_tunnel(push_direction))
+        {
+            if can_block_box(
+                parent.box_positions,
+                new_box_position + &push_direction.into(),
+                analysis,
+            ) {
+                break;
+            }
+            new_path =
+                new_path.with_push(0, new_box_position - &push_direction.into(), push_direction);
+            new_box_position += &push_direction.into();
+            context.record_tunnel_push();
+        }
+
+        // skip pushes into closets
+        if State::enters_closet(box_position, new_box_position, push_direction, analysis) {
+            context.record_closet_push();
+            return None;
+        }
+
+        let mut new_box_positions = self.parent_box_positions.clone();
+        new_box_positions.remove(&box_position);
+        new_box_positions.insert(new_box_position);
+        let new_player_position = new_box_position - &push_direction.into();
+
+        // skip deadlocks
+        if analysis
+            .dead_end_corridors()
+            .iter()
+            .filter(|corridor| corridor.squares.contains(&new_box_position))
+            .any(|corridor| corridor.is_deadlock(&new_box_positions, new_player_position))
+        {
+            context.record_corridor_deadlock();
+            return None;
+        }
+        if deadlock::patterns::is_deadlock(analysis.map(), new_box_position, &new_box_positions) {
+            context.record_pattern_deadlock();
+            return None;
+        }
+        if deadlock::is_freeze_deadlock(analysis.map(), new_box_position, &new_box_positions) {
+            context.record_freeze_deadlock();
+            return None;
+        }
+        Some(CheckedPush {
+            box_position,
+            new_box_position,
+            new_player_position,
+            new_box_positions,
+            new_path,
         })
     }
+
+    /// Returns the state reached by the push, or `Err` with the boxes of the
+    /// dead corral found in it, which are left to the caller to learn, if it
+    /// is pruned.
+    fn successor(
+        &self,
+        parent: Parent<'_>,
+        analysis: &LevelAnalysis,
+        context: &StateContext,
+        push: CheckedPush,
+    ) -> Result<State, Option<BoxPositions>> {
+        let CheckedPush {
+            box_position,
+            new_box_position,
+            new_player_position,
+            new_box_positions,
+            new_path,
+        } = push;
+        let mut new_state = State::successor_of(
+            parent,
+            box_position,
+            new_box_position,
+            new_player_position,
+            new_box_positions,
+            new_path,
+            analysis,
+            context,
+        );
+        if new_state.is_dead(analysis, context) {
+            return Err(None);
+        }
+        if let Some(relevance_cuts) = self.relevance_cuts {
+            new_state.recent_pushes =
+                State::recent_pushes_with(parent.recent_pushes, new_box_position, relevance_cuts);
+        }
+        if context.options().bipartite_deadlocks {
+            match new_state.goal_matching(parent, analysis, context) {
+                Some(goal_matching) => new_state.goal_matching = goal_matching,
+                None => {
+                    context.record_bipartite_deadlock();
+                    return Err(None);
+                }
+            }
+        }
+        // The reachable area is only needed by the normalized hash and
+        // the corrals.
+        if context.options().corral_deadlocks || !context.minimizes_moves() {
+            let new_player_reachable_area = new_state.reachable_area_after_push(
+                &self.player_reachable_area,
+                box_position,
+                new_box_position,
+                analysis,
+                context,
+            );
+            new_state.share_reachable_area(&new_player_reachable_area, analysis);
+            if context.options().corral_deadlocks {
+                if let Some(dead_corral) =
+                    new_state.dead_corral(&new_player_reachable_area, analysis)
+                {
+                    context.record_corral_deadlock();
+                    return Err(Some(dead_corral));
+                }
+            }
+        }
+        Ok(new_state)
+    }
+}
+
+impl Iterator for Pushes<'_> {
+    type Item = State;
+
+    fn next(&mut self) -> Option<State> {
+        while let Some(box_position) = self.box_positions.get(self.next_push / 4).copied() {
+            let push_direction = PUSH_DIRECTIONS[self.next_push % 4];
+            self.next_push += 1;
+            if let Some(successor) = self.push_context.push(
+                self.parent,
+                self.analysis,
+                self.context,
+                box_position,
+                push_direction,
+            ) {
+                self.context.record_generated_state();
+                return Some(successor);
+            }
+        }
+        None
+    }
 }
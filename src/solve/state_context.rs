@@ -0,0 +1,379 @@
+use std::{
+    cell::{Cell, OnceCell, Ref, RefCell, RefMut},
+    sync::Arc,
+};
+
+use nalgebra::Vector2;
+
+use crate::solve::{
+    distance::GoalDistanceGrid,
+    hasher,
+    learned_deadlocks::LearnedDeadlocks,
+    level_analysis::LevelAnalysis,
+    lower_bound_cache::LowerBoundCache,
+    path_finding::PathFinder,
+    pattern_database::PatternDatabase,
+    solver::{HeuristicKind, PruningStatistics, RelevanceCuts, SolverOptions, Strategy},
+};
+
+/// The number of counters of the generated states, of the pushes and states
+/// pruned by each rule and of the flood fills.
+pub(crate) const COUNTERS: usize = 13;
+
+/// The counts of the generated states, of the pushes and states pruned by each
+/// rule and of the flood fills, taken from a context on another thread.
+pub(crate) type Counts = [usize; COUNTERS];
+
+/// Goal distances keyed by the sorted positions of the boxes frozen on goals.
+type FrozenGoalDistances = hasher::HashMap<Vec<Vector2<i32>>, Arc<[GoalDistanceGrid]>>;
+
+/// The options of a search, and the caches and counters which the methods of
+/// [`State`](crate::solve::state::State) fill while evaluating states.
+///
+/// The [`LevelAnalysis`] passed along with the context is shared between
+/// threads, while each search, and each thread evaluating the pushes of its
+/// states, has a context of its own.
+pub struct StateContext {
+    pub(crate) options: SolverOptions,
+    pub(crate) pattern_database: OnceCell<PatternDatabase>,
+    lower_bound_cache: RefCell<LowerBoundCache>,
+    learned_deadlocks: RefCell<LearnedDeadlocks>,
+    frozen_goal_distances: RefCell<FrozenGoalDistances>,
+    path_finder: RefCell<PathFinder>,
+    /// The contexts evaluating the pushes of a state on the threads of rayon,
+    /// if `parallel_successors` is enabled.
+    successor_workers: RefCell<Vec<StateContext>>,
+    /// Whether relevance cuts are applied, until the search is exhausted.
+    pub(crate) relevance_cuts_active: bool,
+    /// The number of states created, which orders states of equal priority.
+    pub(crate) sequence: Cell<u64>,
+    /// The number of successors generated, before duplicates are skipped.
+    generated_states: Cell<usize>,
+    corral_deadlocks: Cell<usize>,
+    pi_corrals: Cell<usize>,
+    bipartite_deadlocks: Cell<usize>,
+    pattern_deadlocks: Cell<usize>,
+    cut_pushes: Cell<usize>,
+    blocked_pushes: Cell<usize>,
+    unreachable_pushes: Cell<usize>,
+    tunnel_pushes: Cell<usize>,
+    closet_pushes: Cell<usize>,
+    corridor_deadlocks: Cell<usize>,
+    freeze_deadlocks: Cell<usize>,
+    flood_fills: Cell<usize>,
+}
+
+impl StateContext {
+    /// Creates an empty context for states of the level with the options.
+    pub fn new(options: SolverOptions, analysis: &LevelAnalysis) -> Self {
+        let dimensions = analysis.map().dimensions();
+        Self {
+            lower_bound_cache: RefCell::new(LowerBoundCache::new(
+                options.lower_bound_cache_capacity,
+            )),
+            learned_deadlocks: RefCell::new(LearnedDeadlocks::new(
+                options.learned_deadlocks_capacity,
+            )),
+            relevance_cuts_active: options.relevance_cuts.is_some(),
+            options,
+            pattern_database: OnceCell::new(),
+            frozen_goal_distances: RefCell::new(hasher::HashMap::default()),
+            path_finder: RefCell::new(PathFinder::new(dimensions)),
+            successor_workers: RefCell::new(Vec::new()),
+            sequence: Cell::new(0),
+            generated_states: Cell::new(0),
+            corral_deadlocks: Cell::new(0),
+            pi_corrals: Cell::new(0),
+            bipartite_deadlocks: Cell::new(0),
+            pattern_deadlocks: Cell::new(0),
+            cut_pushes: Cell::new(0),
+            blocked_pushes: Cell::new(0),
+            unreachable_pushes: Cell::new(0),
+            tunnel_pushes: Cell::new(0),
+            closet_pushes: Cell::new(0),
+            corridor_deadlocks: Cell::new(0),
+            freeze_deadlocks: Cell::new(0),
+            flood_fills: Cell::new(0),
+        }
+    }
+
+    pub fn options(&self) -> &SolverOptions {
+        &self.options
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.options.strategy
+    }
+
+    /// Returns the heuristics whose maximum is the lower bound of a state.
+    pub fn heuristics(&self) -> &[HeuristicKind] {
+        &self.options.heuristics
+    }
+
+    /// Checks if the strategy minimizes the moves before the pushes.
+    pub(crate) fn minimizes_moves(&self) -> bool {
+        matches!(
+            self.options.strategy,
+            Strategy::OptimalMovePush | Strategy::OptimalPushMove
+        )
+    }
+
+    /// Returns the relevance cuts applied to the successors, which are lifted
+    /// once the search with them has been exhausted.
+    pub(crate) fn active_relevance_cuts(&self) -> Option<RelevanceCuts> {
+        self.options
+            .relevance_cuts
+            .filter(|_| self.relevance_cuts_active)
+    }
+
+    /// Returns the sequence number of a newly created state.
+    pub(crate) fn next_sequence(&self) -> u64 {
+        let sequence = self.sequence.get();
+        self.sequence.set(sequence + 1);
+        sequence
+    }
+
+    /// Returns the pattern database, or `None` if it is not built yet.
+    pub fn pattern_database(&self) -> Option<&PatternDatabase> {
+        self.pattern_database.get()
+    }
+
+    /// Returns the cache of lower bounds keyed by box configuration.
+    pub fn lower_bound_cache(&self) -> Ref<'_, LowerBoundCache> {
+        self.lower_bound_cache.borrow()
+    }
+
+    /// Returns the cache of lower bounds for updating.
+    pub(crate) fn lower_bound_cache_mut(&self) -> RefMut<'_, LowerBoundCache> {
+        self.lower_bound_cache.borrow_mut()
+    }
+
+    /// Returns the deadlocks learned while evaluating states.
+    pub fn learned_deadlocks(&self) -> Ref<'_, LearnedDeadlocks> {
+        self.learned_deadlocks.borrow()
+    }
+
+    /// Returns the learned deadlocks for updating.
+    pub(crate) fn learned_deadlocks_mut(&self) -> RefMut<'_, LearnedDeadlocks> {
+        self.learned_deadlocks.borrow_mut()
+    }
+
+    /// Returns the path finder whose buffers are reused to walk the player
+    /// between pushes.
+    pub(crate) fn path_finder_mut(&self) -> RefMut<'_, PathFinder> {
+        self.path_finder.borrow_mut()
+    }
+
+    /// Returns the distances from each position to each remaining goal, where
+    /// the boxes frozen on goals are treated as walls.
+    ///
+    /// `frozen_boxes` must be sorted, since it is used as the cache key.
+    pub fn frozen_goal_distances(
+        &self,
+        analysis: &LevelAnalysis,
+        frozen_boxes: &[Vector2<i32>],
+    ) -> Arc<[GoalDistanceGrid]> {
+        if let Some(goal_distances) = self.frozen_goal_distances.borrow().get(frozen_boxes) {
+            return goal_distances.clone();
+        }
+        let goal_distances: Arc<[GoalDistanceGrid]> = analysis
+            .calculate_frozen_goal_distances(&frozen_boxes.iter().copied().collect())
+            .into();
+        self.frozen_goal_distances
+            .borrow_mut()
+            .insert(frozen_boxes.to_vec(), goal_distances.clone());
+        goal_distances
+    }
+
+    /// Returns the contexts evaluating the pushes of a state on the threads of
+    /// rayon, one per thread, created with the options of this context on
+    /// first use.
+    pub(crate) fn successor_workers_mut(
+        &self,
+        analysis: &LevelAnalysis,
+    ) -> RefMut<'_, Vec<StateContext>> {
+        let mut workers = self.successor_workers.borrow_mut();
+        if workers.is_empty() {
+            workers.extend((0..rayon::current_num_threads()).map(|_| {
+                let mut worker = StateContext::new(self.options.clone(), analysis);
+                worker.pattern_database = self.pattern_database.clone();
+                worker
+            }));
+        }
+        // The strategy changes when the search falls back to another one.
+        for worker in workers.iter_mut() {
+            worker.options.strategy = self.options.strategy;
+        }
+        workers
+    }
+
+    /// Returns the counters of the generated states, of the pushes and states
+    /// pruned by each rule and of the flood fills.
+    fn counters(&self) -> [&Cell<usize>; COUNTERS] {
+        [
+            &self.generated_states,
+            &self.corral_deadlocks,
+            &self.pi_corrals,
+            &self.bipartite_deadlocks,
+            &self.pattern_deadlocks,
+            &self.cut_pushes,
+            &self.blocked_pushes,
+            &self.unreachable_pushes,
+            &self.tunnel_pushes,
+            &self.closet_pushes,
+            &self.corridor_deadlocks,
+            &self.freeze_deadlocks,
+            &self.flood_fills,
+        ]
+    }
+
+    /// Returns the counts of the generated states, of the pushes and states
+    /// pruned by each rule and of the flood fills.
+    pub(crate) fn counts(&self) -> Counts {
+        self.counters().map(Cell::get)
+    }
+
+    /// Sets the counts, such as counts returned by [`StateContext::counts`]
+    /// before states were evaluated.
+    pub(crate) fn set_counts(&self, counts: Counts) {
+        for (counter, count) in self.counters().into_iter().zip(counts) {
+            counter.set(count);
+        }
+    }
+
+    /// Returns the counts, resetting them.
+    pub(crate) fn take_counts(&self) -> Counts {
+        self.counters().map(Cell::take)
+    }
+
+    /// Adds the counts taken from a context on another thread.
+    pub(crate) fn add_counts(&self, counts: Counts) {
+        for (counter, count) in self.counters().into_iter().zip(counts) {
+            counter.set(counter.get() + count);
+        }
+    }
+
+    /// Adds the numbers of pushes and states pruned by a search on another
+    /// thread.
+    pub(crate) fn add_pruned_states(&self, pruned_states: PruningStatistics) {
+        let add = |counter: &Cell<usize>, count| counter.set(counter.get() + count);
+        add(&self.blocked_pushes, pruned_states.blocked_pushes);
+        add(&self.unreachable_pushes, pruned_states.unreachable_pushes);
+        add(&self.tunnel_pushes, pruned_states.tunnel_pushes);
+        add(&self.closet_pushes, pruned_states.closet_pushes);
+        add(&self.corridor_deadlocks, pruned_states.corridor_deadlocks);
+        add(&self.freeze_deadlocks, pruned_states.freeze_deadlocks);
+        add(&self.pattern_deadlocks, pruned_states.pattern_deadlocks);
+        add(&self.corral_deadlocks, pruned_states.corral_deadlocks);
+        add(&self.bipartite_deadlocks, pruned_states.bipartite_deadlocks);
+        add(&self.cut_pushes, pruned_states.cut_pushes);
+        add(&self.pi_corrals, pruned_states.pi_corrals);
+    }
+
+    /// Returns the numbers of pushes and states pruned by each rule, except
+    /// the duplicate states.
+    pub fn pruned_states(&self) -> PruningStatistics {
+        PruningStatistics {
+            blocked_pushes: self.blocked_pushes.get(),
+            unreachable_pushes: self.unreachable_pushes.get(),
+            tunnel_pushes: self.tunnel_pushes.get(),
+            closet_pushes: self.closet_pushes.get(),
+            corridor_deadlocks: self.corridor_deadlocks.get(),
+            freeze_deadlocks: self.freeze_deadlocks.get(),
+            pattern_deadlocks: self.pattern_deadlocks.get(),
+            corral_deadlocks: self.corral_deadlocks.get(),
+            pi_corrals: self.pi_corrals.get(),
+            bipartite_deadlocks: self.bipartite_deadlocks.get(),
+            cut_pushes: self.cut_pushes.get(),
+            duplicate_states: 0,
+        }
+    }
+
+    /// Returns the number of successors generated, before duplicates are
+    /// skipped.
+    pub fn generated_states(&self) -> usize {
+        self.generated_states.get()
+    }
+
+    /// Records a generated successor.
+    pub(crate) fn record_generated_state(&self) {
+        self.generated_states.set(self.generated_states.get() + 1);
+    }
+
+    /// Adds the number of successors generated by a search on another
+    /// thread.
+    pub(crate) fn add_generated_states(&self, generated_states: usize) {
+        self.generated_states
+            .set(self.generated_states.get() + generated_states);
+    }
+
+    /// Returns the number of times the area reachable by the player has been
+    /// calculated for a state.
+    pub fn flood_fills(&self) -> usize {
+        self.flood_fills.get()
+    }
+
+    /// Records a calculation of the area reachable by the player.
+    pub(crate) fn record_flood_fill(&self) {
+        self.flood_fills.set(self.flood_fills.get() + 1);
+    }
+
+    /// Records a state pruned by corral deadlock detection.
+    pub(crate) fn record_corral_deadlock(&self) {
+        self.corral_deadlocks.set(self.corral_deadlocks.get() + 1);
+    }
+
+    /// Records a state whose successors were restricted to a PI-corral.
+    pub(crate) fn record_pi_corral(&self) {
+        self.pi_corrals.set(self.pi_corrals.get() + 1);
+    }
+
+    /// Records a state pruned by deadlock patterns.
+    pub(crate) fn record_pattern_deadlock(&self) {
+        self.pattern_deadlocks.set(self.pattern_deadlocks.get() + 1);
+    }
+
+    /// Records a push skipped by relevance cuts.
+    pub(crate) fn record_cut_push(&self) {
+        self.cut_pushes.set(self.cut_pushes.get() + 1);
+    }
+
+    /// Records a push of a box onto a wall, another box or a dead square.
+    pub(crate) fn record_blocked_push(&self) {
+        self.blocked_pushes.set(self.blocked_pushes.get() + 1);
+    }
+
+    /// Records a push from a square the player cannot reach.
+    pub(crate) fn record_unreachable_push(&self) {
+        self.unreachable_pushes
+            .set(self.unreachable_pushes.get() + 1);
+    }
+
+    /// Records a push through a tunnel merged into the push entering it.
+    pub(crate) fn record_tunnel_push(&self) {
+        self.tunnel_pushes.set(self.tunnel_pushes.get() + 1);
+    }
+
+    /// Records a push of a box into a closet.
+    pub(crate) fn record_closet_push(&self) {
+        self.closet_pushes.set(self.closet_pushes.get() + 1);
+    }
+
+    /// Records a push of a box into a dead-end corridor it cannot leave.
+    pub(crate) fn record_corridor_deadlock(&self) {
+        self.corridor_deadlocks
+            .set(self.corridor_deadlocks.get() + 1);
+    }
+
+    /// Records a push freezing a box off a goal.
+    pub(crate) fn record_freeze_deadlock(&self) {
+        self.freeze_deadlocks.set(self.freeze_deadlocks.get() + 1);
+    }
+
+    /// Records a state pruned because its boxes cannot be pushed onto distinct
+    /// goals.
+    pub(crate) fn record_bipartite_deadlock(&self) {
+        self.bipartite_deadlocks
+            .set(self.bipartite_deadlocks.get() + 1);
+    }
+}
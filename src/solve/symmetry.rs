@@ -0,0 +1,72 @@
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles};
+
+/// A transformation of the squares of the level by a reflection or rotation
+/// of its bounding box.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Symmetry {
+    Identity,
+    /// Reflection across the vertical axis.
+    FlipHorizontal,
+    /// Reflection across the horizontal axis.
+    FlipVertical,
+    Rotate180,
+    /// Reflection across the main diagonal.
+    Transpose,
+    /// Reflection across the anti-diagonal.
+    AntiTranspose,
+    Rotate90,
+    Rotate270,
+}
+
+impl Symmetry {
+    /// Returns the symmetries which map the floor and goals of the level onto
+    /// themselves, starting with the identity.
+    pub fn of(map: &Map) -> Vec<Self> {
+        let dimensions = map.dimensions();
+        let mut candidates = vec![
+            Self::Identity,
+            Self::FlipHorizontal,
+            Self::FlipVertical,
+            Self::Rotate180,
+        ];
+        // The other transformations swap the width and height.
+        if dimensions.x == dimensions.y {
+            candidates.extend([
+                Self::Transpose,
+                Self::AntiTranspose,
+                Self::Rotate90,
+                Self::Rotate270,
+            ]);
+        }
+        let tiles = |position: Vector2<i32>| map[position] & (Tiles::Floor | Tiles::Goal);
+        candidates
+            .into_iter()
+            .filter(|symmetry| {
+                (0..dimensions.y).all(|y| {
+                    (0..dimensions.x).all(|x| {
+                        let position = Vector2::new(x, y);
+                        tiles(position) == tiles(symmetry.apply(position, dimensions))
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the image of the position in a level of the given dimensions.
+    pub fn apply(self, position: Vector2<i32>, dimensions: Vector2<i32>) -> Vector2<i32> {
+        let max_x = dimensions.x - 1;
+        let max_y = dimensions.y - 1;
+        let (x, y) = (position.x, position.y);
+        match self {
+            Self::Identity => Vector2::new(x, y),
+            Self::FlipHorizontal => Vector2::new(max_x - x, y),
+            Self::FlipVertical => Vector2::new(x, max_y - y),
+            Self::Rotate180 => Vector2::new(max_x - x, max_y - y),
+            Self::Transpose => Vector2::new(y, x),
+            Self::AntiTranspose => Vector2::new(max_y - y, max_x - x),
+            Self::Rotate90 => Vector2::new(max_y - y, x),
+            Self::Rotate270 => Vector2::new(y, max_x - x),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::solve::{
+    hasher::HashSet,
+    solver::{hash_table_bytes, VisitedFilter},
+};
+
+/// The number of bits of the Bloom filter set for each state.
+const BLOOM_HASHES: u32 = 4;
+
+/// The normalized hashes of the visited states, remembered exactly or by a
+/// Bloom filter.
+pub struct VisitedStates {
+    hashes: Hashes,
+    len: usize,
+}
+
+enum Hashes {
+    Exact(HashSet<u64>),
+    Approximate(BloomFilter),
+}
+
+impl VisitedStates {
+    pub fn new(filter: VisitedFilter) -> Self {
+        let hashes = match filter {
+            VisitedFilter::Exact => Hashes::Exact(HashSet::default()),
+            VisitedFilter::Approximate { bytes } => Hashes::Approximate(BloomFilter::new(bytes)),
+        };
+        Self { hashes, len: 0 }
+    }
+
+    /// Marks the hash as visited. Returns whether it had not been visited,
+    /// which the Bloom filter may deny for a new hash.
+    pub fn insert(&mut self, hash: u64) -> bool {
+        let is_new = match &mut self.hashes {
+            Hashes::Exact(hashes) => hashes.insert(hash),
+            Hashes::Approximate(filter) => filter.insert(hash),
+        };
+        self.len += usize::from(is_new);
+        is_new
+    }
+
+    /// Returns the number of states marked as visited.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if there are no states marked as visited.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the probability that a new state is claimed to have been
+    /// visited, or `None` if the visited states are remembered exactly.
+    pub fn false_positive_rate(&self) -> Option<f64> {
+        match &self.hashes {
+            Hashes::Exact(_) => None,
+            Hashes::Approximate(filter) => Some(filter.false_positive_rate(self.len)),
+        }
+    }
+
+    /// Returns the bytes allocated for the hashes.
+    pub fn allocated_bytes(&self) -> usize {
+        match &self.hashes {
+            Hashes::Exact(hashes) => hash_table_bytes(hashes.capacity(), size_of::<u64>()),
+            Hashes::Approximate(filter) => filter.words.capacity() * size_of::<u64>(),
+        }
+    }
+}
+
+/// A set of hashes of a fixed size, which may claim to contain hashes that
+/// were never inserted.
+struct BloomFilter {
+    words: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter using about the given number of bytes.
+    fn new(bytes: usize) -> Self {
+        Self {
+            words: vec![0; bytes.div_ceil(size_of::<u64>()).max(1)],
+        }
+    }
+
+    /// Sets the bits of the hash. Returns whether any of them was not set.
+    fn insert(&mut self, hash: u64) -> bool {
+        let bits = self.words.len() as u64 * u64::BITS as u64;
+        // The bits are derived from both halves of the hash by double
+        // hashing. The step is odd, so the bits of a hash are distinct.
+        let (first, second) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        let mut is_new = false;
+        for i in 0..BLOOM_HASHES as u64 {
+            let bit = first.wrapping_add(i.wrapping_mul(second)) % bits;
+            let word = &mut self.words[(bit / u64::BITS as u64) as usize];
+            let mask = 1 << (bit % u64::BITS as u64);
+            is_new |= *word & mask == 0;
+            *word |= mask;
+        }
+        is_new
+    }
+
+    /// Returns the probability that a hash which was not inserted is
+    /// claimed to be contained, after inserting `len` hashes.
+    fn false_positive_rate(&self, len: usize) -> f64 {
+        let bits = (self.words.len() * u64::BITS as usize) as f64;
+        let hashes = BLOOM_HASHES as f64;
+        (1.0 - (-hashes * len as f64 / bits).exp()).powf(hashes)
+    }
+}
@@ -0,0 +1,64 @@
+use nalgebra::Vector2;
+
+/// Random keys of the squares of a level, one for a box and one for the
+/// player on each square.
+///
+/// The hash of a state is the XOR of the keys of its boxes and its player, so
+/// it is updated by a push in constant time. The keys are derived from a fixed
+/// seed, so solvers of the same level hash states alike.
+#[derive(Clone)]
+pub struct ZobristKeys {
+    width: i32,
+    box_keys: Vec<u64>,
+    player_keys: Vec<u64>,
+}
+
+impl ZobristKeys {
+    pub fn new(dimensions: Vector2<i32>) -> Self {
+        let squares = (dimensions.x * dimensions.y) as usize;
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let mut keys = std::iter::repeat_with(|| split_mix64(&mut seed));
+        Self {
+            width: dimensions.x,
+            box_keys: keys.by_ref().take(squares).collect(),
+            player_keys: keys.take(squares).collect(),
+        }
+    }
+
+    /// Returns the key of a box on the square.
+    pub fn box_key(&self, position: Vector2<i32>) -> u64 {
+        self.box_keys[self.index(position)]
+    }
+
+    /// Returns the key of the player on the square.
+    pub fn player_key(&self, position: Vector2<i32>) -> u64 {
+        self.player_keys[self.index(position)]
+    }
+
+    /// Returns the XOR of the keys of the boxes.
+    pub fn boxes_hash(&self, box_positions: impl IntoIterator<Item = Vector2<i32>>) -> u64 {
+        box_positions
+            .into_iter()
+            .fold(0, |hash, position| hash ^ self.box_key(position))
+    }
+
+    /// Returns the hash of the box positions after pushing a box from one
+    /// square to another.
+    pub fn moved_box_hash(&self, hash: u64, from: Vector2<i32>, to: Vector2<i32>) -> u64 {
+        hash ^ self.box_key(from) ^ self.box_key(to)
+    }
+
+    fn index(&self, position: Vector2<i32>) -> usize {
+        debug_assert!(position.x >= 0 && position.x < self.width && position.y >= 0);
+        (position.y * self.width + position.x) as usize
+    }
+}
+
+/// Returns the next number of the SplitMix64 sequence.
+fn split_mix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -3,12 +3,57 @@
 #[cfg(test)]
 mod tests {
     // use super::test::Bencher;
-    use crate::{board::Board, solve::solver::*};
-    use soukoban::Level;
-    use std::{ops::RangeBounds, time::Duration};
+    use crate::{
+        board::Board,
+        solve::{
+            arena::{OpenList, QueueKind},
+            box_positions::BoxPositions,
+            cancellation::CancellationToken,
+            deadlock,
+            events::{event_channel, SolveEvent},
+            handle::SolveHandle,
+            hint::Hint,
+            level_analysis::{self, LevelAnalysis, LevelError, Region},
+            path::Path,
+            path_finding::PathFinder,
+            portfolio::{PortfolioPolicy, PortfolioSpec},
+            search_algorithm::{AStarSearch, SearchAlgorithm, SearchContext},
+            solver::*,
+            square_grid::CellIndex,
+            state::State,
+            symmetry::Symmetry,
+        },
+        verify::{self, ReplayError, SolutionMetrics},
+    };
+    use itertools::Itertools;
+    use nalgebra::Vector2;
+    use soukoban::{direction::Direction, path_finding::find_path, Action, Actions, Level, Tiles};
+    use std::{
+        collections::HashSet,
+        fs,
+        hash::{BuildHasher, RandomState},
+        ops::RangeBounds,
+        sync::Arc,
+        time::Duration,
+    };
 
     #[cfg(not(debug_assertions))]
-    use std::fs;
+    use crate::solve::hasher;
+    #[cfg(feature = "serde")]
+    use crate::solve::{
+        analysis::AnalysisError,
+        serialization::{LevelRecord, SolutionRecord},
+    };
+    #[cfg(feature = "count-allocations")]
+    use crate::solve::{bit_grid::BitGrid, visited::VisitedStates};
+    #[cfg(not(debug_assertions))]
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    #[cfg(feature = "count-allocations")]
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        collections::VecDeque,
+    };
 
     #[allow(dead_code)]
     fn solve<R: RangeBounds<usize> + IntoIterator<Item = usize>>(
@@ -24,12 +69,15 @@ mod tests {
             }
             println!("#{} ({})", id + 1, id);
             let level = levels[id].clone();
-            let mut solver = Solver::new(
+            let mut solver = Solver::with_options(
                 level.map().clone(),
-                Strategy::Fast,
-                LowerBoundMethod::MinimumMove,
+                SolverOptions {
+                    strategy: Strategy::Fast,
+                    lower_bound_method: LowerBoundMethod::MinimumMove,
+                    ..Default::default()
+                },
             );
-            let solution = solver.search(Duration::from_secs(time_limit));
+            let solution = solver.search_for(Duration::from_secs(time_limit));
             if solution.is_err() {
                 println!("{}", level.map());
                 println!("{:?}\n\n", solution.clone().err());
@@ -47,79 +95,7169 @@ mod tests {
         failed
     }
 
+    fn load_level(xsb: &str) -> Level {
+        Level::load_from_str(xsb).next().unwrap().unwrap()
+    }
+
+    fn verify_solution(level: &Level, solution: &Actions) -> bool {
+        verify::verify_solution(level, solution).is_ok()
+    }
+
     #[test]
-    #[cfg(not(debug_assertions))]
-    fn solve_microban_2() {
-        let levels =
-            Level::load_from_str(&fs::read_to_string("assets/levels/microban_II_135.xsb").unwrap())
-                .collect::<Result<Vec<_>, _>>()
-                .unwrap();
-        assert!(
-            solve(
-                &levels,
-                0..135,
-                &[
-                    65, 66, 67, 71, 73, 74, 77, 93, 94, 98, 100, 101, 103, 108, 110, 112, 114, 117,
-                    118, 119, 120, 123, 124, 125, 129, 130
-                ],
-                10 * 4
-            ) == 0
+    fn matching_lower_bound() {
+        // Both boxes are closest to the left goal.
+        let level = load_level(
+            r#"
+#########
+#@      #
+#       #
+#.$$   .#
+#       #
+#       #
+#########
+"#,
         );
+        let lower_bound = |heuristic| {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    lower_bound_method: LowerBoundMethod::ManhattanDistance,
+                    heuristics: vec![heuristic],
+                    ..Default::default()
+                },
+            );
+            solver
+                .best_state()
+                .unwrap()
+                .lower_bound(solver.analysis(), solver.context())
+        };
+        assert_eq!(lower_bound(HeuristicKind::ClosestGoal), Some(3));
+        assert_eq!(lower_bound(HeuristicKind::Matching), Some(5));
+
+        let solve = |heuristic| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    lower_bound_method: LowerBoundMethod::MinimumPush,
+                    heuristics: vec![heuristic],
+                    ..Default::default()
+                },
+            );
+            solver.search_for(Duration::from_secs(10)).unwrap()
+        };
+        let closest_goal_solution = solve(HeuristicKind::ClosestGoal);
+        let matching_solution = solve(HeuristicKind::Matching);
+        assert!(verify_solution(&level, &matching_solution));
+        assert_eq!(closest_goal_solution.pushes(), matching_solution.pushes());
     }
 
     #[test]
-    #[cfg(not(debug_assertions))]
-    fn solve_microban() {
-        let levels =
-            Level::load_from_str(&fs::read_to_string("assets/levels/microban_155.xsb").unwrap())
-                .collect::<Result<Vec<_>, _>>()
-                .unwrap();
-        assert!(
-            solve(
-                &levels,
-                0..155,
-                &[92, 97, 108, 110, 113, 121, 138, 142, 144, 145, 152],
-                10 * 4
-            ) == 0
+    fn minimum_push_lower_bounds() {
+        let level = load_level(
+            r#"
+#######
+#@    #
+# $   #
+#    .#
+#######
+"#,
         );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                lower_bound_method: LowerBoundMethod::MinimumPush,
+                ..Default::default()
+            },
+        );
+        let lower_bounds = solver.lower_bounds();
+        assert_eq!(lower_bounds[&Vector2::new(5, 3)], 0);
+        assert_eq!(lower_bounds[&Vector2::new(4, 2)], 2);
+        assert_eq!(lower_bounds[&Vector2::new(2, 2)], 4);
+        // Boxes can never be pushed off the top row or the left column.
+        for position in [
+            Vector2::new(1, 1),
+            Vector2::new(3, 1),
+            Vector2::new(1, 2),
+            Vector2::new(1, 3),
+        ] {
+            assert!(!lower_bounds.contains_key(&position));
+        }
+
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
     }
 
     #[test]
-    #[cfg(not(debug_assertions))]
-    fn solve_box_world() {
-        let levels =
-            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
-                .collect::<Result<Vec<_>, _>>()
+    fn lower_bound_to_walled_off_goal() {
+        // The goal closest to the box by Manhattan distance is behind a wall.
+        let level = load_level(
+            r#"
+#########
+#.   #  #
+#    #  #
+#  $ #. #
+#    #  #
+#      @#
+#       #
+#########
+"#,
+        );
+        for lower_bound_method in [LowerBoundMethod::MinimumPush, LowerBoundMethod::MinimumMove] {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    lower_bound_method,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(solver.lower_bounds()[&Vector2::new(3, 3)], 4);
+        }
+    }
+
+    #[test]
+    fn unreachable_floor() {
+        let level = load_level(
+            r#"
+##########
+#@ $  .  #
+#  ####  #
+#  #  #  #
+#  ####  #
+#        #
+##########
+"#,
+        );
+        for lower_bound_method in [
+            LowerBoundMethod::MinimumPush,
+            LowerBoundMethod::MinimumMove,
+            LowerBoundMethod::ManhattanDistance,
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    lower_bound_method,
+                    ..Default::default()
+                },
+            );
+            if lower_bound_method != LowerBoundMethod::ManhattanDistance {
+                assert!(!solver.lower_bounds().contains_key(&Vector2::new(4, 3)));
+                assert!(!solver.lower_bounds().contains_key(&Vector2::new(5, 3)));
+            }
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+        }
+    }
+
+    #[test]
+    fn dead_squares() {
+        let level = load_level(
+            r#"
+#######
+#     #
+# $ @ #
+#.    #
+#######
+"#,
+        );
+        let solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                lower_bound_method: LowerBoundMethod::ManhattanDistance,
+                ..Default::default()
+            },
+        );
+        // The top row and the right column run along walls without goals.
+        let expected_dead_squares: HashSet<_> = (1..6)
+            .map(|x| Vector2::new(x, 1))
+            .chain((1..4).map(|y| Vector2::new(5, y)))
+            .collect();
+        assert_eq!(*solver.dead_squares(), expected_dead_squares);
+        for dead_square in &expected_dead_squares {
+            assert!(!solver.lower_bounds().contains_key(dead_square));
+        }
+
+        let state = solver.best_state().unwrap();
+        let successors = state.successors(solver.analysis(), solver.context());
+        assert!(!successors.is_empty());
+        for successor in successors {
+            assert!(successor
+                .box_positions
+                .iter()
+                .all(|position| !expected_dead_squares.contains(&position)));
+        }
+    }
+
+    #[test]
+    fn square_grid() {
+        let level = load_level(
+            r#"
+#######
+#     #
+# $ @ #
+#.    #
+#######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let dimensions = level.map().dimensions();
+        for y in -1..=dimensions.y {
+            for x in -1..=dimensions.x {
+                let position = Vector2::new(x, y);
+                let Some(square) = solver.squares().get(position) else {
+                    assert!(x < 0 || y < 0 || x >= dimensions.x || y >= dimensions.y);
+                    continue;
+                };
+                assert_eq!(
+                    square.lower_bound,
+                    solver.lower_bounds().get(&position).copied()
+                );
+                assert_eq!(square.is_dead, solver.dead_squares().contains(&position));
+                for direction in [
+                    Direction::Up,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Right,
+                ] {
+                    assert_eq!(
+                        square.is_tunnel(direction),
+                        solver.tunnels().contains(&(position, direction))
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pushes_into_corners() {
+        // Returns whether a successor has a box at the position.
+        let has_successor_with_box = |xsb, box_position| {
+            let level = load_level(xsb);
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            solver
+                .best_state()
+                .unwrap()
+                .successors(solver.analysis(), solver.context())
+                .iter()
+                .any(|successor| successor.box_positions.contains(&box_position))
+        };
+
+        // A box pushed into a corner without a goal can never be moved again.
+        assert!(!has_successor_with_box(
+            r#"
+######
+# $. #
+#   @#
+#    #
+######
+"#,
+            Vector2::new(1, 1)
+        ));
+
+        // Goals in corners can still be pushed onto.
+        assert!(has_successor_with_box(
+            r#"
+######
+#.$  #
+#   @#
+#    #
+######
+"#,
+            Vector2::new(1, 1)
+        ));
+    }
+
+    #[test]
+    fn closet_entrances() {
+        let level = load_level(
+            r#"
+#######
+#.    #
+#  $  #
+#  @  #
+### ###
+  # #
+  # #
+  ###
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        // A box can be pulled into the closet, but the player can never get
+        // behind it to push it back out.
+        assert!(!solver.dead_squares().contains(&Vector2::new(3, 5)));
+        assert!(solver
+            .closet_entrances()
+            .contains(&(Vector2::new(3, 4), Direction::Down)));
+
+        let state = State::new(
+            Vector2::new(3, 3),
+            BoxPositions::new(solver.map.dimensions(), [Vector2::new(3, 4)]),
+            Path::default(),
+            solver.analysis(),
+            solver.context(),
+        );
+        assert!(state
+            .successors(solver.analysis(), solver.context())
+            .is_empty());
+
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+
+        // The box can be pushed back out of a larger room.
+        let level = load_level(
+            r#"
+#######
+#.    #
+#  $  #
+#  @  #
+### ###
+ #   #
+ #   #
+ #   #
+ #####
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert!(solver.closet_entrances().is_empty());
+    }
+
+    #[test]
+    fn dead_end_corridors() {
+        let level = load_level(
+            r#"
+#######
+#.    #
+# $$$ #
+#  @  #
+### ###
+  # #
+  #.#
+  #.#
+  # #
+  ###
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let [corridor] = solver.dead_end_corridors() else {
+            panic!("expected a single dead-end corridor");
+        };
+        assert_eq!(
+            corridor.squares,
+            (4..=8)
+                .rev()
+                .map(|y| Vector2::new(3, y))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(corridor.goal_depths, [1, 2]);
+
+        let successors = |player_position, box_positions: [Vector2<i32>; 3]| {
+            State::new(
+                player_position,
+                BoxPositions::new(solver.map.dimensions(), box_positions),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            )
+            .successors(solver.analysis(), solver.context())
+        };
+
+        // A third box does not fit into the corridor with two goals, although
+        // it is not frozen yet.
+        let box_positions = BoxPositions::new(
+            level.map().dimensions(),
+            [Vector2::new(3, 4), Vector2::new(3, 6), Vector2::new(3, 7)],
+        );
+        assert!(!deadlock::is_freeze_deadlock(
+            level.map(),
+            Vector2::new(3, 4),
+            &box_positions
+        ));
+        assert!(corridor.is_deadlock(&box_positions, Vector2::new(3, 3)));
+        // Unless the player is behind the box.
+        assert!(!corridor.is_deadlock(&box_positions, Vector2::new(3, 5)));
+        for successor in successors(
+            Vector2::new(3, 2),
+            [Vector2::new(3, 3), Vector2::new(3, 6), Vector2::new(3, 7)],
+        ) {
+            assert_eq!(
+                corridor
+                    .squares
+                    .iter()
+                    .filter(|square| successor.box_positions.contains(square))
+                    .count(),
+                2
+            );
+        }
+
+        // A box pushed past both goals can never be pushed back.
+        let box_positions = BoxPositions::new(
+            level.map().dimensions(),
+            [Vector2::new(3, 8), Vector2::new(2, 2), Vector2::new(4, 2)],
+        );
+        assert!(corridor.is_deadlock(&box_positions, Vector2::new(3, 7)));
+        for successor in successors(
+            Vector2::new(3, 6),
+            [Vector2::new(3, 7), Vector2::new(2, 2), Vector2::new(4, 2)],
+        ) {
+            assert!(!successor.box_positions.contains(&Vector2::new(3, 8)));
+        }
+
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+    }
+
+    #[test]
+    fn freeze_deadlock() {
+        let is_freeze_deadlock = |xsb, box_position| {
+            let level = load_level(xsb);
+            deadlock::is_freeze_deadlock(level.map(), box_position, &BoxPositions::of(level.map()))
+        };
+
+        // 2x2 blocks
+        let block_on_goals = r#"
+######
+#    #
+# ** #
+# ** #
+#  @ #
+######
+"#;
+        assert!(!is_freeze_deadlock(block_on_goals, Vector2::new(2, 2)));
+        let block_off_goal = r#"
+######
+#    #
+# ** #
+# *$.#
+#  @ #
+######
+"#;
+        assert!(is_freeze_deadlock(block_off_goal, Vector2::new(2, 2)));
+        assert!(is_freeze_deadlock(block_off_goal, Vector2::new(3, 3)));
+
+        // Chains along walls
+        let chain_on_goals = r#"
+#####
+#***#
+#   #
+# @ #
+#####
+"#;
+        assert!(!is_freeze_deadlock(chain_on_goals, Vector2::new(2, 1)));
+        let chain_off_goal = r#"
+#####
+#**$#
+#  .#
+# @ #
+#####
+"#;
+        assert!(is_freeze_deadlock(chain_off_goal, Vector2::new(1, 1)));
+        assert!(is_freeze_deadlock(chain_off_goal, Vector2::new(3, 1)));
+        // The box on the goal is frozen by the walls alone, but freezes the
+        // other box off goal.
+        let frozen_by_goal_box = r#"
+######
+#*$  #
+#  . #
+#  @ #
+######
+"#;
+        assert!(is_freeze_deadlock(frozen_by_goal_box, Vector2::new(1, 1)));
+        let pair_along_wall = r#"
+#######
+#  *$ #
+#   . #
+#  @  #
+#######
+"#;
+        assert!(is_freeze_deadlock(pair_along_wall, Vector2::new(3, 1)));
+        let movable_along_wall = r#"
+#######
+#  $  #
+#   . #
+#  @  #
+#######
+"#;
+        assert!(!is_freeze_deadlock(movable_along_wall, Vector2::new(3, 1)));
+    }
+
+    #[test]
+    fn freeze_deadlock_rings() {
+        // Returns whether a successor pushes the box from one position to the
+        // other.
+        let has_successor = |level: &Level, from: Vector2<i32>, to: Vector2<i32>| {
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            let mut box_positions = BoxPositions::of(level.map());
+            box_positions.remove(&from);
+            box_positions.insert(to);
+            solver
+                .best_state()
+                .unwrap()
+                .successors(solver.analysis(), solver.context())
+                .iter()
+                .any(|successor| successor.box_positions == box_positions)
+        };
+
+        // Each box of a ring around a pillar is blocked by the pillar along one
+        // axis only.
+        let level = load_level(
+            r#"
+#########
+#.     .#
+#   $   #
+# $ #$  #
+#   $   #
+#.  @  .#
+#########
+"#,
+        );
+        assert!(has_successor(
+            &level,
+            Vector2::new(2, 3),
+            Vector2::new(3, 3)
+        ));
+
+        // Each box of a ring around a floor can be pushed into it.
+        let level = load_level(
+            r#"
+#######
+#.... #
+# $$$ #
+# $ $ #
+# $$$ #
+#.... #
+#    @#
+#######
+"#,
+        );
+        for box_position in level.map().box_positions() {
+            assert!(!deadlock::is_freeze_deadlock(
+                level.map(),
+                *box_position,
+                &BoxPositions::of(level.map())
+            ));
+        }
+
+        // A genuine 2x2 freeze.
+        let level = load_level(
+            r#"
+#########
+#.     .#
+#  $$   #
+#  $ $  #
+#.    @.#
+#########
+"#,
+        );
+        assert!(!has_successor(
+            &level,
+            Vector2::new(5, 3),
+            Vector2::new(4, 3)
+        ));
+    }
+
+    #[test]
+    fn deadlock_patterns() {
+        // Every box matched by a pattern is also frozen.
+        let is_pattern_deadlock = |xsb, box_position| {
+            let level = load_level(xsb);
+            let box_positions = &BoxPositions::of(level.map());
+            let is_deadlock =
+                deadlock::patterns::is_deadlock(level.map(), box_position, box_positions);
+            if is_deadlock {
+                assert!(deadlock::is_freeze_deadlock(
+                    level.map(),
+                    box_position,
+                    box_positions
+                ));
+            }
+            is_deadlock
+        };
+
+        // Squares of walls and boxes
+        let corner = r#"
+#####
+#$  #
+#  .#
+# @ #
+#####
+"#;
+        assert!(is_pattern_deadlock(corner, Vector2::new(1, 1)));
+        let square = r#"
+######
+#    #
+# $$ #
+# $* #
+#  @.#
+######
+"#;
+        for box_position in [
+            Vector2::new(2, 2),
+            Vector2::new(3, 2),
+            Vector2::new(2, 3),
+            Vector2::new(3, 3),
+        ] {
+            assert!(is_pattern_deadlock(square, box_position));
+        }
+        let square_on_goals = r#"
+######
+#    #
+# ** #
+# ** #
+#  @ #
+######
+"#;
+        assert!(!is_pattern_deadlock(square_on_goals, Vector2::new(2, 2)));
+        let pair_along_wall = r#"
+######
+#$$  #
+#  ..#
+# @  #
+######
+"#;
+        assert!(is_pattern_deadlock(pair_along_wall, Vector2::new(2, 1)));
+
+        // Adjacent boxes blocked by walls on opposite sides, in each orientation
+        let vertical = r#"
+#######
+#  $# #
+# #$  #
+#   ..#
+#  @  #
+#######
+"#;
+        assert!(is_pattern_deadlock(vertical, Vector2::new(3, 1)));
+        assert!(is_pattern_deadlock(vertical, Vector2::new(3, 2)));
+        let vertical_reflected = r#"
+#######
+# #$  #
+#  $# #
+#   ..#
+#  @  #
+#######
+"#;
+        assert!(is_pattern_deadlock(vertical_reflected, Vector2::new(3, 2)));
+        let horizontal = r#"
+#######
+#  #  #
+# $$  #
+# #  .#
+#  @ .#
+#######
+"#;
+        assert!(is_pattern_deadlock(horizontal, Vector2::new(2, 2)));
+        let horizontal_reflected = r#"
+#######
+# #   #
+# $$  #
+#  # .#
+#  @ .#
+#######
+"#;
+        assert!(is_pattern_deadlock(
+            horizontal_reflected,
+            Vector2::new(3, 2)
+        ));
+        let vertical_on_goals = r#"
+#######
+#  *# #
+# #*  #
+#     #
+#  @  #
+#######
+"#;
+        assert!(!is_pattern_deadlock(vertical_on_goals, Vector2::new(3, 1)));
+        let vertical_without_wall = r#"
+#######
+#  $  #
+# #$  #
+#   ..#
+#  @  #
+#######
+"#;
+        assert!(!is_pattern_deadlock(
+            vertical_without_wall,
+            Vector2::new(3, 2)
+        ));
+
+        // Pattern deadlocks are found before the recursive freeze check.
+        let level = load_level(
+            r#"
+#######
+#     #
+# $ $ #
+#.. @ #
+#######
+"#,
+        );
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        assert!(solver.pattern_deadlocks() > 0);
+    }
+
+    #[test]
+    fn corral_deadlocks() {
+        let level = load_level(
+            r#"
+##########
+#   #    #
+# $   $. #
+#   #    #
+#   #  .@#
+##########
+"#,
+        );
+        let solver = |corral_deadlocks| {
+            Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    corral_deadlocks,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // Pushing the box into the doorway locks the other box in the left room.
+        let doorway_successors = |solver: &Solver| {
+            let state = State::new(
+                Vector2::new(6, 2),
+                BoxPositions::new(
+                    solver.map.dimensions(),
+                    [Vector2::new(2, 2), Vector2::new(5, 2)],
+                ),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            );
+            state
+                .successors(solver.analysis(), solver.context())
+                .into_iter()
+                .filter(|successor| successor.box_positions.contains(&Vector2::new(4, 2)))
+                .count()
+        };
+        assert_eq!(doorway_successors(&solver(false)), 1);
+        let solver_with_corral_deadlocks = solver(true);
+        assert_eq!(doorway_successors(&solver_with_corral_deadlocks), 0);
+        assert_eq!(solver_with_corral_deadlocks.corral_deadlocks(), 1);
+
+        let mut solver = solver(true);
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+    }
+
+    #[test]
+    fn bipartite_deadlocks() {
+        let level = load_level(
+            r#"
+########
+#. $   #
+#      #
+#   $ .#
+#   @  #
+########
+"#,
+        );
+        let solver = |bipartite_deadlocks| {
+            Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    bipartite_deadlocks,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // Boxes against the top wall can only be pushed onto the left goal, so
+        // pushing the other box against it leaves both competing for one goal.
+        let top_wall_successors = |solver: &Solver| {
+            let state = State::new(
+                Vector2::new(5, 3),
+                BoxPositions::new(
+                    solver.map.dimensions(),
+                    [Vector2::new(3, 1), Vector2::new(5, 2)],
+                ),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            );
+            state
+                .successors(solver.analysis(), solver.context())
+                .into_iter()
+                .filter(|successor| successor.box_positions.contains(&Vector2::new(5, 1)))
+                .count()
+        };
+        assert_eq!(top_wall_successors(&solver(false)), 1);
+        let solver_with_bipartite_deadlocks = solver(true);
+        assert_eq!(top_wall_successors(&solver_with_bipartite_deadlocks), 0);
+        assert_eq!(solver_with_bipartite_deadlocks.bipartite_deadlocks(), 1);
+
+        let mut solver = solver(true);
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+    }
+
+    #[test]
+    fn learned_deadlocks() {
+        let level = load_level(
+            r#"
+###########
+#   #     #
+# $   $ . #
+# $ #   . #
+#   #   .@#
+###########
+"#,
+        );
+        let solve = |learned_deadlocks_capacity| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    corral_deadlocks: true,
+                    learned_deadlocks_capacity,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            solver
+        };
+
+        // Pushing the box into the doorway traps it together with the two boxes
+        // in the left room, whichever pushes have been made before.
+        let solver_without_learning = solve(0);
+        assert_eq!(solver_without_learning.learned_deadlocks().len(), 0);
+        assert_eq!(solver_without_learning.learned_deadlocks().hits(), 0);
+        let solver = solve(10_000);
+        assert!(!solver.learned_deadlocks().is_empty());
+        assert!(solver.learned_deadlocks().hits() > 0);
+        assert!(solver.corral_deadlocks() < solver_without_learning.corral_deadlocks());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn analysis_persistence() {
+        let level = load_level(
+            r#"
+###########
+#   #     #
+# $   $ . #
+# $ #   . #
+#   #   .@#
+###########
+"#,
+        );
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            corral_deadlocks: true,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(level.map().clone(), options.clone());
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        let mut analysis = Vec::new();
+        solver.export_analysis(&mut analysis).unwrap();
+
+        let mut imported_solver =
+            Solver::import_analysis(level.map().clone(), options.clone(), analysis.as_slice())
                 .unwrap();
+        assert_eq!(imported_solver.dead_squares(), solver.dead_squares());
+        assert_eq!(imported_solver.tunnels(), solver.tunnels());
+        assert_eq!(imported_solver.goal_distances(), solver.goal_distances());
+        assert_eq!(
+            imported_solver.learned_deadlocks().len(),
+            solver.learned_deadlocks().len()
+        );
+
+        // The learned deadlocks spare the second solve its corral searches.
+        let solution = imported_solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        assert!(imported_solver.corral_deadlocks() < solver.corral_deadlocks());
+
+        // The imported analysis is used as is, instead of being recalculated.
+        let mut tampered_analysis: toml::Table =
+            toml::from_str(std::str::from_utf8(&analysis).unwrap()).unwrap();
+        tampered_analysis.insert("dead_squares".to_string(), toml::Value::Array(Vec::new()));
+        let tampered_solver = Solver::import_analysis(
+            level.map().clone(),
+            options.clone(),
+            tampered_analysis.to_string().as_bytes(),
+        )
+        .unwrap();
+        assert!(tampered_solver.dead_squares().is_empty());
+
+        // Analyses of other levels or options are rejected.
+        let other_level = load_level(
+            r#"
+#####
+#@$.#
+#####
+"#,
+        );
+        assert!(matches!(
+            Solver::import_analysis(
+                other_level.map().clone(),
+                options.clone(),
+                analysis.as_slice()
+            ),
+            Err(AnalysisError::FingerprintMismatch { .. })
+        ));
+        let other_options = SolverOptions {
+            lower_bound_method: LowerBoundMethod::MinimumPush,
+            ..options
+        };
+        assert!(matches!(
+            Solver::import_analysis(level.map().clone(), other_options, analysis.as_slice()),
+            Err(AnalysisError::FingerprintMismatch { .. })
+        ));
+        assert!(matches!(
+            Solver::import_analysis(level.map().clone(), SolverOptions::default(), &b"?"[..]),
+            Err(AnalysisError::Format(_))
+        ));
+    }
+
+    /// Solves the level with and without PI-corral pruning, and returns the
+    /// pushes and expanded states of both, or `None` if either times out.
+    fn solve_with_pi_corral_pruning(
+        level: &Level,
+        timeout: Duration,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let solve = |pi_corral_pruning| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    lower_bound_method: LowerBoundMethod::MinimumPush,
+                    pi_corral_pruning,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(timeout).ok()?;
+            assert!(verify_solution(level, &solution));
+            Some((solution.pushes(), solver.expanded_states()))
+        };
+        Some((solve(false)?, solve(true)?))
+    }
+
+    #[test]
+    fn pi_corral_pruning() {
+        // Microban #37
+        let level = load_level(
+            r#"
+      ###
+##### #.#
+#   ###.#
+#   $ #.#
+# $  $  #
+#####@# #
+    #   #
+    #####
+"#,
+        );
+        let ((pushes, expanded_states), (pruned_pushes, pruned_expanded_states)) =
+            solve_with_pi_corral_pruning(&level, Duration::from_secs(10)).unwrap();
+        assert_eq!(pruned_pushes, pushes);
+        assert!(pruned_expanded_states < expanded_states);
+    }
+
+    #[test]
+    fn goal_distances() {
+        let level = load_level(
+            r#"
+#######
+#.   .#
+#  $  #
+#@    #
+#######
+"#,
+        );
+        let solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                lower_bound_method: LowerBoundMethod::MinimumPush,
+                ..Default::default()
+            },
+        );
+        let goal_distances = solver.goal_distances();
+        assert_eq!(goal_distances.len(), 2);
+
+        let left = &goal_distances[0];
+        assert_eq!(left.goal_position(), Vector2::new(1, 1));
+        assert_eq!(left[Vector2::new(1, 1)], Some(0));
+        assert_eq!(left[Vector2::new(3, 1)], Some(2));
+        assert_eq!(left[Vector2::new(2, 2)], Some(2));
+        assert_eq!(left[Vector2::new(4, 2)], Some(4));
+        assert_eq!(left[Vector2::new(5, 1)], None);
+        assert_eq!(left[Vector2::new(1, 3)], None);
+
+        let right = &goal_distances[1];
+        assert_eq!(right.goal_position(), Vector2::new(5, 1));
+        assert_eq!(right[Vector2::new(4, 2)], Some(2));
+        assert_eq!(right[Vector2::new(1, 1)], None);
+
+        assert_eq!(solver.lower_bounds()[&Vector2::new(3, 2)], 3);
+        assert_eq!(solver.lower_bounds()[&Vector2::new(4, 2)], 2);
+        assert!(!solver.lower_bounds().contains_key(&Vector2::new(3, 3)));
+    }
+
+    #[test]
+    fn make_way_penalties() {
+        // The box on the goal must make way before the box in the corridor can
+        // be pushed out, and then return, which the distances alone miss by 2
+        // pushes.
+        let level = load_level(
+            r#"
+#########
+#####   #
+#####   #
+#.  $* @#
+#####   #
+#####   #
+#########
+"#,
+        );
+        let options = |make_way_penalties| SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            lower_bound_method: LowerBoundMethod::MinimumPush,
+            heuristics: vec![HeuristicKind::Matching],
+            make_way_penalties,
+            ..Default::default()
+        };
+        let solver = Solver::with_options(level.map().clone(), options(false));
+        assert_eq!(
+            solver
+                .best_state()
+                .unwrap()
+                .lower_bound(solver.analysis(), solver.context()),
+            Some(3)
+        );
+
+        let mut solver = Solver::with_options(level.map().clone(), options(true));
+        assert_eq!(
+            solver
+                .best_state()
+                .unwrap()
+                .lower_bound(solver.analysis(), solver.context()),
+            Some(5)
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        assert_eq!(solution.pushes(), 5);
+
+        // The configurations written with the former name are still read.
+        #[cfg(feature = "serde")]
         assert!(
-            solve(
-                &levels,
-                0..100,
-                &[
-                    17, 20, 22, 23, 25, 26, 27, 32, 33, 35, 37, 38, 39, 41, 42, 44, 45, 46, 47, 48,
-                    49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 60, 66, 67, 68, 70, 71, 72, 73, 75, 78,
-                    79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 92, 93, 94, 95, 96, 97, 98, 99
-                ],
-                10 * 4
-            ) == 0
+            toml::from_str::<SolverOptions>("linear_conflicts = true")
+                .unwrap()
+                .make_way_penalties
         );
     }
 
-    // #[bench]
-    // fn bench_solver(b: &mut Bencher) {
-    //     let levels = Level::load_from_file(Path::new("assets/levels/box_world_100.xsb")).unwrap();
-    //     b.iter(|| {
-    //         let mut solver = Solver::new(
-    //             levels[2].clone(),
-    //             Strategy::Fast,
-    //             LowerBoundMethod::MinimumPush,
-    //         );
-    //         solver.search(Duration::from_secs(10))
-    //     });
-    // }
+    #[test]
+    fn incremental_lower_bound() {
+        let level = load_level(
+            r#"
+#######
+#.    #
+#  $  #
+# $@  #
+#.    #
+#######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let root = solver.best_state().unwrap();
+        let successors = root.successors(solver.analysis(), solver.context());
+        assert!(!successors.is_empty());
+        for successor in successors {
+            let recalculated = State::new(
+                successor.player_position,
+                successor.box_positions.clone(),
+                successor.path.clone(),
+                solver.analysis(),
+                solver.context(),
+            );
+            assert_eq!(
+                successor.lower_bound(solver.analysis(), solver.context()),
+                recalculated.lower_bound(solver.analysis(), solver.context())
+            );
+        }
+    }
 
-    // #[bench]
-    // fn bench_load_levels_from_file(b: &mut Bencher) {
-    //     b.iter(|| Level::load_from_file(Path::new("assets/levels/box_world_100.xsb")).unwrap());
-    // }
+    #[test]
+    fn dead_states() {
+        // The box starts in a corner that is not a goal.
+        let level = load_level(
+            r#"
+#####
+#$ .#
+# @ #
+#####
+"#,
+        );
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert!(solver.best_state().is_none());
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::NoSolution {
+                reason: NoSolutionReason::BoxOnDeadSquare(position),
+                ..
+            }) if position == Vector2::new(1, 1)
+        ));
+
+        // Both boxes can only reach the goal next to the top wall.
+        let level = load_level(
+            r#"
+########
+#  $.$ #
+#      #
+#   @ .#
+########
+"#,
+        );
+        let solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                lower_bound_method: LowerBoundMethod::MinimumPush,
+                heuristics: vec![HeuristicKind::Matching],
+                ..Default::default()
+            },
+        );
+        assert!(solver.best_state().is_none());
+    }
+
+    #[test]
+    fn no_solution_reasons() {
+        let search = |xsb| match Solver::with_options(
+            load_level(xsb).map().clone(),
+            SolverOptions::default(),
+        )
+        .search_for(Duration::from_secs(10))
+        {
+            Err(SolveError::NoSolution { reason, .. }) => Some(reason),
+            _ => None,
+        };
+
+        // The boxes are in a room the player cannot enter.
+        assert_eq!(
+            search(
+                r#"
+#######
+#@ #$ #
+#  # .#
+#######
+"#
+            ),
+            Some(NoSolutionReason::PlayerSealedOff)
+        );
+
+        // The second box can only be pushed along the bottom wall.
+        assert_eq!(
+            search(
+                r#"
+#######
+#. $ .#
+#   @ #
+# $   #
+#######
+"#
+            ),
+            Some(NoSolutionReason::BoxOnDeadSquare(Vector2::new(2, 3)))
+        );
+
+        // Both boxes can only be pushed onto the goal next to the top wall,
+        // which is only found by searching.
+        let result = search(
+            r#"
+########
+#  $.$ #
+#      #
+#   @ .#
+########
+"#,
+        );
+        assert!(matches!(
+            result,
+            Some(NoSolutionReason::SearchExhausted { visited_states }) if visited_states > 1
+        ));
+    }
+
+    #[test]
+    fn estimated_pushes() {
+        let solver = |xsb| {
+            Solver::with_options(
+                load_level(xsb).map().clone(),
+                SolverOptions {
+                    lower_bound_method: LowerBoundMethod::MinimumPush,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let solved = solver(
+            r#"
+#####
+#@*.#
+#####
+"#,
+        );
+        assert_eq!(solved.estimated_pushes(), Some(0));
+
+        let normal = solver(
+            r#"
+#######
+#@    #
+# $   #
+#    .#
+#######
+"#,
+        );
+        assert_eq!(normal.estimated_pushes(), Some(4));
+        assert!(!normal.is_trivially_unsolvable());
+
+        let dead_corner = solver(
+            r#"
+#####
+#$ .#
+# @ #
+#####
+"#,
+        );
+        assert_eq!(dead_corner.estimated_pushes(), None);
+        assert!(dead_corner.is_trivially_unsolvable());
+    }
+
+    #[test]
+    fn frozen_boxes_as_walls() {
+        // Pushing the lower box onto its goal freezes it in the corner, which
+        // leaves no square to push the other box into the upper goal from.
+        let level = load_level(
+            r#"
+#########
+###.#####
+#       #
+###.$@  #
+####    #
+####  $ #
+####    #
+#########
+"#,
+        );
+        let frozen_push_successors = |frozen_boxes_as_walls| {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    frozen_boxes_as_walls,
+                    ..Default::default()
+                },
+            );
+            let state = State::new(
+                solver.map.player_position(),
+                BoxPositions::of(&solver.map),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            );
+            state
+                .successors(solver.analysis(), solver.context())
+                .into_iter()
+                .filter(|successor| successor.box_positions.contains(&Vector2::new(3, 3)))
+                .count()
+        };
+        assert_eq!(frozen_push_successors(false), 1);
+        assert_eq!(frozen_push_successors(true), 0);
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                frozen_boxes_as_walls: true,
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+    }
+
+    #[test]
+    fn pattern_database() {
+        // Microban #3, where the two boxes get in each other's way.
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let solve = |heuristic| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    lower_bound_method: LowerBoundMethod::MinimumPush,
+                    heuristics: vec![heuristic],
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            (solution.pushes(), solver.expanded_states())
+        };
+        let (matching_pushes, matching_expanded_states) = solve(HeuristicKind::Matching);
+        let (pattern_database_pushes, pattern_database_expanded_states) =
+            solve(HeuristicKind::PatternDatabase { group_size: 2 });
+        assert_eq!(pattern_database_pushes, matching_pushes);
+        assert!(pattern_database_expanded_states < matching_expanded_states);
+    }
+
+    #[test]
+    fn combined_heuristics() {
+        let estimated_pushes = |level: &Level, heuristics| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    lower_bound_method: LowerBoundMethod::MinimumPush,
+                    heuristics,
+                    ..Default::default()
+                },
+            );
+            // Builds the pattern database before estimating.
+            solver.search_for(Duration::from_secs(10)).unwrap();
+            solver.estimated_pushes().unwrap()
+        };
+        let assert_combined = |level: &Level, tighter, looser| {
+            let tighter_lower_bound = estimated_pushes(level, vec![tighter]);
+            let looser_lower_bound = estimated_pushes(level, vec![looser]);
+            assert!(
+                tighter_lower_bound > looser_lower_bound,
+                "{tighter_lower_bound} {looser_lower_bound}"
+            );
+            assert_eq!(
+                estimated_pushes(level, vec![tighter, looser]),
+                tighter_lower_bound
+            );
+            assert_eq!(
+                estimated_pushes(level, vec![looser, tighter]),
+                tighter_lower_bound
+            );
+        };
+
+        // Both boxes share the same nearest goal.
+        let level = load_level(
+            r#"
+#########
+#@      #
+#       #
+#.$$   .#
+#       #
+#       #
+#########
+"#,
+        );
+        assert_combined(
+            &level,
+            HeuristicKind::Matching,
+            HeuristicKind::PatternDatabase { group_size: 1 },
+        );
+
+        // The box on the goal must make way for the other box.
+        let level = load_level(
+            r#"
+#######
+#     #
+#     #
+#.*$  #
+#     #
+#  @  #
+#######
+"#,
+        );
+        assert_combined(
+            &level,
+            HeuristicKind::PatternDatabase { group_size: 2 },
+            HeuristicKind::Matching,
+        );
+    }
+
+    #[test]
+    fn lower_bound_cache() {
+        let level = load_level(
+            r#"
+#########
+#       #
+# $  $  #
+#   @   #
+#     . #
+# .     #
+#########
+"#,
+        );
+        let solve = |lower_bound_cache_capacity| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    heuristics: vec![HeuristicKind::Matching],
+                    lower_bound_cache_capacity,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            let lower_bound_cache = solver.lower_bound_cache();
+            assert!(lower_bound_cache.len() <= lower_bound_cache_capacity);
+            (
+                solution.pushes(),
+                lower_bound_cache.hits(),
+                lower_bound_cache.misses(),
+            )
+        };
+        let (pushes, hits, _) = solve(1_000);
+        assert!(hits > 0);
+
+        let (uncached_pushes, uncached_hits, _) = solve(0);
+        assert_eq!(uncached_pushes, pushes);
+        assert_eq!(uncached_hits, 0);
+
+        let (evicted_pushes, evicted_hits, _) = solve(10);
+        assert_eq!(evicted_pushes, pushes);
+        assert!(evicted_hits < hits);
+    }
+
+    #[test]
+    fn weighted_strategy() {
+        // Microban #29
+        let level = load_level(
+            r#"
+     #####
+     #   ##
+     #    #
+ ######   #
+##     #. #
+# $ $ @  ##
+# ######.#
+#        #
+##########
+"#,
+        );
+        let solve = |strategy| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    lower_bound_method: LowerBoundMethod::MinimumPush,
+                    heuristics: vec![HeuristicKind::Matching],
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            (solution.pushes(), solver.expanded_states())
+        };
+        let (optimal_pushes, _) = solve(Strategy::OptimalPushMove);
+        let (pushes, expanded_states) = solve(Strategy::Weighted(1.0));
+        assert_eq!(pushes, optimal_pushes);
+
+        let (weighted_pushes, weighted_expanded_states) = solve(Strategy::Weighted(3.0));
+        assert!(weighted_pushes as f32 <= optimal_pushes as f32 * 3.0);
+        assert!(weighted_expanded_states < expanded_states);
+
+        // Microban #31, on which states are first reached along paths which
+        // are not the cheapest, so they are only within the bound if reopened.
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+        );
+        let optimal_pushes = 6;
+        for weight in [1.0, 1.2] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::Weighted(weight),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(solver.optimality_bound(), Some(weight));
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            assert!(solution.pushes() as f32 <= optimal_pushes as f32 * weight);
+        }
+        // The approximate visited filter cannot reopen states.
+        let solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::Weighted(1.0),
+                visited_filter: VisitedFilter::Approximate { bytes: 1 << 20 },
+                ..Default::default()
+            },
+        );
+        assert_eq!(solver.optimality_bound(), None);
+    }
+
+    #[test]
+    fn ida_star() {
+        // Microban #29
+        let level = load_level(
+            r#"
+     #####
+     #   ##
+     #    #
+ ######   #
+##     #. #
+# $ $ @  ##
+# ######.#
+#        #
+##########
+"#,
+        );
+        let memory_cap = 50;
+        let solve = |algorithm| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    algorithm,
+                    transposition_table_capacity: memory_cap,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            (solution.pushes(), solver.stored_states())
+        };
+        let (optimal_pushes, stored_states) = solve(Algorithm::AStar);
+        assert!(stored_states > memory_cap);
+
+        let (pushes, stored_states) = solve(Algorithm::IdaStar);
+        assert_eq!(pushes, optimal_pushes);
+        // The transposition table and the current path.
+        assert!(stored_states <= memory_cap + pushes);
+    }
+
+    #[test]
+    fn beam_search_options() {
+        let level = load_level(
+            r#"
+#####
+#@$.#
+#####
+"#,
+        );
+        let search = |strategy, width| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    algorithm: Algorithm::Beam { width },
+                    ..Default::default()
+                },
+            );
+            solver.search_for(Duration::from_secs(10))
+        };
+        assert!(search(Strategy::Fast, 10).is_ok());
+        assert!(search(Strategy::Mixed, 10).is_ok());
+        for strategy in [
+            Strategy::OptimalMovePush,
+            Strategy::OptimalPushMove,
+            Strategy::Weighted(1.5),
+        ] {
+            assert!(matches!(
+                search(strategy, 10),
+                Err(SolveError::InvalidOptions(_))
+            ));
+        }
+        assert!(matches!(
+            search(Strategy::Fast, 0),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn beam_search() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let level = &levels[20];
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(1)),
+            Err(SolveError::Timeout { .. })
+        ));
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                algorithm: Algorithm::Beam { width: 100 },
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(level, &solution));
+        assert!(solver.discarded_states() > 0);
+        assert_eq!(solver.optimality_bound(), None);
+    }
+
+    #[test]
+    fn bidirectional_search() {
+        let levels = [
+            r#"
+#####
+#@$.#
+#####
+"#,
+            // Microban #29
+            r#"
+     #####
+     #   ##
+     #    #
+ ######   #
+##     #. #
+# $ $ @  ##
+# ######.#
+#        #
+##########
+"#,
+            // The player must end up in a different area than it starts in.
+            r#"
+#######
+#. #  #
+#  $ @#
+#. $  #
+#######
+"#,
+            r#"
+###########
+#   #     #
+# $   $ . #
+# $ #   . #
+#   #   .@#
+###########
+"#,
+        ];
+        for xsb in levels {
+            let level = load_level(xsb);
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    algorithm: Algorithm::Bidirectional,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+        }
+
+        let level = load_level(levels[0]);
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                algorithm: Algorithm::Bidirectional,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn solve_backward() {
+        let levels = [
+            r#"
+#####
+#@$.#
+#####
+"#,
+            // Microban #29
+            r#"
+     #####
+     #   ##
+     #    #
+ ######   #
+##     #. #
+# $ $ @  ##
+# ######.#
+#        #
+##########
+"#,
+            r#"
+#######
+#. #  #
+#  $ @#
+#. $  #
+#######
+"#,
+            r#"
+######
+#@ $.#
+#  * #
+######
+"#,
+        ];
+        for xsb in levels {
+            let level = load_level(xsb);
+            let options = SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            };
+            let mut solver = Solver::with_options(level.map().clone(), options.clone());
+            let forward_solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            let mut solver = Solver::with_options(level.map().clone(), options);
+            let backward_solution = solver.solve_backward(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &backward_solution));
+            assert_eq!(backward_solution.pushes(), forward_solution.pushes());
+
+            let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            let solution = solver.solve_backward(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+        }
+    }
+
+    #[test]
+    fn greedy_strategy() {
+        // Boxworld #63
+        let level = load_level(
+            r#"
+   #####
+####.  ##
+# $.$.  #
+#@$# #$ #
+# $. .  #
+####$#$ #
+  #. .  #
+  #######
+"#,
+        );
+        let solve = |strategy| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(60)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            solver.expanded_states()
+        };
+        // Pushing boxes onto goals first leads straight to a solution, while
+        // preferring fewer moves wanders between states of equal lower bound.
+        let greedy_expanded_states = solve(Strategy::Greedy);
+        assert!(greedy_expanded_states < 2_000);
+        let fast_expanded_states = solve(Strategy::Fast);
+        assert!(fast_expanded_states > greedy_expanded_states * 2);
+    }
+
+    #[test]
+    fn long_corridor() {
+        let corridor_length = 10_500;
+        let xsb = format!(
+            "{}\n#@{}$.#\n{}\n",
+            "#".repeat(corridor_length + 5),
+            " ".repeat(corridor_length),
+            "#".repeat(corridor_length + 5)
+        );
+        let level = load_level(&xsb);
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalMovePush,
+                lower_bound_method: LowerBoundMethod::ManhattanDistance,
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        assert_eq!(solution.moves(), corridor_length + 1);
+        assert_eq!(solution.pushes(), 1);
+    }
+
+    #[test]
+    fn optimal_move_push() {
+        // Microban #31
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalMovePush,
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        // A state first reached with more moves must not hide the same state
+        // reached later with fewer moves.
+        assert_eq!(solution.moves(), 17);
+        assert_eq!(solution.pushes(), 6);
+    }
+
+    #[test]
+    fn optimal_pushes() {
+        // Microban #38
+        let level = load_level(
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        );
+        let solve = |strategy, algorithm| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    algorithm,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            (solution.pushes(), solver.expanded_states())
+        };
+        let (optimal_pushes, push_move_expanded_states) =
+            solve(Strategy::OptimalPushMove, Algorithm::AStar);
+        let (pushes, expanded_states) = solve(Strategy::OptimalPushes, Algorithm::AStar);
+        assert_eq!(pushes, optimal_pushes);
+        // States differing only in the player position within its area are
+        // no longer searched separately.
+        assert!(expanded_states < push_move_expanded_states);
+
+        let (pushes, _) = solve(Strategy::OptimalPushes, Algorithm::IdaStar);
+        assert_eq!(pushes, optimal_pushes);
+    }
+
+    #[test]
+    fn solvable_within() {
+        // Microban #38
+        let level = load_level(
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        let optimal_pushes = 8;
+        assert_eq!(
+            solver.search_for(Duration::from_secs(10)).unwrap().pushes(),
+            optimal_pushes
+        );
+        // The timeout carries the statistics of the bounded search rather
+        // than those of the search above.
+        let Err(SolveError::Timeout { statistics }) =
+            solver.solvable_within(optimal_pushes, Duration::ZERO)
+        else {
+            panic!("the search should time out");
+        };
+        assert!(solver.statistics().expanded_states > 0);
+        assert_eq!(statistics.expanded_states, 0);
+        assert_eq!(statistics.open_states, 1);
+        assert_eq!(statistics.visited_states, 1);
+        assert_eq!(statistics.initial_lower_bound, solver.estimated_pushes());
+        assert_eq!(statistics.solution, None);
+
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushMove,
+            Strategy::OptimalPushes,
+            Strategy::Mixed,
+        ] {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let timeout = Duration::from_secs(10);
+            assert_eq!(solver.solvable_within(optimal_pushes, timeout), Ok(true));
+            assert_eq!(
+                solver.solvable_within(optimal_pushes - 1, timeout),
+                Ok(false)
+            );
+            assert!(matches!(
+                solver.solvable_within(optimal_pushes, Duration::ZERO),
+                Err(SolveError::Timeout { .. })
+            ));
+        }
+
+        // The cuts would only let the box pushed first be pushed.
+        let level = load_level(
+            r#"
+#######
+#.$ $.#
+#  @  #
+#######
+"#,
+        );
+        let solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                relevance_cuts: Some(RelevanceCuts {
+                    threshold: 0,
+                    window: 1,
+                }),
+                ..Default::default()
+            },
+        );
+        assert_eq!(solver.solvable_within(2, Duration::from_secs(10)), Ok(true));
+        assert_eq!(
+            solver.solvable_within(1, Duration::from_secs(10)),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn deterministic_search() {
+        let levels = [
+            // Microban #31
+            r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+            // Microban #38
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+            // Microban #54
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        ];
+        for level in levels.map(load_level) {
+            for (strategy, algorithm) in [
+                (Strategy::Fast, Algorithm::AStar),
+                (Strategy::OptimalPushMove, Algorithm::AStar),
+                (Strategy::OptimalPushes, Algorithm::IdaStar),
+                (Strategy::Fast, Algorithm::Bidirectional),
+            ] {
+                // Every solver hashes its box positions differently, so the
+                // iteration order of the boxes differs between the runs.
+                let solve = || {
+                    let mut solver = Solver::with_options(
+                        level.map().clone(),
+                        SolverOptions {
+                            strategy,
+                            algorithm,
+                            pi_corral_pruning: true,
+                            ..Default::default()
+                        },
+                    );
+                    let solution = solver.search_for(Duration::from_secs(10));
+                    (solution, solver.expanded_states(), solver.stored_states())
+                };
+                assert_eq!(solve(), solve(), "{:?} {:?}", strategy, algorithm);
+            }
+
+            // Resuming the search after timeouts does not change it.
+            let search = |timeout| {
+                let mut solver =
+                    Solver::with_options(level.map().clone(), SolverOptions::default());
+                loop {
+                    match solver.search_for(timeout) {
+                        Err(SolveError::Timeout { .. }) => continue,
+                        solution => return (solution, solver.expanded_states()),
+                    }
+                }
+            };
+            assert_eq!(
+                search(Duration::from_micros(100)),
+                search(Duration::from_secs(10))
+            );
+        }
+    }
+
+    #[test]
+    fn step() {
+        // Microban #38
+        let level = load_level(
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        );
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushMove,
+            Strategy::OptimalPushes,
+        ] {
+            let options = SolverOptions {
+                strategy,
+                ..Default::default()
+            };
+            let mut solver = Solver::with_options(level.map().clone(), options.clone());
+            let solution = solver.search_for(Duration::from_secs(10));
+
+            let mut stepped_solver = Solver::with_options(level.map().clone(), options);
+            let Ok(SearchStep::Expanded(initial_state)) = stepped_solver.step() else {
+                panic!("expected the initial state to be expanded");
+            };
+            assert_eq!(initial_state.player_position, level.map().player_position());
+            assert_eq!(initial_state.box_positions, BoxPositions::of(level.map()));
+            assert_eq!(
+                Some(initial_state.lower_bound),
+                stepped_solver.estimated_pushes()
+            );
+            assert_eq!((initial_state.moves, initial_state.pushes), (0, 0));
+            let stepped_solution = loop {
+                match stepped_solver.step().unwrap() {
+                    SearchStep::Expanded(state) => assert!(state.pushes > 0),
+                    SearchStep::Solved(solution) => break Ok(solution),
+                    SearchStep::Exhausted => panic!("expected a solution"),
+                }
+            };
+            assert_eq!(stepped_solution, solution);
+            assert_eq!(stepped_solver.expanded_states(), solver.expanded_states());
+        }
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                algorithm: Algorithm::IdaStar,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(solver.step(), Err(SolveError::InvalidOptions(_))));
+    }
+
+    #[test]
+    fn open_states_deduplication() {
+        // Microban #38
+        let level = load_level(
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        );
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushMove,
+            Strategy::OptimalPushes,
+            Strategy::Mixed,
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let (mut generated, mut pushed) = (0, 0);
+            let solution = loop {
+                match solver.step().unwrap() {
+                    SearchStep::Expanded(expanded) => {
+                        let state = State::new(
+                            expanded.player_position,
+                            expanded.box_positions,
+                            Path::default(),
+                            solver.analysis(),
+                            solver.context(),
+                        );
+                        generated += state.successors(solver.analysis(), solver.context()).len();
+                        pushed += expanded.successors;
+                    }
+                    SearchStep::Solved(solution) => break solution,
+                    SearchStep::Exhausted => panic!("expected a solution"),
+                }
+            };
+            println!(
+                "{:?}: {} of {} successors pushed",
+                strategy, pushed, generated
+            );
+            // Successors reached before at the same or a lower cost are not
+            // pushed onto the heap again.
+            assert!(pushed < generated);
+            assert_eq!(
+                Ok(solution),
+                Solver::with_options(
+                    level.map().clone(),
+                    SolverOptions {
+                        strategy,
+                        ..Default::default()
+                    },
+                )
+                .search_for(Duration::from_secs(10))
+            );
+        }
+    }
+
+    #[test]
+    fn resume_search() {
+        // Microban #35
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(level.map().clone(), options.clone());
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        let expanded_states = solver.expanded_states();
+
+        // The search continues from the open states left by the timeout.
+        let mut sliced_solver = Solver::with_options(level.map().clone(), options);
+        let slice = Duration::from_millis(100);
+        assert!(matches!(
+            sliced_solver.search_for(slice),
+            Err(SolveError::Timeout { .. })
+        ));
+        let sliced_solution = loop {
+            match sliced_solver.search_for(slice) {
+                Err(SolveError::Timeout { .. }) => continue,
+                result => break result.unwrap(),
+            }
+        };
+        assert_eq!(sliced_solution, solution);
+        assert_eq!(sliced_solver.expanded_states(), expanded_states);
+
+        // Restarting discards the search.
+        sliced_solver.restart();
+        assert_eq!(sliced_solver.expanded_states(), 0);
+        assert_eq!(sliced_solver.stored_states(), 0);
+        assert_eq!(
+            sliced_solver.search_for(Duration::from_secs(10)),
+            Ok(solution)
+        );
+        assert_eq!(sliced_solver.expanded_states(), expanded_states);
+    }
+
+    #[test]
+    fn search_graph() {
+        // Microban #1
+        let level = load_level(
+            r#"
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                record_graph: Some(1000),
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        let graph = solver.search_graph().unwrap();
+        assert!(!graph.truncated());
+
+        // The solution path leads from the initial state to a solved state.
+        let root = &graph.nodes()[0];
+        let initial_state = State::new(
+            level.map().player_position(),
+            BoxPositions::of(level.map()),
+            Path::default(),
+            solver.analysis(),
+            solver.context(),
+        );
+        assert_eq!(
+            root.key,
+            initial_state.normalized_hash(solver.analysis(), solver.context())
+        );
+        let goal = graph.nodes().iter().find(|node| node.solved).unwrap();
+        assert_eq!(
+            (goal.moves, goal.pushes),
+            (solution.moves(), solution.pushes())
+        );
+        let mut reached = HashSet::from([root.key]);
+        let mut path_found = false;
+        while !path_found {
+            let reached_len = reached.len();
+            for (from, to) in graph.edges() {
+                if reached.contains(from) {
+                    reached.insert(*to);
+                }
+            }
+            path_found = reached.contains(&goal.key);
+            assert!(path_found || reached.len() > reached_len);
+        }
+
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.contains(&format!("\"{}\" -> ", root.key)));
+        let mut json = Vec::new();
+        graph.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"truncated\":false,\"nodes\":["));
+        assert!(json.contains(&format!("\"key\":\"{}\"", goal.key)));
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                record_graph: Some(1),
+                ..Default::default()
+            },
+        );
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        let graph = solver.search_graph().unwrap();
+        assert!(graph.truncated());
+        assert_eq!(graph.nodes().len(), 1);
+    }
+
+    #[test]
+    fn parallel_search() {
+        let levels = [
+            // Microban #29
+            r#"
+     #####
+     #   ##
+     #    #
+ ######   #
+##     #. #
+# $ $ @  ##
+# ######.#
+#        #
+##########
+"#,
+            // Microban #31
+            r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+            // Microban #38
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        ];
+        for xsb in levels {
+            let level = load_level(xsb);
+            let solve = |strategy, threads| {
+                let mut solver = Solver::with_options(
+                    level.map().clone(),
+                    SolverOptions {
+                        strategy,
+                        threads,
+                        ..Default::default()
+                    },
+                );
+                let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+                assert!(verify_solution(&level, &solution));
+                solution
+            };
+            for strategy in [Strategy::OptimalPushMove, Strategy::OptimalPushes] {
+                assert_eq!(solve(strategy, 4).pushes(), solve(strategy, 1).pushes());
+            }
+            assert_eq!(
+                solve(Strategy::OptimalMovePush, 4).moves(),
+                solve(Strategy::OptimalMovePush, 1).moves()
+            );
+            solve(Strategy::Fast, 4);
+        }
+
+        let mut solver = Solver::with_options(
+            load_level(levels[0]).map().clone(),
+            SolverOptions {
+                threads: 0,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn portfolio() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let specs = [
+            PortfolioSpec {
+                strategy: Strategy::OptimalPushMove,
+                algorithm: Algorithm::AStar,
+            },
+            PortfolioSpec {
+                strategy: Strategy::Fast,
+                algorithm: Algorithm::AStar,
+            },
+        ];
+        let timeout = Duration::from_secs(60);
+        let result = solver.solve_portfolio(&specs, PortfolioPolicy::FirstSolution, timeout);
+        assert_eq!(result.winner, Some(1));
+        assert!(verify_solution(&level, result.solution().unwrap()));
+        // The optimal run is cancelled long before it could finish.
+        let [optimal_run, fast_run] = &result.runs[..] else {
+            unreachable!()
+        };
+        assert!(matches!(
+            optimal_run.result,
+            Err(SolveError::Timeout { .. })
+        ));
+        assert!(fast_run.elapsed < Duration::from_secs(1));
+        assert!(optimal_run.elapsed < fast_run.elapsed + Duration::from_secs(1));
+        assert_eq!([optimal_run.spec, fast_run.spec], specs);
+        assert!(fast_run.expanded_states > 0);
+        assert!(fast_run.stored_states > 0);
+
+        let result = solver.solve_portfolio(&specs, PortfolioPolicy::BestWithinDeadline, timeout);
+        assert_eq!(result.winner, Some(0));
+        assert!(verify_solution(&level, result.solution().unwrap()));
+        assert!(
+            result.runs[0].result.as_ref().unwrap().pushes()
+                < result.runs[1].result.as_ref().unwrap().pushes()
+        );
+    }
+
+    #[test]
+    fn approximate_visited_filter() {
+        // Microban #36
+        let level = load_level(
+            r#"
+####
+#  ############
+# $ $ $ $ $ @ #
+# .....       #
+###############
+"#,
+        );
+        let solver = |visited_filter| {
+            Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    visited_filter,
+                    ..Default::default()
+                },
+            )
+        };
+        let mut exact_solver = solver(VisitedFilter::Exact);
+        let solution = exact_solver.search_for(Duration::from_secs(10)).unwrap();
+        assert_eq!(exact_solver.visited_false_positive_rate(), None);
+
+        // A filter with few false positives searches like the exact set.
+        let mut large_filter_solver = solver(VisitedFilter::Approximate { bytes: 1 << 20 });
+        assert_eq!(
+            large_filter_solver.search_for(Duration::from_secs(10)),
+            Ok(solution.clone())
+        );
+        assert_eq!(
+            large_filter_solver.expanded_states(),
+            exact_solver.expanded_states()
+        );
+
+        // A smaller filter still solves the level, using less memory than the
+        // exact set.
+        let mut small_filter_solver = solver(VisitedFilter::Approximate { bytes: 8192 });
+        let small_filter_solution = small_filter_solver
+            .search_for(Duration::from_secs(10))
+            .unwrap();
+        assert!(verify_solution(&level, &small_filter_solution));
+        assert!(small_filter_solver.visited_false_positive_rate().unwrap() < 0.01);
+        assert!(
+            small_filter_solver.estimated_memory_bytes() < exact_solver.estimated_memory_bytes()
+        );
+
+        // A full filter prunes the solutions, which does not prove that there
+        // is none.
+        let mut tiny_filter_solver = solver(VisitedFilter::Approximate { bytes: 64 });
+        assert!(matches!(
+            tiny_filter_solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::NoSolution {
+                reason: NoSolutionReason::FilterExhausted { .. },
+                ..
+            })
+        ));
+        assert!(tiny_filter_solver.visited_false_positive_rate().unwrap() > 0.5);
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushes,
+                visited_filter: VisitedFilter::Approximate { bytes: 8192 },
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn max_expansions() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        for algorithm in [Algorithm::AStar, Algorithm::IdaStar] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    algorithm,
+                    max_expansions: Some(100),
+                    ..Default::default()
+                },
+            );
+            assert!(matches!(
+                solver.search_for(Duration::from_secs(10)),
+                Err(SolveError::ExpansionLimit { expanded: 100, .. })
+            ));
+            assert_eq!(solver.expanded_states(), 100);
+        }
+
+        // Whichever limit is reached first stops the search.
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                max_expansions: Some(u64::MAX),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::ZERO),
+            Err(SolveError::Timeout { .. })
+        ));
+    }
+
+    /// Counts the bytes allocated and not yet freed by the current thread.
+    ///
+    /// It is only the global allocator with the `count-allocations` feature,
+    /// since it slows down every test, and so are the tests reading it.
+    #[cfg(feature = "count-allocations")]
+    struct CountingAllocator;
+
+    #[cfg(feature = "count-allocations")]
+    thread_local! {
+        static ALLOCATED_BYTES: Cell<isize> = const { Cell::new(0) };
+        static PEAK_ALLOCATED_BYTES: Cell<isize> = const { Cell::new(0) };
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[cfg(feature = "count-allocations")]
+    fn add_allocated_bytes(bytes: isize) {
+        // The thread local is unavailable while the thread is being torn down.
+        let _ = ALLOCATED_BYTES.try_with(|allocated| {
+            allocated.set(allocated.get() + bytes);
+            let _ = PEAK_ALLOCATED_BYTES.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+        });
+    }
+
+    #[cfg(feature = "count-allocations")]
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            add_allocated_bytes(layout.size() as isize);
+            let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            add_allocated_bytes(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            add_allocated_bytes(new_size as isize - layout.size() as isize);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[cfg(feature = "count-allocations")]
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn out_of_memory() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        let max_memory_bytes = 100_000;
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                max_memory_bytes: Some(max_memory_bytes),
+                ..Default::default()
+            },
+        );
+        let Err(
+            error @ SolveError::OutOfMemory {
+                estimated_bytes,
+                visited,
+                open,
+                statistics,
+            },
+        ) = &solver.search_for(Duration::from_secs(10))
+        else {
+            panic!("the search should run out of memory");
+        };
+        assert!(*estimated_bytes > max_memory_bytes);
+        assert_eq!(*estimated_bytes, solver.estimated_memory_bytes());
+        assert_eq!(*visited, solver.stored_states());
+        assert!(*open > 0);
+        assert_eq!(**statistics, solver.statistics());
+        assert!(statistics.expanded_states > 0);
+        assert_ne!(
+            *error,
+            SolveError::Timeout {
+                statistics: statistics.clone()
+            }
+        );
+        assert!(error.to_string().starts_with("out of memory"));
+    }
+
+    #[test]
+    #[cfg(feature = "count-allocations")]
+    fn max_memory_bytes() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        // The estimated memory grows like the memory actually allocated.
+        for (strategy, algorithm) in [
+            (Strategy::Fast, Algorithm::AStar),
+            (Strategy::OptimalPushMove, Algorithm::AStar),
+            (Strategy::OptimalPushes, Algorithm::AStar),
+            (Strategy::Fast, Algorithm::Beam { width: 100 }),
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    algorithm,
+                    max_expansions: Some(1000),
+                    ..Default::default()
+                },
+            );
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get);
+            let estimated_bytes = solver.estimated_memory_bytes();
+            let _ = solver.search_for(Duration::from_secs(10));
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get) - allocated_bytes;
+            let estimated_bytes = solver.estimated_memory_bytes() - estimated_bytes;
+            println!(
+                "{:?} {:?}: allocated {} estimated {}",
+                strategy, algorithm, allocated_bytes, estimated_bytes
+            );
+            // The estimate is within a quarter of the bytes actually allocated.
+            let allocated_bytes = allocated_bytes as usize;
+            assert!(estimated_bytes * 4 > allocated_bytes * 3);
+            assert!(estimated_bytes * 4 < allocated_bytes * 5);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "count-allocations")]
+    fn solve_statistics_memory() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        for (strategy, algorithm) in [
+            (Strategy::Fast, Algorithm::AStar),
+            (Strategy::OptimalPushMove, Algorithm::AStar),
+            (Strategy::OptimalPushes, Algorithm::AStar),
+            (Strategy::Fast, Algorithm::Beam { width: 100 }),
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    algorithm,
+                    max_expansions: Some(2000),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(solver.statistics(), SolveStatistics::default());
+            assert_eq!(solver.statistics().micros_per_expansion(), None);
+            // Analyses computed on first use are allocated before the search.
+            let _ = solver
+                .best_state()
+                .unwrap()
+                .successors(solver.analysis(), solver.context());
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get);
+            PEAK_ALLOCATED_BYTES.with(|peak| peak.set(allocated_bytes));
+            let _ = solver.search_for(Duration::from_secs(10));
+            let peak_bytes = (PEAK_ALLOCATED_BYTES.with(Cell::get) - allocated_bytes) as usize;
+            let allocated_bytes = (ALLOCATED_BYTES.with(Cell::get) - allocated_bytes) as usize;
+            let statistics = solver.statistics();
+            println!(
+                "{:?} {:?}: {:?}, allocated {} peak {}",
+                strategy, algorithm, statistics, allocated_bytes, peak_bytes
+            );
+            assert_eq!(statistics.visited_states, solver.stored_states());
+            assert_eq!(statistics.expanded_states, solver.expanded_states());
+            assert!(statistics.micros_per_expansion().unwrap() > 0.0);
+            assert_eq!(
+                statistics.approx_memory_bytes,
+                solver.estimated_memory_bytes()
+            );
+            // The estimate is within a quarter of the bytes actually allocated.
+            assert!(statistics.approx_memory_bytes * 4 > allocated_bytes * 3);
+            assert!(statistics.approx_memory_bytes * 4 < allocated_bytes * 5);
+            // The peak is only sampled while the time is read, so it may miss
+            // the highest allocation.
+            assert!(statistics.peak_memory_bytes >= statistics.approx_memory_bytes);
+            assert!(statistics.peak_memory_bytes * 2 > peak_bytes);
+            assert!(statistics.peak_memory_bytes * 4 < peak_bytes * 5);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "count-allocations", not(debug_assertions)))]
+    fn visited_hashes_memory() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        // Returns the bytes allocated by the search and the visited states.
+        let search = |verify_hashes| {
+            let mut solver = Solver::with_options(
+                levels[23].map().clone(),
+                SolverOptions {
+                    max_expansions: Some(100_000),
+                    verify_hashes,
+                    ..Default::default()
+                },
+            );
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get);
+            let _ = solver.search_for(Duration::from_secs(60));
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get) - allocated_bytes;
+            (allocated_bytes as usize, solver.stored_states())
+        };
+        let (allocated_bytes, visited_states) = search(false);
+        let (verified_allocated_bytes, verified_visited_states) = search(true);
+        assert_eq!(verified_visited_states, visited_states);
+        // Keeping the states takes more memory than all the rest of the search.
+        let verified_bytes = (verified_allocated_bytes - allocated_bytes) / visited_states;
+        println!(
+            "{} visited states: {} bytes allocated, {} bytes per verified state, {} bytes per State",
+            visited_states,
+            allocated_bytes,
+            verified_bytes,
+            size_of::<State>()
+        );
+        assert!(allocated_bytes / visited_states < verified_bytes);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn successors_throughput() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let solver = Solver::with_options(levels[23].map().clone(), SolverOptions::default());
+        let mut states = vec![State::new(
+            levels[23].map().player_position(),
+            BoxPositions::of(levels[23].map()),
+            Path::default(),
+            solver.analysis(),
+            solver.context(),
+        )];
+        // The analyses of the level are computed by the first expansion.
+        states = states[0].successors(solver.analysis(), solver.context());
+        let mut elapsed = Duration::ZERO;
+        let mut expanded_states = 0;
+        while expanded_states < 100_000 && !states.is_empty() {
+            let start = std::time::Instant::now();
+            let successors = states
+                .iter()
+                .flat_map(|state| state.successors(solver.analysis(), solver.context()))
+                .collect_vec();
+            elapsed += start.elapsed();
+            expanded_states += states.len();
+            states = successors;
+            states.truncate(20_000);
+        }
+        println!(
+            "{} expansions: {:.0} expansions/s",
+            expanded_states,
+            expanded_states as f64 / elapsed.as_secs_f64()
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn path_finder_throughput() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let map = levels[23].map();
+        let is_accessible = |position| {
+            !map[position].intersects(Tiles::Wall) && !map.box_positions().contains(&position)
+        };
+        let positions = (0..map.dimensions().y)
+            .flat_map(|y| (0..map.dimensions().x).map(move |x| Vector2::new(x, y)))
+            .filter(|&position| is_accessible(position) && map[position].intersects(Tiles::Floor))
+            .collect_vec();
+        let pairs = positions
+            .iter()
+            .cartesian_product(&positions)
+            .map(|(&from, &to)| (from, to))
+            .collect_vec();
+
+        let start = std::time::Instant::now();
+        let expected = pairs
+            .iter()
+            .map(|&(from, to)| find_path(from, to, is_accessible))
+            .collect_vec();
+        let hash_map_elapsed = start.elapsed();
+
+        let mut path_finder = PathFinder::new(map.dimensions());
+        let start = std::time::Instant::now();
+        let paths = pairs
+            .iter()
+            .map(|&(from, to)| path_finder.find_path(from, to, is_accessible))
+            .collect_vec();
+        let path_finder_elapsed = start.elapsed();
+
+        for (path, expected) in paths.iter().zip(&expected) {
+            assert_eq!(path.as_ref().map(Vec::len), expected.as_ref().map(Vec::len));
+        }
+        println!(
+            "find_path: {:.0} calls/s, PathFinder: {:.0} calls/s",
+            pairs.len() as f64 / hash_map_elapsed.as_secs_f64(),
+            pairs.len() as f64 / path_finder_elapsed.as_secs_f64()
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn visited_lookups() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let solver = Solver::with_options(levels[23].map().clone(), SolverOptions::default());
+        let mut states = vec![solver.best_state().unwrap().clone()];
+        let mut hashes = Vec::new();
+        while hashes.len() < 1_000_000 && !states.is_empty() {
+            let state = states.pop().unwrap();
+            for successor in state.successors(solver.analysis(), solver.context()) {
+                hashes.push(successor.normalized_hash(solver.analysis(), solver.context()));
+                states.push(successor);
+            }
+        }
+
+        // Each hash is looked up once and found again once, like a state
+        // reached from two parents.
+        fn lookups<S: BuildHasher + Default>(hashes: &[u64]) -> Duration {
+            let start = std::time::Instant::now();
+            let mut visited = HashSet::<u64, S>::default();
+            let mut inserted = 0;
+            for &hash in hashes.iter().chain(hashes) {
+                inserted += usize::from(visited.insert(hash));
+            }
+            assert_eq!(inserted, visited.len());
+            start.elapsed()
+        }
+        let siphash = lookups::<RandomState>(&hashes);
+        let solver_hasher = lookups::<hasher::SolverHasher>(&hashes);
+        println!(
+            "{} lookups: SipHash {:.1} ns/lookup, solver hasher {:.1} ns/lookup",
+            2 * hashes.len(),
+            siphash.as_nanos() as f64 / (2 * hashes.len()) as f64,
+            solver_hasher.as_nanos() as f64 / (2 * hashes.len()) as f64
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn state_size() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_II_135.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        // Microban II #130 and #131 have too many squares for bit grids.
+        for level in &levels[129..=130] {
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            let states = solver
+                .best_state()
+                .unwrap()
+                .successors(solver.analysis(), solver.context());
+            let allocated_bytes = states.iter().map(State::allocated_bytes).sum::<usize>();
+            println!(
+                "{} boxes: {} bytes per State, {} bytes allocated per state",
+                level.map().box_positions().len(),
+                size_of::<State>(),
+                allocated_bytes / states.len()
+            );
+        }
+    }
+
+    #[test]
+    fn shared_box_positions() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_II_135.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        // Microban II #130 has 24 boxes and too many squares for bit grids.
+        let map = levels[129].map();
+        let dimensions = map.dimensions();
+        let solver = Solver::with_options(map.clone(), SolverOptions::default());
+        let random_state = RandomState::new();
+        let cells_bytes = 2 * size_of::<usize>() + 24 * size_of::<CellIndex>();
+        let assert_boxes = |box_positions: &BoxPositions, positions: &[Vector2<i32>]| {
+            let mut sorted_positions = positions.to_vec();
+            sorted_positions.sort_by_key(|position| (position.y, position.x));
+            assert_eq!(box_positions.iter().collect_vec(), sorted_positions);
+            assert_eq!(box_positions.len(), positions.len());
+            let created = BoxPositions::new(dimensions, positions.iter().copied());
+            assert!(*box_positions == created);
+            assert_eq!(
+                random_state.hash_one(box_positions),
+                random_state.hash_one(&created)
+            );
+            for y in 0..dimensions.y {
+                for x in 0..dimensions.x {
+                    let position = Vector2::new(x, y);
+                    assert_eq!(
+                        box_positions.contains(&position),
+                        positions.contains(&position)
+                    );
+                }
+            }
+        };
+
+        // Deep paths move more boxes than are stored next to the shared cells.
+        let mut states = vec![solver.best_state().unwrap().clone()];
+        let mut shared_successors = 0;
+        for _ in 0..100 {
+            let state = states.pop().unwrap();
+            let successors = state.successors(solver.analysis(), solver.context());
+            // The successors share the same cells, each counting a part of them.
+            let allocated_bytes: usize = successors
+                .iter()
+                .map(|successor| successor.box_positions.allocated_bytes())
+                .sum();
+            if successors.len() > 1 {
+                assert!(allocated_bytes <= cells_bytes);
+                shared_successors += 1;
+            }
+            for successor in &successors {
+                assert_boxes(
+                    &successor.box_positions,
+                    &successor.box_positions.iter().collect_vec(),
+                );
+            }
+            states.extend(successors);
+        }
+        assert!(shared_successors > 50);
+
+        // Cloning the boxes allocates nothing.
+        #[cfg(feature = "count-allocations")]
+        {
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get);
+            let clones = states
+                .iter()
+                .map(|state| state.box_positions.clone())
+                .collect_vec();
+            let allocated_bytes = ALLOCATED_BYTES.with(Cell::get) - allocated_bytes;
+            assert_eq!(
+                allocated_bytes as usize,
+                clones.capacity() * size_of::<BoxPositions>()
+            );
+        }
+
+        // Moving a box back and forth cancels out, and moving many boxes
+        // copies the cells.
+        let mut positions = map.box_positions().iter().copied().collect_vec();
+        let mut box_positions = BoxPositions::of(map);
+        for index in 0..positions.len() {
+            let position = positions[index];
+            let floor = (0..dimensions.y)
+                .flat_map(|y| (0..dimensions.x).map(move |x| Vector2::new(x, y)))
+                .find(|position| {
+                    !map[*position].intersects(Tiles::Wall) && !positions.contains(position)
+                })
+                .unwrap();
+            assert!(!box_positions.remove(&floor));
+            assert!(box_positions.remove(&position));
+            assert!(!box_positions.contains(&position));
+            assert!(box_positions.insert(position));
+            assert!(!box_positions.insert(position));
+            assert!(box_positions.remove(&position));
+            assert!(box_positions.insert(floor));
+            positions[index] = floor;
+            assert_boxes(&box_positions, &positions);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    #[cfg(feature = "count-allocations")]
+    fn box_positions_benchmark() {
+        // 25 boxes in a room with too many squares for bit grids.
+        let level = load_level(
+            r#"
+######################
+#                    #
+# $ $ $ $ $          #
+#                    #
+# $ $ $ $ $  ......  #
+#            ......  #
+# $ $ $ $ $  ......  #
+#            ......  #
+# $ $ $ $ $  .       #
+#                    #
+# $ $ $ $ $     @    #
+#                    #
+#                    #
+######################
+"#,
+        );
+        let dimensions = level.map().dimensions();
+        assert_eq!(level.map().box_positions().len(), 25);
+        // The boxes are stored as cells shared with the successors.
+        assert!(BitGrid::new(dimensions).is_none());
+        assert!(CellIndex::fits(dimensions));
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let mut states = VecDeque::from([solver.best_state().unwrap().clone()]);
+        let mut expanded_states = Vec::new();
+        while expanded_states.len() < 20_000 {
+            let state = states.pop_front().unwrap();
+            states.extend(state.successors(solver.analysis(), solver.context()));
+            expanded_states.push(state);
+        }
+
+        let allocated_bytes = ALLOCATED_BYTES.with(Cell::get);
+        let start = std::time::Instant::now();
+        let successors = expanded_states
+            .iter()
+            .flat_map(|state| state.successors(solver.analysis(), solver.context()))
+            .collect_vec();
+        let successors_elapsed = start.elapsed();
+        let allocated_bytes = ALLOCATED_BYTES.with(Cell::get) - allocated_bytes;
+        let box_bytes: usize = successors
+            .iter()
+            .map(|successor| successor.box_positions.allocated_bytes())
+            .sum();
+        // Each successor counts a part of the cells it shares with others.
+        let cells_bytes = 2 * size_of::<usize>() + 25 * size_of::<CellIndex>();
+        assert!(box_bytes / successors.len() < cells_bytes);
+
+        let start = std::time::Instant::now();
+        let clones = successors
+            .iter()
+            .map(|successor| successor.box_positions.clone())
+            .collect_vec();
+        let clones_elapsed = start.elapsed();
+        // Copying every box of each successor instead, as a sorted vector.
+        let copies = successors
+            .iter()
+            .map(|successor| successor.box_positions.iter().collect_vec())
+            .collect_vec();
+        let start = std::time::Instant::now();
+        let copy_clones = copies.iter().map(Vec::clone).collect_vec();
+        let copies_elapsed = start.elapsed();
+        println!(
+            "{} successors: {:?} generating, {} bytes allocated per successor, {} bytes of boxes \
+             per successor, {:?} cloning boxes, {:?} copying {} bytes of boxes",
+            clones.len(),
+            successors_elapsed,
+            (allocated_bytes as usize - successors.capacity() * size_of::<State>())
+                / successors.len(),
+            box_bytes / successors.len(),
+            clones_elapsed,
+            copies_elapsed,
+            copy_clones[0].capacity() * size_of::<Vector2<i32>>()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "count-allocations", not(debug_assertions)))]
+    fn open_list_allocations() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let mut solver = Solver::with_options(
+            levels[23].map().clone(),
+            SolverOptions {
+                max_expansions: Some(100_000),
+                ..Default::default()
+            },
+        );
+        let allocated_bytes = ALLOCATED_BYTES.with(Cell::get);
+        PEAK_ALLOCATED_BYTES.with(|peak| peak.set(allocated_bytes));
+        let allocations = ALLOCATIONS.with(Cell::get);
+        let start = std::time::Instant::now();
+        let _ = solver.search_for(Duration::from_secs(60));
+        let elapsed = start.elapsed();
+        let peak_bytes = PEAK_ALLOCATED_BYTES.with(Cell::get) - allocated_bytes;
+        let allocations = ALLOCATIONS.with(Cell::get) - allocations;
+        println!(
+            "{} expansions: {} allocations, {} bytes at peak, {} bytes estimated, {:?}",
+            solver.expanded_states(),
+            allocations,
+            peak_bytes,
+            solver.estimated_memory_bytes(),
+            elapsed
+        );
+        // The estimate includes the arena and the entries of the open list.
+        assert!(solver.estimated_memory_bytes() > peak_bytes as usize / 2);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn pi_corral_pruning_microban() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_155.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let mut expanded_states = 0;
+        let mut pruned_expanded_states = 0;
+        for level in &levels[..34] {
+            let Some(((pushes, expanded), (pruned_pushes, pruned_expanded))) =
+                solve_with_pi_corral_pruning(level, Duration::from_secs(10))
+            else {
+                continue;
+            };
+            assert_eq!(pruned_pushes, pushes);
+            expanded_states += expanded;
+            pruned_expanded_states += pruned_expanded;
+        }
+        println!(
+            "Expanded states: {} -> {} ({} saved)",
+            expanded_states,
+            pruned_expanded_states,
+            expanded_states - pruned_expanded_states
+        );
+        assert!(pruned_expanded_states < expanded_states);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn bit_grid_throughput() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_155.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        for strategy in [Strategy::Fast, Strategy::OptimalPushMove] {
+            // Returns the expanded states and the search time of the levels,
+            // padded with walls to fall back to sorted box positions.
+            let search = |padded: bool| {
+                let mut expanded_states = 0;
+                let mut elapsed = Duration::ZERO;
+                for level in &levels[..60] {
+                    let mut map = level.map().to_string();
+                    if padded {
+                        map = map.replacen('\n', &format!("{}\n", "#".repeat(40)), 1);
+                    }
+                    let mut solver = Solver::with_options(
+                        load_level(&map).map().clone(),
+                        SolverOptions {
+                            strategy,
+                            max_expansions: Some(200_000),
+                            symmetry_reduction: false,
+                            ..Default::default()
+                        },
+                    );
+                    let start = std::time::Instant::now();
+                    let _ = solver.search_for(Duration::from_secs(10));
+                    elapsed += start.elapsed();
+                    expanded_states += solver.expanded_states();
+                }
+                (expanded_states, elapsed)
+            };
+            let (expanded_states, elapsed) = search(false);
+            let (padded_expanded_states, padded_elapsed) = search(true);
+            println!(
+                "{:?}: {} expanded states, {:.0}/s with bit grids, {:.0}/s without",
+                strategy,
+                expanded_states,
+                expanded_states as f64 / elapsed.as_secs_f64(),
+                padded_expanded_states as f64 / padded_elapsed.as_secs_f64()
+            );
+            assert_eq!(padded_expanded_states, expanded_states);
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn zobrist_hashing_throughput() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_155.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let mut sip_hash_elapsed = Duration::ZERO;
+        let mut zobrist_elapsed = Duration::ZERO;
+        let mut hashed_states = 0;
+        for level in &levels[..60] {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    symmetry_reduction: false,
+                    ..Default::default()
+                },
+            );
+            let mut states = vec![State::new(
+                level.map().player_position(),
+                BoxPositions::of(level.map()),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            )];
+            for _ in 0..3 {
+                states = states
+                    .iter()
+                    .flat_map(|state| state.successors(solver.analysis(), solver.context()))
+                    .collect();
+                // Hashes the box positions and the player position as the
+                // states were hashed before Zobrist hashing.
+                let start = std::time::Instant::now();
+                for state in &states {
+                    let mut hasher = DefaultHasher::new();
+                    state.player_position.hash(&mut hasher);
+                    state.box_positions.hash(&mut hasher);
+                    std::hint::black_box(hasher.finish());
+                }
+                sip_hash_elapsed += start.elapsed();
+                let start = std::time::Instant::now();
+                for state in &states {
+                    std::hint::black_box(state.exact_hash(solver.analysis()));
+                }
+                zobrist_elapsed += start.elapsed();
+                hashed_states += states.len();
+            }
+        }
+        println!(
+            "{} states: {:.1} ns per SipHash, {:.1} ns per Zobrist hash",
+            hashed_states,
+            sip_hash_elapsed.as_nanos() as f64 / hashed_states as f64,
+            zobrist_elapsed.as_nanos() as f64 / hashed_states as f64
+        );
+        assert!(zobrist_elapsed < sip_hash_elapsed);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn flood_fills_microban() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_155.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        for (strategy, corral_deadlocks) in [
+            (Strategy::Fast, false),
+            (Strategy::Fast, true),
+            (Strategy::OptimalPushMove, false),
+            (Strategy::OptimalPushes, false),
+        ] {
+            let mut expanded_states = 0;
+            let mut flood_fills = 0;
+            let mut elapsed = Duration::ZERO;
+            for level in &levels[..60] {
+                let mut solver = Solver::with_options(
+                    level.map().clone(),
+                    SolverOptions {
+                        strategy,
+                        corral_deadlocks,
+                        max_expansions: Some(200_000),
+                        ..Default::default()
+                    },
+                );
+                let start = std::time::Instant::now();
+                let _ = solver.search_for(Duration::from_secs(10));
+                elapsed += start.elapsed();
+                expanded_states += solver.expanded_states();
+                flood_fills += solver.flood_fills();
+            }
+            println!(
+                "{:?} (corral deadlocks: {}): {:.2} flood fills per expansion, {:.0} expansions/s",
+                strategy,
+                corral_deadlocks,
+                flood_fills as f64 / expanded_states as f64,
+                expanded_states as f64 / elapsed.as_secs_f64()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn incremental_reachable_area_open_floor() {
+        let level = load_level(
+            r#"
+######################
+#                    #
+#  $    $      $     #
+#     ...   ...   $  #
+#  $         #       #
+#     $  @        $  #
+#   ...    #   ...   #
+#       $     $      #
+#                    #
+######################
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let mut states = vec![State::new(
+            level.map().player_position(),
+            BoxPositions::of(level.map()),
+            Path::default(),
+            solver.analysis(),
+            solver.context(),
+        )];
+        let mut flood_fill_elapsed = Duration::ZERO;
+        let mut update_elapsed = Duration::ZERO;
+        let mut pushes = 0;
+        for _ in 0..2 {
+            let mut next_states = Vec::new();
+            for state in &states {
+                let area = state.player_reachable_area(solver.analysis(), solver.context());
+                for successor in state.successors(solver.analysis(), solver.context()) {
+                    let from = state
+                        .box_positions
+                        .iter()
+                        .find(|position| !successor.box_positions.contains(position))
+                        .unwrap();
+                    let to = successor
+                        .box_positions
+                        .iter()
+                        .find(|position| !state.box_positions.contains(position))
+                        .unwrap();
+                    let start = std::time::Instant::now();
+                    std::hint::black_box(
+                        successor.player_reachable_area(solver.analysis(), solver.context()),
+                    );
+                    flood_fill_elapsed += start.elapsed();
+                    let start = std::time::Instant::now();
+                    std::hint::black_box(successor.reachable_area_after_push(
+                        &area,
+                        from,
+                        to,
+                        solver.analysis(),
+                        solver.context(),
+                    ));
+                    update_elapsed += start.elapsed();
+                    pushes += 1;
+                    next_states.push(successor);
+                }
+            }
+            states = next_states;
+        }
+        println!(
+            "{} pushes: {:.0} ns per flood fill, {:.0} ns per update",
+            pushes,
+            flood_fill_elapsed.as_nanos() as f64 / pushes as f64,
+            update_elapsed.as_nanos() as f64 / pushes as f64
+        );
+        assert!(update_elapsed < flood_fill_elapsed);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn solve_microban_2() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_II_135.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert!(
+            solve(
+                &levels,
+                0..135,
+                &[
+                    65, 66, 67, 71, 73, 74, 77, 93, 94, 98, 100, 101, 103, 108, 110, 112, 114, 117,
+                    118, 119, 120, 123, 124, 125, 129, 130
+                ],
+                10 * 4
+            ) == 0
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn solve_microban() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/microban_155.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert!(
+            solve(
+                &levels,
+                0..155,
+                &[92, 97, 108, 110, 113, 121, 138, 142, 144, 145, 152],
+                10 * 4
+            ) == 0
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn solve_box_world() {
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert!(
+            solve(
+                &levels,
+                0..100,
+                &[
+                    17, 20, 22, 23, 25, 26, 27, 32, 33, 35, 37, 38, 39, 41, 42, 44, 45, 46, 47, 48,
+                    49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 60, 66, 67, 68, 70, 71, 72, 73, 75, 78,
+                    79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 92, 93, 94, 95, 96, 97, 98, 99
+                ],
+                10 * 4
+            ) == 0
+        );
+    }
+
+    // #[bench]
+    // fn bench_solver(b: &mut Bencher) {
+    //     let levels = Level::load_from_file(Path::new("assets/levels/box_world_100.xsb")).unwrap();
+    //     b.iter(|| {
+    //         let mut solver = Solver::with_options(
+    //             levels[2].clone(),
+    //             Strategy::Fast,
+    //             LowerBoundMethod::MinimumPush,
+    //         );
+    //         solver.search_for(Duration::from_secs(10))
+    //     });
+    // }
+
+    // #[bench]
+    // fn bench_load_levels_from_file(b: &mut Bencher) {
+    //     b.iter(|| Level::load_from_file(Path::new("assets/levels/box_world_100.xsb")).unwrap());
+    // }
+
+    #[test]
+    fn anytime_search() {
+        // Microban #38
+        let level = load_level(
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        let optimal_pushes = solver.search_for(Duration::from_secs(10)).unwrap().pushes();
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                anytime: true,
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        let first_solution = solver.first_solution().unwrap();
+        assert!(verify_solution(&level, first_solution));
+        assert!(first_solution.pushes() > solution.pushes());
+        assert_eq!(solution.pushes(), optimal_pushes);
+        assert!(solver.optimality_proven());
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                algorithm: Algorithm::IdaStar,
+                anytime: true,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn packing_order() {
+        // Microban #87
+        let level = load_level(
+            r#"
+     ####
+######  #
+#       #
+#  ... .#
+##$######
+# $  #
+#   $###
+##  $  #
+ ## @  #
+  ######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let packing_order = solver.packing_order().unwrap();
+        assert_eq!(packing_order.entrance(), Vector2::new(2, 3));
+        assert_eq!(
+            packing_order.goals(),
+            [
+                Vector2::new(3, 3),
+                Vector2::new(4, 3),
+                Vector2::new(5, 3),
+                Vector2::new(7, 3)
+            ]
+        );
+        assert_eq!(
+            packing_order.next_goal(&BoxPositions::of(level.map())),
+            Some(Vector2::new(3, 3))
+        );
+        assert_eq!(
+            packing_order.next_goal(&BoxPositions::new(
+                level.map().dimensions(),
+                [Vector2::new(4, 3)]
+            )),
+            None
+        );
+
+        let solve = |strategy, goal_macros| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    goal_macros,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            (solution.pushes(), solver.expanded_states())
+        };
+        let (_, expanded_states) = solve(Strategy::Fast, false);
+        let (_, macro_expanded_states) = solve(Strategy::Fast, true);
+        assert!(macro_expanded_states * 10 < expanded_states);
+        assert_eq!(
+            solve(Strategy::OptimalPushMove, true).0,
+            solve(Strategy::OptimalPushMove, false).0
+        );
+
+        // Levels without a goal room have no packing order.
+        let level = load_level(
+            r#"
+#####
+#@$.#
+#####
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert!(solver.packing_order().is_none());
+    }
+
+    #[test]
+    fn successor_ordering() {
+        let levels = [
+            // Microban #16
+            r#"
+ ####
+ #  ####
+ #     ##
+## ##   #
+#. .# @$##
+#   # $$ #
+#  .#    #
+##########
+"#,
+            // Microban #29
+            r#"
+     #####
+     #   ##
+     #    #
+ ######   #
+##     #. #
+# $ $ @  ##
+# ######.#
+#        #
+##########
+"#,
+        ];
+        for xsb in levels {
+            let level = load_level(xsb);
+            let solve = |algorithm, strategy, successor_ordering| {
+                let mut solver = Solver::with_options(
+                    level.map().clone(),
+                    SolverOptions {
+                        algorithm,
+                        strategy,
+                        successor_ordering,
+                        ..Default::default()
+                    },
+                );
+                let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+                assert!(verify_solution(&level, &solution));
+                (solution, solver.expanded_states())
+            };
+            // Depth-first search expands the successors in the order they are
+            // generated, so good pushes found early save expansions.
+            let (solution, expanded_states) = solve(
+                Algorithm::IdaStar,
+                Strategy::OptimalPushMove,
+                SuccessorOrdering::Position,
+            );
+            let (ordered_solution, ordered_expanded_states) = solve(
+                Algorithm::IdaStar,
+                Strategy::OptimalPushMove,
+                SuccessorOrdering::Inertia,
+            );
+            assert_eq!(ordered_solution.pushes(), solution.pushes());
+            assert!(ordered_expanded_states * 3 < expanded_states * 2);
+
+            let (solution, _) = solve(
+                Algorithm::AStar,
+                Strategy::OptimalPushMove,
+                SuccessorOrdering::Position,
+            );
+            let (ordered_solution, _) = solve(
+                Algorithm::AStar,
+                Strategy::OptimalPushMove,
+                SuccessorOrdering::Inertia,
+            );
+            assert_eq!(
+                (ordered_solution.pushes(), ordered_solution.moves()),
+                (solution.pushes(), solution.moves())
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_successors() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let map = levels[23].map();
+        let pruning = SolverOptions {
+            corral_deadlocks: true,
+            pi_corral_pruning: true,
+            bipartite_deadlocks: true,
+            frozen_boxes_as_walls: true,
+            relevance_cuts: Some(RelevanceCuts {
+                threshold: 4,
+                window: 3,
+            }),
+            ..Default::default()
+        };
+        let move_optimal = SolverOptions {
+            strategy: Strategy::OptimalMovePush,
+            successor_ordering: SuccessorOrdering::Inertia,
+            ..Default::default()
+        };
+        // The pushes are split between several threads even on a single core.
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        thread_pool.install(|| {
+            for options in [SolverOptions::default(), pruning, move_optimal] {
+                let solver = |parallel_successors| {
+                    Solver::with_options(
+                        map.clone(),
+                        SolverOptions {
+                            parallel_successors,
+                            max_expansions: Some(500),
+                            ..options.clone()
+                        },
+                    )
+                };
+                let (serial_solver, parallel_solver) = (solver(false), solver(true));
+                let initial_state = |solver: &Solver| {
+                    State::new(
+                        map.player_position(),
+                        BoxPositions::of(map),
+                        Path::default(),
+                        solver.analysis(),
+                        solver.context(),
+                    )
+                };
+                let successors = |states: &[State], solver: &Solver| {
+                    states
+                        .iter()
+                        .flat_map(|state| state.successors(solver.analysis(), solver.context()))
+                        .collect_vec()
+                };
+                let key = |state: &State| {
+                    (
+                        state.player_position,
+                        state.box_positions.clone(),
+                        state.path.moves(),
+                        state.priority(),
+                        state.sequence(),
+                    )
+                };
+                let mut serial_states = vec![initial_state(&serial_solver)];
+                let mut parallel_states = vec![initial_state(&parallel_solver)];
+                for _ in 0..3 {
+                    serial_states = successors(&serial_states, &serial_solver);
+                    parallel_states = successors(&parallel_states, &parallel_solver);
+                    assert!(!serial_states.is_empty());
+                    assert_eq!(
+                        parallel_states.iter().map(key).collect_vec(),
+                        serial_states.iter().map(key).collect_vec()
+                    );
+                }
+                assert_eq!(
+                    parallel_solver.generated_states(),
+                    serial_solver.generated_states()
+                );
+                assert_eq!(
+                    parallel_solver.context().pruned_states(),
+                    serial_solver.context().pruned_states()
+                );
+
+                // The search expands the same states in the same order.
+                let (mut serial_solver, mut parallel_solver) = (solver(false), solver(true));
+                let serial_result = serial_solver.search_for(Duration::from_secs(60));
+                let parallel_result = parallel_solver.search_for(Duration::from_secs(60));
+                assert_eq!(parallel_result.ok(), serial_result.ok());
+                assert_eq!(
+                    parallel_solver.best_state().map(key),
+                    serial_solver.best_state().map(key)
+                );
+                assert_eq!(
+                    parallel_solver.generated_states(),
+                    serial_solver.generated_states()
+                );
+                assert_eq!(
+                    parallel_solver.context().pruned_states(),
+                    serial_solver.context().pruned_states()
+                );
+                assert_eq!(
+                    parallel_solver.learned_deadlocks().len(),
+                    serial_solver.learned_deadlocks().len()
+                );
+                assert!(parallel_solver
+                    .statistics()
+                    .micros_per_expansion()
+                    .is_some());
+            }
+        });
+    }
+
+    #[test]
+    fn relevance_cuts() {
+        let relevance_cuts = RelevanceCuts {
+            threshold: 6,
+            window: 2,
+        };
+        let levels = [
+            // Box World #61
+            r#"
+    #####
+   ##   ####
+   # ..* $ #
+#### #.#   #
+#    .*.#@##
+# #$##$## #
+#     $ $ #
+##  #   ###
+ ########
+"#,
+            // Microban #141
+            r#"
+  ########
+  #  # . #
+  #   .*.#
+  #  # * #
+####$##.##
+#      $ #
+# $ ## $ #
+#   @#   #
+##########
+"#,
+        ];
+        for xsb in levels {
+            let level = load_level(xsb);
+            let solve = |relevance_cuts| {
+                let mut solver = Solver::with_options(
+                    level.map().clone(),
+                    SolverOptions {
+                        relevance_cuts,
+                        ..Default::default()
+                    },
+                );
+                let solution = solver.search_for(Duration::from_secs(30)).unwrap();
+                assert!(verify_solution(&level, &solution));
+                (solver.expanded_states(), solver.cut_pushes())
+            };
+            let (expanded_states, cut_pushes) = solve(None);
+            assert_eq!(cut_pushes, 0);
+            let (cut_expanded_states, cut_pushes) = solve(Some(relevance_cuts));
+            assert!(cut_pushes > 0);
+            assert!(cut_expanded_states * 2 < expanded_states);
+        }
+
+        // Only the box pushed last can be pushed, so the search with the cuts
+        // is exhausted before the other box is pushed.
+        let level = load_level(
+            r#"
+#######
+#.$ $.#
+#  @  #
+#######
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                relevance_cuts: Some(RelevanceCuts {
+                    threshold: 0,
+                    window: 1,
+                }),
+                ..Default::default()
+            },
+        );
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        assert!(solver.cut_pushes() > 0);
+
+        // The search graph and the statistics of the run with the cuts are
+        // kept when it is repeated without them.
+        let search = |relevance_cuts| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    relevance_cuts,
+                    record_graph: Some(1000),
+                    ..Default::default()
+                },
+            );
+            solver.search_for(Duration::from_secs(10)).unwrap();
+            let nodes = solver.search_graph().unwrap().nodes().len();
+            (nodes, solver.expanded_states(), solver.generated_states())
+        };
+        let (nodes, expanded_states, generated_states) = search(None);
+        let (cut_nodes, cut_expanded_states, cut_generated_states) = search(Some(RelevanceCuts {
+            threshold: 0,
+            window: 1,
+        }));
+        assert!(cut_nodes > nodes);
+        assert!(cut_expanded_states > expanded_states);
+        assert!(cut_generated_states > generated_states);
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                relevance_cuts: Some(relevance_cuts),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn symmetry_reduction() {
+        let levels = [
+            // Microban #40
+            (
+                r#"
+ #####
+ #   #
+##   ##
+# $$$ #
+# .+. #
+#######
+"#,
+                Symmetry::FlipHorizontal,
+            ),
+            // Microban #31
+            (
+                r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+                Symmetry::Transpose,
+            ),
+        ];
+        for (xsb, symmetry) in levels {
+            let level = load_level(xsb);
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    symmetry_reduction: true,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(solver.symmetries(), [Symmetry::Identity, symmetry]);
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            assert_eq!(solver.symmetries(), [Symmetry::Identity]);
+
+            for strategy in [Strategy::OptimalPushMove, Strategy::OptimalMovePush] {
+                let solve = |symmetry_reduction| {
+                    let mut solver = Solver::with_options(
+                        level.map().clone(),
+                        SolverOptions {
+                            strategy,
+                            symmetry_reduction,
+                            ..Default::default()
+                        },
+                    );
+                    let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+                    assert!(verify_solution(&level, &solution));
+                    (solution, solver.expanded_states())
+                };
+                let (solution, expanded_states) = solve(false);
+                let (reduced_solution, reduced_expanded_states) = solve(true);
+                assert_eq!(
+                    (reduced_solution.moves(), reduced_solution.pushes()),
+                    (solution.moves(), solution.pushes())
+                );
+                // Mirror images of the states are searched once.
+                assert!(reduced_expanded_states * 10 < expanded_states * 6);
+            }
+        }
+
+        let level = load_level(
+            r#"
+#####
+#@$.#
+#  ##
+#####
+"#,
+        );
+        let solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                symmetry_reduction: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(solver.symmetries(), [Symmetry::Identity]);
+    }
+
+    #[test]
+    fn reachable_area_sharing() {
+        // Microban #1
+        let level = load_level(
+            r#"
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+"#,
+        );
+        for (strategy, corral_deadlocks) in
+            [(Strategy::OptimalPushes, false), (Strategy::Fast, true)]
+        {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    corral_deadlocks,
+                    ..Default::default()
+                },
+            );
+            let flood_fills = solver.flood_fills();
+            let state = State::new(
+                level.map().player_position(),
+                BoxPositions::of(level.map()),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            );
+            // Checking for deadlocks and hashing the state share a flood fill.
+            assert!(!state.is_dead(solver.analysis(), solver.context()));
+            let normalized_hash = state.normalized_hash(solver.analysis(), solver.context());
+            assert_eq!(solver.state_key(&state), normalized_hash);
+            assert_eq!(solver.flood_fills(), flood_fills + 1);
+
+            // Expanding the state fills its area again, since only the hash is
+            // kept. The areas of the successors are updated from it, except
+            // for pushing the box left, which cuts off the lower left corner.
+            let flood_fills = solver.flood_fills();
+            let successors = state.successors(solver.analysis(), solver.context());
+            for successor in &successors {
+                assert!(!successor.is_dead(solver.analysis(), solver.context()));
+                successor.normalized_hash(solver.analysis(), solver.context());
+            }
+            assert_eq!(
+                state.normalized_hash(solver.analysis(), solver.context()),
+                normalized_hash
+            );
+            let splits = successors
+                .iter()
+                .filter(|successor| successor.box_positions.contains(&Vector2::new(2, 4)))
+                .count();
+            assert_eq!(solver.flood_fills(), flood_fills + 1 + splits);
+        }
+    }
+
+    #[test]
+    fn incremental_reachable_area() {
+        let levels = [
+            // Microban #1
+            r#"
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+"#,
+            // Microban #38
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+            r#"
+##########
+#        #
+# $  $   #
+#   ##   #
+#  .@.   #
+#   ..   #
+# $   $  #
+#        #
+##########
+"#,
+        ];
+        // A xorshift generator, so that the pushes are the same on every run.
+        let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut random = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+        for level in levels.map(load_level) {
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            let initial_state = State::new(
+                level.map().player_position(),
+                BoxPositions::of(level.map()),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            );
+            let is_free = |state: &State, position: Vector2<i32>| {
+                !solver.map[position].intersects(Tiles::Wall)
+                    && !state.box_positions.contains(&position)
+            };
+            let mut state = initial_state.clone();
+            let mut area = state.player_reachable_area(solver.analysis(), solver.context());
+            let (mut updates, mut refills) = (0, 0);
+            for i in 0..2_000 {
+                let pushes = state
+                    .box_positions
+                    .iter()
+                    .cartesian_product([
+                        Direction::Up,
+                        Direction::Down,
+                        Direction::Left,
+                        Direction::Right,
+                    ])
+                    .filter(|&(box_position, direction)| {
+                        area.contains(&(box_position - &direction.into()))
+                            && is_free(&state, box_position + &direction.into())
+                    })
+                    .collect_vec();
+                // Restart from time to time, and when the boxes are stuck.
+                if pushes.is_empty() || i % 100 == 0 {
+                    state = initial_state.clone();
+                    area = state.player_reachable_area(solver.analysis(), solver.context());
+                    continue;
+                }
+                // Boxes are pushed over several squares at times, like
+                // through tunnels.
+                let (from, direction) = pushes[random(pushes.len())];
+                let mut to = from + &direction.into();
+                for _ in 0..random(3) {
+                    if !is_free(&state, to + &direction.into()) {
+                        break;
+                    }
+                    to += &direction.into();
+                }
+                let mut box_positions = state.box_positions.clone();
+                box_positions.remove(&from);
+                box_positions.insert(to);
+                state = State::new(
+                    to - &direction.into(),
+                    box_positions,
+                    Path::default(),
+                    solver.analysis(),
+                    solver.context(),
+                );
+                let flood_fills = solver.flood_fills();
+                let updated_area = state.reachable_area_after_push(
+                    &area,
+                    from,
+                    to,
+                    solver.analysis(),
+                    solver.context(),
+                );
+                refills += solver.flood_fills() - flood_fills;
+                area = state.player_reachable_area(solver.analysis(), solver.context());
+                assert_eq!(updated_area, area);
+                updates += 1;
+            }
+            // Most pushes do not split the area, so it is updated without
+            // another flood fill.
+            println!("{} of {} updates filled the area again", refills, updates);
+            assert!(refills > 0 && refills < updates / 4);
+        }
+    }
+
+    #[test]
+    fn zobrist_hashing() {
+        let levels = [
+            // Microban #31
+            r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+            // Microban #38
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+        ];
+        for level in levels.map(load_level) {
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            let mut states = vec![State::new(
+                level.map().player_position(),
+                BoxPositions::of(level.map()),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            )];
+            for _ in 0..3 {
+                states = states
+                    .iter()
+                    .flat_map(|state| state.successors(solver.analysis(), solver.context()))
+                    .collect();
+                for state in &states {
+                    // The hashes updated by the pushes equal the hashes
+                    // calculated from scratch.
+                    let recalculated_state = State::new(
+                        state.player_position,
+                        state.box_positions.clone(),
+                        Path::default(),
+                        solver.analysis(),
+                        solver.context(),
+                    );
+                    assert_eq!(
+                        state.exact_hash(solver.analysis()),
+                        recalculated_state.exact_hash(solver.analysis())
+                    );
+                    assert_eq!(
+                        state.normalized_hash(solver.analysis(), solver.context()),
+                        recalculated_state.normalized_hash(solver.analysis(), solver.context())
+                    );
+                }
+            }
+
+            // Verifying the hashes finds no collisions and does not change the
+            // search.
+            let solve = |verify_hashes| {
+                let mut solver = Solver::with_options(
+                    level.map().clone(),
+                    SolverOptions {
+                        verify_hashes,
+                        ..Default::default()
+                    },
+                );
+                let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+                (solution, solver.expanded_states(), solver.hash_collisions())
+            };
+            let (solution, expanded_states, hash_collisions) = solve(true);
+            assert_eq!(hash_collisions, 0);
+            assert_eq!(solve(false), (solution, expanded_states, 0));
+        }
+    }
+
+    #[test]
+    fn solve_all() {
+        let level = load_level(
+            r#"
+######
+#@   #
+# $  #
+#   .#
+######
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        let solutions = solver.solve_all(3, Duration::from_secs(10)).unwrap();
+        assert_eq!(solutions.len(), 3);
+        let push_sequences: HashSet<_> = solutions
+            .iter()
+            .map(|solution| {
+                assert!(verify_solution(&level, solution));
+                let mut player_position = level.map().player_position();
+                let mut pushes = Vec::new();
+                for action in &**solution {
+                    if action.is_push() {
+                        pushes.push((player_position, action.direction()));
+                    }
+                    player_position += &action.direction().into();
+                }
+                pushes
+            })
+            .collect();
+        assert_eq!(push_sequences.len(), 3);
+        assert!(
+            solutions
+                .windows(2)
+                .all(|pair| (pair[0].pushes(), pair[0].moves())
+                    <= (pair[1].pushes(), pair[1].moves()))
+        );
+        let best_solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert_eq!(
+            (solutions[0].pushes(), solutions[0].moves()),
+            (best_solution.pushes(), best_solution.moves())
+        );
+
+        // Only as many solutions as exist are returned.
+        let level = load_level(
+            r#"
+#####
+#@$.#
+#####
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let solutions = solver.solve_all(3, Duration::from_secs(10)).unwrap();
+        assert_eq!(solutions.len(), 1);
+
+        // Pushing either box first is a transposition reaching the same goal
+        // state, so only one of "LrR" and "RlL" is returned.
+        let level = load_level(
+            r#"
+#######
+#.$@$.#
+#######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let solutions = solver.solve_all(3, Duration::from_secs(10)).unwrap();
+        assert_eq!(solutions.len(), 1);
+        assert!(["LrR", "RlL"].contains(&solutions[0].to_string().as_str()));
+    }
+
+    #[test]
+    fn branch_and_bound() {
+        // Microban #31
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+##@$.##
+# $$  #
+# . . #
+###   #
+  #####
+"#,
+        );
+        let solve = |branch_and_bound| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    branch_and_bound,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            (solution, solver)
+        };
+        let (solution, solver) = solve(false);
+        assert!(solver.incumbents().is_empty());
+        let (bounded_solution, bounded_solver) = solve(true);
+        assert_eq!(bounded_solution.pushes(), solution.pushes());
+        assert!(bounded_solver.optimality_proven());
+        assert!(bounded_solver.expanded_states() < solver.expanded_states());
+
+        // The probe finds a solution within 2 pushes of optimal, which the
+        // bounded search improves on.
+        let incumbents = bounded_solver.incumbents();
+        assert_eq!(incumbents.len(), 2);
+        assert!(incumbents[0].pushes() > solution.pushes());
+        assert!(incumbents[0].pushes() <= solution.pushes() + 2);
+        assert_eq!(bounded_solver.first_solution(), Some(&incumbents[0]));
+        assert_eq!(incumbents[1], bounded_solution);
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::Fast,
+                branch_and_bound: true,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn path_reconstruction() {
+        // Microban #1.
+        let level = load_level(
+            r#"
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+"#,
+        );
+        for strategy in [Strategy::OptimalPushMove, Strategy::OptimalPushes] {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let mut states = vec![State::new(
+                level.map().player_position(),
+                BoxPositions::of(level.map()),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            )];
+            for _ in 0..3 {
+                states = states
+                    .iter()
+                    .flat_map(|state| state.successors(solver.analysis(), solver.context()))
+                    .collect();
+                for state in &states {
+                    // Replaying the reconstructed actions reaches the state.
+                    let actions = state.actions_with_moves(solver.analysis(), solver.context());
+                    let mut board = Board::with_map(level.map().clone());
+                    for action in &*actions {
+                        board.do_action(action.direction());
+                    }
+                    assert_eq!(board.actions(), &actions);
+                    assert_eq!(board.map.player_position(), state.player_position);
+                    assert_eq!(BoxPositions::of(&board.map), state.box_positions);
+                    assert_eq!(actions.pushes(), state.path.pushes());
+                    if strategy == Strategy::OptimalPushes {
+                        assert_eq!(state.path.moves(), state.path.pushes());
+                    } else {
+                        assert_eq!(actions.moves(), state.path.moves());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn path_finder_random_grids() {
+        // A xorshift generator, so that the grids are the same on every run.
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+        let dimensions = Vector2::new(13, 9);
+        // Reusing the path finder between grids checks that the squares of
+        // earlier calls are forgotten.
+        let mut path_finder = PathFinder::new(dimensions);
+        for _ in 0..50 {
+            let walls: HashSet<_> = (0..dimensions.y)
+                .flat_map(|y| (0..dimensions.x).map(move |x| Vector2::new(x, y)))
+                .filter(|_| random(10) < 3)
+                .collect();
+            let is_accessible = |position: Vector2<i32>| {
+                position.x >= 0
+                    && position.y >= 0
+                    && position.x < dimensions.x
+                    && position.y < dimensions.y
+                    && !walls.contains(&position)
+            };
+            for _ in 0..20 {
+                let mut random_position = || {
+                    Vector2::new(
+                        random(dimensions.x as usize) as i32,
+                        random(dimensions.y as usize) as i32,
+                    )
+                };
+                let from = random_position();
+                let to = random_position();
+                let expected = find_path(from, to, is_accessible);
+                let path = path_finder.find_path(from, to, is_accessible);
+                assert_eq!(path.as_ref().map(Vec::len), expected.as_ref().map(Vec::len));
+                let Some(path) = path else {
+                    continue;
+                };
+                assert_eq!(path.first(), Some(&from));
+                assert_eq!(path.last(), Some(&to));
+                assert!(path[1..].iter().all(|&position| is_accessible(position)));
+                assert!(path
+                    .windows(2)
+                    .all(|pos| Direction::try_from(pos[1] - pos[0]).is_ok()));
+            }
+        }
+
+        // Paths leaving the level are still found.
+        let is_accessible = |position: Vector2<i32>| {
+            position.y < 0 || position == Vector2::new(0, 0) || position == Vector2::new(2, 0)
+        };
+        for (to, len) in [(Vector2::new(1, -1), 3), (Vector2::new(2, 0), 5)] {
+            let path = path_finder.find_path(Vector2::new(0, 0), to, is_accessible);
+            assert_eq!(path, find_path(Vector2::new(0, 0), to, is_accessible));
+            assert_eq!(path.map(|path| path.len()), Some(len));
+        }
+    }
+
+    #[test]
+    fn state_hash_insertion_order() {
+        // Microban #54
+        let xsb = r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#;
+        // Walls added to the first row make the level too large for bit grids.
+        let (first_row, rows) = xsb.trim_start().split_once('\n').unwrap();
+        let padded_xsb = format!("{}{}\n{}", first_row, "#".repeat(40), rows);
+        for xsb in [xsb, &padded_xsb] {
+            let level = load_level(xsb);
+            let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+            let dimensions = solver.map.dimensions();
+            let random_state = RandomState::new();
+            let state_of = |box_positions| {
+                State::new(
+                    level.map().player_position(),
+                    box_positions,
+                    Path::default(),
+                    solver.analysis(),
+                    solver.context(),
+                )
+            };
+            let state = state_of(BoxPositions::of(level.map()));
+            let box_positions = state.box_positions.iter().collect_vec();
+            for order in box_positions
+                .iter()
+                .copied()
+                .permutations(box_positions.len())
+            {
+                // The same boxes, created at once and inserted one at a time
+                // with the first one removed and inserted again.
+                let mut inserted = BoxPositions::new(dimensions, []);
+                for position in &order {
+                    inserted.insert(*position);
+                }
+                inserted.remove(&order[0]);
+                inserted.insert(order[0]);
+                for box_positions in [BoxPositions::new(dimensions, order), inserted] {
+                    let reordered_state = state_of(box_positions);
+                    assert!(reordered_state == state);
+                    assert_eq!(
+                        random_state.hash_one(&reordered_state),
+                        random_state.hash_one(&state)
+                    );
+                    assert_eq!(
+                        reordered_state.normalized_hash(solver.analysis(), solver.context()),
+                        state.normalized_hash(solver.analysis(), solver.context())
+                    );
+                    assert_eq!(
+                        reordered_state.exact_hash(solver.analysis()),
+                        state.exact_hash(solver.analysis())
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bit_grid_fallback() {
+        let levels = [
+            // Microban #38
+            r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#,
+            // Microban #54
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        ];
+        for xsb in levels {
+            // Walls added to the first row make the level too large for bit
+            // grids, without moving any square.
+            let (first_row, rows) = xsb.trim_start().split_once('\n').unwrap();
+            let padded_xsb = format!("{}{}\n{}", first_row, "#".repeat(40), rows);
+            for (strategy, algorithm) in [
+                (Strategy::Fast, Algorithm::AStar),
+                (Strategy::OptimalPushMove, Algorithm::AStar),
+                (Strategy::Fast, Algorithm::Bidirectional),
+            ] {
+                let solve = |xsb| {
+                    let mut solver = Solver::with_options(
+                        load_level(xsb).map().clone(),
+                        SolverOptions {
+                            strategy,
+                            algorithm,
+                            ..Default::default()
+                        },
+                    );
+                    let solution = solver.search_for(Duration::from_secs(10));
+                    let has_bit_grids = solver.square_masks().is_some();
+                    (
+                        has_bit_grids,
+                        (solution, solver.expanded_states(), solver.stored_states()),
+                    )
+                };
+                let (has_bit_grids, bit_grid_search) = solve(xsb);
+                let (padded_has_bit_grids, fallback_search) = solve(&padded_xsb);
+                assert!(has_bit_grids);
+                assert!(!padded_has_bit_grids);
+                assert_eq!(
+                    bit_grid_search, fallback_search,
+                    "{:?} {:?}",
+                    strategy, algorithm
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cell_index_fallback() {
+        // Microban #38
+        let xsb = r#"
+##########
+#        #
+# ##.### #
+# # $$ . #
+# . @$## #
+#####    #
+    ######
+"#;
+        // Walls added to the first row give the level more squares than cell
+        // indices, without moving any square.
+        let (first_row, rows) = xsb.trim_start().split_once('\n').unwrap();
+        let padded_xsb = format!("{}{}\n{}", first_row, "#".repeat(10_000), rows);
+        let padded_level = load_level(&padded_xsb);
+        assert!(!CellIndex::fits(padded_level.map().dimensions()));
+
+        let box_positions = BoxPositions::of(padded_level.map());
+        let mut moved_box_positions = box_positions.clone();
+        assert!(moved_box_positions.remove(&Vector2::new(4, 3)));
+        assert!(moved_box_positions.insert(Vector2::new(4, 2)));
+        assert!(!moved_box_positions.contains(&Vector2::new(4, 3)));
+        assert!(!moved_box_positions.contains(&Vector2::new(-1, 3)));
+        assert!(moved_box_positions.remove(&Vector2::new(4, 2)));
+        assert!(moved_box_positions.insert(Vector2::new(4, 3)));
+        assert_eq!(moved_box_positions, box_positions);
+        assert!(box_positions
+            .iter()
+            .tuple_windows()
+            .all(|(a, b)| (a.y, a.x) < (b.y, b.x)));
+
+        let solve = |xsb, verify_hashes| {
+            let mut solver = Solver::with_options(
+                load_level(xsb).map().clone(),
+                SolverOptions {
+                    verify_hashes,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10));
+            (solution, solver.expanded_states())
+        };
+        assert_eq!(solve(xsb, false), solve(&padded_xsb, false));
+        // Visited states are only compared in full by their cell indices.
+        assert!(matches!(
+            solve(&padded_xsb, true).0,
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn prove_optimal() {
+        // Microban #1.
+        let level = load_level(
+            r#"
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let timeout = Duration::from_secs(10);
+        let optimal: Actions = "dlUrrrdLullddrUluRuulDrddrruLdlUU".parse().unwrap();
+        assert_eq!(
+            solver.prove_optimal(&optimal, Metric::Moves, timeout),
+            Ok(Verdict::Optimal)
+        );
+        assert_eq!(
+            solver.prove_optimal(&optimal, Metric::Pushes, timeout),
+            Ok(Verdict::Optimal)
+        );
+
+        // Two extra moves do not change the pushes.
+        let padded: Actions = "rldlUrrrdLullddrUluRuulDrddrruLdlUU".parse().unwrap();
+        let Ok(Verdict::Improved(solution)) = solver.prove_optimal(&padded, Metric::Moves, timeout)
+        else {
+            panic!("padded solution was not improved");
+        };
+        assert!(verify_solution(&level, &solution));
+        assert_eq!((solution.moves(), solution.pushes()), (33, 8));
+        assert_eq!(
+            solver.prove_optimal(&padded, Metric::Pushes, timeout),
+            Ok(Verdict::Optimal)
+        );
+
+        let unsolved: Actions = "dlUrrrdLullddrUluRuulDrddrruLdlU".parse().unwrap();
+        assert!(matches!(
+            solver.prove_optimal(&unsolved, Metric::Moves, timeout),
+            Err(SolveError::InvalidSolution(_))
+        ));
+        let blocked: Actions = "U".parse().unwrap();
+        assert_eq!(
+            solver.prove_optimal(&blocked, Metric::Moves, timeout),
+            Err(SolveError::InvalidSolution(
+                "step 0 is a push but there is no box at (2, 2)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn reopen_cheaper_states() {
+        // Microban II #59. The lower bound of the pattern database can drop
+        // by more than one per push, so some states are reached with fewer
+        // pushes by another push order after they have been expanded.
+        let level = load_level(
+            r#"
+ ######
+ #    ###
+ # $  $ #
+ ##$# # #
+### #@# ##
+# . . .  #
+#   ##   #
+##########
+"#,
+        );
+        let solve = |strategy| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    heuristics: vec![HeuristicKind::PatternDatabase { group_size: 2 }],
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(60)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            solution
+        };
+        assert_eq!(solve(Strategy::OptimalPushes).pushes(), 22);
+        let solution = solve(Strategy::OptimalPushMove);
+        assert_eq!((solution.pushes(), solution.moves()), (22, 107));
+        let solution = solve(Strategy::OptimalMovePush);
+        assert_eq!(solution.moves(), 107);
+    }
+
+    #[test]
+    fn stall_policy() {
+        // Microban #35, which `OptimalPushMove` takes over 20000 expansions
+        // to solve.
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let solver = |stall_policy| {
+            Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    stall_policy,
+                    max_expansions: Some(1000),
+                    ..Default::default()
+                },
+            )
+        };
+        let mut optimal_solver = solver(None);
+        assert!(matches!(
+            optimal_solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::ExpansionLimit { expanded: 1000, .. })
+        ));
+        assert_eq!(optimal_solver.stall_fallback(), None);
+
+        let mut stalled_solver = solver(Some(StallPolicy {
+            expansions: 20,
+            fallback: Strategy::Fast,
+        }));
+        let solution = stalled_solver.search_for(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution));
+        let expanded_states = stalled_solver.stall_fallback().unwrap();
+        assert!(expanded_states >= 20 && expanded_states < stalled_solver.expanded_states());
+        assert_eq!(stalled_solver.strategy(), Strategy::Fast);
+        stalled_solver.restart();
+        assert_eq!(stalled_solver.strategy(), Strategy::OptimalPushMove);
+        assert_eq!(stalled_solver.stall_fallback(), None);
+
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushes,
+                stall_policy: Some(StallPolicy {
+                    expansions: 20,
+                    fallback: Strategy::Fast,
+                }),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn forced_pushes() {
+        // The box can only be pushed right until it leaves the corridor.
+        let level = load_level(
+            r#"
+##########
+####     #
+#@$    . #
+####     #
+##########
+"#,
+        );
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalMovePush,
+            Strategy::OptimalPushMove,
+            Strategy::OptimalPushes,
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let initial_state = solver.best_state().unwrap();
+            assert_eq!(initial_state.player_position, Vector2::new(4, 2));
+            assert_eq!(
+                initial_state.box_positions,
+                BoxPositions::new(solver.map.dimensions(), [Vector2::new(5, 2)])
+            );
+            assert_eq!(
+                initial_state
+                    .actions_with_moves(solver.analysis(), solver.context())
+                    .to_string(),
+                "RRR"
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+            assert_eq!(solution.to_string(), "RRRRR");
+        }
+
+        // The forced push solves the level.
+        let level = load_level(
+            r#"
+#####
+#@$.#
+#####
+"#,
+        );
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert!(solver
+            .best_state()
+            .unwrap()
+            .is_solved(solver.analysis(), solver.context()));
+        assert_eq!(
+            solver
+                .search_for(Duration::from_secs(10))
+                .unwrap()
+                .to_string(),
+            "R"
+        );
+
+        // The cuts only let the box pushed last be pushed after the first
+        // pushes, but the other box can be pushed as well.
+        let level = load_level(
+            r#"
+########
+#@$ .  #
+### ## #
+  #  $.#
+  ######
+"#,
+        );
+        for relevance_cuts in [
+            None,
+            Some(RelevanceCuts {
+                threshold: 0,
+                window: 1,
+            }),
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    relevance_cuts,
+                    pi_corral_pruning: true,
+                    ..Default::default()
+                },
+            );
+            let initial_state = solver.best_state().unwrap();
+            assert_eq!(
+                initial_state
+                    .actions_with_moves(solver.analysis(), solver.context())
+                    .to_string(),
+                "RR"
+            );
+            // The forced pushes are not part of the search.
+            assert_eq!(solver.generated_states(), 0);
+            assert_eq!(solver.cut_pushes(), 0);
+            assert_eq!(solver.pi_corrals(), 0);
+            assert_eq!(solver.statistics().pruning, Default::default());
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            assert!(verify_solution(&level, &solution));
+        }
+    }
+
+    #[test]
+    fn solve_for() {
+        // Microban #7
+        let level = load_level(
+            r#"
+#######
+#     #
+# .$. #
+# $.$ #
+# .$. #
+# $.$ #
+#  @  #
+#######
+"#,
+        );
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(level.map().clone(), options.clone());
+        let mut slices = 0;
+        let solution = loop {
+            slices += 1;
+            match solver.solve_for(Duration::from_millis(1)) {
+                SolveProgress::Running {
+                    visited,
+                    open,
+                    best_lower_bound,
+                } => {
+                    assert!(visited > 0 && open > 0);
+                    assert!(
+                        best_lower_bound
+                            <= solver
+                                .best_state()
+                                .unwrap()
+                                .lower_bound(solver.analysis(), solver.context())
+                                .unwrap()
+                    );
+                }
+                SolveProgress::Solved(solution) => break solution,
+                SolveProgress::Failed(error) => panic!("{:?}", error),
+            }
+        };
+        assert!(slices > 1);
+        let mut one_shot_solver = Solver::with_options(level.map().clone(), options);
+        assert_eq!(
+            one_shot_solver.search_for(Duration::from_secs(10)),
+            Ok(solution)
+        );
+        assert_eq!(one_shot_solver.expanded_states(), solver.expanded_states());
+    }
+
+    #[test]
+    fn shared_level_analysis() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        let options = SolverOptions::default();
+        let analysis = Arc::new(LevelAnalysis::new(level.map().clone(), &options));
+        let solutions: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = [Strategy::Fast, Strategy::OptimalPushes]
+                .into_iter()
+                .map(|strategy| {
+                    let analysis = analysis.clone();
+                    let options = SolverOptions {
+                        strategy,
+                        ..options.clone()
+                    };
+                    scope.spawn(move || {
+                        let mut search = Search::new(&analysis, options);
+                        search.search_for(Duration::from_secs(10))
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        // Both threads used the same analysis, which is not copied by solvers.
+        let solver = Solver::with_analysis(analysis.clone(), options.clone());
+        assert!(Arc::ptr_eq(solver.analysis(), &analysis));
+        assert!(std::ptr::eq(solver.lower_bounds(), analysis.lower_bounds()));
+        for (strategy, solution) in [Strategy::Fast, Strategy::OptimalPushes]
+            .into_iter()
+            .zip(solutions)
+        {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..options.clone()
+                },
+            );
+            assert_eq!(solution, solver.search_for(Duration::from_secs(10)));
+        }
+
+        let mut solver = Solver::with_analysis(
+            analysis,
+            SolverOptions {
+                lower_bound_method: LowerBoundMethod::ManhattanDistance,
+                ..options
+            },
+        );
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(1)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn lazy_successors() {
+        let level = load_level(
+            r#"
+#########
+#@$.    #
+#       #
+#  *  * #
+#       #
+#########
+"#,
+        );
+        let options = SolverOptions {
+            strategy: Strategy::Fast,
+            ..Default::default()
+        };
+        let solver = Solver::with_options(level.map().clone(), options.clone());
+        let state = solver.best_state().unwrap().clone();
+        let solved_state = state
+            .successors_iter(solver.analysis(), solver.context())
+            .find(|successor| successor.is_solved(solver.analysis(), solver.context()))
+            .unwrap();
+        let lazy_constructions = solved_state.sequence() - state.sequence();
+
+        let solver = Solver::with_options(level.map().clone(), options.clone());
+        let successors = state.successors(solver.analysis(), solver.context());
+        let eager_constructions = successors
+            .iter()
+            .map(|successor| successor.sequence() - state.sequence())
+            .max()
+            .unwrap();
+        println!("State constructions: {lazy_constructions} lazily, {eager_constructions} eagerly");
+        assert!(lazy_constructions < eager_constructions);
+        assert_eq!(
+            state
+                .successors_iter(solver.analysis(), solver.context())
+                .count(),
+            successors.len()
+        );
+
+        // The search stops at the solved successor.
+        let mut solver = Solver::with_options(level.map().clone(), options);
+        let solution = solver.search_for(Duration::from_secs(1)).unwrap();
+        assert_eq!(solution.to_string(), "R");
+        assert_eq!(solver.expanded_states(), 1);
+    }
+
+    #[test]
+    fn normalized_hash_matches_key() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert_eq!(solver.symmetries().len(), 1);
+        let width = level.map().dimensions().x;
+        let zobrist_keys = solver.zobrist_keys();
+        // A xorshift generator, so that the walks are the same on every run.
+        let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut random = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+        for _ in 0..20 {
+            let mut state = solver.best_state().unwrap().clone();
+            for _ in 0..30 {
+                // The hash is calculated from the boxes and the normalized
+                // player position of the key, without building a normalized
+                // state.
+                let (box_cells, player_cell) =
+                    state.normalized_key(solver.analysis(), solver.context());
+                let hash = zobrist_keys
+                    .boxes_hash(box_cells.iter().map(|cell| cell.position(width)))
+                    ^ zobrist_keys.player_key(player_cell.position(width));
+                assert_eq!(
+                    state.normalized_hash(solver.analysis(), solver.context()),
+                    hash
+                );
+
+                // Moving the player within its area keeps the hash and the
+                // key.
+                let area = state.player_reachable_area(solver.analysis(), solver.context());
+                let player_position = area
+                    .iter()
+                    .sorted_by_key(|position| (position.y, position.x))
+                    .nth(random(area.len()))
+                    .copied()
+                    .unwrap();
+                let moved_state = State::new(
+                    player_position,
+                    state.box_positions.clone(),
+                    Path::default(),
+                    solver.analysis(),
+                    solver.context(),
+                );
+                assert_eq!(
+                    moved_state.normalized_hash(solver.analysis(), solver.context()),
+                    hash
+                );
+                assert_eq!(
+                    moved_state.normalized_key(solver.analysis(), solver.context()),
+                    (box_cells, player_cell)
+                );
+
+                let mut successors = state.successors(solver.analysis(), solver.context());
+                if successors.is_empty() {
+                    break;
+                }
+                state = successors.swap_remove(random(successors.len()));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "count-allocations")]
+    fn dedup_without_allocations() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let mut states = vec![solver.best_state().unwrap().clone()];
+        let mut successors = Vec::new();
+        while successors.len() < 1000 && !states.is_empty() {
+            let state = states.pop().unwrap();
+            for successor in state.successors(solver.analysis(), solver.context()) {
+                successors.push(successor.clone());
+                states.push(successor);
+            }
+        }
+        let mut visited = VisitedStates::new(VisitedFilter::default());
+        for successor in &successors {
+            visited.insert(successor.normalized_hash(solver.analysis(), solver.context()));
+        }
+
+        // Checking the successors again, like states reached from another
+        // parent, only looks up the hashes kept by the states.
+        let allocations = ALLOCATIONS.with(Cell::get);
+        let start = std::time::Instant::now();
+        let found = successors
+            .iter()
+            .filter(|successor| {
+                !visited.insert(successor.normalized_hash(solver.analysis(), solver.context()))
+            })
+            .count();
+        let elapsed = start.elapsed();
+        assert_eq!(ALLOCATIONS.with(Cell::get), allocations);
+        assert_eq!(found, successors.len());
+        println!(
+            "{} dedup checks: {:.1} ns/check",
+            successors.len(),
+            elapsed.as_nanos() as f64 / successors.len() as f64
+        );
+    }
+
+    #[test]
+    fn bucket_queue_order() {
+        // Microban #54
+        let level = load_level(
+            r#"
+      ######
+      #    #
+  ##### .  #
+###  ###.  #
+# $  $  . ##
+# @$$ # . #
+##    #####
+ ######
+"#,
+        );
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushes,
+            Strategy::Mixed,
+            Strategy::OptimalPushMove,
+        ] {
+            let solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            // Both queues pop the states in the same order, so searches find
+            // the same solutions whichever queue is used.
+            let mut heap = OpenList::new(QueueKind::BinaryHeap);
+            let mut buckets = OpenList::new(QueueKind::Buckets);
+            let initial_state = solver.best_state().unwrap().clone();
+            heap.push(initial_state.clone());
+            buckets.push(initial_state);
+            let mut visited = HashSet::new();
+            let mut solution = None;
+            while let Some(state) = heap.pop() {
+                let bucket_state = buckets.pop().unwrap();
+                assert_eq!(bucket_state.sequence(), state.sequence());
+                assert_eq!(heap.len(), buckets.len());
+                if state.is_solved(solver.analysis(), solver.context()) {
+                    solution = Some(state.actions_with_moves(solver.analysis(), solver.context()));
+                    break;
+                }
+                for successor in state.successors(solver.analysis(), solver.context()) {
+                    if visited
+                        .insert(successor.normalized_hash(solver.analysis(), solver.context()))
+                    {
+                        heap.push(successor.clone());
+                        buckets.push(successor);
+                    }
+                }
+            }
+            assert!(solution.is_some());
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn open_list_pops() {
+        // Box World #24
+        let levels =
+            Level::load_from_str(&fs::read_to_string("assets/levels/box_world_100.xsb").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushMove,
+            Strategy::OptimalPushes,
+            Strategy::Mixed,
+        ] {
+            let solver = Solver::with_options(
+                levels[23].map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let mut states = vec![solver.best_state().unwrap().clone()];
+            let mut generated = Vec::new();
+            while generated.len() < 200_000 && !states.is_empty() {
+                let state = states.pop().unwrap();
+                for successor in state.successors(solver.analysis(), solver.context()) {
+                    generated.push(successor.clone());
+                    states.push(successor);
+                }
+            }
+
+            // Half of the states are pushed before popping starts, and the
+            // pops are then interleaved with the remaining pushes, like a
+            // search expanding states.
+            let pops_per_second = |kind| {
+                let mut open_list = OpenList::new(kind);
+                let (first, second) = generated.split_at(generated.len() / 2);
+                for state in first {
+                    open_list.push(state.clone());
+                }
+                let start = std::time::Instant::now();
+                let mut pops = 0;
+                for state in second {
+                    open_list.push(state.clone());
+                    pops += usize::from(open_list.pop().is_some());
+                }
+                while open_list.pop().is_some() {
+                    pops += 1;
+                }
+                assert_eq!(pops, generated.len());
+                pops as f64 / start.elapsed().as_secs_f64()
+            };
+            let heap = pops_per_second(QueueKind::BinaryHeap);
+            let buckets = pops_per_second(QueueKind::Buckets);
+            println!(
+                "{:?}: binary heap {:.2}M pops/s, buckets {:.2}M pops/s",
+                strategy,
+                heap / 1e6,
+                buckets / 1e6
+            );
+        }
+    }
+
+    #[test]
+    fn duplicates_skipped_when_generated() {
+        // The boxes can be pushed in any order, so most states are reached
+        // from several parents.
+        let level = load_level(
+            r#"
+########
+#      #
+# $ $ .#
+#@ $  .#
+#     .#
+########
+"#,
+        );
+        for strategy in [Strategy::Fast, Strategy::OptimalPushes] {
+            let options = SolverOptions {
+                strategy,
+                ..Default::default()
+            };
+            let mut solver = Solver::with_options(level.map().clone(), options.clone());
+            let mut expanded = HashSet::new();
+            let solution = loop {
+                match solver.step().unwrap() {
+                    SearchStep::Expanded(expanded_state) => {
+                        // Each state is expanded once.
+                        let state = State::new(
+                            expanded_state.player_position,
+                            expanded_state.box_positions,
+                            Path::default(),
+                            solver.analysis(),
+                            solver.context(),
+                        );
+                        assert!(expanded
+                            .insert(state.normalized_hash(solver.analysis(), solver.context())));
+                    }
+                    SearchStep::Solved(solution) => break solution,
+                    SearchStep::Exhausted => panic!("no solution"),
+                }
+            };
+            assert!(solver.duplicate_states() > 0);
+            // With `Fast`, the expansion generating the solved state returns
+            // the solution instead.
+            assert!(solver.expanded_states() - expanded.len() <= 1);
+
+            let mut search_solver = Solver::with_options(level.map().clone(), options);
+            let search_solution = search_solver.search_for(Duration::from_secs(10)).unwrap();
+            assert_eq!(solution.pushes(), search_solution.pushes());
+            assert_eq!(search_solver.duplicate_states(), solver.duplicate_states());
+        }
+    }
+
+    #[test]
+    fn pressure_relief() {
+        // Microban #3, whose open states exceed the threshold after a few
+        // expansions.
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let solve = |pressure_relief| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    pressure_relief,
+                    ..Default::default()
+                },
+            );
+            let result = solver.search_for(Duration::from_secs(10));
+            (solver, result)
+        };
+        let (solver, solution) = solve(None);
+        let solution = solution.unwrap();
+        assert_eq!(solver.statistics().pressure_relief, None);
+        assert_eq!(solver.statistics().pressure_reliefs, 0);
+
+        let relief = |policy| {
+            Some(PressureRelief {
+                max_open_states: 8,
+                policy,
+            })
+        };
+        // Preferring deeper states and spilling states keep the order of
+        // priorities, so the solution is still optimal.
+        for policy in [
+            ReliefPolicy::PreferDeeper,
+            ReliefPolicy::Spill { percent: 50 },
+        ] {
+            let (relieved_solver, relieved_solution) = solve(relief(policy));
+            let relieved_solution = relieved_solution.unwrap();
+            assert!(verify_solution(&level, &relieved_solution));
+            assert_eq!(
+                (relieved_solution.pushes(), relieved_solution.moves()),
+                (solution.pushes(), solution.moves())
+            );
+            assert_eq!(relieved_solver.optimality_bound(), Some(1.0));
+            let statistics = relieved_solver.statistics();
+            assert_eq!(statistics.pressure_relief, Some(policy));
+            assert!(statistics.relieved_states > 0);
+            if policy == ReliefPolicy::PreferDeeper {
+                assert_eq!(statistics.pressure_reliefs, 1);
+            }
+            assert_eq!(relieved_solver.discarded_states(), 0);
+        }
+
+        // Pruning gives up optimality.
+        let (pruned_solver, pruned_solution) = solve(relief(ReliefPolicy::Prune { percent: 50 }));
+        let statistics = pruned_solver.statistics();
+        assert_eq!(
+            statistics.pressure_relief,
+            Some(ReliefPolicy::Prune { percent: 50 })
+        );
+        assert!(statistics.pressure_reliefs > 0);
+        assert_eq!(pruned_solver.discarded_states(), statistics.relieved_states);
+        assert_eq!(pruned_solver.optimality_bound(), None);
+        let pruned_solution = pruned_solution.unwrap();
+        assert!(verify_solution(&level, &pruned_solution));
+        assert!(pruned_solution.pushes() >= solution.pushes());
+
+        for policy in [
+            ReliefPolicy::Prune { percent: 0 },
+            ReliefPolicy::Spill { percent: 100 },
+        ] {
+            assert!(matches!(
+                solve(relief(policy)).1,
+                Err(SolveError::InvalidOptions(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn pruning_statistics() {
+        // Microban #53, where pushing either lower box up freezes it next to
+        // the wall in the middle.
+        let level = load_level(
+            r#"
+ #####
+##. .##
+# * * #
+#  #  #
+# $ $ #
+## @ ##
+ #####
+"#,
+        );
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        let pruning = solver.statistics().pruning;
+        assert!(pruning.freeze_deadlocks > 0);
+        assert!(pruning.blocked_pushes > 0);
+        assert!(pruning.unreachable_pushes > 0);
+        assert_eq!(pruning.duplicate_states, solver.duplicate_states());
+
+        // The counters are reset for each run.
+        solver.restart();
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        assert_eq!(solver.statistics().pruning, pruning);
+
+        // Microban #3, whose boxes are pushed through a tunnel and into
+        // deadlock patterns.
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        let pruning = solver.statistics().pruning;
+        assert!(pruning.tunnel_pushes > 0);
+        assert!(pruning.closet_pushes > 0);
+        assert_eq!(pruning.pattern_deadlocks, solver.pattern_deadlocks());
+        assert!(pruning.pattern_deadlocks > 0);
+        assert_eq!(pruning.freeze_deadlocks, 0);
+    }
+
+    #[test]
+    fn solver_builder() {
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let mut solver = Solver::builder(level.map().clone())
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        // Only the timeout differs from the default options.
+        assert_eq!(
+            *solver.options(),
+            SolverOptions {
+                timeout: Some(Duration::from_secs(10)),
+                ..Default::default()
+            }
+        );
+        let solution = solver.solve().unwrap();
+        let mut default_solver =
+            Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert_eq!(
+            default_solver.search_for(Duration::from_secs(10)).unwrap(),
+            solution
+        );
+        // The deprecated constructor and search of the former API still
+        // forward to the same solver.
+        #[allow(deprecated)]
+        let former_solution = Solver::new(
+            level.map().clone(),
+            Strategy::default(),
+            LowerBoundMethod::default(),
+        )
+        .search(Duration::from_secs(10))
+        .unwrap();
+        assert_eq!(former_solution, solution);
+        #[allow(deprecated)]
+        let former_solution = {
+            let mut solver = Solver::from(level.clone());
+            solver.initial(Strategy::default());
+            solver.solve().unwrap()
+        };
+        assert_eq!(former_solution, solution);
+
+        let mut solver = Solver::builder(level.map().clone())
+            .strategy(Strategy::OptimalPushes)
+            .timeout(Duration::from_secs(10))
+            .threads(2)
+            .build()
+            .unwrap();
+        assert_eq!(solver.strategy(), Strategy::OptimalPushes);
+        assert_eq!(solver.options().threads, 2);
+        let mut single_solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushes,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            solver.solve().unwrap().pushes(),
+            single_solver
+                .search_for(Duration::from_secs(10))
+                .unwrap()
+                .pushes()
+        );
+
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            ..Default::default()
+        };
+        let solver = Solver::builder(level.map().clone())
+            .strategy(Strategy::Greedy)
+            .options(options.clone())
+            .max_expansions(100)
+            .max_memory_bytes(1 << 30)
+            .build()
+            .unwrap();
+        assert_eq!(
+            *solver.options(),
+            SolverOptions {
+                max_expansions: Some(100),
+                max_memory_bytes: Some(1 << 30),
+                ..options
+            }
+        );
+
+        for builder in [
+            // Nothing limits the search.
+            Solver::builder(level.map().clone()),
+            Solver::builder(level.map().clone()).timeout(Duration::ZERO),
+            Solver::builder(level.map().clone())
+                .timeout(Duration::ZERO)
+                .max_expansions(100),
+            Solver::builder(level.map().clone())
+                .timeout(Duration::from_secs(10))
+                .threads(0),
+            Solver::builder(level.map().clone())
+                .timeout(Duration::from_secs(10))
+                .strategy(Strategy::OptimalPushes)
+                .algorithm(Algorithm::Beam { width: 10 }),
+        ] {
+            assert!(matches!(
+                builder.build(),
+                Err(SolveError::InvalidOptions(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn progress_callback() {
+        // Microban #35, which `OptimalPushMove` takes over 20000 expansions
+        // to solve.
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let solve = |progress_interval| {
+            let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let mut solver = Solver::builder(level.map().clone())
+                .strategy(Strategy::OptimalPushMove)
+                .max_expansions(100)
+                .progress_interval(progress_interval)
+                .on_progress({
+                    let reports = reports.clone();
+                    move |progress| reports.lock().unwrap().push(*progress)
+                })
+                .build()
+                .unwrap();
+            assert!(matches!(
+                solver.solve(),
+                Err(SolveError::ExpansionLimit { expanded: 100, .. })
+            ));
+            let reports = reports.lock().unwrap().clone();
+            reports
+        };
+
+        let reports = solve(ProgressInterval::Expansions(10));
+        assert_eq!(
+            reports
+                .iter()
+                .map(|progress| progress.expanded_states)
+                .collect_vec(),
+            (1..=10).map(|i| i * 10).collect_vec()
+        );
+        for (previous, progress) in reports.iter().tuple_windows() {
+            assert!(progress.visited_states > previous.visited_states);
+            assert!(progress.elapsed >= previous.elapsed);
+            assert!(progress.best_lower_bound <= previous.best_lower_bound);
+        }
+        assert!(reports.iter().all(|progress| progress.open_states > 0));
+        assert!(reports.iter().any(|progress| progress.depth > 0));
+
+        // The time is read every few expansions.
+        assert!(!solve(ProgressInterval::Time(Duration::ZERO)).is_empty());
+        assert!(solve(ProgressInterval::Time(Duration::from_secs(3600))).is_empty());
+    }
+
+    /// Solves a level, between markers on the standard output. Only run by
+    /// [`silent_solver`] in a child process.
+    #[test]
+    #[ignore]
+    fn silent_solver_child() {
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        print!("<solver>");
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                progress_interval: ProgressInterval::Expansions(1),
+                ..Default::default()
+            },
+        );
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        print!("</solver>");
+    }
+
+    #[test]
+    fn silent_solver() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "test::tests::silent_solver_child",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        // The solver writes nothing without a progress callback.
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("<solver></solver>"), "{stdout}");
+    }
+
+    #[test]
+    fn solve_events() {
+        // Microban #35
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let (sender, receiver) = event_channel(1024);
+        let solver = Solver::builder(level.map().clone())
+            .timeout(Duration::from_secs(60))
+            .progress_interval(ProgressInterval::Expansions(1))
+            .events(sender);
+        let solver_thread = std::thread::spawn(move || solver.build().unwrap().solve());
+
+        let mut events = Vec::new();
+        while let Some(event) = receiver.recv() {
+            events.push(event);
+        }
+        let solution = solver_thread.join().unwrap().unwrap();
+        assert_eq!(receiver.dropped(), 0);
+
+        assert_eq!(events.first(), Some(&SolveEvent::Started));
+        let visited = events
+            .iter()
+            .filter_map(|event| match event {
+                SolveEvent::Progress { visited, .. } => Some(*visited),
+                _ => None,
+            })
+            .collect_vec();
+        assert!(!visited.is_empty());
+        assert!(visited.iter().tuple_windows().all(|(a, b)| a <= b));
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                SolveEvent::SolutionFound {
+                    moves: solution.moves(),
+                    pushes: solution.pushes()
+                },
+                SolveEvent::Finished { error: None }
+            ]
+        );
+
+        // A slow receiver misses the oldest events, but not the last ones.
+        let (sender, receiver) = event_channel(1);
+        let mut solver = Solver::builder(level.map().clone())
+            .strategy(Strategy::OptimalPushMove)
+            .max_expansions(1000)
+            .progress_interval(ProgressInterval::Expansions(10))
+            .events(sender)
+            .build()
+            .unwrap();
+        let error = solver.solve().unwrap_err();
+        drop(solver);
+        assert!(receiver.dropped() > 0);
+        assert_eq!(
+            receiver.try_recv(),
+            Some(SolveEvent::Finished { error: Some(error) })
+        );
+        assert_eq!(receiver.recv(), None);
+
+        // The receiver waits for the events of a solver created without
+        // them, until the timeout.
+        let (sender, receiver) = event_channel(16);
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(10)), None);
+        solver.send_events(sender);
+        solver.search_for(Duration::from_secs(10)).unwrap();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(10)),
+            Some(SolveEvent::Started)
+        );
+    }
+
+    #[test]
+    fn solve_statistics() {
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushMove,
+            Strategy::OptimalMovePush,
+        ] {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy,
+                    ..Default::default()
+                },
+            );
+            let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+            let statistics = solver.statistics();
+            assert_eq!(
+                statistics.solution,
+                Some((solution.moves(), solution.pushes()))
+            );
+            assert!(solution.pushes() <= solution.moves());
+            assert!(statistics.expanded_states <= statistics.generated_states);
+            assert_eq!(statistics.generated_states, solver.generated_states());
+            assert!(statistics.pruning.duplicate_states <= statistics.generated_states);
+            assert!(statistics.open_states <= statistics.peak_open_states);
+            assert!(statistics.visited_states <= statistics.peak_visited_states);
+            assert!(statistics.peak_visited_states <= statistics.generated_states + 1);
+            let initial_lower_bound = statistics.initial_lower_bound.unwrap();
+            assert_eq!(Some(initial_lower_bound), solver.estimated_pushes());
+            assert!(initial_lower_bound <= solution.pushes());
+            assert!(statistics.search_time > Duration::ZERO);
+            assert_eq!(statistics.optimality_guaranteed, strategy != Strategy::Fast);
+        }
+
+        // The statistics of a failed search are kept up to the failure.
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                max_expansions: Some(10),
+                ..Default::default()
+            },
+        );
+        let Err(SolveError::ExpansionLimit {
+            expanded: 10,
+            statistics: error_statistics,
+        }) = solver.search_for(Duration::from_secs(10))
+        else {
+            panic!("the search should reach the expansion limit");
+        };
+        let statistics = solver.statistics();
+        assert_eq!(*error_statistics, statistics);
+        assert_eq!(statistics.expanded_states, 10);
+        assert_eq!(statistics.solution, None);
+        assert!(statistics.generated_states >= 10);
+        assert!(statistics.peak_open_states > 0);
+        assert!(statistics.initial_lower_bound.is_some());
+    }
+
+    #[test]
+    fn cancellation() {
+        // A room whose push optimal solution takes far longer than the
+        // cancellation to find.
+        let level = load_level(
+            r#"
+############
+#          #
+# $ $ $ $  #
+#          #
+#  $ $ $ $ #
+#          #
+#  ......  #
+#   ..     #
+#         @#
+############
+"#,
+        );
+        for threads in [1, 2] {
+            let cancellation = CancellationToken::new();
+            let mut solver = Solver::builder(level.map().clone())
+                .strategy(Strategy::OptimalPushes)
+                .timeout(Duration::from_secs(60))
+                .threads(threads)
+                .cancellation(cancellation.clone())
+                .build()
+                .unwrap();
+            let timer = std::time::Instant::now();
+            let canceller = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                cancellation.cancel();
+            });
+            let result = solver.solve();
+            let elapsed = timer.elapsed();
+            canceller.join().unwrap();
+            let Err(SolveError::Cancelled { statistics }) = result else {
+                panic!("not cancelled: {result:?}");
+            };
+            assert!(elapsed >= Duration::from_millis(50));
+            assert!(elapsed < Duration::from_millis(500), "{elapsed:?}");
+            assert_eq!(*statistics, solver.statistics());
+            assert!(statistics.expanded_states > 0);
+
+            // The search stays cancelled.
+            assert!(matches!(solver.solve(), Err(SolveError::Cancelled { .. })));
+        }
+
+        // A token set on an existing solver cancels its next search.
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let cancellation = CancellationToken::new();
+        solver.set_cancellation(cancellation.clone());
+        cancellation.cancel();
+        assert!(matches!(
+            solver.search_for(Duration::from_secs(60)),
+            Err(SolveError::Cancelled { .. })
+        ));
+
+        // The analysis of the level is cancelled as well.
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let analysis = LevelAnalysis::new(level.map().clone(), &SolverOptions::default());
+        assert!(analysis.try_goal_distances(&cancellation).is_none());
+        assert!(matches!(
+            Solver::builder(level.map().clone())
+                .timeout(Duration::from_secs(60))
+                .cancellation(cancellation)
+                .build(),
+            Err(SolveError::Cancelled { .. })
+        ));
+        assert_eq!(
+            analysis.try_goal_distances(&CancellationToken::new()),
+            Some(analysis.goal_distances())
+        );
+    }
+
+    #[test]
+    fn reset_strategy() {
+        // Microban #35
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        let lower_bounds: *const _ = solver.lower_bounds();
+        let analysis = solver.analysis().clone();
+        // The optimal search times out, and is discarded by the reset.
+        assert!(matches!(
+            solver.search_for(Duration::from_millis(1)),
+            Err(SolveError::Timeout { .. })
+        ));
+        assert!(solver.expanded_states() > 0);
+
+        solver.reset(Strategy::Fast);
+        assert_eq!(solver.strategy(), Strategy::Fast);
+        assert_eq!(solver.expanded_states(), 0);
+        assert_eq!(solver.statistics(), SolveStatistics::default());
+        // The analysis of the level, with its lower bounds, is not redone.
+        assert!(Arc::ptr_eq(solver.analysis(), &analysis));
+        assert!(std::ptr::eq(solver.lower_bounds(), lower_bounds));
+
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        let mut fast_solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert_eq!(
+            fast_solver.search_for(Duration::from_secs(10)).unwrap(),
+            solution
+        );
+        assert_eq!(solver.expanded_states(), fast_solver.expanded_states());
+
+        // Back to the optimal strategy, the search starts over.
+        solver.reset(Strategy::OptimalPushMove);
+        assert_eq!(solver.strategy(), Strategy::OptimalPushMove);
+        let mut optimal_solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            solver.search_for(Duration::from_secs(10)).unwrap(),
+            optimal_solver.search_for(Duration::from_secs(10)).unwrap()
+        );
+        assert_eq!(solver.expanded_states(), optimal_solver.expanded_states());
+        assert!(std::ptr::eq(solver.lower_bounds(), lower_bounds));
+    }
+
+    #[test]
+    fn level_validation() {
+        let try_new = |xsb: &str| {
+            Solver::try_new(load_level(xsb).map().clone(), SolverOptions::default()).map(|_| ())
+        };
+        let level = load_level(
+            r#"
+#####
+#@$.#
+#####
+"#,
+        );
+        assert!(Solver::try_from(level.clone()).is_ok());
+
+        let mut map = level.map().clone();
+        let player_position = map.player_position();
+        map[player_position].remove(Tiles::Player);
+        assert_eq!(
+            Solver::try_new(map, SolverOptions::default()).err(),
+            Some(LevelError::NoPlayer)
+        );
+
+        assert_eq!(
+            try_new(
+                r#"
+#####
+#@ .#
+#####
+"#
+            ),
+            Err(LevelError::NoBoxes)
+        );
+
+        assert_eq!(
+            try_new(
+                r#"
+######
+#@$$.#
+######
+"#
+            ),
+            Err(LevelError::BoxGoalMismatch { boxes: 2, goals: 1 })
+        );
+
+        // The player is sealed in a pocket.
+        assert_eq!(
+            try_new(
+                r#"
+#######
+#@#$ .#
+#######
+"#
+            ),
+            Err(LevelError::UnreachableBox {
+                position: Vector2::new(3, 1)
+            })
+        );
+
+        let mut map = level.map().clone();
+        map[Vector2::new(3, 1)].insert(Tiles::Wall);
+        let error = Solver::try_new(map, SolverOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            LevelError::GoalInWall {
+                position: Vector2::new(3, 1)
+            }
+        );
+        assert_eq!(error.to_string(), "the goal at (3, 1) is in a wall");
+
+        assert_eq!(
+            try_new("#@$.#"),
+            Err(LevelError::TooSmall {
+                dimensions: Vector2::new(5, 1)
+            })
+        );
+
+        // The builder validates the level as well.
+        assert!(matches!(
+            Solver::builder(load_level("#@$.#").map().clone())
+                .timeout(Duration::from_secs(1))
+                .build(),
+            Err(SolveError::InvalidLevel(LevelError::TooSmall { .. }))
+        ));
+    }
+
+    #[test]
+    fn replay_solution() {
+        let level = load_level(
+            r#"
+#######
+#@ $ .#
+#######
+"#,
+        );
+        let replay = |actions: &str| verify::verify_solution(&level, &actions.parse().unwrap());
+        assert_eq!(
+            replay("rRR"),
+            Ok(SolutionMetrics {
+                moves: 3,
+                pushes: 2
+            })
+        );
+
+        assert_eq!(
+            replay("l"),
+            Err(ReplayError::WalksIntoWall {
+                step: 0,
+                position: Vector2::new(0, 1)
+            })
+        );
+        assert_eq!(
+            replay("rr"),
+            Err(ReplayError::MovesIntoBox {
+                step: 1,
+                position: Vector2::new(3, 1)
+            })
+        );
+        assert_eq!(
+            replay("R"),
+            Err(ReplayError::PushesNothing {
+                step: 0,
+                position: Vector2::new(2, 1)
+            })
+        );
+        let error = replay("rRRR").unwrap_err();
+        assert_eq!(
+            error,
+            ReplayError::BoxBlocked {
+                step: 3,
+                position: Vector2::new(6, 1)
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "step 3 pushes a box into an obstacle at (6, 1)"
+        );
+
+        assert_eq!(
+            replay("rR"),
+            Err(ReplayError::NotSolved {
+                box_positions: vec![Vector2::new(4, 1)]
+            })
+        );
+        assert_eq!(
+            replay(""),
+            Err(ReplayError::NotSolved {
+                box_positions: vec![Vector2::new(3, 1)]
+            })
+        );
+
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let solution = Solver::with_options(level.map().clone(), SolverOptions::default())
+            .search_for(Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(
+            verify::verify_solution(&level, &solution),
+            Ok(SolutionMetrics {
+                moves: solution.moves(),
+                pushes: solution.pushes()
+            })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialization() {
+        use serde::{Deserialize, Serialize};
+
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+Title: 3
+Author: David W. Skinner
+"#,
+        );
+        let record =
+            toml::from_str::<LevelRecord>(&toml::to_string(&LevelRecord::from(&level)).unwrap())
+                .unwrap();
+        assert_eq!(record, LevelRecord::from(&level));
+        let deserialized_level = record.to_level().unwrap();
+        assert_eq!(deserialized_level.map(), level.map());
+        assert_eq!(deserialized_level.metadata(), level.metadata());
+        assert_eq!(record.metadata["title"], "3");
+
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushes,
+            ..Default::default()
+        };
+        let options = toml::from_str::<SolverOptions>(&toml::to_string(&options).unwrap()).unwrap();
+        let mut solver = Solver::with_options(level.map().clone(), options.clone());
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        let mut deserialized_solver =
+            Solver::with_options(deserialized_level.map().clone(), options);
+        assert_eq!(
+            deserialized_solver
+                .search_for(Duration::from_secs(10))
+                .unwrap()
+                .pushes(),
+            solution.pushes()
+        );
+
+        let record = toml::from_str::<SolutionRecord>(
+            &toml::to_string(&SolutionRecord::from(&solution)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(record.pushes, solution.pushes());
+        let actions = record.actions().unwrap();
+        assert_eq!(actions, solution);
+        assert_eq!(
+            verify::verify_solution(&deserialized_level, &actions),
+            Ok(SolutionMetrics {
+                moves: record.moves,
+                pushes: record.pushes
+            })
+        );
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Outcome {
+            statistics: SolveStatistics,
+            errors: Vec<SolveError>,
+        }
+        let outcome = Outcome {
+            statistics: solver.statistics(),
+            errors: vec![
+                SolveError::Timeout {
+                    statistics: Box::new(solver.statistics()),
+                },
+                SolveError::NoSolution {
+                    reason: NoSolutionReason::BoxOnDeadSquare(Vector2::new(1, 2)),
+                    statistics: Box::default(),
+                },
+                SolveError::InvalidLevel(LevelError::GoalInWall {
+                    position: Vector2::new(3, 1),
+                }),
+                SolveError::Cancelled {
+                    statistics: Box::new(solver.statistics()),
+                },
+            ],
+        };
+        assert_eq!(
+            toml::from_str::<Outcome>(&toml::to_string(&outcome).unwrap()).unwrap(),
+            outcome
+        );
+    }
+
+    #[test]
+    fn solve_error_context() {
+        // Microban #35
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let mut solver = Solver::with_options(
+            level.map().clone(),
+            SolverOptions {
+                strategy: Strategy::OptimalPushMove,
+                ..Default::default()
+            },
+        );
+        let Err(error @ SolveError::Timeout { statistics }) =
+            &solver.search_for(Duration::from_millis(20))
+        else {
+            panic!("the search should time out");
+        };
+        assert_eq!(**statistics, solver.statistics());
+        assert!(statistics.expanded_states > 0);
+        assert!(!statistics.search_time.is_zero());
+        let best_lower_bound = statistics.best_lower_bound.unwrap();
+        assert!(best_lower_bound > 0);
+        assert!(best_lower_bound <= statistics.initial_lower_bound.unwrap());
+        assert!(statistics.most_boxes_on_goals <= level.map().box_positions().len());
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "timed out after {:.3} s and {} expanded states, best lower bound {}, \
+                 at most {} boxes on goals",
+                statistics.search_time.as_secs_f64(),
+                statistics.expanded_states,
+                best_lower_bound,
+                statistics.most_boxes_on_goals
+            )
+        );
+
+        // Both boxes can only be pushed onto the goal next to the top wall,
+        // which is only found by searching.
+        let mut solver = Solver::with_options(
+            load_level(
+                r#"
+########
+#  $.$ #
+#      #
+#   @ .#
+########
+"#,
+            )
+            .map()
+            .clone(),
+            SolverOptions::default(),
+        );
+        let Err(error @ SolveError::NoSolution { reason, statistics }) =
+            &solver.search_for(Duration::from_secs(10))
+        else {
+            panic!("the level has no solution");
+        };
+        assert!(reason.proves_unsolvable());
+        assert_eq!(statistics.expanded_states, solver.expanded_states());
+        assert!(statistics.expanded_states > 0);
+        assert!(error.to_string().starts_with(&format!(
+            "no solution after {} expanded states: all ",
+            statistics.expanded_states
+        )));
+        assert!(!NoSolutionReason::BeamExhausted {
+            discarded_states: 1
+        }
+        .proves_unsolvable());
+        let error: Box<dyn std::error::Error> = Box::new(error.clone());
+        assert!(error.to_string().ends_with("reachable states searched"));
+    }
+
+    #[test]
+    fn solution_optimality() {
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let solve = |options: SolverOptions| {
+            Solver::with_options(level.map().clone(), options)
+                .search_solution(Duration::from_secs(10))
+                .unwrap()
+        };
+
+        let solution = solve(SolverOptions::default());
+        assert_eq!(solution.strategy, Strategy::Fast);
+        assert_eq!(solution.optimality, Optimality::None);
+        assert_eq!(solution.moves, solution.actions.moves());
+        assert_eq!(solution.pushes, solution.actions.pushes());
+
+        let optimal = solve(SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            ..Default::default()
+        });
+        assert_eq!(optimal.strategy, Strategy::OptimalPushMove);
+        assert_eq!(optimal.optimality, Optimality::PushOptimal);
+
+        let solution = solve(SolverOptions {
+            strategy: Strategy::OptimalMovePush,
+            ..Default::default()
+        });
+        assert_eq!(solution.optimality, Optimality::MoveOptimal);
+        assert!(solution.moves <= optimal.moves);
+
+        // Shortcuts void the guarantee of an optimal strategy, and beam
+        // search is rejected with it.
+        assert!(matches!(
+            Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    algorithm: Algorithm::Beam { width: 1000 },
+                    ..Default::default()
+                },
+            )
+            .search_solution(Duration::from_secs(10)),
+            Err(SolveError::InvalidOptions(_))
+        ));
+        let solution = solve(SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            pressure_relief: Some(PressureRelief {
+                max_open_states: 10,
+                policy: ReliefPolicy::Prune { percent: 50 },
+            }),
+            ..Default::default()
+        });
+        assert_eq!(solution.optimality, Optimality::None);
+        let solution = solve(SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            stall_policy: Some(StallPolicy {
+                expansions: 1,
+                fallback: Strategy::Fast,
+            }),
+            ..Default::default()
+        });
+        assert_eq!(solution.strategy, Strategy::Fast);
+        assert_eq!(solution.optimality, Optimality::None);
+
+        // A weight of 1 is push optimal, unless the approximate visited
+        // filter may have pruned the optimal solutions.
+        let solution = solve(SolverOptions {
+            strategy: Strategy::Weighted(1.0),
+            ..Default::default()
+        });
+        assert_eq!(solution.optimality, Optimality::PushOptimal);
+        assert_eq!(solution.pushes, optimal.pushes);
+        let solution = solve(SolverOptions {
+            strategy: Strategy::Weighted(1.0),
+            visited_filter: VisitedFilter::Approximate { bytes: 1 << 20 },
+            ..Default::default()
+        });
+        assert_eq!(solution.optimality, Optimality::None);
+    }
+
+    #[test]
+    fn solve_handle() {
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let mut handle = Solver::spawn(level.map().clone(), SolverOptions::default());
+        let timer = std::time::Instant::now();
+        let result = loop {
+            if let Some(result) = handle.poll() {
+                break result;
+            }
+            assert!(timer.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        let solution = result.unwrap();
+        assert!(verify_solution(&level, &solution.actions));
+        assert_eq!(handle.poll(), Some(Ok(solution.clone())));
+        assert_eq!(handle.join(), Ok(solution));
+
+        // A room whose push optimal solution takes far longer than the test.
+        let level = load_level(
+            r#"
+############
+#          #
+# $ $ $ $  #
+#          #
+#  $ $ $ $ #
+#          #
+#  ......  #
+#   ..     #
+#         @#
+############
+"#,
+        );
+        let wait_for_progress = |handle: &SolveHandle| {
+            let timer = std::time::Instant::now();
+            while handle.progress().expanded_states == 0 {
+                assert!(timer.elapsed() < Duration::from_secs(10));
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        };
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushes,
+            progress_interval: ProgressInterval::Expansions(100),
+            ..Default::default()
+        };
+        let mut handle = Solver::spawn(level.map().clone(), options.clone());
+        wait_for_progress(&handle);
+        assert_eq!(handle.poll(), None);
+        handle.cancel();
+        let Err(SolveError::Cancelled { statistics }) = handle.join() else {
+            panic!("the search should be cancelled");
+        };
+        assert!(statistics.expanded_states >= 100);
+
+        // Dropping the handle mid-solve stops the worker, which drops the
+        // solver and its progress callback.
+        let guard = Arc::new(());
+        let weak_guard = Arc::downgrade(&guard);
+        let handle = Solver::builder(level.map().clone())
+            .options(options)
+            .on_progress(move |_| {
+                let _ = &guard;
+            })
+            .spawn();
+        wait_for_progress(&handle);
+        let timer = std::time::Instant::now();
+        drop(handle);
+        assert!(timer.elapsed() < Duration::from_secs(1));
+        assert!(weak_guard.upgrade().is_none());
+    }
+
+    /// Pushes random boxes from the initial state, starting over whenever a
+    /// walk gets stuck or reaches its length.
+    struct RandomWalk {
+        seed: u64,
+        length: usize,
+    }
+
+    impl RandomWalk {
+        fn next_index(&mut self, len: usize) -> usize {
+            // xorshift64
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            (self.seed % len as u64) as usize
+        }
+    }
+
+    impl SearchAlgorithm for RandomWalk {
+        fn search(&mut self, context: &mut SearchContext) -> Result<Actions, SolveError> {
+            loop {
+                let mut state = context.initial_state();
+                for _ in 0..self.length {
+                    if context.is_solved(&state) {
+                        return Ok(context.actions(&state));
+                    }
+                    let mut successors = context.expand(&state)?;
+                    if successors.is_empty() {
+                        break;
+                    }
+                    assert!(successors
+                        .iter()
+                        .all(|successor| context.lower_bound(successor).is_some()));
+                    state = successors.swap_remove(self.next_index(successors.len()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn search_algorithm() {
+        let level = load_level(
+            r#"
+######
+#    #
+# $$ #
+# .. #
+#  @ #
+######
+"#,
+        );
+        let mut solver = Solver::builder(level.map().clone())
+            .timeout(Duration::from_secs(10))
+            .search_algorithm(RandomWalk {
+                seed: 0x2545_f491_4f6c_dd1d,
+                length: 20,
+            })
+            .build()
+            .unwrap();
+        let solution = solver.search_solution(Duration::from_secs(10)).unwrap();
+        assert!(verify_solution(&level, &solution.actions));
+        assert_eq!(solution.optimality, Optimality::None);
+        assert!(solver.statistics().expanded_states > 0);
+        assert_eq!(solver.optimality_bound(), None);
+
+        // The context shows the analysis and the options of the solver.
+        let analysis = solver.analysis().clone();
+        let options = solver.options().clone();
+        let context = SearchContext::new(
+            &mut solver,
+            std::time::Instant::now(),
+            Duration::from_secs(10),
+        );
+        assert!(std::ptr::eq(context.analysis(), &*analysis));
+        assert_eq!(*context.options(), options);
+        let reason = NoSolutionReason::SearchExhausted { visited_states: 0 };
+        assert!(matches!(
+            context.no_solution(reason.clone()),
+            SolveError::NoSolution { reason: error_reason, .. } if error_reason == reason
+        ));
+
+        // Microban #35
+        let level = load_level(
+            r#"
+  ####
+ ##  #
+ #. $#
+ #.$ #
+ #.$ #
+ #.$ #
+ #. $##
+ #   @#
+ ##   #
+  #####
+"#,
+        );
+        let mut solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        solver.set_search_algorithm(RandomWalk {
+            seed: 1,
+            length: 100,
+        });
+        assert!(matches!(
+            solver.search_for(Duration::from_millis(20)),
+            Err(SolveError::Timeout { .. })
+        ));
+
+        // The built-in A* selected as a custom algorithm searches as the
+        // default algorithm.
+        let mut solver = Solver::builder(level.map().clone())
+            .timeout(Duration::from_secs(10))
+            .search_algorithm(AStarSearch)
+            .build()
+            .unwrap();
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        let mut default_solver =
+            Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert_eq!(
+            default_solver.search_for(Duration::from_secs(10)).unwrap(),
+            solution
+        );
+        assert_eq!(solver.expanded_states(), default_solver.expanded_states());
+    }
+
+    /// The message and keys of each record logged on the thread.
+    #[cfg(feature = "log")]
+    type CapturedRecords = Vec<(String, Vec<String>)>;
+
+    #[cfg(feature = "log")]
+    thread_local! {
+        static CAPTURED_RECORDS: std::cell::RefCell<Option<CapturedRecords>> =
+            const { std::cell::RefCell::new(None) };
+    }
+
+    /// Records the messages logged on threads which are capturing, since
+    /// the tests share the global logger.
+    #[cfg(feature = "log")]
+    struct CapturingLogger;
+
+    #[cfg(feature = "log")]
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            CAPTURED_RECORDS.with_borrow(Option::is_some)
+        }
+
+        fn log(&self, record: &log::Record) {
+            struct Keys(Vec<String>);
+
+            impl<'kvs> log::kv::VisitSource<'kvs> for Keys {
+                fn visit_pair(
+                    &mut self,
+                    key: log::kv::Key<'kvs>,
+                    _: log::kv::Value<'kvs>,
+                ) -> Result<(), log::kv::Error> {
+                    self.0.push(key.to_string());
+                    Ok(())
+                }
+            }
+
+            CAPTURED_RECORDS.with_borrow_mut(|records| {
+                if let Some(records) = records {
+                    let mut keys = Keys(Vec::new());
+                    record.key_values().visit(&mut keys).unwrap();
+                    records.push((record.args().to_string(), keys.0));
+                }
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Returns the records logged on the thread while running `f`.
+    #[cfg(feature = "log")]
+    fn capture_logs(f: impl FnOnce()) -> CapturedRecords {
+        static LOGGER: CapturingLogger = CapturingLogger;
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(log::LevelFilter::Debug);
+        });
+        CAPTURED_RECORDS.set(Some(Vec::new()));
+        f();
+        CAPTURED_RECORDS.take().unwrap()
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn logging() {
+        let count = |records: &CapturedRecords, message: &str| {
+            records
+                .iter()
+                .filter(|(record_message, _)| record_message == message)
+                .count()
+        };
+        let keys = |records: &CapturedRecords, message: &str| {
+            records
+                .iter()
+                .find(|(record_message, _)| record_message == message)
+                .map(|(_, keys)| keys.clone())
+                .unwrap()
+        };
+
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let records = capture_logs(|| {
+            let mut solver = Solver::builder(level.map().clone())
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap();
+            solver.solve().unwrap();
+        });
+        for message in [
+            "goal distances calculated",
+            "lower bounds calculated",
+            "dead squares calculated",
+            "solver created",
+            "search started",
+            "solution found",
+        ] {
+            assert_eq!(count(&records, message), 1, "{message}");
+        }
+        assert_eq!(count(&records, "search stopped"), 0);
+        assert_eq!(
+            keys(&records, "solver created"),
+            ["fingerprint", "boxes", "initial_lower_bound"]
+        );
+        assert_eq!(
+            keys(&records, "solution found"),
+            ["moves", "pushes", "expanded_states", "elapsed"]
+        );
+        assert_eq!(
+            keys(&records, "dead squares calculated"),
+            ["analysis", "count", "elapsed"]
+        );
+
+        // A search stopped by a limit logs the error instead of a solution.
+        let records = capture_logs(|| {
+            let mut solver = Solver::with_options(
+                level.map().clone(),
+                SolverOptions {
+                    strategy: Strategy::OptimalPushMove,
+                    max_expansions: Some(1),
+                    ..Default::default()
+                },
+            );
+            assert!(solver.search_for(Duration::from_secs(10)).is_err());
+        });
+        assert_eq!(count(&records, "search started"), 1);
+        assert_eq!(count(&records, "search stopped"), 1);
+        assert_eq!(count(&records, "solution found"), 0);
+        assert_eq!(
+            keys(&records, "search stopped"),
+            ["error", "expanded_states", "elapsed"]
+        );
+
+        // Records are only captured on the thread which asked for them.
+        let map = level.map().clone();
+        let records = capture_logs(|| {
+            std::thread::spawn(move || {
+                Solver::with_options(map, SolverOptions::default());
+            })
+            .join()
+            .unwrap();
+        });
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn level_analysis_api() {
+        let level = load_level(
+            r#"
+ #######
+ #@    #
+ # $ $ #
+ #     #
+ ### ###
+   # #
+  ## ##
+  #. .#
+  #   #
+  #   #
+  #####
+"#,
+        );
+        let analysis = level_analysis::analyze(&level);
+
+        // The squares along the walls of the upper room, from which no box
+        // can be pushed away, and the bottom row of the goal room.
+        let dead_squares: HashSet<_> = [
+            (2, 1),
+            (3, 1),
+            (4, 1),
+            (5, 1),
+            (6, 1),
+            (2, 2),
+            (6, 2),
+            (2, 3),
+            (6, 3),
+            (3, 9),
+            (4, 9),
+            (5, 9),
+        ]
+        .into_iter()
+        .map(|(x, y)| Vector2::new(x, y))
+        .collect();
+        assert_eq!(analysis.dead_squares(), &dead_squares);
+
+        // The corridor between the rooms is a tunnel in both directions.
+        for (x, y, direction) in [
+            (4, 4, Direction::Down),
+            (4, 5, Direction::Down),
+            (4, 5, Direction::Up),
+            (4, 6, Direction::Up),
+        ] {
+            assert!(analysis
+                .tunnels()
+                .contains(&(Vector2::new(x, y), direction)));
+        }
+
+        // The goal room lies below the last square of the corridor, which
+        // every box passes to reach the goals.
+        let cells = [
+            (3, 7),
+            (5, 7),
+            (3, 8),
+            (4, 8),
+            (5, 8),
+            (3, 9),
+            (4, 9),
+            (5, 9),
+        ]
+        .into_iter()
+        .map(|(x, y)| Vector2::new(x, y))
+        .collect();
+        let goal_room = Region {
+            cells,
+            entrances: vec![Vector2::new(4, 7)],
+        };
+        assert_eq!(analysis.goal_rooms(), std::slice::from_ref(&goal_room));
+        assert_eq!(
+            analysis.packing_order().unwrap().entrance(),
+            goal_room.entrances[0]
+        );
+
+        assert_eq!(analysis.lower_bound_at(Vector2::new(3, 7)), Some(0));
+        assert_eq!(analysis.lower_bound_at(Vector2::new(4, 7)), Some(1));
+        // The box has to be pushed past the goals and back up, since the
+        // player cannot get around it in the corridor.
+        assert_eq!(analysis.lower_bound_at(Vector2::new(4, 6)), Some(4));
+        assert_eq!(analysis.lower_bound_at(Vector2::new(2, 1)), None);
+        assert_eq!(analysis.lower_bound_at(Vector2::new(0, 0)), None);
+
+        // The solver shares the same analyses.
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        assert_eq!(solver.analysis().goal_rooms(), [goal_room]);
+        assert_eq!(solver.analysis().dead_squares(), &dead_squares);
+
+        // No single square separates the goals of an open room from the
+        // player.
+        let level = load_level(
+            r#"
+######
+#@   #
+# $$ #
+# .. #
+#    #
+######
+"#,
+        );
+        assert!(level_analysis::analyze(&level).goal_rooms().is_empty());
+    }
+
+    #[test]
+    fn hint() {
+        // Plays the moves of the hint, which end with its push, and checks
+        // that the pushed box is not deadlocked.
+        let play_hint = |board: &mut Board, hint: &Hint, solver: &Solver| {
+            let (push, moves) = hint.actions.split_last().unwrap();
+            assert!(matches!(push, Action::Push(_)));
+            assert!(moves.iter().all(|action| matches!(action, Action::Move(_))));
+            for action in hint.actions.iter() {
+                assert!(board.moveable(action.direction()));
+                board.do_action(action.direction());
+            }
+            let box_position = board.map.player_position() + &push.direction().into();
+            assert!(!solver.analysis().dead_squares().contains(&box_position));
+            let state = State::new(
+                board.map.player_position(),
+                BoxPositions::of(&board.map),
+                Path::default(),
+                solver.analysis(),
+                solver.context(),
+            );
+            assert!(!state.is_dead(solver.analysis(), solver.context()));
+        };
+
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        // Following the hints solves the level.
+        let mut board = Board::with_map(level.map().clone());
+        let mut hints = 0;
+        while !board.is_solved() {
+            let hint = solver
+                .hint(
+                    board.map.player_position(),
+                    board.map.box_positions(),
+                    Duration::from_secs(10),
+                )
+                .unwrap();
+            assert!(hint.solved);
+            play_hint(&mut board, &hint, &solver);
+            hints += 1;
+            assert!(hints <= 100);
+            if board.is_solved() {
+                assert_eq!(hint.remaining_pushes, 0);
+            }
+        }
+        assert!(verify_solution(&level, board.actions()));
+        // The search of the solver has not been touched.
+        assert_eq!(solver.expanded_states(), 0);
+
+        // No hint for a solved position or positions of another level.
+        let goals = level.map().goal_positions().clone();
+        let player_position = level.map().player_position();
+        assert_eq!(
+            solver.hint(player_position, &goals, Duration::from_secs(1)),
+            None
+        );
+        let boxes = level.map().box_positions().clone();
+        assert_eq!(
+            solver.hint(Vector2::new(0, 0), &boxes, Duration::from_secs(1)),
+            None
+        );
+        let box_position = *boxes.iter().next().unwrap();
+        assert_eq!(
+            solver.hint(box_position, &boxes, Duration::from_secs(1)),
+            None
+        );
+        assert_eq!(
+            solver.hint(
+                player_position,
+                &HashSet::from([box_position]),
+                Duration::from_secs(1)
+            ),
+            None
+        );
+
+        // A budget too short for a solution still recommends a push towards
+        // the most promising state.
+        let level = load_level(
+            r#"
+############
+#          #
+# $ $ $ $  #
+#          #
+#  $ $ $ $ #
+#          #
+#  ......  #
+#   ..     #
+#         @#
+############
+"#,
+        );
+        let solver = Solver::with_options(level.map().clone(), SolverOptions::default());
+        let mut board = Board::with_map(level.map().clone());
+        let hint = solver
+            .hint(
+                board.map.player_position(),
+                board.map.box_positions(),
+                Duration::from_nanos(1),
+            )
+            .unwrap();
+        assert!(!hint.solved);
+        assert!(hint.remaining_pushes > 0);
+        play_hint(&mut board, &hint, &solver);
+    }
+
+    #[test]
+    fn initial_from() {
+        // Microban #3
+        let level = load_level(
+            r#"
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+"#,
+        );
+        let options = SolverOptions {
+            strategy: Strategy::OptimalPushMove,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(level.map().clone(), options);
+        let solution = solver.search_for(Duration::from_secs(10)).unwrap();
+        assert_eq!(solution.pushes(), 13);
+
+        // Play the optimal solution up to its third push, then solve the
+        // rest from there.
+        let mut board = Board::with_map(level.map().clone());
+        for action in solution.iter() {
+            board.do_action(action.direction());
+            if board.actions().pushes() == 3 {
+                break;
+            }
+        }
+        for strategy in [Strategy::Fast, Strategy::OptimalPushMove] {
+            let mut rest_solver = solver
+                .initial_from(
+                    board.map.player_position(),
+                    board.map.box_positions().clone(),
+                    strategy,
+                )
+                .unwrap();
+            assert_eq!(rest_solver.strategy(), strategy);
+            let rest = rest_solver.solve().unwrap();
+            let mut actions = board.actions().clone();
+            actions.extend(rest.iter().copied());
+            assert!(verify_solution(&level, &actions));
+            if strategy == Strategy::OptimalPushMove {
+                assert_eq!(rest.pushes(), 10);
+            }
+        }
+
+        // A box pushed into a corner cannot reach a goal.
+        let mut rest_solver = solver
+            .initial_from(
+                level.map().player_position(),
+                HashSet::from([Vector2::new(3, 1), Vector2::new(6, 3)]),
+                Strategy::Fast,
+            )
+            .unwrap();
+        assert!(matches!(
+            rest_solver.solve(),
+            Err(SolveError::NoSolution {
+                reason: NoSolutionReason::BoxOnDeadSquare(position),
+                ..
+            }) if position == Vector2::new(3, 1)
+        ));
+
+        // Positions which do not fit the level.
+        let player_position = level.map().player_position();
+        let box_positions = level.map().box_positions().clone();
+        let initial_from = |player_position, box_positions| {
+            solver
+                .initial_from(player_position, box_positions, Strategy::Fast)
+                .err()
+        };
+        assert_eq!(
+            initial_from(Vector2::new(0, 0), box_positions.clone()),
+            Some(LevelError::PlayerInWall {
+                position: Vector2::new(0, 0)
+            })
+        );
+        assert_eq!(
+            initial_from(player_position, HashSet::from([Vector2::new(6, 2)])),
+            Some(LevelError::BoxGoalMismatch { boxes: 1, goals: 2 })
+        );
+        assert_eq!(
+            initial_from(
+                player_position,
+                HashSet::from([Vector2::new(6, 2), Vector2::new(8, 2)])
+            ),
+            Some(LevelError::UnreachableBox {
+                position: Vector2::new(8, 2)
+            })
+        );
+        let error = initial_from(Vector2::new(6, 2), box_positions).unwrap();
+        assert_eq!(
+            error,
+            LevelError::PlayerOnBox {
+                position: Vector2::new(6, 2)
+            }
+        );
+        assert_eq!(error.to_string(), "the player at (6, 2) is on a box");
+    }
 }
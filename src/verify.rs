@@ -0,0 +1,125 @@
+use std::{collections::HashSet, fmt};
+
+use nalgebra::Vector2;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use soukoban::{Action, Actions, Level, Map, Tiles};
+
+/// The moves and pushes of a verified solution.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SolutionMetrics {
+    pub moves: usize,
+    pub pushes: usize,
+}
+
+/// The reason a sequence of actions does not solve a level. Steps are
+/// indices into the actions.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ReplayError {
+    /// The player walks into a wall or off the level.
+    WalksIntoWall { step: usize, position: Vector2<i32> },
+    /// A move walks into a box.
+    MovesIntoBox { step: usize, position: Vector2<i32> },
+    /// A push has no box to push.
+    PushesNothing { step: usize, position: Vector2<i32> },
+    /// A push moves a box into a wall, another box or off the level.
+    BoxBlocked { step: usize, position: Vector2<i32> },
+    /// Every action is legal, but the boxes at the positions are not on
+    /// goals at the end.
+    NotSolved { box_positions: Vec<Vector2<i32>> },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WalksIntoWall { step, position } => write!(
+                f,
+                "step {} walks into a wall at ({}, {})",
+                step, position.x, position.y
+            ),
+            Self::MovesIntoBox { step, position } => write!(
+                f,
+                "step {} is a move into the box at ({}, {})",
+                step, position.x, position.y
+            ),
+            Self::PushesNothing { step, position } => write!(
+                f,
+                "step {} is a push but there is no box at ({}, {})",
+                step, position.x, position.y
+            ),
+            Self::BoxBlocked { step, position } => write!(
+                f,
+                "step {} pushes a box into an obstacle at ({}, {})",
+                step, position.x, position.y
+            ),
+            Self::NotSolved { box_positions } => write!(
+                f,
+                "{} boxes are not on goals at the end",
+                box_positions.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Replays the actions on the level and checks that they solve it.
+///
+/// The check only reads the tiles of the level and shares no code with the
+/// solver, so it can verify the solutions found by the solver as well as
+/// imported ones.
+pub fn verify_solution(level: &Level, actions: &Actions) -> Result<SolutionMetrics, ReplayError> {
+    replay(level.map(), actions)
+}
+
+/// Replays the actions from the initial state of the map, as
+/// [`verify_solution`].
+pub fn replay(map: &Map, actions: &Actions) -> Result<SolutionMetrics, ReplayError> {
+    let is_wall =
+        |position: Vector2<i32>| !map.in_bounds(position) || map[position].intersects(Tiles::Wall);
+    let mut player_position = map.player_position();
+    let mut box_positions: HashSet<Vector2<i32>> = map.box_positions().iter().copied().collect();
+    let mut metrics = SolutionMetrics {
+        moves: 0,
+        pushes: 0,
+    };
+    for (step, action) in actions.iter().enumerate() {
+        let direction: Vector2<i32> = action.direction().into();
+        let position = player_position + direction;
+        if is_wall(position) {
+            return Err(ReplayError::WalksIntoWall { step, position });
+        }
+        match action {
+            Action::Move(_) if box_positions.contains(&position) => {
+                return Err(ReplayError::MovesIntoBox { step, position });
+            }
+            Action::Move(_) => {}
+            Action::Push(_) => {
+                if !box_positions.remove(&position) {
+                    return Err(ReplayError::PushesNothing { step, position });
+                }
+                let box_position = position + direction;
+                if is_wall(box_position) || !box_positions.insert(box_position) {
+                    return Err(ReplayError::BoxBlocked {
+                        step,
+                        position: box_position,
+                    });
+                }
+                metrics.pushes += 1;
+            }
+        }
+        metrics.moves += 1;
+        player_position = position;
+    }
+    let mut box_positions: Vec<_> = box_positions
+        .into_iter()
+        .filter(|position| !map.goal_positions().contains(position))
+        .collect();
+    if !box_positions.is_empty() {
+        box_positions.sort_by_key(|position| (position.y, position.x));
+        return Err(ReplayError::NotSolved { box_positions });
+    }
+    Ok(metrics)
+}